@@ -3,7 +3,7 @@
 
 use std::{
     collections::HashMap,
-    path::Path,
+    path::{ Path, PathBuf },
     sync::{ atomic::{ AtomicU32, Ordering }, Arc, Mutex, RwLock },
 };
 
@@ -153,23 +153,42 @@ impl BufferPoolManager {
         let mut manager_guard = manager.lock().unwrap();
 
         let (file_id, _) = manager_guard.open_from_db_file(path).unwrap();
+        let pages = manager_guard.allocated_pages(file_id);
 
         drop(manager_guard);
 
         {
             self.file_page_map.insert(file_id, HashMap::new());
             let mut page = self.file_page_map.get_mut(&file_id).unwrap();
+
+            // Pages already in the file start out on disk only
             page.insert(0, None);
+            for page_id in pages {
+                page.insert(page_id, None);
+            }
         }
 
         file_id
     }
 
     pub fn new_page(&self, file_id: FileId) -> PageId {
+        self.create_page(file_id, false)
+    }
+
+    /// Allocates a page past the last one of the file rather than reusing a freed one
+    pub fn append_page(&self, file_id: FileId) -> PageId {
+        self.create_page(file_id, true)
+    }
+
+    fn create_page(&self, file_id: FileId, at_end: bool) -> PageId {
         let manager = &self.manager;
         let mut manager_guard = manager.lock().unwrap();
 
-        let (page_id, _) = manager_guard.allocate_page(file_id);
+        let (page_id, _) = if at_end {
+            manager_guard.append_page(file_id)
+        } else {
+            manager_guard.allocate_page(file_id)
+        };
 
         self.next_page_id.fetch_add(1, Ordering::Relaxed);
 
@@ -194,6 +213,51 @@ impl BufferPoolManager {
     }
 
     // Study this
+    pub fn is_allocated(&self, file_id: FileId, page_id: PageId) -> bool {
+        self.manager.lock().unwrap().is_allocated(file_id, page_id)
+    }
+
+    pub fn free_page_count(&self, file_id: FileId) -> usize {
+        self.manager.lock().unwrap().free_page_count(file_id)
+    }
+
+    /// Writes every page of the file currently held in a frame back to disk
+    pub fn flush_file(&self, file_id: FileId) -> bool {
+        let in_memory: Vec<(PageId, FrameId)> = match self.file_page_map.get(&file_id) {
+            Some(page_frame_map) =>
+                page_frame_map
+                    .iter()
+                    .filter_map(|(page_id, frame_id)| frame_id.map(|frame_id| (*page_id, frame_id)))
+                    .collect(),
+            None => {
+                return false;
+            }
+        };
+
+        for (page_id, frame_id) in in_memory {
+            let data = {
+                let frame_guard = self.frames.read().unwrap();
+
+                match frame_guard.get(&frame_id).and_then(|frame| frame.as_ref()) {
+                    Some(frame) => frame.read().unwrap().data,
+                    None => {
+                        continue;
+                    }
+                }
+            };
+
+            if !self.flush_page_sync(file_id, page_id, &data) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    pub fn file_path(&self, file_id: FileId) -> Option<PathBuf> {
+        self.manager.lock().unwrap().file_path(file_id)
+    }
+
     pub fn delete_page(&self, file_id: FileId, page_id: PageId) -> bool {
         let page_frame_map = self.file_page_map.clone();
 
@@ -207,7 +271,8 @@ impl BufferPoolManager {
         let frame_id = match page_frame_map.get(&page_id).and_then(|id| id.clone()) {
             Some(id) => id,
             None => {
-                return false;
+                // Page is not in memory, only the disk slot has to be released
+                return self.manager.lock().unwrap().delete_page(file_id, page_id).is_ok();
             }
         };
 
@@ -226,6 +291,7 @@ impl BufferPoolManager {
 
         if self.manager.lock().unwrap().delete_page(file_id, page_id).is_ok() {
             page_frame_map.insert(page_id, None);
+            self.replacer.lock().unwrap().remove(frame_id);
             self.free_frames.push(frame.read().unwrap().frame_id);
             return true;
         }
@@ -1,7 +1,11 @@
 #![allow(unused_variables)] 
 #![allow(dead_code)] 
 
-use std::{ collections::VecDeque, sync::{ atomic::{ AtomicU32, Ordering }, Arc, RwLock } };
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{ atomic::{ AtomicU32, AtomicU64, Ordering }, Arc, RwLock },
+};
 
 use anyhow::Ok;
 
@@ -45,6 +49,9 @@ pub struct Flusher {
 
     pub(self) txn_ticker: AtomicU32,
 
+    // Pages written through this flusher, for telling whether the file changed in between
+    pub(self) writes: AtomicU64,
+
     pub(self) context: Context,
 }
 
@@ -56,6 +63,7 @@ impl Flusher {
             lock_table: DashMap::new(),
 
             txn_ticker: AtomicU32::new(0),
+            writes: AtomicU64::new(0),
             context: Context {
                 stack: Arc::new(RwLock::new(VecDeque::new())),
                 context_type: None,
@@ -66,6 +74,52 @@ impl Flusher {
         self.inner.new_page(self.file)
     }
 
+    /// Allocates a page past the last one of the file, see `BufferPoolManager::append_page`
+    pub fn append_page(&self) -> u32 {
+        self.inner.append_page(self.file)
+    }
+
+    /// Number of pages written through the flusher so far
+    pub fn write_count(&self) -> u64 {
+        self.writes.load(Ordering::SeqCst)
+    }
+
+    /// Returns a page to the file's free list so a later `new_page` can reuse it
+    pub fn free_page(&self, page_id: u32) -> anyhow::Result<()> {
+        if self.lock_table.contains_key(&page_id) {
+            return Err(anyhow::Error::msg("Cannot free a locked page"));
+        }
+
+        if !self.inner.delete_page(self.file, page_id) {
+            return Err(anyhow::Error::msg("Unable to free page"));
+        }
+
+        Ok(())
+    }
+
+    /// Whether the page is allocated in the file backing this flusher
+    pub fn is_allocated(&self, page_id: u32) -> bool {
+        self.inner.is_allocated(self.file, page_id)
+    }
+
+    /// Number of freed pages the file can hand out again
+    pub fn free_page_count(&self) -> usize {
+        self.inner.free_page_count(self.file)
+    }
+
+    /// Persists every page of the file still held by the buffer pool
+    pub fn flush_all(&self) -> anyhow::Result<()> {
+        if !self.inner.flush_file(self.file) {
+            return Err(anyhow::Error::msg("Unable to flush file"));
+        }
+
+        Ok(())
+    }
+
+    pub fn file_path(&self) -> Option<PathBuf> {
+        self.inner.file_path(self.file)
+    }
+
     pub fn new_file(&self) -> u64 {
         self.inner.allocate_file()
     }
@@ -195,6 +249,7 @@ impl Flusher {
                     guard.get_frame().data.copy_from_slice(&data);
                     drop(guard);
 
+                    self.writes.fetch_add(1, Ordering::SeqCst);
                     return Ok(());
                 }
             }
@@ -281,6 +336,25 @@ impl Flusher {
         Ok(())
     }
 
+    /// Rewrites a page the current context does not hold, keeping it EX locked from the read
+    /// to the write so no other writer can change it in between
+    pub fn update_flush(
+        &self,
+        page_id: u32,
+        update: impl FnOnce([u8; PAGE_SIZE]) -> anyhow::Result<[u8; PAGE_SIZE]>
+    ) -> anyhow::Result<()> {
+        self.aqquire_ex(page_id)?;
+
+        let guard = self.inner.read_page(self.file, page_id);
+        let data = guard.get_frame().data;
+        drop(guard);
+
+        let written = update(data).and_then(|data| self.write_all(page_id, data));
+
+        self.lock_table.remove(&page_id).unwrap();
+        written
+    }
+
     pub fn release_ex(&self) -> anyhow::Result<()> {
         let context = &self.context;
 
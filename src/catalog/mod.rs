@@ -1,8 +1,12 @@
 use schema::{ Schema, SchemaDataBuilder };
 
-use crate::{ index::tree::byte_box::ByteBox, storage::tuple::{ to_flat_schema, Tuple } };
+use crate::{
+    index::{ index_api::IndexKind, tree::{ byte_box::ByteBox, tree_page::codec::Codec } },
+    storage::tuple::{ to_flat_schema, Tuple },
+};
 
 pub mod schema;
+pub mod table;
 
 pub fn pages_directory_schema() -> Schema {
     let allocated_pages = SchemaDataBuilder::new()
@@ -18,13 +22,62 @@ pub fn pages_directory_schema() -> Schema {
 
 pub fn table_schema() -> Schema {
     let table = SchemaDataBuilder::new()
+        // Page the table's index is reopened from, see `Index::entry_page_id`
         .add_int(String::from("ROOT_PAGE_ID"), ByteBox::int(0))
+        .add_varchar(String::from("FILE_PATH"), ByteBox::varchar("", 100))
         // .add_big_int(String::from("MONO_ID"), ByteBox::big_int(0))
-        .add_char(String::from("KEY_DATATYPE"), ByteBox::varchar("", 50))
-        .add_char(String::from("VALUE_DATATYPE"), ByteBox::varchar("", 50))
+        .add_varchar(String::from("KEY_DATATYPE"), ByteBox::varchar("", 50))
+        .add_varchar(String::from("VALUE_DATATYPE"), ByteBox::varchar("", 50))
+        // Name of the table's `IndexKind`, which the engine builds the table's index as
+        .add_char(String::from("INDEX_KIND"), ByteBox::char("", 10))
         .build();
 
     let table_tuple = Tuple::TupleData(table);
 
     to_flat_schema(&table_tuple)
 }
+
+/// Row of the table directory for a table kept in an index of the kind, in the file at the path
+pub fn table_row(root_page_id: i32, file_path: &str, codec: &Codec, kind: IndexKind) -> Tuple {
+    let table = SchemaDataBuilder::new()
+        .add_int(String::from("ROOT_PAGE_ID"), ByteBox::int(root_page_id))
+        .add_varchar(String::from("FILE_PATH"), ByteBox::varchar(file_path, 100))
+        .add_varchar(
+            String::from("KEY_DATATYPE"),
+            ByteBox::varchar(&codec.key_type.to_string(), 50)
+        )
+        .add_varchar(
+            String::from("VALUE_DATATYPE"),
+            ByteBox::varchar(&codec.value_type.to_string(), 50)
+        )
+        .add_char(String::from("INDEX_KIND"), ByteBox::char(kind.name(), 10))
+        .build();
+
+    Tuple::TupleData(table)
+}
+
+/// Text of a column of an encoded table directory row
+pub fn table_row_text(row: &ByteBox, column: &str) -> anyhow::Result<String> {
+    let value = table_row_value(row, column)?;
+    Ok(String::from_utf8(value.data.to_vec())?)
+}
+
+/// Integer of a column of an encoded table directory row
+pub fn table_row_int(row: &ByteBox, column: &str) -> anyhow::Result<i32> {
+    let value = table_row_value(row, column)?;
+    let bytes = value.data
+        .get(..4)
+        .ok_or(anyhow::Error::msg(format!("Column {} holds no integer", column)))?;
+
+    Ok(i32::from_le_bytes(bytes.try_into()?))
+}
+
+fn table_row_value(row: &ByteBox, column: &str) -> anyhow::Result<ByteBox> {
+    let schema = table_schema();
+    let position = schema.columns
+        .iter()
+        .position(|field| field.name == column)
+        .ok_or(anyhow::Error::msg(format!("No column named {}", column)))?;
+
+    Ok(Tuple::decode(&schema, row.data.to_vec()).get(position).clone())
+}
@@ -0,0 +1,377 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::time::SystemTime;
+
+use crate::{
+    catalog::schema::Schema,
+    index::{
+        index_api::Index,
+        tree::{
+            byte_box::{ ByteBox, DataType },
+            db::{
+                btree_obj::{ BPTree, BTreeBuilder },
+                btree_ttl::{ unix_millis, ExpiryReaper },
+            },
+            index_types::{ KeyValuePair, NodeKey },
+            iterators::cursor::Cursor,
+            tree_page::codec::Codec,
+        },
+    },
+    storage::tuple::Tuple,
+    wal::Wal,
+};
+
+/// A tree from the values of one tuple column to the primary keys of the rows holding them
+///
+/// Rows may share a column value, so the tree allows duplicates, each row keeping its own
+/// entry under the value. The tree lives in an anonymous file the catalog does not record, so
+/// an index lasts as long as its `Table` and has to be created again once the table is reopened.
+pub struct SecondaryIndex {
+    pub column: String,
+
+    // Position of the column in the table's schema
+    pub(crate) position: usize,
+
+    pub tree: BPTree,
+}
+
+/// Rows keyed on a primary key, with secondary indexes over columns of their tuples
+///
+/// Writes go through the table, which keeps every secondary index in step with the rows. Rows
+/// written to the primary index directly are not seen by its indexes. Every stored row has its
+/// index entries, expired rows included until they are reaped with `reap`. Secondary indexes
+/// are not persisted, see `SecondaryIndex`.
+pub struct Table {
+    pub name: String,
+    pub primary: Box<dyn Index>,
+
+    // Schema of the rows, None for tables whose values are not tuples and can not be indexed
+    pub(crate) schema: Option<Schema>,
+
+    pub(crate) indexes: Vec<SecondaryIndex>,
+}
+
+/// A write to a secondary index, kept so it can be undone
+enum IndexWrite {
+    Inserted(usize, KeyValuePair),
+    Deleted(usize, KeyValuePair),
+}
+
+impl Table {
+    /// Wraps an index of rows, which has to keep a single row per key
+    pub fn new(name: String, primary: Box<dyn Index>) -> anyhow::Result<Table> {
+        let schema = match &primary.codec().value_type {
+            DataType::Tuple(schema) => Some(schema.clone()),
+            _ => None,
+        };
+
+        if primary.as_tree().is_some_and(|tree| tree.allow_duplicates) {
+            return Err(anyhow::Error::msg("Tables need a single row per primary key"));
+        }
+
+        Ok(Table { name, primary, schema, indexes: Vec::new() })
+    }
+
+    /// Builds a secondary index over the column from the rows already in the table
+    ///
+    /// Only tables of tuple rows kept in a tree can be indexed, as the rows are read back in
+    /// key order.
+    pub fn create_index(&mut self, column: &str) -> anyhow::Result<()> {
+        if self.index_on(column).is_ok() {
+            return Err(anyhow::Error::msg(format!("Column {} is already indexed", column)));
+        }
+
+        let value_type = self.primary.codec().value_type.to_string();
+        let schema = self.schema
+            .as_ref()
+            .ok_or(anyhow::Error::msg(format!("Tables need tuple values, found {}", value_type)))?;
+        let kind = self.primary.kind();
+        let primary = self.primary
+            .as_tree()
+            .ok_or(anyhow::Error::msg(format!("{} tables can not be indexed", kind)))?;
+
+        let position = schema.columns
+            .iter()
+            .position(|candidate| candidate.name == column)
+            .ok_or(anyhow::Error::msg(format!("No column named {}", column)))?;
+
+        let column_type = schema.columns[position].datatype.clone();
+        let key_type = primary.codec.key_type.clone();
+
+        if !column_type.is_scalar() || !key_type.is_scalar() {
+            return Err(anyhow::Error::msg("Indexed columns and primary keys must be scalar"));
+        }
+
+        // Entries are keyed on the column value and primary key, which have to fit a composite key
+        BTreeBuilder::check_composite(
+            &DataType::Composite(vec![column_type.clone(), key_type.clone()])
+        )?;
+
+        // Duplicates are bulk loaded sorted by the column value, then by primary key. A cursor
+        // rather than a scan reads the rows, so expired rows not yet reaped are indexed too.
+        let mut entries: Vec<KeyValuePair> = Vec::new();
+        let mut cursor = Cursor::new(primary);
+        let mut positioned = cursor.seek_first()?;
+        while positioned {
+            if let Some(row) = cursor.entry()? {
+                entries.push(KeyValuePair {
+                    key: self.column_value(position, &row.value),
+                    value: row.key,
+                });
+            }
+
+            positioned = cursor.next()?;
+        }
+
+        entries.sort_by_cached_key(|entry| {
+            ByteBox::composite_bound(&[entry.key.clone(), entry.value.clone()])
+        });
+
+        let tree = BTreeBuilder::new()
+            .allow_duplicates(true)
+            .tree_schema(Codec { key_type: column_type, value_type: key_type })
+            .bulk_load(entries)?;
+
+        self.indexes.push(SecondaryIndex { column: column.to_string(), position, tree });
+
+        Ok(())
+    }
+
+    /// Columns with a secondary index, in the order they were indexed
+    pub fn indexed_columns(&self) -> Vec<&str> {
+        self.indexes
+            .iter()
+            .map(|index| index.column.as_str())
+            .collect()
+    }
+
+    pub fn get(&self, key: &ByteBox) -> anyhow::Result<KeyValuePair> {
+        self.primary.get(key)
+    }
+
+    /// Inserts a row, failing if its key is already present
+    ///
+    /// Should writing to an index fail, the writes already made are undone, leaving the table
+    /// as it was. The same holds for `put` and `delete`.
+    pub fn insert(&self, row: KeyValuePair) -> anyhow::Result<()> {
+        // Trees only refuse an entry matching a stored one in its value as well
+        if self.primary.contains_key(&row.key)? {
+            return Err(anyhow::Error::msg("Key already present"));
+        }
+
+        // An expired row is still stored, along with its index entries
+        if let Some((expired, true)) = self.stored_row(&row.key)? {
+            self.remove_row(expired)?;
+        }
+
+        self.primary.insert(row.clone())?;
+
+        let writes = self.indexes
+            .iter()
+            .enumerate()
+            .map(|(idx, index)| IndexWrite::Inserted(idx, self.index_entry(index, &row)))
+            .collect();
+
+        self.write_indexes(writes, || self.primary.delete(&row.key))
+    }
+
+    /// Inserts a row, replacing the row stored under its key if there is one
+    ///
+    /// Only indexes over columns whose value changed are written to. An expired row under the
+    /// key is removed with its index entries first, as if it had been reaped.
+    pub fn put(&self, row: KeyValuePair) -> anyhow::Result<()> {
+        let previous = match self.stored_row(&row.key)? {
+            Some((expired, true)) => {
+                self.remove_row(expired)?;
+                None
+            }
+            Some((previous, false)) => Some(previous),
+            None => None,
+        };
+        self.primary.put(row.clone())?;
+
+        let mut writes = Vec::new();
+        for (idx, index) in self.indexes.iter().enumerate() {
+            let entry = self.index_entry(index, &row);
+
+            if let Some(previous) = &previous {
+                let stale = self.index_entry(index, previous);
+
+                if stale.key == entry.key {
+                    continue;
+                }
+
+                writes.push(IndexWrite::Deleted(idx, stale));
+            }
+
+            writes.push(IndexWrite::Inserted(idx, entry));
+        }
+
+        self.write_indexes(writes, || {
+            match &previous {
+                Some(previous) => self.primary.put(previous.clone()),
+                None => self.primary.delete(&row.key),
+            }
+        })
+    }
+
+    /// Removes the row stored under the key, failing if there is none
+    pub fn delete(&self, key: &ByteBox) -> anyhow::Result<()> {
+        let row = self.get(key)?;
+        self.remove_row(row)
+    }
+
+    /// Deletes the expired rows of the next leaf of the primary tree along with their index
+    /// entries, returning how many there were, see `ExpiryReaper::step`
+    ///
+    /// Only trees hold expiring rows, so tables kept in other indexes have nothing to reap.
+    pub fn reap(&self, reaper: &mut ExpiryReaper, wal: Option<&Wal>) -> anyhow::Result<usize> {
+        let primary = match self.primary.as_tree() {
+            Some(primary) => primary,
+            None => {
+                return Ok(0);
+            }
+        };
+
+        if self.indexes.is_empty() {
+            return reaper.step(primary, wal);
+        }
+
+        let rows = reaper.step_rows(primary, wal)?;
+        for row in &rows {
+            for index in &self.indexes {
+                index.tree.delete(NodeKey::KeyValuePair(self.index_entry(index, row)))?;
+            }
+        }
+
+        Ok(rows.len())
+    }
+
+    /// Writes every page of the primary index still held in memory to its file
+    ///
+    /// Secondary indexes live in anonymous files and are not flushed, see `SecondaryIndex`.
+    pub fn flush(&self) -> anyhow::Result<()> {
+        self.primary.flush()
+    }
+
+    /// Removes a stored row and its index entries
+    fn remove_row(&self, row: KeyValuePair) -> anyhow::Result<()> {
+        self.primary.delete(&row.key)?;
+
+        let writes = self.indexes
+            .iter()
+            .enumerate()
+            .map(|(idx, index)| IndexWrite::Deleted(idx, self.index_entry(index, &row)))
+            .collect();
+
+        self.write_indexes(writes, || self.primary.insert(row.clone()))
+    }
+
+    /// Every row whose column holds the value, in primary key order
+    ///
+    /// The column has to have been indexed with `create_index`. Expired rows keep their index
+    /// entries until they are reaped, and are left out.
+    pub fn lookup_by(&self, column: &str, value: &ByteBox) -> anyhow::Result<Vec<KeyValuePair>> {
+        let index = self.index_on(column)?;
+
+        let mut rows = Vec::new();
+        for entry in index.tree.scan(value.clone()..=value.clone()) {
+            if self.primary.contains_key(&entry.value)? {
+                rows.push(self.get(&entry.value)?);
+            }
+        }
+
+        Ok(rows)
+    }
+
+    fn index_on(&self, column: &str) -> anyhow::Result<&SecondaryIndex> {
+        self.indexes
+            .iter()
+            .find(|index| index.column == column)
+            .ok_or(anyhow::Error::msg(format!("Column {} is not indexed", column)))
+    }
+
+    /// Makes the writes to the indexes, undoing them and the primary tree's write if one fails
+    fn write_indexes(
+        &self,
+        writes: Vec<IndexWrite>,
+        undo_primary: impl FnOnce() -> anyhow::Result<()>
+    ) -> anyhow::Result<()> {
+        for (done, write) in writes.iter().enumerate() {
+            if let Err(err) = self.apply_index_write(write, false) {
+                let undone = writes[..done]
+                    .iter()
+                    .rev()
+                    .try_for_each(|write| self.apply_index_write(write, true))
+                    .and_then(|_| undo_primary());
+
+                return match undone {
+                    Ok(_) => Err(err),
+                    Err(undo_err) =>
+                        Err(
+                            anyhow::Error::msg(
+                                format!("{}, and undoing the write failed: {}", err, undo_err)
+                            )
+                        ),
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_index_write(&self, write: &IndexWrite, undo: bool) -> anyhow::Result<()> {
+        match (write, undo) {
+            (IndexWrite::Inserted(idx, entry), false) | (IndexWrite::Deleted(idx, entry), true) => {
+                self.indexes[*idx].tree.insert(entry.clone())
+            }
+            (IndexWrite::Deleted(idx, entry), false) | (IndexWrite::Inserted(idx, entry), true) => {
+                self.indexes[*idx].tree.delete(NodeKey::KeyValuePair(entry.clone()))
+            }
+        }
+    }
+
+    /// Entry of the secondary index pointing at the row
+    fn index_entry(&self, index: &SecondaryIndex, row: &KeyValuePair) -> KeyValuePair {
+        KeyValuePair {
+            key: self.column_value(index.position, &row.value),
+            value: row.key.clone(),
+        }
+    }
+
+    /// Value of the column of a row, only called once `create_index` found the table's schema
+    fn column_value(&self, position: usize, value: &ByteBox) -> ByteBox {
+        let schema = self.schema.as_ref().expect("Indexed tables have a schema");
+        Tuple::decode(schema, value.data.to_vec()).get(position).clone()
+    }
+
+    /// The row stored under the key and whether it has expired
+    ///
+    /// Expired rows are read as absent but stay stored until they are reaped, so they are
+    /// looked up in the primary tree as they are stored rather than through `get`.
+    fn stored_row(&self, key: &ByteBox) -> anyhow::Result<Option<(KeyValuePair, bool)>> {
+        let primary = match self.primary.as_tree() {
+            Some(primary) => primary,
+            None => {
+                if !self.primary.contains_key(key)? {
+                    return Ok(None);
+                }
+
+                return self.get(key).map(|row| Some((row, false)));
+            }
+        };
+
+        let value = match primary.stored_value(key)? {
+            Some(value) => value,
+            None => {
+                return Ok(None);
+            }
+        };
+
+        let expired = value.is_expired(unix_millis(SystemTime::now()));
+        let row = KeyValuePair { key: key.clone(), value: primary.resolve_value(value)? };
+
+        Ok(Some((row, expired)))
+    }
+}
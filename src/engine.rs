@@ -1,17 +1,32 @@
 #![allow(unused_variables)] 
 #![allow(dead_code)] 
 
-use std::{ cell::RefCell, path::Path, sync::{ Arc, Mutex } };
+use std::{ cell::RefCell, ops::Bound, path::Path, sync::{ Arc, Mutex } };
 
 use bincode::config;
 
 use crate::{
     buffer::{ buffer_pool_manager::BufferPoolManager, flusher::Flusher },
-    catalog::{ pages_directory_schema, schema::Schema, table_schema },
-    index::tree::{
-        byte_box::DataType,
-        db::btree_obj::{ BPTree, BTreeBuilder },
-        tree_page::codec::Codec,
+    catalog::{
+        pages_directory_schema,
+        schema::Schema,
+        table::Table,
+        table_row,
+        table_row_int,
+        table_row_text,
+        table_schema,
+    },
+    index::{
+        index_api::{ build_index, open_index, Index, IndexKind },
+        tree::{
+            byte_box::{ ByteBox, DataType },
+            db::{
+                btree_obj::{ BPTree, BTreeBuilder, DEFAULT_FILL_FACTOR },
+                btree_ttl::ExpiryReaper,
+            },
+            index_types::{ KeyValuePair, NodeKey },
+            tree_page::codec::Codec,
+        },
     },
     storage::disk::manager::Manager,
     wal::Wal,
@@ -20,8 +35,6 @@ use crate::{
 pub struct StorageOptions {
     // Block size in bytes
     pub page_size: usize,
-    // The approximate leaf node max capacity
-    pub target_sst_size: usize,
     pub enable_wal: bool,
 }
 pub struct TableGenerator;
@@ -55,12 +68,17 @@ pub struct StorageEngine {
     pub(crate) pages_directory: Arc<BPTree>,
     pub(crate) table_directory: Arc<BPTree>,
 
-    pub(crate) state: Arc<BPTree>,
+    // The open table, kept in an index of the kind the table directory records for it, see
+    // `open_table`. Every write goes through it, keeping its secondary indexes in step.
+    pub(crate) state: Table,
 
     pub(crate) bpm: Arc<BufferPoolManager>,
 
     // Experimental
     pub(crate) wal: Option<Wal>,
+
+    // Deletes expired rows of the open table, see `reap_expired`
+    pub(crate) reaper: RefCell<ExpiryReaper>,
 }
 
 impl StorageEngine {
@@ -86,7 +104,14 @@ impl StorageEngine {
             },
             flusher: flusher.clone(),
             index_id: 0,
+            log_id: 0,
             root_page_id: RefCell::new(0),
+            fill_factor: DEFAULT_FILL_FACTOR,
+            allow_duplicates: false,
+            order_statistics: false,
+            meta_page_id: None,
+            versions: None,
+            merge_operator: None,
         });
 
         // Root PagSeID of 1
@@ -98,7 +123,14 @@ impl StorageEngine {
             },
             flusher: flusher.clone(),
             index_id: 1,
+            log_id: 1,
             root_page_id: RefCell::new(1),
+            fill_factor: DEFAULT_FILL_FACTOR,
+            allow_duplicates: false,
+            order_statistics: false,
+            meta_page_id: None,
+            versions: None,
+            merge_operator: None,
         });
         unimplemented!()
     }
@@ -134,6 +166,194 @@ impl StorageEngine {
     }
 }
 
+impl StorageEngine {
+    pub fn get(&self, key: &ByteBox) -> anyhow::Result<KeyValuePair> {
+        self.state.get(key)
+    }
+
+    pub fn insert(&self, entry: KeyValuePair) -> anyhow::Result<()> {
+        self.state.insert(entry)?;
+        self.reap_expired().map(|_| ())
+    }
+
+    pub fn put(&self, entry: KeyValuePair) -> anyhow::Result<()> {
+        self.state.put(entry)?;
+        self.reap_expired().map(|_| ())
+    }
+
+    pub fn delete(&self, key: &ByteBox) -> anyhow::Result<()> {
+        self.state.delete(key)?;
+        self.reap_expired().map(|_| ())
+    }
+
+    /// Entries of the open table within the bounds, for tables whose index keeps keys in order
+    pub fn scan(
+        &self,
+        range: (Bound<ByteBox>, Bound<ByteBox>)
+    ) -> anyhow::Result<Box<dyn Iterator<Item = KeyValuePair>>> {
+        self.state.primary.scan(range)
+    }
+
+    /// Builds the table's index and records it in the table directory, along with its kind and
+    /// the file and page it is reopened from
+    pub fn create_table(&self, name: &str, kind: IndexKind, codec: &Codec) -> anyhow::Result<()> {
+        let key = Self::table_key(name)?;
+        if self.table_directory.contains_key(&key)? {
+            return Err(anyhow::Error::msg(format!("Table {} already exists", name)));
+        }
+
+        let index = build_index(kind, codec.clone())?;
+        index.flush()?;
+
+        let (page, path) = match (index.entry_page_id(), index.file_path()) {
+            (Some(page), Some(path)) => (page, path),
+            _ => {
+                return Err(anyhow::Error::msg(format!("Table {} can not be reopened", name)));
+            }
+        };
+
+        let row = table_row(page as i32, &path.to_string_lossy(), codec, kind);
+        self.table_directory.insert(KeyValuePair {
+            key,
+            value: ByteBox::tuple(&row),
+        })
+    }
+
+    /// Opens the table, reopening its index from the file and page the table directory records
+    ///
+    /// The directory keeps the names of the key and value types rather than the types, so the
+    /// codec is passed in and checked against them. The table open until then is checkpointed
+    /// first, so its entries are there when it is opened again and the log only ever holds
+    /// writes of the open table. Its secondary indexes are not persisted and are dropped.
+    pub fn open_table(&mut self, name: &str, codec: Codec) -> anyhow::Result<()> {
+        let row = self.table_directory.search(NodeKey::GuidePost(Self::table_key(name)?))?;
+
+        if
+            table_row_text(&row.value, "KEY_DATATYPE")? != codec.key_type.to_string() ||
+            table_row_text(&row.value, "VALUE_DATATYPE")? != codec.value_type.to_string()
+        {
+            return Err(anyhow::Error::msg(format!("Table {} has another schema", name)));
+        }
+
+        self.checkpoint()?;
+
+        self.state = Table::new(name.to_string(), Self::open_recorded(&row.value)?)?;
+        self.reaper = RefCell::new(ExpiryReaper::new());
+        Ok(())
+    }
+
+    /// Reopens the index of a table directory row from the file and page it records
+    fn open_recorded(row: &ByteBox) -> anyhow::Result<Box<dyn Index>> {
+        let kind = IndexKind::from_name(&table_row_text(row, "INDEX_KIND")?)?;
+        let path = table_row_text(row, "FILE_PATH")?;
+        let page = table_row_int(row, "ROOT_PAGE_ID")?;
+
+        let index = open_index(kind, Path::new(&path))?;
+        if index.entry_page_id() != Some(page as u32) {
+            return Err(anyhow::Error::msg(format!("No table at page {} of {}", page, path)));
+        }
+
+        Ok(index)
+    }
+
+    /// Flushes the open table and empties the log, whose writes are then all on disk
+    pub fn checkpoint(&self) -> anyhow::Result<()> {
+        self.state.flush()?;
+
+        match &self.wal {
+            Some(wal) => wal.truncate(),
+            None => Ok(()),
+        }
+    }
+
+    /// Replays the log onto every table and empties it, for the engine to run as it starts
+    ///
+    /// Writes logged before a crash may not have reached the files of their tables, so every
+    /// table is reopened, given its logged entries again and flushed before the log is emptied.
+    /// Only trees log their writes. It runs before any table is opened, as the tables are
+    /// reopened on their own.
+    pub fn recover(&self) -> anyhow::Result<()> {
+        let wal = match &self.wal {
+            Some(wal) => wal,
+            None => {
+                return Ok(());
+            }
+        };
+
+        let entries = wal.entries()?;
+
+        let mut rows = self.table_directory.scan(..);
+        for row in &mut rows {
+            let index = Self::open_recorded(&row.value)?;
+
+            if let Some(tree) = index.as_tree() {
+                tree.replay(&entries)?;
+                tree.flush()?;
+            }
+        }
+
+        if let Some(err) = rows.error() {
+            return Err(anyhow::Error::msg(format!("Reading the table directory failed: {}", err)));
+        }
+
+        wal.truncate()
+    }
+
+    fn table_key(name: &str) -> anyhow::Result<ByteBox> {
+        if name.len() > 25 {
+            return Err(anyhow::Error::msg(format!("Table name {} is too long", name)));
+        }
+
+        Ok(ByteBox::varchar(name, 25))
+    }
+
+    /// Indexes the column of the open table's tuples, see `Table::create_index`
+    pub fn create_index(&mut self, table: &str, column: &str) -> anyhow::Result<()> {
+        self.table_mut(table)?.create_index(column)
+    }
+
+    /// Rows of the table whose column holds the value, through the column's secondary index
+    pub fn lookup_by(
+        &self,
+        table: &str,
+        column: &str,
+        value: &ByteBox
+    ) -> anyhow::Result<Vec<KeyValuePair>> {
+        self.table(table)?.lookup_by(column, value)
+    }
+
+    /// The table, which has to be the open one
+    pub fn table(&self, name: &str) -> anyhow::Result<&Table> {
+        if self.state.name != name {
+            return Err(anyhow::Error::msg(format!("Table {} is not open", name)));
+        }
+
+        Ok(&self.state)
+    }
+
+    pub fn table_mut(&mut self, name: &str) -> anyhow::Result<&mut Table> {
+        if self.state.name != name {
+            return Err(anyhow::Error::msg(format!("Table {} is not open", name)));
+        }
+
+        Ok(&mut self.state)
+    }
+
+    /// Deletes the expired rows of one leaf of the open table, returning how many it deleted
+    ///
+    /// A BPTree is never shared between threads, so rather than reaping on a thread of its own
+    /// the engine calls this after every `insert`, `put` and `delete`, moving on a leaf each time.
+    /// Rows are reaped through the table, which deletes their index entries along with them.
+    pub fn reap_expired(&self) -> anyhow::Result<usize> {
+        self.state.reap(&mut self.reaper.borrow_mut(), self.wal.as_ref())
+    }
+
+    /// Number of expired entries the engine has deleted
+    pub fn reaped_entries(&self) -> u64 {
+        self.reaper.borrow().reaped()
+    }
+}
+
 impl Manager {
     fn shutdown(page_directory: Arc<BPTree>) -> anyhow::Result<()> {
         unimplemented!()
@@ -0,0 +1,129 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::io::{ Cursor, Read, Seek, SeekFrom, Write };
+
+use byteorder::{ LittleEndian, ReadBytesExt, WriteBytesExt };
+
+use crate::index::{
+    hash::hash_page_layout::{ BucketPageHeader, BUCKET_PAGE_HEADER_SIZE, HASH_BUCKET_PAGE_TYPE },
+    tree::{
+        byte_box::{ ByteBox, DataType },
+        index_types::KeyValuePair,
+        tree_node::node_type::PagePointer,
+        tree_page::{
+            codec::Codec,
+            tree_page_layout::{ NodeHeader, OVERFLOW_VALUE_FLAG, PAGE_SIZE, PTR_SIZE },
+        },
+    },
+};
+
+/// Entries of an extendible hash index whose hashes agree on their lowest `local_depth` bits
+///
+/// Entries are kept unordered, each as its key and value behind their lengths. Large values are
+/// kept in overflow pages as in a tree leaf, their length flagged with `OVERFLOW_VALUE_FLAG`.
+#[derive(Debug, Clone)]
+pub struct HashBucket {
+    pub page: PagePointer,
+    pub local_depth: u32,
+    pub entries: Vec<KeyValuePair>,
+}
+
+impl HashBucket {
+    pub fn new(page: PagePointer, local_depth: u32) -> Self {
+        Self { page, local_depth, entries: Vec::new() }
+    }
+
+    /// Bytes an entry takes up in the bucket page
+    pub fn entry_size(entry: &KeyValuePair) -> usize {
+        2 * PTR_SIZE + entry.key.data.len() + entry.value.data.len()
+    }
+
+    /// Whether the page still has room for the entry
+    pub fn fits(&self, entry: &KeyValuePair) -> bool {
+        let used: usize = self.entries.iter().map(HashBucket::entry_size).sum();
+
+        BUCKET_PAGE_HEADER_SIZE + used + HashBucket::entry_size(entry) <= PAGE_SIZE
+    }
+
+    /// Position of the entry stored under the key
+    pub fn position(&self, key: &ByteBox) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.key.data == key.data)
+    }
+
+    pub fn encode(&self) -> anyhow::Result<[u8; PAGE_SIZE]> {
+        let mut raw = [0u8; PAGE_SIZE];
+        let mut cursor = Cursor::new(&mut raw[..]);
+
+        cursor.seek(SeekFrom::Start(NodeHeader::NodeType.offset() as u64))?;
+        cursor.write_u8(HASH_BUCKET_PAGE_TYPE)?;
+
+        cursor.seek(SeekFrom::Start(NodeHeader::Pointer.offset() as u64))?;
+        cursor.write_u32::<LittleEndian>(self.page)?;
+
+        cursor.seek(SeekFrom::Start(BucketPageHeader::LocalDepth.offset() as u64))?;
+        cursor.write_u32::<LittleEndian>(self.local_depth)?;
+
+        cursor.seek(SeekFrom::Start(BucketPageHeader::NumEntries.offset() as u64))?;
+        cursor.write_u32::<LittleEndian>(self.entries.len() as u32)?;
+
+        cursor.seek(SeekFrom::Start(BUCKET_PAGE_HEADER_SIZE as u64))?;
+        for entry in &self.entries {
+            let mut value_len = entry.value.data.len() as u32;
+            if entry.value.datatype == DataType::Overflow {
+                value_len |= OVERFLOW_VALUE_FLAG;
+            }
+
+            cursor.write_u32::<LittleEndian>(entry.key.data.len() as u32)?;
+            cursor.write_all(&entry.key.data)?;
+            cursor.write_u32::<LittleEndian>(value_len)?;
+            cursor.write_all(&entry.value.data)?;
+        }
+
+        Ok(raw)
+    }
+
+    pub fn decode(raw: &[u8; PAGE_SIZE], codec: &Codec) -> anyhow::Result<Self> {
+        let mut cursor = Cursor::new(&raw[..]);
+
+        cursor.seek(SeekFrom::Start(NodeHeader::NodeType.offset() as u64))?;
+        if cursor.read_u8()? != HASH_BUCKET_PAGE_TYPE {
+            return Err(anyhow::Error::msg("Expected a hash bucket page"));
+        }
+
+        cursor.seek(SeekFrom::Start(NodeHeader::Pointer.offset() as u64))?;
+        let page = cursor.read_u32::<LittleEndian>()?;
+
+        cursor.seek(SeekFrom::Start(BucketPageHeader::LocalDepth.offset() as u64))?;
+        let local_depth = cursor.read_u32::<LittleEndian>()?;
+
+        cursor.seek(SeekFrom::Start(BucketPageHeader::NumEntries.offset() as u64))?;
+        let num_entries = cursor.read_u32::<LittleEndian>()?;
+
+        cursor.seek(SeekFrom::Start(BUCKET_PAGE_HEADER_SIZE as u64))?;
+        let mut entries = Vec::with_capacity(num_entries as usize);
+
+        for _ in 0..num_entries {
+            let key_len = cursor.read_u32::<LittleEndian>()?;
+            let mut key = vec![0u8; key_len as usize];
+            cursor.read_exact(&mut key)?;
+
+            let value_len = cursor.read_u32::<LittleEndian>()?;
+            let mut value = vec![0u8; (value_len & !OVERFLOW_VALUE_FLAG) as usize];
+            cursor.read_exact(&mut value)?;
+
+            let value_type = if value_len & OVERFLOW_VALUE_FLAG != 0 {
+                &DataType::Overflow
+            } else {
+                &codec.value_type
+            };
+
+            entries.push(KeyValuePair {
+                key: codec.key_type.to_byte_box(&key),
+                value: value_type.to_byte_box(&value),
+            });
+        }
+
+        Ok(Self { page, local_depth, entries })
+    }
+}
@@ -0,0 +1,289 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::io::{ Cursor, Read, Seek, SeekFrom, Write };
+
+use bincode::config;
+use byteorder::{ LittleEndian, ReadBytesExt, WriteBytesExt };
+
+use crate::{
+    buffer::flusher::Flusher,
+    index::{
+        hash::hash_page_layout::{
+            DirectoryPageHeader,
+            DIRECTORY_EXTENSIONS_OFFSET,
+            DIRECTORY_PAGE_HEADER_SIZE,
+            DIRECTORY_SLOTS_OFFSET,
+            EXTENSION_PAGE_SLOTS,
+            HASH_DIRECTORY_EXTENSION_PAGE_TYPE,
+            HASH_DIRECTORY_PAGE_ID,
+            HASH_DIRECTORY_PAGE_TYPE,
+            HEAD_DIRECTORY_SLOTS,
+            MAX_DIRECTORY_EXTENSIONS,
+            MAX_GLOBAL_DEPTH,
+        },
+        tree::{
+            byte_box::DataType,
+            tree_node::node_type::PagePointer,
+            tree_page::{
+                codec::Codec,
+                tree_page_layout::{ NodeHeader, COMMON_NODE_HEADER_SIZE, PAGE_SIZE, PTR_SIZE },
+            },
+        },
+    },
+};
+
+/// Bucket page of every slot of an extendible hash index, with the schema of its entries
+///
+/// A key belongs to the slot given by the lowest `global_depth` bits of its hash. A bucket of
+/// local depth `d` is shared by the `2^(global_depth - d)` slots agreeing on the lowest `d` bits.
+///
+/// The first `HEAD_DIRECTORY_SLOTS` slots live in the directory page, which also lists the
+/// extension pages holding the rest in slot order. Extension pages are taken as the directory
+/// doubles and freed as it halves, when it is written with `HashDirectory::write`.
+#[derive(Clone)]
+pub struct HashDirectory {
+    pub global_depth: u32,
+    pub buckets: Vec<PagePointer>,
+    pub codec: Codec,
+    pub extensions: Vec<PagePointer>,
+
+    // Slots as the extension pages hold them, so writes skip the pages left unchanged
+    pub(crate) stored: Vec<PagePointer>,
+}
+
+impl HashDirectory {
+    /// Slot of the directory a key with the given hash belongs to
+    pub fn slot_of(&self, hash: u64) -> usize {
+        (hash & ((1u64 << self.global_depth) - 1)) as usize
+    }
+
+    /// Doubles the slots, the upper half pointing at the same buckets as the lower one
+    pub fn double(&mut self) -> anyhow::Result<()> {
+        if self.global_depth >= MAX_GLOBAL_DEPTH {
+            return Err(anyhow::Error::msg("Hash directory is full"));
+        }
+
+        self.buckets.extend_from_within(..);
+        self.global_depth += 1;
+
+        Ok(())
+    }
+
+    /// Whether no bucket is as deep as the directory, so it can do with half the slots
+    ///
+    /// A bucket shallower than the directory has every slot of the lower half it is in repeated
+    /// half the directory further on, while one as deep as it has a single slot.
+    pub fn can_halve(&self) -> bool {
+        let half = self.buckets.len() / 2;
+
+        self.global_depth > 0 && self.buckets[..half] == self.buckets[half..]
+    }
+
+    /// Halves the slots, which is only sound once no bucket is as deep as the directory
+    pub fn halve(&mut self) {
+        self.global_depth -= 1;
+        self.buckets.truncate(1 << self.global_depth);
+    }
+
+    /// Number of extension pages the slots of a directory of the given depth take
+    pub fn extensions_needed(global_depth: u32) -> usize {
+        (1usize << global_depth).saturating_sub(HEAD_DIRECTORY_SLOTS).div_ceil(EXTENSION_PAGE_SLOTS)
+    }
+
+    /// Reads the directory page and every extension page
+    pub fn read(flusher: &Flusher) -> anyhow::Result<Self> {
+        let mut directory = HashDirectory::decode_head(&flusher.read_drop(HASH_DIRECTORY_PAGE_ID))?;
+        let len = 1usize << directory.global_depth;
+
+        for page in directory.extensions.clone() {
+            let slots = (len - directory.buckets.len()).min(EXTENSION_PAGE_SLOTS);
+            let raw = flusher.read_drop(page);
+
+            directory.buckets.extend(decode_extension(&raw, 0..slots)?);
+        }
+
+        directory.stored = directory.buckets.clone();
+
+        Ok(directory)
+    }
+
+    /// Slot a hash belongs to and its bucket page, reading only the directory page holding it
+    pub fn locate(flusher: &Flusher, hash: u64) -> anyhow::Result<(usize, PagePointer)> {
+        let head = HashDirectory::decode_head(&flusher.read_drop(HASH_DIRECTORY_PAGE_ID))?;
+        let slot = head.slot_of(hash);
+
+        if slot < HEAD_DIRECTORY_SLOTS {
+            return Ok((slot, head.buckets[slot]));
+        }
+
+        let (page, idx) = (
+            (slot - HEAD_DIRECTORY_SLOTS) / EXTENSION_PAGE_SLOTS,
+            (slot - HEAD_DIRECTORY_SLOTS) % EXTENSION_PAGE_SLOTS,
+        );
+        let raw = flusher.read_drop(head.extensions[page]);
+
+        Ok((slot, decode_extension(&raw, idx..idx + 1)?[0]))
+    }
+
+    /// Writes the directory out, taking or freeing extension pages as its depth asks
+    pub fn write(&mut self, flusher: &Flusher) -> anyhow::Result<()> {
+        let needed = HashDirectory::extensions_needed(self.global_depth);
+
+        if needed > MAX_DIRECTORY_EXTENSIONS {
+            return Err(anyhow::Error::msg("Hash directory does not fit its extension pages"));
+        }
+
+        while self.extensions.len() < needed {
+            self.extensions.push(flusher.new_page());
+        }
+
+        for page in self.extensions.split_off(needed) {
+            flusher.free_page(page)?;
+        }
+
+        flusher.write_flush(self.encode_head()?, HASH_DIRECTORY_PAGE_ID)?;
+
+        let extension_slots = self.buckets.get(HEAD_DIRECTORY_SLOTS..).unwrap_or_default();
+        let stored_slots = self.stored.get(HEAD_DIRECTORY_SLOTS..).unwrap_or_default();
+
+        let chunks = extension_slots.chunks(EXTENSION_PAGE_SLOTS);
+        let mut stored_chunks = stored_slots.chunks(EXTENSION_PAGE_SLOTS);
+
+        for (page, slots) in self.extensions.iter().zip(chunks) {
+            if stored_chunks.next() != Some(slots) {
+                flusher.write_flush(encode_extension(*page, slots)?, *page)?;
+            }
+        }
+
+        self.stored = self.buckets.clone();
+
+        Ok(())
+    }
+
+    fn encode_head(&self) -> anyhow::Result<[u8; PAGE_SIZE]> {
+        let schema = bincode
+            ::encode_to_vec((&self.codec.key_type, &self.codec.value_type), config::standard())
+            .map_err(|err| anyhow::Error::msg(format!("Unable to encode index schema: {}", err)))?;
+
+        if DIRECTORY_PAGE_HEADER_SIZE + schema.len() > DIRECTORY_SLOTS_OFFSET {
+            return Err(anyhow::Error::msg("Index schema does not fit in the directory page"));
+        }
+
+        let mut raw = [0u8; PAGE_SIZE];
+        let mut cursor = Cursor::new(&mut raw[..]);
+
+        cursor.seek(SeekFrom::Start(NodeHeader::NodeType.offset() as u64))?;
+        cursor.write_u8(HASH_DIRECTORY_PAGE_TYPE)?;
+
+        cursor.seek(SeekFrom::Start(NodeHeader::Pointer.offset() as u64))?;
+        cursor.write_u32::<LittleEndian>(HASH_DIRECTORY_PAGE_ID)?;
+
+        cursor.seek(SeekFrom::Start(DirectoryPageHeader::GlobalDepth.offset() as u64))?;
+        cursor.write_u32::<LittleEndian>(self.global_depth)?;
+
+        cursor.seek(SeekFrom::Start(DirectoryPageHeader::SchemaLength.offset() as u64))?;
+        cursor.write_u32::<LittleEndian>(schema.len() as u32)?;
+
+        cursor.seek(SeekFrom::Start(DIRECTORY_PAGE_HEADER_SIZE as u64))?;
+        cursor.write_all(&schema)?;
+
+        cursor.seek(SeekFrom::Start(DIRECTORY_SLOTS_OFFSET as u64))?;
+        for bucket in self.buckets.iter().take(HEAD_DIRECTORY_SLOTS) {
+            cursor.write_u32::<LittleEndian>(*bucket)?;
+        }
+
+        cursor.seek(SeekFrom::Start(DIRECTORY_EXTENSIONS_OFFSET as u64))?;
+        for page in &self.extensions {
+            cursor.write_u32::<LittleEndian>(*page)?;
+        }
+
+        Ok(raw)
+    }
+
+    /// Reads the directory page, leaving the slots of extension pages out
+    fn decode_head(raw: &[u8; PAGE_SIZE]) -> anyhow::Result<Self> {
+        let mut cursor = Cursor::new(&raw[..]);
+
+        cursor.seek(SeekFrom::Start(NodeHeader::NodeType.offset() as u64))?;
+        if cursor.read_u8()? != HASH_DIRECTORY_PAGE_TYPE {
+            return Err(anyhow::Error::msg("Expected a hash directory page"));
+        }
+
+        cursor.seek(SeekFrom::Start(DirectoryPageHeader::GlobalDepth.offset() as u64))?;
+        let global_depth = cursor.read_u32::<LittleEndian>()?;
+
+        if global_depth > MAX_GLOBAL_DEPTH {
+            return Err(anyhow::Error::msg(format!("Global depth {} is too deep", global_depth)));
+        }
+
+        cursor.seek(SeekFrom::Start(DirectoryPageHeader::SchemaLength.offset() as u64))?;
+        let schema_len = cursor.read_u32::<LittleEndian>()? as usize;
+
+        if DIRECTORY_PAGE_HEADER_SIZE + schema_len > DIRECTORY_SLOTS_OFFSET {
+            return Err(anyhow::Error::msg("Index schema overruns the directory page"));
+        }
+
+        let mut schema = vec![0u8; schema_len];
+        cursor.seek(SeekFrom::Start(DIRECTORY_PAGE_HEADER_SIZE as u64))?;
+        cursor.read_exact(&mut schema)?;
+
+        let ((key_type, value_type), _): ((DataType, DataType), usize) = bincode
+            ::decode_from_slice(&schema, config::standard())
+            .map_err(|err| anyhow::Error::msg(format!("Unable to decode index schema: {}", err)))?;
+
+        cursor.seek(SeekFrom::Start(DIRECTORY_SLOTS_OFFSET as u64))?;
+        let buckets = (0..(1usize << global_depth).min(HEAD_DIRECTORY_SLOTS))
+            .map(|_| cursor.read_u32::<LittleEndian>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        cursor.seek(SeekFrom::Start(DIRECTORY_EXTENSIONS_OFFSET as u64))?;
+        let extensions = (0..HashDirectory::extensions_needed(global_depth))
+            .map(|_| cursor.read_u32::<LittleEndian>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            global_depth,
+            buckets,
+            codec: Codec { key_type, value_type },
+            extensions,
+            stored: Vec::new(),
+        })
+    }
+}
+
+fn encode_extension(page: PagePointer, slots: &[PagePointer]) -> anyhow::Result<[u8; PAGE_SIZE]> {
+    let mut raw = [0u8; PAGE_SIZE];
+    let mut cursor = Cursor::new(&mut raw[..]);
+
+    cursor.seek(SeekFrom::Start(NodeHeader::NodeType.offset() as u64))?;
+    cursor.write_u8(HASH_DIRECTORY_EXTENSION_PAGE_TYPE)?;
+
+    cursor.seek(SeekFrom::Start(NodeHeader::Pointer.offset() as u64))?;
+    cursor.write_u32::<LittleEndian>(page)?;
+
+    cursor.seek(SeekFrom::Start(COMMON_NODE_HEADER_SIZE as u64))?;
+    for bucket in slots {
+        cursor.write_u32::<LittleEndian>(*bucket)?;
+    }
+
+    Ok(raw)
+}
+
+/// Reads the slots of an extension page within the range
+fn decode_extension(
+    raw: &[u8; PAGE_SIZE],
+    slots: std::ops::Range<usize>
+) -> anyhow::Result<Vec<PagePointer>> {
+    let mut cursor = Cursor::new(&raw[..]);
+
+    cursor.seek(SeekFrom::Start(NodeHeader::NodeType.offset() as u64))?;
+    if cursor.read_u8()? != HASH_DIRECTORY_EXTENSION_PAGE_TYPE {
+        return Err(anyhow::Error::msg("Expected a hash directory extension page"));
+    }
+
+    cursor.seek(SeekFrom::Start((COMMON_NODE_HEADER_SIZE + slots.start * PTR_SIZE) as u64))?;
+    slots
+        .map(|_| cursor.read_u32::<LittleEndian>().map_err(anyhow::Error::from))
+        .collect()
+}
@@ -0,0 +1,539 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::{ collections::{ HashMap, HashSet }, path::{ Path, PathBuf }, sync::Arc };
+
+use crate::{
+    buffer::{ buffer_pool_manager::FileId, flusher::Flusher },
+    index::{
+        hash::{
+            bucket::HashBucket,
+            directory::HashDirectory,
+            hash_page_layout::HASH_DIRECTORY_PAGE_ID,
+        },
+        index_api::{ Index, IndexKind, IndexStats },
+        tree::{
+            byte_box::{ ByteBox, DataType },
+            db::{ btree_obj::BTreeBuilder, btree_verify::Violation },
+            index_types::{ KeyValuePair, NodeKey },
+            tree_node::node_type::PagePointer,
+            tree_page::{
+                codec::Codec,
+                overflow::OverflowChain,
+                tree_page_layout::{ MAX_KEY_SIZE, OVERFLOW_THRESHOLD },
+            },
+        },
+    },
+};
+
+/// Hash of a key's bytes, whose low bits pick its directory slot
+///
+/// FNV-1a followed by a final mix, so that keys differing only in their last bytes still
+/// spread over the low bits.
+pub fn hash_key(key: &ByteBox) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+
+    for byte in &key.data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+
+    hash
+}
+
+/// An on-disk extendible hash index
+///
+/// The first page of the file is the directory, mapping the low bits of a key's hash to the
+/// bucket page holding it. Slots past those the first page holds are kept in extension pages.
+/// A full bucket is split in two on the next bit of the hash, doubling the directory first when
+/// the bucket was already told apart on every bit the directory uses. A bucket emptied by a
+/// delete is merged back into its buddy, and the directory halves once no bucket needs all of
+/// its bits.
+///
+/// Lookups of a single key read the directory page holding its slot and one bucket. Entries are
+/// not kept in key order, so there are no range scans.
+pub struct HashIndex {
+    pub flusher: Arc<Flusher>,
+    pub index_id: FileId,
+    pub codec: Codec,
+}
+
+pub struct HashIndexBuilder {
+    pub(crate) schema: Codec,
+}
+
+impl HashIndexBuilder {
+    pub fn new() -> HashIndexBuilder {
+        HashIndexBuilder {
+            schema: Codec { key_type: DataType::None, value_type: DataType::None },
+        }
+    }
+
+    pub fn index_schema(&mut self, codec: Codec) -> &mut Self {
+        self.schema = codec;
+        self
+    }
+
+    pub fn build(&self) -> anyhow::Result<HashIndex> {
+        let (flusher, file_id) = BTreeBuilder::new_flusher();
+
+        let directory_page = flusher.new_page();
+        if directory_page != HASH_DIRECTORY_PAGE_ID {
+            return Err(anyhow::Error::msg("Hash directory has to be the first page of its file"));
+        }
+
+        let bucket = HashBucket::new(flusher.new_page(), 0);
+        flusher.write_flush(bucket.encode()?, bucket.page)?;
+
+        let mut directory = HashDirectory {
+            global_depth: 0,
+            buckets: vec![bucket.page],
+            codec: self.schema.clone(),
+            extensions: Vec::new(),
+            stored: Vec::new(),
+        };
+        directory.write(&flusher)?;
+
+        Ok(HashIndex {
+            flusher: Arc::new(flusher),
+            index_id: file_id,
+            codec: self.schema.clone(),
+        })
+    }
+
+    /// Opens an index file written by `build`, taking its schema from the directory page
+    pub fn build_from_file(
+        &self,
+        path: impl AsRef<Path> + std::marker::Copy + std::fmt::Debug
+    ) -> anyhow::Result<HashIndex> {
+        let (flusher, file_id) = BTreeBuilder::open_flusher(path);
+
+        if !flusher.is_allocated(HASH_DIRECTORY_PAGE_ID) {
+            return Err(anyhow::Error::msg(format!("{:?} holds no hash index", path)));
+        }
+
+        let directory = HashDirectory::read(&flusher)?;
+
+        Ok(HashIndex {
+            flusher: Arc::new(flusher),
+            index_id: file_id,
+            codec: directory.codec,
+        })
+    }
+}
+
+impl HashIndex {
+    /// Inserts the entry, failing if its key is already present
+    pub fn insert(&self, entry: KeyValuePair) -> anyhow::Result<()> {
+        self.write(entry, false)
+    }
+
+    /// Inserts the entry, replacing the value stored under its key if there is one
+    pub fn put(&self, entry: KeyValuePair) -> anyhow::Result<()> {
+        self.write(entry, true)
+    }
+
+    pub fn search(&self, search: NodeKey) -> anyhow::Result<KeyValuePair> {
+        let key = HashIndex::search_key(search);
+        let bucket = self.bucket_of(&key)?;
+
+        let idx = bucket.position(&key).ok_or(anyhow::Error::msg("Key not present"))?;
+        let entry = bucket.entries[idx].clone();
+
+        Ok(KeyValuePair {
+            key: entry.key,
+            value: self.resolve_value(entry.value)?,
+        })
+    }
+
+    pub fn contains_key(&self, key: &ByteBox) -> anyhow::Result<bool> {
+        Ok(self.bucket_of(key)?.position(key).is_some())
+    }
+
+    /// Removes the entry with the searched key
+    pub fn delete(&self, search: NodeKey) -> anyhow::Result<()> {
+        let key = HashIndex::search_key(search);
+        let (slot, page) = HashDirectory::locate(&self.flusher, hash_key(&key))?;
+        let mut bucket = self.read_bucket(page)?;
+
+        let idx = bucket.position(&key).ok_or(anyhow::Error::msg("Key not present"))?;
+        let removed = bucket.entries.remove(idx);
+
+        self.flusher.write_flush(bucket.encode()?, bucket.page)?;
+        self.free_value(&removed.value)?;
+
+        if !bucket.entries.is_empty() {
+            return Ok(());
+        }
+
+        // Only merging needs every slot of the directory
+        let mut directory = self.read_directory()?;
+        if self.merge(&mut directory, slot, bucket)? {
+            directory.write(&self.flusher)?;
+        }
+
+        Ok(())
+    }
+
+    /// Number of hash bits the directory tells buckets apart by
+    pub fn global_depth(&self) -> anyhow::Result<u32> {
+        Ok(self.read_directory()?.global_depth)
+    }
+
+    /// Entries and pages of the index, read from every bucket
+    pub fn stats(&self) -> anyhow::Result<IndexStats> {
+        let directory = self.read_directory()?;
+        let pages: HashSet<PagePointer> = directory.buckets.iter().copied().collect();
+
+        let mut num_entries = 0;
+        for page in &pages {
+            num_entries += self.read_bucket(*page)?.entries.len();
+        }
+
+        Ok(IndexStats {
+            kind: IndexKind::Hash,
+            num_entries,
+            node_pages: pages.len() + 1 + directory.extensions.len(),
+            free_pages: self.flusher.free_page_count(),
+        })
+    }
+
+    /// Writes every page of the index still held in memory to its file
+    pub fn flush(&self) -> anyhow::Result<()> {
+        self.flusher.flush_all()
+    }
+
+    /// File the index lives in, for reopening it with `HashIndexBuilder::build_from_file`
+    pub fn file_path(&self) -> Option<PathBuf> {
+        self.flusher.file_path()
+    }
+
+    /// Checks the directory and every bucket, an empty list meaning the index is sound
+    ///
+    /// Every slot has to point at an allocated bucket no deeper than the directory, shared by
+    /// exactly the slots agreeing with it on its local depth, and every entry has to hash to
+    /// the slots of its bucket.
+    pub fn verify(&self) -> Vec<Violation> {
+        let mut violations = Vec::new();
+
+        let directory = match self.read_directory() {
+            Ok(directory) => directory,
+            Err(err) => {
+                return vec![Violation { page: HASH_DIRECTORY_PAGE_ID, message: err.to_string() }];
+            }
+        };
+
+        for page in &directory.extensions {
+            if !self.flusher.is_allocated(*page) {
+                let message = "directory extension page is free".to_string();
+                violations.push(Violation { page: *page, message });
+            }
+        }
+
+        let mut slots: HashMap<PagePointer, Vec<usize>> = HashMap::new();
+        for (slot, page) in directory.buckets.iter().enumerate() {
+            slots.entry(*page).or_default().push(slot);
+        }
+
+        for (page, bucket_slots) in slots {
+            let mut report = |message: String| violations.push(Violation { page, message });
+
+            if !self.flusher.is_allocated(page) {
+                report("bucket page is free".to_string());
+                continue;
+            }
+
+            let bucket = match self.read_bucket(page) {
+                Ok(bucket) => bucket,
+                Err(err) => {
+                    report(err.to_string());
+                    continue;
+                }
+            };
+
+            if bucket.local_depth > directory.global_depth {
+                report(format!("local depth {} exceeds the directory's", bucket.local_depth));
+                continue;
+            }
+
+            let mask = (1usize << bucket.local_depth) - 1;
+            let expected = 1usize << (directory.global_depth - bucket.local_depth);
+
+            if bucket_slots.len() != expected {
+                report(format!("shared by {} slots, expected {}", bucket_slots.len(), expected));
+            }
+
+            if bucket_slots.iter().any(|slot| (slot & mask) != (bucket_slots[0] & mask)) {
+                report("shared by slots disagreeing on its local depth".to_string());
+            }
+
+            let mut keys = HashSet::new();
+            for entry in &bucket.entries {
+                if ((hash_key(&entry.key) as usize) & mask) != (bucket_slots[0] & mask) {
+                    report(format!("holds key {:?} hashing to another bucket", entry.key.data));
+                }
+
+                if !keys.insert(entry.key.data.clone()) {
+                    report(format!("holds key {:?} more than once", entry.key.data));
+                }
+
+                if entry.value.datatype != DataType::Overflow {
+                    continue;
+                }
+
+                let unallocated = OverflowChain::from_stub(&entry.value)
+                    .and_then(|chain| chain.pages(&self.flusher))
+                    .map(|pages| pages.into_iter().find(|page| !self.flusher.is_allocated(*page)));
+
+                match unallocated {
+                    Ok(Some(overflow)) => report(format!("overflow page {} is free", overflow)),
+                    Ok(None) => {}
+                    Err(err) => report(err.to_string()),
+                }
+            }
+        }
+
+        violations.sort_by_key(|violation| violation.page);
+        violations
+    }
+
+    fn write(&self, mut entry: KeyValuePair, replace: bool) -> anyhow::Result<()> {
+        self.check_entry(&entry)?;
+
+        // Checked ahead of writing the value to overflow pages it would then have to free
+        if !replace && self.contains_key(&entry.key)? {
+            return Err(anyhow::Error::msg("Entry already exists"));
+        }
+
+        // Stored values are never larger than the overflow threshold, so the entry fits a
+        // bucket once the keys hashing like it on every bit the directory can use are split away
+        entry.value = self.store_value(entry.value)?;
+
+        let hash = hash_key(&entry.key);
+
+        loop {
+            let (slot, page) = HashDirectory::locate(&self.flusher, hash)?;
+            let mut bucket = self.read_bucket(page)?;
+
+            let existing = bucket.position(&entry.key);
+            if existing.is_some() && !replace {
+                self.free_value(&entry.value)?;
+                return Err(anyhow::Error::msg("Entry already exists"));
+            }
+
+            let replaced = existing.map(|idx| bucket.entries.remove(idx));
+
+            if bucket.fits(&entry) {
+                bucket.entries.push(entry);
+                self.flusher.write_flush(bucket.encode()?, bucket.page)?;
+
+                return match replaced {
+                    Some(replaced) => self.free_value(&replaced.value),
+                    None => Ok(()),
+                };
+            }
+
+            // The replaced entry is split along with the others and dropped on the next pass
+            bucket.entries.extend(replaced);
+
+            // Only splitting needs every slot of the directory
+            let split = self
+                .read_directory()
+                .and_then(|mut directory| self.split(&mut directory, bucket));
+
+            if let Err(err) = split {
+                self.free_value(&entry.value)?;
+                return Err(err);
+            }
+        }
+    }
+
+    /// Splits a full bucket on the next bit of its hashes, doubling the directory if needed
+    fn split(&self, directory: &mut HashDirectory, mut bucket: HashBucket) -> anyhow::Result<()> {
+        if bucket.local_depth == directory.global_depth {
+            directory.double()?;
+        }
+
+        let bit = 1u64 << bucket.local_depth;
+        let mut image = HashBucket::new(self.flusher.new_page(), bucket.local_depth + 1);
+        bucket.local_depth += 1;
+
+        let (moved, kept) = std::mem::take(&mut bucket.entries)
+            .into_iter()
+            .partition(|entry| hash_key(&entry.key) & bit != 0);
+        image.entries = moved;
+        bucket.entries = kept;
+
+        self.flusher.write_flush(image.encode()?, image.page)?;
+        self.flusher.write_flush(bucket.encode()?, bucket.page)?;
+
+        for (slot, page) in directory.buckets.iter_mut().enumerate() {
+            if *page == bucket.page && (slot as u64) & bit != 0 {
+                *page = image.page;
+            }
+        }
+
+        directory.write(&self.flusher)
+    }
+
+    /// Folds a bucket and its buddy into one while either is empty and both sit at one depth
+    ///
+    /// Returns whether the directory changed, halving it once no bucket needs all of its bits.
+    fn merge(
+        &self,
+        directory: &mut HashDirectory,
+        slot: usize,
+        mut bucket: HashBucket
+    ) -> anyhow::Result<bool> {
+        let mut changed = false;
+
+        while bucket.local_depth > 0 {
+            let buddy_slot = slot ^ (1 << (bucket.local_depth - 1));
+            let buddy = self.read_bucket(directory.buckets[buddy_slot])?;
+
+            if buddy.local_depth != bucket.local_depth {
+                break;
+            }
+
+            let (empty, mut kept) = match (bucket.entries.is_empty(), buddy.entries.is_empty()) {
+                (true, _) => (bucket, buddy),
+                (false, true) => (buddy, bucket),
+                (false, false) => {
+                    break;
+                }
+            };
+
+            for page in directory.buckets.iter_mut().filter(|page| **page == empty.page) {
+                *page = kept.page;
+            }
+
+            kept.local_depth -= 1;
+            self.flusher.write_flush(kept.encode()?, kept.page)?;
+            self.flusher.free_page(empty.page)?;
+
+            changed = true;
+            bucket = kept;
+        }
+
+        while changed && directory.can_halve() {
+            directory.halve();
+        }
+
+        Ok(changed)
+    }
+
+    /// Bucket of the key, reading only the directory page holding its slot
+    fn bucket_of(&self, key: &ByteBox) -> anyhow::Result<HashBucket> {
+        self.read_bucket(HashDirectory::locate(&self.flusher, hash_key(key))?.1)
+    }
+
+    fn read_directory(&self) -> anyhow::Result<HashDirectory> {
+        HashDirectory::read(&self.flusher)
+    }
+
+    fn read_bucket(&self, page: PagePointer) -> anyhow::Result<HashBucket> {
+        HashBucket::decode(&self.flusher.read_drop(page), &self.codec)
+    }
+
+    fn search_key(search: NodeKey) -> ByteBox {
+        match search {
+            NodeKey::GuidePost(key) => key,
+            NodeKey::KeyValuePair(entry) => entry.key,
+        }
+    }
+
+    fn check_entry(&self, entry: &KeyValuePair) -> anyhow::Result<()> {
+        if !self.codec.key_type.accepts(&entry.key.datatype) {
+            return Err(anyhow::Error::msg("Schema Mismatch"));
+        }
+
+        if entry.key.data.len() > MAX_KEY_SIZE {
+            return Err(anyhow::Error::msg("Key too large"));
+        }
+
+        if self.codec.value_type.to_string() != entry.value.datatype.to_string() {
+            return Err(anyhow::Error::msg("Schema Mismatch"));
+        }
+
+        Ok(())
+    }
+
+    fn store_value(&self, value: ByteBox) -> anyhow::Result<ByteBox> {
+        if value.data.len() <= OVERFLOW_THRESHOLD {
+            return Ok(value);
+        }
+
+        Ok(OverflowChain::write(&self.flusher, &value.data)?.to_stub())
+    }
+
+    fn resolve_value(&self, value: ByteBox) -> anyhow::Result<ByteBox> {
+        if value.datatype != DataType::Overflow {
+            return Ok(value);
+        }
+
+        let data = OverflowChain::from_stub(&value)?.read(&self.flusher)?;
+        Ok(self.codec.value_type.to_byte_box(&data))
+    }
+
+    fn free_value(&self, value: &ByteBox) -> anyhow::Result<()> {
+        if value.datatype != DataType::Overflow {
+            return Ok(());
+        }
+
+        OverflowChain::from_stub(value)?.free(&self.flusher)
+    }
+}
+
+impl Index for HashIndex {
+    fn kind(&self) -> IndexKind {
+        IndexKind::Hash
+    }
+
+    fn codec(&self) -> &Codec {
+        &self.codec
+    }
+
+    fn get(&self, key: &ByteBox) -> anyhow::Result<KeyValuePair> {
+        HashIndex::search(self, NodeKey::GuidePost(key.clone()))
+    }
+
+    fn insert(&self, entry: KeyValuePair) -> anyhow::Result<()> {
+        HashIndex::insert(self, entry)
+    }
+
+    fn put(&self, entry: KeyValuePair) -> anyhow::Result<()> {
+        HashIndex::put(self, entry)
+    }
+
+    fn delete(&self, key: &ByteBox) -> anyhow::Result<()> {
+        HashIndex::delete(self, NodeKey::GuidePost(key.clone()))
+    }
+
+    fn contains_key(&self, key: &ByteBox) -> anyhow::Result<bool> {
+        HashIndex::contains_key(self, key)
+    }
+
+    fn stats(&self) -> anyhow::Result<IndexStats> {
+        HashIndex::stats(self)
+    }
+
+    fn verify(&self) -> Vec<Violation> {
+        HashIndex::verify(self)
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        HashIndex::flush(self)
+    }
+
+    fn file_path(&self) -> Option<PathBuf> {
+        HashIndex::file_path(self)
+    }
+
+    fn entry_page_id(&self) -> Option<u32> {
+        Some(HASH_DIRECTORY_PAGE_ID)
+    }
+}
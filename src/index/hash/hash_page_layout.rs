@@ -0,0 +1,99 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use crate::index::tree::tree_page::tree_page_layout::{
+    COMMON_NODE_HEADER_SIZE,
+    PAGE_SIZE,
+    PTR_SIZE,
+};
+
+/// Node type byte of the directory page, after the tree's meta page (0x05)
+pub const HASH_DIRECTORY_PAGE_TYPE: u8 = 0x06;
+
+/// Node type byte of bucket pages
+pub const HASH_BUCKET_PAGE_TYPE: u8 = 0x07;
+
+/// Node type byte of the pages holding the directory slots past the first page's
+pub const HASH_DIRECTORY_EXTENSION_PAGE_TYPE: u8 = 0x08;
+
+/// The directory is the first page of the index file
+pub const HASH_DIRECTORY_PAGE_ID: u32 = 0;
+
+#[derive(Debug)]
+pub enum DirectoryPageHeader {
+    GlobalDepth,
+    SchemaLength,
+}
+
+impl DirectoryPageHeader {
+    pub fn size(&self) -> usize {
+        match *self {
+            DirectoryPageHeader::GlobalDepth => GLOBAL_DEPTH_SIZE,
+            DirectoryPageHeader::SchemaLength => HASH_SCHEMA_LENGTH_SIZE,
+        }
+    }
+
+    pub fn offset(&self) -> usize {
+        match *self {
+            DirectoryPageHeader::GlobalDepth => GLOBAL_DEPTH_OFFSET,
+            DirectoryPageHeader::SchemaLength => HASH_SCHEMA_LENGTH_OFFSET,
+        }
+    }
+}
+
+// Defining the constants specific to the directory page header
+pub const GLOBAL_DEPTH_OFFSET: usize = COMMON_NODE_HEADER_SIZE;
+pub const GLOBAL_DEPTH_SIZE: usize = PTR_SIZE;
+
+pub const HASH_SCHEMA_LENGTH_OFFSET: usize = GLOBAL_DEPTH_OFFSET + GLOBAL_DEPTH_SIZE;
+pub const HASH_SCHEMA_LENGTH_SIZE: usize = PTR_SIZE;
+
+pub const DIRECTORY_PAGE_HEADER_SIZE: usize = HASH_SCHEMA_LENGTH_OFFSET + HASH_SCHEMA_LENGTH_SIZE;
+
+/// The schema sits between the header and the slots, which start a quarter into the page
+pub const DIRECTORY_SLOTS_OFFSET: usize = PAGE_SIZE / 4;
+
+/// Slots kept in the directory page itself, the rest go to extension pages
+pub const HEAD_DIRECTORY_SLOTS: usize = 512;
+
+/// The extension pages are listed after the slots of the directory page
+pub const DIRECTORY_EXTENSIONS_OFFSET: usize =
+    DIRECTORY_SLOTS_OFFSET + HEAD_DIRECTORY_SLOTS * PTR_SIZE;
+pub const MAX_DIRECTORY_EXTENSIONS: usize = (PAGE_SIZE - DIRECTORY_EXTENSIONS_OFFSET) / PTR_SIZE;
+
+/// Slots of an extension page, which hold nothing but the common node header besides them
+pub const EXTENSION_PAGE_SLOTS: usize = (PAGE_SIZE - COMMON_NODE_HEADER_SIZE) / PTR_SIZE;
+
+/// Deepest the directory gets, its slots filling the directory page and every extension page
+pub const MAX_GLOBAL_DEPTH: u32 = 18;
+
+#[derive(Debug)]
+pub enum BucketPageHeader {
+    LocalDepth,
+    NumEntries,
+}
+
+impl BucketPageHeader {
+    pub fn size(&self) -> usize {
+        match *self {
+            BucketPageHeader::LocalDepth => LOCAL_DEPTH_SIZE,
+            BucketPageHeader::NumEntries => BUCKET_NUM_ENTRIES_SIZE,
+        }
+    }
+
+    pub fn offset(&self) -> usize {
+        match *self {
+            BucketPageHeader::LocalDepth => LOCAL_DEPTH_OFFSET,
+            BucketPageHeader::NumEntries => BUCKET_NUM_ENTRIES_OFFSET,
+        }
+    }
+}
+
+// Defining the constants specific to the bucket page header
+pub const LOCAL_DEPTH_OFFSET: usize = COMMON_NODE_HEADER_SIZE;
+pub const LOCAL_DEPTH_SIZE: usize = PTR_SIZE;
+
+pub const BUCKET_NUM_ENTRIES_OFFSET: usize = LOCAL_DEPTH_OFFSET + LOCAL_DEPTH_SIZE;
+pub const BUCKET_NUM_ENTRIES_SIZE: usize = PTR_SIZE;
+
+pub const BUCKET_PAGE_HEADER_SIZE: usize = BUCKET_NUM_ENTRIES_OFFSET + BUCKET_NUM_ENTRIES_SIZE;
@@ -0,0 +1,4 @@
+pub mod hash_page_layout;
+pub mod directory;
+pub mod bucket;
+pub mod hash_index;
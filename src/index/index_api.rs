@@ -0,0 +1,133 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::{ fmt, ops::Bound, path::{ Path, PathBuf } };
+
+use bincode::{ Decode, Encode };
+
+use crate::index::{
+    hash::hash_index::HashIndexBuilder,
+    tree::{
+        byte_box::ByteBox,
+        db::{ btree_obj::{ BPTree, BTreeBuilder }, btree_verify::Violation },
+        index_types::KeyValuePair,
+        tree_page::codec::Codec,
+    },
+};
+
+/// Structure an index is built as, recorded by the catalog for every table
+#[derive(Debug, Clone, Copy, Encode, Decode, PartialEq, Eq, Hash)]
+pub enum IndexKind {
+    BPTree,
+    Hash,
+}
+
+impl IndexKind {
+    /// Name the catalog stores the kind under
+    pub fn name(&self) -> &'static str {
+        match self {
+            IndexKind::BPTree => "BPTREE",
+            IndexKind::Hash => "HASH",
+        }
+    }
+
+    pub fn from_name(name: &str) -> anyhow::Result<IndexKind> {
+        match name {
+            "BPTREE" => Ok(IndexKind::BPTree),
+            "HASH" => Ok(IndexKind::Hash),
+            _ => Err(anyhow::Error::msg(format!("Unknown index kind {}", name))),
+        }
+    }
+}
+
+impl fmt::Display for IndexKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Figures every kind of index reports, see `Index::stats`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexStats {
+    pub kind: IndexKind,
+    pub num_entries: usize,
+
+    /// Pages holding entries or leading to them, overflow pages left out
+    pub node_pages: usize,
+
+    /// Pages freed in the index's file and not yet reused
+    pub free_pages: usize,
+}
+
+/// Operations the engine and the catalog run against an index, whatever its structure
+///
+/// Every index maps unique keys to values of the schema of its `Codec`. Range scans need keys
+/// to be kept in order, which not every structure does, so `supports_range_scan` tells whether
+/// `scan` can be called.
+pub trait Index {
+    fn kind(&self) -> IndexKind;
+
+    fn codec(&self) -> &Codec;
+
+    /// The entry stored under the key, failing if there is none
+    fn get(&self, key: &ByteBox) -> anyhow::Result<KeyValuePair>;
+
+    /// Inserts the entry, failing if its key is already present
+    fn insert(&self, entry: KeyValuePair) -> anyhow::Result<()>;
+
+    /// Inserts the entry, replacing the value stored under its key if there is one
+    fn put(&self, entry: KeyValuePair) -> anyhow::Result<()>;
+
+    /// Removes the entry stored under the key, failing if there is none
+    fn delete(&self, key: &ByteBox) -> anyhow::Result<()>;
+
+    fn contains_key(&self, key: &ByteBox) -> anyhow::Result<bool>;
+
+    fn supports_range_scan(&self) -> bool {
+        false
+    }
+
+    /// Entries with keys within the bounds in key order
+    fn scan(
+        &self,
+        range: (Bound<ByteBox>, Bound<ByteBox>)
+    ) -> anyhow::Result<Box<dyn Iterator<Item = KeyValuePair>>> {
+        Err(anyhow::Error::msg(format!("{} indexes do not support range scans", self.kind())))
+    }
+
+    fn stats(&self) -> anyhow::Result<IndexStats>;
+
+    /// Every broken invariant of the index, an empty list meaning it is sound
+    fn verify(&self) -> Vec<Violation>;
+
+    /// Writes every page of the index still held in memory to its file
+    fn flush(&self) -> anyhow::Result<()>;
+
+    /// File the index lives in, for reopening it with `open_index`
+    fn file_path(&self) -> Option<PathBuf>;
+
+    /// Page the index is reopened from, the meta page of a tree or the directory of a hash
+    /// index, None for trees sharing a file that keep no meta page
+    fn entry_page_id(&self) -> Option<u32>;
+
+    /// The index as a tree, for what only trees offer such as expiring entries
+    fn as_tree(&self) -> Option<&BPTree> {
+        None
+    }
+}
+
+/// Builds an empty index of the given kind over a file of its own
+pub fn build_index(kind: IndexKind, codec: Codec) -> anyhow::Result<Box<dyn Index>> {
+    Ok(match kind {
+        IndexKind::BPTree => Box::new(BTreeBuilder::new().tree_schema(codec).build()?),
+        IndexKind::Hash => Box::new(HashIndexBuilder::new().index_schema(codec).build()?),
+    })
+}
+
+/// Reopens an index of the given kind from the file it was flushed to
+pub fn open_index(kind: IndexKind, path: &Path) -> anyhow::Result<Box<dyn Index>> {
+    Ok(match kind {
+        IndexKind::BPTree => Box::new(BTreeBuilder::new().build_from_file(path)?),
+        IndexKind::Hash => Box::new(HashIndexBuilder::new().build_from_file(path)?),
+    })
+}
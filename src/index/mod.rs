@@ -1 +1,3 @@
 pub mod tree;
+pub mod hash;
+pub mod index_api;
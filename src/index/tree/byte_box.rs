@@ -10,8 +10,12 @@ use std::io::Cursor;
 use byteorder::ReadBytesExt;
 
 use crate::catalog::schema::Schema;
+use crate::index::tree::tree_page::tree_page_layout::EXPIRY_SIZE;
 use crate::storage::tuple::Tuple;
 
+/// Largest composite key in bytes, as a box records its length in a single byte
+pub const MAX_COMPOSITE_SIZE: usize = u8::MAX as usize;
+
 #[derive(Clone)]
 pub struct ByteBox {
     pub data: Bytes,
@@ -27,6 +31,7 @@ enum ByteValue<'a> {
     F32(f32),
     Bool(bool),
     Str(&'a str),
+    Composite(Vec<ByteBox>),
     None,
 }
 
@@ -38,6 +43,7 @@ impl<'a> PartialEq for ByteValue<'a> {
             (F32(a), F32(b)) => a == b,
             (Bool(a), Bool(b)) => a == b,
             (Str(a), Str(b)) => a == b,
+            (Composite(a), Composite(b)) => a == b,
             (None, None) => true,
             _ => false,
         }
@@ -54,6 +60,7 @@ impl<'a> PartialOrd for ByteValue<'a> {
             (F32(a), F32(b)) => a.partial_cmp(b),
             (Bool(a), Bool(b)) => Some(a.cmp(b)),
             (Str(a), Str(b)) => Some(a.cmp(b)),
+            (Composite(a), Composite(b)) => Some(a.cmp(b)),
             (None, None) => Some(Ordering::Equal),
             _ => Option::None,
         }
@@ -93,6 +100,12 @@ impl ByteBox {
                     Err(_) => None,
                 }
             }
+            DataType::Composite(_) => {
+                match self.components() {
+                    Ok(components) => Composite(components),
+                    Err(_) => None,
+                }
+            }
             DataType::None => None,
             _ => { None }
         }
@@ -140,14 +153,22 @@ impl ByteBox {
         ByteBox::new(Bytes::copy_from_slice(&buffer), DataType::Decimal)
     }
 
+    /// A string of a `Char(charlen)` column
+    ///
+    /// The value has the declared length as its type, like values decoded from a page or tuple,
+    /// rather than the length of `val`. Values longer than `charlen` are rejected like varchars.
     pub fn char(val: &str, charlen: usize) -> ByteBox {
         let char_bytes = val.as_bytes();
         let len = char_bytes.len();
 
+        if len > charlen {
+            panic!("String Upperbound crossed");
+        }
+
         ByteBox {
             data: Bytes::copy_from_slice(char_bytes),
-            datatype: DataType::Char(len),
-            data_size: len,
+            datatype: DataType::Char(charlen),
+            data_size: charlen,
             data_length: len as u8,
         }
     }
@@ -175,6 +196,171 @@ impl ByteBox {
 
         ByteBox::new(Bytes::copy_from_slice(&buffer), DataType::Boolean)
     }
+
+    /// Packs several values into a single key ordered component by component
+    ///
+    /// String components are prefixed with their length, every other component is stored as is.
+    /// Components have to be scalars and the key at most `MAX_COMPOSITE_SIZE` bytes long.
+    pub fn composite(components: &[ByteBox]) -> anyhow::Result<ByteBox> {
+        let non_scalar = components.iter().find(|component| !component.datatype.is_scalar());
+
+        if let Some(component) = non_scalar {
+            return Err(
+                anyhow::Error::msg(
+                    format!(
+                        "Composite key components must be scalars, found {}",
+                        component.datatype.to_string()
+                    )
+                )
+            );
+        }
+
+        let key = ByteBox::composite_bound(components);
+
+        if key.data.len() > MAX_COMPOSITE_SIZE {
+            return Err(
+                anyhow::Error::msg(
+                    format!(
+                        "Composite key of {} bytes is over the limit of {}",
+                        key.data.len(),
+                        MAX_COMPOSITE_SIZE
+                    )
+                )
+            );
+        }
+
+        Ok(key)
+    }
+
+    /// Packs values into a composite key that is only searched with and never stored
+    ///
+    /// Bounds of scans and seeks are built from keys callers pass in, which may be longer than
+    /// any stored key, so the size is not checked.
+    pub(crate) fn composite_bound(components: &[ByteBox]) -> ByteBox {
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut types: Vec<DataType> = Vec::with_capacity(components.len());
+
+        for component in components {
+            if component.datatype.fixed_size().is_none() {
+                buffer
+                    .write_u32::<LittleEndian>(component.data.len() as u32)
+                    .expect("Error writing an unsigned 32-bit integer");
+            }
+
+            buffer.extend_from_slice(&component.data);
+            types.push(component.datatype.clone());
+        }
+
+        ByteBox::new(Bytes::from(buffer), DataType::Composite(types))
+    }
+
+    /// Splits a composite key back into its components
+    pub fn components(&self) -> anyhow::Result<Vec<ByteBox>> {
+        let types = match &self.datatype {
+            DataType::Composite(types) => types,
+            _ => {
+                return Err(anyhow::Error::msg("Not a composite key"));
+            }
+        };
+
+        let mut components = Vec::with_capacity(types.len());
+        let mut offset = 0;
+
+        for datatype in types {
+            let len = match datatype.fixed_size() {
+                Some(size) => size,
+                None => {
+                    let mut rdr = Cursor::new(self.data.get(offset..).unwrap_or_default());
+                    offset += 4;
+
+                    rdr.read_u32::<LittleEndian>()? as usize
+                }
+            };
+
+            let data = self.data
+                .get(offset..offset + len)
+                .ok_or(anyhow::Error::msg("Truncated composite key"))?;
+            offset += len;
+
+            components.push(datatype.to_byte_box(data));
+        }
+
+        Ok(components)
+    }
+
+    /// Shortest key that still sorts after `lower` and at or before `upper`
+    ///
+    /// Used as the separator between two nodes. Strings order byte by byte, so the leading bytes
+    /// of `upper` up to the first one differing from `lower` do. Other types are returned whole.
+    pub fn shortest_separator(lower: &ByteBox, upper: &ByteBox) -> ByteBox {
+        if !matches!(upper.datatype, DataType::Char(_) | DataType::Varchar(_)) || lower >= upper {
+            return upper.clone();
+        }
+
+        let shared = lower.data
+            .iter()
+            .zip(upper.data.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        // Keep the separator valid UTF-8 by ending it on a character boundary
+        let mut end = (shared + 1).min(upper.data.len());
+        while end < upper.data.len() && (upper.data[end] & 0xc0) == 0x80 {
+            end += 1;
+        }
+
+        upper.datatype.to_byte_box(&upper.data[..end])
+    }
+
+    /// The value of an integer box, None for every other type
+    pub fn as_integer(&self) -> Option<i64> {
+        match self.datatype {
+            DataType::BigInt | DataType::Int | DataType::SmallInt => {
+                match self.to_value() {
+                    ByteValue::I64(value) => Some(value),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Wraps a value with the time it expires at, in milliseconds since the Unix epoch
+    pub fn expiring(value: ByteBox, expires_at: u64) -> ByteBox {
+        let mut buffer: Vec<u8> = Vec::with_capacity(EXPIRY_SIZE + value.data.len());
+        buffer
+            .write_u64::<LittleEndian>(expires_at)
+            .expect("Error writing an unsigned 64-bit integer");
+        buffer.extend_from_slice(&value.data);
+
+        ByteBox::new(Bytes::from(buffer), DataType::Expiring(Box::new(value.datatype)))
+    }
+
+    /// When the value expires, None for values that never do
+    pub fn expires_at(&self) -> Option<u64> {
+        match self.datatype {
+            DataType::Expiring(_) => {
+                let bytes = self.data.get(..EXPIRY_SIZE)?;
+                Some(u64::from_le_bytes(bytes.try_into().unwrap()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the value expired at or before `now`, in milliseconds since the Unix epoch
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at().is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// The value an expiring box wraps, any other box is returned as is
+    pub fn without_expiry(self) -> ByteBox {
+        match &self.datatype {
+            DataType::Expiring(inner) => {
+                inner.to_byte_box(self.data.get(EXPIRY_SIZE..).unwrap_or_default())
+            }
+            _ => self,
+        }
+    }
 }
 
 // Implementing `PartialEq` for equality comparison
@@ -188,7 +374,18 @@ impl Eq for ByteBox {}
 
 impl PartialOrd for ByteBox {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        if self.datatype != other.datatype {
+        let comparable = match (&self.datatype, &other.datatype) {
+            // A composite key compares with any key made of its leading components
+            (DataType::Composite(types), DataType::Composite(other_types)) => {
+                types
+                    .iter()
+                    .zip(other_types)
+                    .all(|(a, b)| a == b)
+            }
+            (datatype, other_type) => datatype == other_type,
+        };
+
+        if !comparable {
             return None;
         }
         Some(self.to_value().cmp(&other.to_value()))
@@ -201,9 +398,10 @@ impl Ord for ByteBox {
     }
 }
 
-impl fmt::Debug for ByteBox {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let value_str = match self.datatype {
+impl ByteBox {
+    /// The value alone, as the `Debug` output shows it
+    pub fn value_string(&self) -> String {
+        match self.datatype {
             DataType::BigInt => {
                 let mut rdr = Cursor::new(&self.data);
                 match rdr.read_i64::<LittleEndian>() {
@@ -267,8 +465,38 @@ impl fmt::Debug for ByteBox {
                 result
             }
 
+            DataType::Composite(_) =>
+                match self.components() {
+                    Ok(components) => format!("{:?}", components),
+                    Err(_) => "Invalid Composite".to_string(),
+                }
+
+            DataType::Overflow =>
+                match (self.data.get(0..4), self.data.get(4..8)) {
+                    (Some(page), Some(len)) =>
+                        format!(
+                            "page {} ({} bytes)",
+                            u32::from_le_bytes(page.try_into().unwrap()),
+                            u32::from_le_bytes(len.try_into().unwrap())
+                        ),
+                    _ => "Invalid Overflow".to_string(),
+                }
+
+            DataType::Expiring(_) =>
+                format!(
+                    "{:?} expiring at {}",
+                    self.clone().without_expiry(),
+                    self.expires_at().unwrap_or_default()
+                ),
+
             DataType::None => { "None".to_string() }
-        };
+        }
+    }
+}
+
+impl fmt::Debug for ByteBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value_str = self.value_string();
 
         match self.datatype {
             DataType::Tuple(_) => {
@@ -301,6 +529,15 @@ pub enum DataType {
 
     Tuple(Schema),
 
+    /// A key made of several values, compared in order
+    Composite(Vec<DataType>),
+
+    /// A value kept in overflow pages, the box only holds where the chain starts and its length
+    Overflow,
+
+    /// A value that expires, the box holds the expiry time ahead of the value it wraps
+    Expiring(Box<DataType>),
+
     None,
 }
 
@@ -317,10 +554,57 @@ impl DataType {
                 DataType::Boolean => "BOOLEAN",
                 DataType::None => "UNINITIALIZED",
                 DataType::Tuple(schema) => "TUPLE",
+                DataType::Composite(_) => "COMPOSITE",
+                DataType::Overflow => "OVERFLOW",
+                DataType::Expiring(_) => "EXPIRING",
             }
         ).to_string()
     }
 
+    /// Size in bytes of every value of this type, if it does not vary
+    pub fn fixed_size(&self) -> Option<usize> {
+        match self {
+            DataType::BigInt => Some(8),
+            DataType::Int | DataType::Decimal => Some(4),
+            DataType::SmallInt => Some(2),
+            DataType::Boolean => Some(1),
+            _ => None,
+        }
+    }
+
+    /// Largest size in bytes a value of this type takes, if it has one
+    pub fn max_size(&self) -> Option<usize> {
+        match self {
+            DataType::Char(len) | DataType::Varchar(len) => Some(*len),
+            DataType::Composite(types) => {
+                types
+                    .iter()
+                    .map(|component| {
+                        // Components of varying size carry a length prefix
+                        let prefix = if component.fixed_size().is_none() { 4 } else { 0 };
+                        component.max_size().map(|size| size + prefix)
+                    })
+                    .sum()
+            }
+            _ => self.fixed_size(),
+        }
+    }
+
+    /// Whether values of this type are single numbers, booleans or strings
+    pub fn is_scalar(&self) -> bool {
+        matches!(self, DataType::Char(_) | DataType::Varchar(_)) || self.fixed_size().is_some()
+    }
+
+    /// Whether a value of type `other` can be stored in a column of this type
+    ///
+    /// Composite keys have to match component for component, other types only by name.
+    pub fn accepts(&self, other: &DataType) -> bool {
+        match (self, other) {
+            (DataType::Composite(_), _) | (_, DataType::Composite(_)) => self == other,
+            _ => self.to_string() == other.to_string(),
+        }
+    }
+
     pub fn to_byte_box(&self, data: &[u8]) -> ByteBox {
         DataType::from_bytes(data, self)
     }
@@ -6,28 +6,31 @@ use anyhow::Ok;
 use crate::{
     buffer::flusher::Lock,
     index::tree::{
+        byte_box::DataType,
         db::btree_obj::WriteOperation,
         index_types::{ KeyValuePair, NodeKey },
-        tree_page::{ codec::Codec, page::TreePage },
+        tree_page::{ codec::Codec, page::TreePage, tree_page_layout::MAX_KEY_SIZE },
     },
 };
 
 use super::btree_obj::BPTree;
 
 impl BPTree {
-    pub fn insert(&self, entry: KeyValuePair) -> anyhow::Result<()> {
-        // Add real checks in the btree opps file
-        {
-            let test_entry = entry.clone();
-            let key = test_entry.key;
-            let value = test_entry.value;
-
-            if self.codec.value_type.to_string() != value.datatype.to_string() {
-                return Err(anyhow::Error::msg("Schema Mismatch"));
-            }
+    pub fn insert(&self, mut entry: KeyValuePair) -> anyhow::Result<()> {
+        if self.allow_duplicates {
+            entry = BPTree::tiebreak_entry(entry)?;
         }
 
-        let search_key = NodeKey::KeyValuePair(entry.clone());
+        self.check_entry(&entry)?;
+
+        if self.is_copy_on_write() {
+            return self.cow_insert(entry, false);
+        }
+
+        entry.value = self.store_value(entry.value)?;
+
+        // Separators are guide posts, so the descent has to compare against one
+        let search_key = NodeKey::GuidePost(entry.key.clone());
 
         let context = self.tree_descent(search_key.clone(), Lock::EXLOCK, WriteOperation::Insert)?;
         self.flusher.aqquire_context_ex(context.clone())?;
@@ -36,24 +39,68 @@ impl BPTree {
 
         let mut leaf_node = self.codec.decode(leaf_node_page)?;
 
-        leaf_node.insert_entry(entry.clone())?;
+        if let Err(err) = leaf_node.insert_entry(entry.clone()) {
+            self.flusher.release_ex_all()?;
+            self.free_value(&entry.value)?;
+
+            return Err(err);
+        }
 
-        if leaf_node.get_key_array_length() < 2 * self.b {
+        if !self.is_overfull(&leaf_node) {
             let leaf_page = Codec::encode(&leaf_node)?;
             self.flusher.pop_flush_test(leaf_page.get_data())?;
 
-            self.flusher.release_ex()?;
-
-            return Ok(());
+            // Ancestors locked in case the leaf split are no longer needed
+            self.flusher.release_ex_all()?;
         } else {
             self.propogate_upwards(leaf_node)?;
 
-            self.flusher.release_ex()?;
-            return Ok(());
+            self.flusher.release_ex_all()?;
         }
+
+        self.recount_path(&search_key)
     }
 
+    /// Checks an entry, in the form it is stored in, against the schema of the tree
+    pub(crate) fn check_entry(&self, entry: &KeyValuePair) -> anyhow::Result<()> {
+        if !self.codec.key_type.accepts(&entry.key.datatype) {
+            return Err(anyhow::Error::msg("Schema Mismatch"));
+        }
+
+        if entry.key.data.len() > MAX_KEY_SIZE {
+            return Err(anyhow::Error::msg("Key too large"));
+        }
+
+        // An expiring value is checked by the value it wraps
+        let value_type = match &entry.value.datatype {
+            DataType::Expiring(inner) => inner.as_ref(),
+            datatype => datatype,
+        };
+
+        if self.codec.value_type.to_string() != value_type.to_string() {
+            return Err(anyhow::Error::msg("Schema Mismatch"));
+        }
+
+        Ok(())
+    }
+
+    /// Removes the entry with the searched key, failing if the tree holds none
+    ///
+    /// In a tree with duplicates a key value pair removes that entry alone, while a guide post
+    /// removes every entry of the key.
     pub fn delete(&self, search: NodeKey) -> anyhow::Result<()> {
+        if self.allow_duplicates {
+            return self.delete_duplicates(search);
+        }
+
+        self.delete_entry(search)
+    }
+
+    pub(crate) fn delete_entry(&self, search: NodeKey) -> anyhow::Result<()> {
+        if self.is_copy_on_write() {
+            return self.cow_delete(search);
+        }
+
         let context = self.tree_descent(search.clone(), Lock::EXLOCK, WriteOperation::Delete)?;
         self.flusher.aqquire_context_ex(context.clone())?;
 
@@ -62,9 +109,17 @@ impl BPTree {
         let mut leaf_node = self.codec.decode(leaf_node_page)?;
         let leaf_pointer = leaf_node.pointer;
 
-        leaf_node.remove_entry(&search.clone().to_guide_post()?)?;
+        let removed = leaf_node.remove_entry(&search.clone().to_guide_post()?);
+
+        let removed = match removed.ok().flatten() {
+            Some(removed) => removed,
+            None => {
+                self.flusher.release_ex_all()?;
+                return Err(anyhow::Error::msg("Key not present"));
+            }
+        };
 
-        if leaf_node.get_key_array_length() >= self.b - 1 || leaf_node.is_root {
+        if !self.is_underfull(&leaf_node) || leaf_node.is_root {
             if leaf_node.is_root {
                 leaf_node.next_pointer = None;
             }
@@ -72,8 +127,6 @@ impl BPTree {
             self.flusher.pop_flush_test(leaf_page.get_data())?;
 
             self.flusher.release_ex_all()?;
-
-            return Ok(());
         } else {
             let parent_page = TreePage::new(self.flusher.read_parent()?);
             let parent = self.codec.decode(&parent_page)?;
@@ -81,9 +134,12 @@ impl BPTree {
             self.borrow_if_needed(parent, leaf_node, leaf_pointer)?;
 
             self.flusher.release_ex_all()?;
-
-            return Ok(());
         }
+
+        self.recount_path(&NodeKey::GuidePost(search.to_guide_post()?))?;
+
+        // The leaf no longer points at the chain, so its pages can be reused
+        self.free_value(&removed.value)
     }
 
     pub fn get_entry(&self, search: NodeKey) -> anyhow::Result<NodeKey> {
@@ -0,0 +1,334 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use crate::{
+    index::tree::{
+        byte_box::{ ByteBox, DataType },
+        index_types::{ KeyValuePair, NodeKey },
+        tree_node::{ node_type::NodeType, tree_node_inner::NodeInner },
+        tree_page::{ codec::Codec, page::TreePage },
+    },
+    wal::{ Commands, Wal, WalEntry },
+};
+
+use super::btree_obj::BPTree;
+
+/// A single write of a `WriteBatch`
+#[derive(Debug, Clone)]
+pub(crate) enum BatchOp {
+    Put(KeyValuePair),
+    Delete(ByteBox),
+}
+
+impl BatchOp {
+    fn key(&self) -> &ByteBox {
+        match self {
+            BatchOp::Put(entry) => &entry.key,
+            BatchOp::Delete(key) => key,
+        }
+    }
+}
+
+/// Puts and deletes over one or more trees, applied together by `WriteBatch::apply`
+///
+/// Operations on a tree are applied in key order. Those on the same key keep the order they
+/// were added in, so the last put of a key is the one that stays.
+#[derive(Default)]
+pub struct WriteBatch<'a> {
+    trees: Vec<&'a BPTree>,
+
+    // Operations of every tree, in the order of `trees`
+    ops: Vec<Vec<BatchOp>>,
+}
+
+impl<'a> WriteBatch<'a> {
+    pub fn new() -> Self {
+        WriteBatch { trees: Vec::new(), ops: Vec::new() }
+    }
+
+    /// Stores the entry in the tree, replacing the value of its key as `BPTree::put` does
+    pub fn put(&mut self, tree: &'a BPTree, entry: KeyValuePair) -> &mut Self {
+        self.tree_ops(tree).push(BatchOp::Put(entry));
+        self
+    }
+
+    /// Removes every entry of the key from the tree, doing nothing if there is none
+    pub fn delete(&mut self, tree: &'a BPTree, key: ByteBox) -> &mut Self {
+        self.tree_ops(tree).push(BatchOp::Delete(key));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn tree_ops(&mut self, tree: &'a BPTree) -> &mut Vec<BatchOp> {
+        let idx = match self.trees.iter().position(|known| std::ptr::eq(*known, tree)) {
+            Some(idx) => idx,
+            None => {
+                self.trees.push(tree);
+                self.ops.push(Vec::new());
+                self.trees.len() - 1
+            }
+        };
+
+        &mut self.ops[idx]
+    }
+
+    /// Applies every operation of the batch, logging it to `wal` first as a single record
+    ///
+    /// Operations are checked against the schema of their tree before anything is logged or
+    /// written, so a batch holding an invalid operation leaves every tree as it was. A write
+    /// failing after that leaves the trees holding part of the batch. With a `wal` the batch is
+    /// already logged by then, and replaying the log with `BPTree::replay` completes it. Without
+    /// one nothing undoes the writes made so far.
+    pub fn apply(self, wal: Option<&Wal>) -> anyhow::Result<()> {
+        for (tree, ops) in self.trees.iter().zip(&self.ops) {
+            for op in ops {
+                tree.check_batch_op(op)?;
+            }
+        }
+
+        if let Some(wal) = wal {
+            let entries: Vec<WalEntry> = self.trees
+                .iter()
+                .zip(&self.ops)
+                .flat_map(|(tree, ops)| ops.iter().map(|op| BPTree::wal_entry(tree, op)))
+                .collect();
+
+            wal.put_batch(&entries)?;
+            wal.sync()?;
+        }
+
+        for (tree, mut ops) in self.trees.into_iter().zip(self.ops) {
+            // A stable sort keeps operations on the same key in the order they were added
+            ops.sort_by(|a, b| a.key().cmp(b.key()));
+
+            tree.apply_sorted(ops)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Sorted operations are applied a leaf at a time. The leaf of the first pending key is found
+// with a single descent, which also yields the separator bounding the leaf from above, and every
+// following key below that separator is written to the same leaf. A leaf the writes would leave
+// over or underfull, and every tree with duplicates, goes through the regular path key by key.
+impl BPTree {
+    fn check_batch_op(&self, op: &BatchOp) -> anyhow::Result<()> {
+        match op {
+            BatchOp::Put(entry) if self.allow_duplicates => {
+                self.check_entry(&BPTree::tiebreak_entry(entry.clone())?)
+            }
+            BatchOp::Put(entry) => self.check_entry(entry),
+            BatchOp::Delete(key) => {
+                if !self.key_type().accepts(&key.datatype) {
+                    return Err(anyhow::Error::msg("Schema Mismatch"));
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Applies the logged entries of this tree again, in the order they were logged
+    ///
+    /// Every operation of a batch leaves its key in the same state however often it is applied,
+    /// so replaying a log completes batches cut short by a failure and repeats the rest. Entries
+    /// are told apart by the log id of their tree, see `BTreeBuilder::log_id`.
+    pub fn replay(&self, entries: &[WalEntry]) -> anyhow::Result<()> {
+        let entries = entries.iter().filter(|entry| entry.table_id == self.log_id);
+
+        for entry in entries {
+            let key = self.key_type().to_byte_box(&entry.key.data);
+
+            let op = match (&entry.command, &entry.data) {
+                (Commands::DEL, _) => BatchOp::Delete(key),
+                (_, Some(value)) => {
+                    BatchOp::Put(KeyValuePair { key, value: self.logged_value(value) })
+                }
+                (_, None) => {
+                    return Err(anyhow::Error::msg("Logged put without a value"));
+                }
+            };
+
+            self.check_batch_op(&op)?;
+            self.apply_op(op)?;
+        }
+
+        Ok(())
+    }
+
+    /// Rebuilds a value read back from the log with the type the tree stores
+    fn logged_value(&self, value: &ByteBox) -> ByteBox {
+        match &value.datatype {
+            DataType::Expiring(_) => {
+                DataType::Expiring(Box::new(self.codec.value_type.clone())).to_byte_box(&value.data)
+            }
+            _ => self.codec.value_type.to_byte_box(&value.data),
+        }
+    }
+
+    fn wal_entry(&self, op: &BatchOp) -> WalEntry {
+        let (command, data) = match op {
+            BatchOp::Put(entry) => (Commands::PUT, Some(entry.value.clone())),
+            BatchOp::Delete(_) => (Commands::DEL, None),
+        };
+
+        WalEntry { command, table_id: self.log_id, key: op.key().clone(), data }
+    }
+
+    fn apply_sorted(&self, ops: Vec<BatchOp>) -> anyhow::Result<()> {
+        // Copy-on-write trees never rewrite a leaf in place
+        if self.allow_duplicates || self.is_copy_on_write() {
+            for op in ops {
+                self.apply_op(op)?;
+            }
+
+            return Ok(());
+        }
+
+        let mut start = 0;
+
+        while start < ops.len() {
+            let (leaf, upper) = self.find_leaf_bounded(ops[start].key())?;
+
+            let end = match &upper {
+                Some(upper) => start + ops[start..].partition_point(|op| op.key() < upper),
+                None => ops.len(),
+            };
+
+            if !self.apply_to_leaf(leaf, &ops[start..end])? {
+                for op in &ops[start..end] {
+                    self.apply_op(op.clone())?;
+                }
+            }
+
+            start = end;
+        }
+
+        Ok(())
+    }
+
+    /// Applies the operations to the leaf with a single write, unless they leave it over or
+    /// underfull, in which case nothing is written and false is returned
+    fn apply_to_leaf(&self, mut leaf: NodeInner, ops: &[BatchOp]) -> anyhow::Result<bool> {
+        // Values moved to overflow pages for this leaf, and values the leaf no longer holds
+        let mut stored = Vec::new();
+        let mut replaced = Vec::new();
+
+        let entries = match &mut leaf.node_type {
+            NodeType::Leaf(entries, _, _) => entries,
+            _ => {
+                return Err(anyhow::Error::msg("Unexpected node type"));
+            }
+        };
+
+        for op in ops {
+            let position = entries.binary_search_by(|entry| {
+                NodeInner::deconstruct_value(entry).0.cmp(op.key())
+            });
+
+            if let Ok(idx) = position {
+                if let NodeKey::KeyValuePair(old) = entries.remove(idx) {
+                    replaced.push(old.value);
+                }
+            }
+
+            if let BatchOp::Put(entry) = op {
+                let value = self.store_value(entry.value.clone())?;
+                stored.push(value.clone());
+
+                entries.insert(
+                    position.unwrap_or_else(|idx| idx),
+                    NodeKey::KeyValuePair(KeyValuePair { key: entry.key.clone(), value })
+                );
+            }
+        }
+
+        if self.is_overfull(&leaf) || (!leaf.is_root && self.is_underfull(&leaf)) {
+            for value in &stored {
+                self.free_value(value)?;
+            }
+
+            return Ok(false);
+        }
+
+        self.flusher.write_flush(Codec::encode(&leaf)?.get_data(), leaf.pointer)?;
+        self.recount_path(&NodeKey::GuidePost(ops[0].key().clone()))?;
+
+        // The leaf no longer points at the chains, so their pages can be reused
+        for value in &replaced {
+            self.free_value(value)?;
+        }
+
+        Ok(true)
+    }
+
+    fn apply_op(&self, op: BatchOp) -> anyhow::Result<()> {
+        match op {
+            BatchOp::Put(entry) if self.allow_duplicates => {
+                let stored_key = BPTree::tiebreak_entry(entry.clone())?.key;
+
+                if self.scan_stored(stored_key.clone()..=stored_key).next().is_some() {
+                    return Ok(());
+                }
+
+                self.insert(entry)
+            }
+            BatchOp::Put(entry) => self.put(entry),
+            BatchOp::Delete(key) => {
+                // Expired entries are still stored, and deleting them is what reaps them
+                let stored = match self.allow_duplicates {
+                    true => self.contains_key(&key)?,
+                    false => self.stored_value(&key)?.is_some(),
+                };
+
+                if !stored {
+                    return Ok(());
+                }
+
+                self.delete(NodeKey::GuidePost(key))
+            }
+        }
+    }
+
+    /// Returns the leaf the key belongs in and the separator above it, if there is one
+    fn find_leaf_bounded(&self, key: &ByteBox) -> anyhow::Result<(NodeInner, Option<ByteBox>)> {
+        let search = NodeKey::GuidePost(key.clone());
+        let mut upper = None;
+
+        let mut current_node = self.codec.decode(
+            &TreePage::new(self.flusher.read_drop(*self.root_page_id.borrow()))
+        )?;
+
+        loop {
+            match &current_node.node_type {
+                NodeType::Internal(children, keys, _) => {
+                    let idx = match keys.binary_search(&search) {
+                        Ok(idx) => idx + 1,
+                        Err(idx) => idx,
+                    };
+
+                    if let Some(separator) = keys.get(idx) {
+                        upper = Some(NodeInner::deconstruct_value(separator).0);
+                    }
+
+                    let page = self.flusher.read_drop(children[idx]);
+                    current_node = self.codec.decode(&TreePage::new(page))?;
+                }
+                NodeType::Leaf(_, _, _) => {
+                    return Ok((current_node, upper));
+                }
+                NodeType::Unexpected => {
+                    return Err(anyhow::Error::msg("Unexpected error"));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,508 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::{ cell::RefCell, sync::Arc };
+
+use crate::{
+    buffer::flusher::Flusher,
+    index::tree::{
+        byte_box::ByteBox,
+        index_types::{ KeyValuePair, NodeKey },
+        tree_node::{ node_type::{ NodeType, PagePointer }, tree_node_inner::NodeInner },
+        tree_page::{
+            codec::{ common_prefix_len, Codec },
+            overflow::OverflowChain,
+            tree_page_layout::{
+                CHILD_COUNT_SIZE,
+                INTERNAL_NODE_HEADER_SIZE,
+                LEAF_NODE_HEADER_SIZE,
+                MAX_KEY_SIZE,
+                OVERFLOW_THRESHOLD,
+                PAGE_SIZE,
+                PTR_SIZE,
+            },
+        },
+    },
+};
+
+use super::{ btree_obj::{ BPTree, BTreeBuilder }, btree_sizing::{ balanced_leaf_split, balanced_split } };
+
+/// Lowest separator of a node's subtree, its page and the entries under it
+pub(crate) type LevelNode = (Option<ByteBox>, PagePointer, u64);
+
+impl BTreeBuilder {
+    /// Builds a tree bottom-up from an iterator of key value pairs sorted by key
+    ///
+    /// Leaves are packed to the builder's fill factor and chained left to right, then every
+    /// internal level is built from keys separating the nodes below it. Each page is written once.
+    /// When duplicates are allowed, entries sharing a key must be sorted by value.
+    pub fn bulk_load(
+        &self,
+        entries: impl IntoIterator<Item = KeyValuePair>
+    ) -> anyhow::Result<BPTree> {
+        if self.b == 1 {
+            return Err(anyhow::Error::msg("Bulk loading requires a b parameter of 0 or at least 2"));
+        }
+
+        if !(self.fill_factor > 0.0 && self.fill_factor <= 1.0) {
+            return Err(anyhow::Error::msg("Fill factor must be within (0, 1]"));
+        }
+
+        let codec = self.tree_codec()?;
+
+        let (flusher, file_id) = BTreeBuilder::new_flusher();
+        let meta_page_id = flusher.new_page();
+
+        let mut level = self.load_leaves(&flusher, &codec, entries)?;
+
+        while level.len() > 1 {
+            level = self.build_internal_level(&flusher, level)?;
+        }
+
+        let root_page_id = level[0].1;
+
+        let tree = BPTree {
+            flusher: Arc::new(flusher),
+            root_page_id: RefCell::new(root_page_id),
+            index_id: file_id,
+            log_id: self.new_log_id(file_id),
+            b: self.b,
+            codec,
+            fill_factor: self.fill_factor,
+            allow_duplicates: self.allow_duplicates,
+            order_statistics: self.order_statistics,
+            meta_page_id: Some(meta_page_id),
+            versions: BTreeBuilder::new_versions(self.copy_on_write),
+            merge_operator: self.merge_operator,
+        };
+        tree.write_meta()?;
+
+        Ok(tree)
+    }
+
+    /// Packs the entries into a chain of leaves as they are read, returning the level they form
+    ///
+    /// Only the entries of the last few leaves are held at a time. Entries are checked before
+    /// their values move to overflow pages, and once one is rejected the chains written for
+    /// those before it are freed again.
+    pub(crate) fn load_leaves(
+        &self,
+        flusher: &Flusher,
+        codec: &Codec,
+        entries: impl IntoIterator<Item = KeyValuePair>
+    ) -> anyhow::Result<Vec<LevelNode>> {
+        let mut leaves = LeafStream::new(self, flusher, codec);
+        let mut stubs: Vec<ByteBox> = Vec::new();
+
+        let level = self
+            .fill_leaves(&mut leaves, codec, &mut stubs, entries)
+            .and_then(|_| leaves.finish());
+
+        if level.is_err() {
+            for stub in &stubs {
+                OverflowChain::from_stub(stub)?.free(flusher)?;
+            }
+        }
+
+        level
+    }
+
+    fn fill_leaves(
+        &self,
+        leaves: &mut LeafStream,
+        codec: &Codec,
+        stubs: &mut Vec<ByteBox>,
+        entries: impl IntoIterator<Item = KeyValuePair>
+    ) -> anyhow::Result<()> {
+        let mut previous: Option<ByteBox> = None;
+
+        for entry in entries {
+            // Duplicates are keyed on their key and value, so they are checked in that form
+            let mut entry = if self.allow_duplicates {
+                BPTree::tiebreak_entry(entry)?
+            } else {
+                entry
+            };
+
+            self.validate_entry(codec, previous.as_ref(), &entry)?;
+            previous = Some(entry.key.clone());
+
+            if entry.value.data.len() > OVERFLOW_THRESHOLD {
+                entry.value = OverflowChain::write(leaves.flusher, &entry.value.data)?.to_stub();
+                stubs.push(entry.value.clone());
+            }
+
+            leaves.push(entry)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the parents of one tree level and returns the level above it
+    ///
+    /// Parents take pages past the end of the file, so a level is contiguous on disk.
+    pub(crate) fn build_internal_level(
+        &self,
+        flusher: &Flusher,
+        level: Vec<LevelNode>
+    ) -> anyhow::Result<Vec<LevelNode>> {
+        let count_size = if self.order_statistics { CHILD_COUNT_SIZE } else { 0 };
+
+        // Every child adds its pointer, its count when counts are kept and, bar the leftmost, its
+        // separator
+        let sizes: Vec<usize> = level
+            .iter()
+            .map(|(key, _, _)| {
+                PTR_SIZE + count_size + key.as_ref().map_or(0, |key| PTR_SIZE + key.data.len())
+            })
+            .collect();
+
+        let groups = self.pack(
+            &sizes,
+            None,
+            INTERNAL_NODE_HEADER_SIZE,
+            PAGE_SIZE - MAX_KEY_SIZE,
+            (self.b > 0).then(|| 2 * self.b)
+        );
+        let is_root = groups.len() == 1;
+
+        let mut parents = Vec::with_capacity(groups.len());
+        let mut children = level.into_iter();
+
+        for group_len in groups {
+            let group: Vec<LevelNode> = children
+                .by_ref()
+                .take(group_len)
+                .collect();
+
+            let first_key = group[0].0.clone();
+
+            let child_pointers: Vec<PagePointer> = group
+                .iter()
+                .map(|(_, pointer, _)| *pointer)
+                .collect();
+
+            let counts: Vec<u64> = group
+                .iter()
+                .map(|(_, _, count)| *count)
+                .collect();
+
+            // The separator of every child but the leftmost divides it from its left sibling
+            let keys: Vec<NodeKey> = group
+                .iter()
+                .skip(1)
+                .map(|(key, _, _)| {
+                    key.clone()
+                        .map(NodeKey::GuidePost)
+                        .ok_or(anyhow::Error::msg("Unexpected empty subtree"))
+                })
+                .collect::<anyhow::Result<_>>()?;
+
+            let pointer = flusher.append_page();
+            let mut node = NodeInner::new(
+                NodeType::Internal(child_pointers, keys, pointer),
+                is_root,
+                pointer,
+                None
+            );
+
+            let count = counts.iter().sum();
+            if self.order_statistics {
+                node.child_counts = Some(counts);
+            }
+
+            flusher.write_flush(Codec::encode(&node)?.get_data(), pointer)?;
+            parents.push((first_key, pointer, count));
+        }
+
+        Ok(parents)
+    }
+
+    /// Rejects an entry that is out of order after `previous`, duplicates it or has the wrong type
+    fn validate_entry(
+        &self,
+        codec: &Codec,
+        previous: Option<&ByteBox>,
+        entry: &KeyValuePair
+    ) -> anyhow::Result<()> {
+        if !codec.key_type.accepts(&entry.key.datatype) {
+            return Err(anyhow::Error::msg("Schema Mismatch: key type"));
+        }
+
+        if codec.value_type.to_string() != entry.value.datatype.to_string() {
+            return Err(anyhow::Error::msg("Schema Mismatch: value type"));
+        }
+
+        if entry.key.data.len() > MAX_KEY_SIZE {
+            return Err(anyhow::Error::msg(format!("Key too large: {:?}", entry.key)));
+        }
+
+        let previous = match previous {
+            Some(previous) => previous,
+            None => {
+                return Ok(());
+            }
+        };
+
+        match previous.cmp(&entry.key) {
+            std::cmp::Ordering::Less => Ok(()),
+            std::cmp::Ordering::Equal => {
+                Err(
+                    anyhow::Error::msg(
+                        format!("Bulk load input contains duplicate key {:?}", entry.key)
+                    )
+                )
+            }
+            std::cmp::Ordering::Greater => {
+                Err(
+                    anyhow::Error::msg(
+                        format!(
+                            "Bulk load input is not sorted: {:?} follows {:?}",
+                            entry.key,
+                            previous
+                        )
+                    )
+                )
+            }
+        }
+    }
+
+    /// Splits items of the given encoded sizes into the lengths of consecutive nodes
+    ///
+    /// When b caps nodes at `max_len` items they are filled by count. Otherwise nodes take items
+    /// until they reach the fill factor of `capacity` bytes, and a last node left underfull
+    /// shares the items of the node before it. Leaves passing their `keys` are measured with
+    /// the prefix their keys share stored once.
+    pub(crate) fn pack(
+        &self,
+        sizes: &[usize],
+        keys: Option<&[&[u8]]>,
+        header: usize,
+        capacity: usize,
+        max_len: Option<usize>
+    ) -> Vec<usize> {
+        if let Some(max_len) = max_len {
+            return group_sizes(sizes.len(), self.target_len(max_len), max_len);
+        }
+
+        let target = header + (((capacity - header) as f64) * self.fill_factor) as usize;
+        let min = ((capacity as f64) * self.fill_factor / 2.0) as usize;
+
+        let compressed = |items: &[usize], keys: Option<&[&[u8]]>| {
+            let prefix = keys.map_or(0, common_prefix_len);
+            header + items.iter().sum::<usize>() + prefix - items.len() * prefix
+        };
+
+        // Prefix the keys of the node share once the item at `to` joins those from `from`
+        let shared_prefix = |from: usize, to: usize, prefix: usize| {
+            keys.map_or(0, |keys| prefix.min(common_prefix_len(&[keys[from], keys[to]])))
+        };
+
+        let mut groups = Vec::new();
+        let (mut len, mut bytes, mut prefix) = (0, header, usize::MAX);
+
+        for (idx, size) in sizes.iter().enumerate() {
+            let grown = shared_prefix(idx - len, idx, prefix);
+
+            if len > 0 && bytes + size + grown - (len + 1) * grown > target {
+                groups.push(len);
+                (len, bytes) = (0, header);
+                prefix = shared_prefix(idx, idx, usize::MAX);
+            } else {
+                prefix = grown;
+            }
+
+            len += 1;
+            bytes += size;
+        }
+        groups.push(len);
+
+        if groups.len() > 1 && bytes < min {
+            let tail_len = groups.pop().unwrap() + groups.pop().unwrap();
+            let tail = &sizes[sizes.len() - tail_len..];
+            let tail_keys = keys.map(|keys| &keys[keys.len() - tail_len..]);
+
+            if compressed(tail, tail_keys) <= capacity {
+                groups.push(tail_len);
+            } else {
+                let at = match tail_keys {
+                    Some(tail_keys) => balanced_leaf_split(tail, tail_keys),
+                    None => balanced_split(tail, false),
+                };
+                groups.extend([at, tail_len - at]);
+            }
+        }
+
+        groups
+    }
+
+    /// Number of entries per node at the configured fill factor, never below half full
+    fn target_len(&self, capacity: usize) -> usize {
+        let target = ((capacity as f64) * self.fill_factor).round() as usize;
+
+        target.clamp(self.b, capacity)
+    }
+}
+
+/// Leaves of a bulk load, written while the sorted entries stream in
+///
+/// Where a leaf ends depends on the entries after it, as the last two leaves share their entries
+/// when the last would be left underfull. Entries are held back until they fill more than two
+/// leaves, and every leaf but the last two is written.
+struct LeafStream<'a> {
+    builder: &'a BTreeBuilder,
+    flusher: &'a Flusher,
+    compresses: bool,
+
+    // Entries of a leaf when b caps them
+    max_len: Option<usize>,
+
+    pending: Vec<KeyValuePair>,
+    pending_bytes: usize,
+
+    // Bytes held back before leaves are packed again
+    pack_at: usize,
+
+    // Page the next leaf goes to, taken when the leaf before it was written
+    next_page: Option<PagePointer>,
+    prev_page: Option<PagePointer>,
+
+    // Last key of the previous leaf, which the separator of the next one only has to exceed
+    previous_key: Option<ByteBox>,
+
+    level: Vec<LevelNode>,
+}
+
+impl<'a> LeafStream<'a> {
+    fn new(builder: &'a BTreeBuilder, flusher: &'a Flusher, codec: &Codec) -> Self {
+        LeafStream {
+            builder,
+            flusher,
+            compresses: Codec::compresses(&codec.key_type),
+            // A leaf holds at most 2b - 1 entries
+            max_len: (builder.b > 0).then(|| 2 * builder.b - 1),
+            pending: Vec::new(),
+            pending_bytes: 0,
+            pack_at: 3 * PAGE_SIZE,
+            next_page: None,
+            prev_page: None,
+            previous_key: None,
+            level: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, entry: KeyValuePair) -> anyhow::Result<()> {
+        self.pending_bytes += Codec::pair_size(&entry);
+        self.pending.push(entry);
+
+        let due = match self.max_len {
+            Some(max_len) => self.pending.len() >= 3 * max_len,
+            None => self.pending_bytes >= self.pack_at,
+        };
+
+        if due {
+            self.write_leaves(false)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the leaves of the entries still held back, returning every leaf of the load
+    fn finish(mut self) -> anyhow::Result<Vec<LevelNode>> {
+        self.write_leaves(true)?;
+        Ok(self.level)
+    }
+
+    fn write_leaves(&mut self, last: bool) -> anyhow::Result<()> {
+        let sizes: Vec<usize> = self.pending.iter().map(Codec::pair_size).collect();
+        let keys: Vec<&[u8]> = self.pending
+            .iter()
+            .map(|entry| &entry.key.data[..])
+            .collect();
+
+        let groups = self.builder.pack(
+            &sizes,
+            self.compresses.then_some(&keys[..]),
+            LEAF_NODE_HEADER_SIZE,
+            PAGE_SIZE,
+            self.max_len
+        );
+
+        let held_back = if last { 0 } else { 2 };
+
+        // Keys sharing a long prefix pack more entries into a leaf than their sizes suggest
+        if groups.len() <= held_back {
+            self.pack_at = self.pending_bytes * 2;
+            return Ok(());
+        }
+
+        let is_root = last && self.level.is_empty() && groups.len() == 1;
+        let count = groups.len() - held_back;
+
+        for (idx, len) in groups[..count].iter().enumerate() {
+            let entries: Vec<KeyValuePair> = self.pending.drain(..*len).collect();
+            self.write_leaf(entries, !last || idx + 1 < count, is_root)?;
+        }
+
+        self.pending_bytes = self.pending.iter().map(Codec::pair_size).sum();
+        self.pack_at = self.pending_bytes + PAGE_SIZE;
+
+        Ok(())
+    }
+
+    fn write_leaf(
+        &mut self,
+        entries: Vec<KeyValuePair>,
+        has_next: bool,
+        is_root: bool
+    ) -> anyhow::Result<()> {
+        let pointer = match self.next_page.take() {
+            Some(pointer) => pointer,
+            None => self.flusher.new_page(),
+        };
+        let next_pointer = has_next.then(|| self.flusher.new_page());
+
+        let first_key = entries.first().map(|entry| {
+            match &self.previous_key {
+                Some(lower) => ByteBox::shortest_separator(lower, &entry.key),
+                None => entry.key.clone(),
+            }
+        });
+        self.previous_key = entries.last().map(|entry| entry.key.clone());
+
+        let len = entries.len() as u64;
+        let leaf_entries: Vec<NodeKey> = entries.into_iter().map(NodeKey::KeyValuePair).collect();
+
+        let mut leaf = NodeInner::new(
+            NodeType::Leaf(leaf_entries, pointer, next_pointer),
+            is_root,
+            pointer,
+            next_pointer
+        );
+        leaf.prev_pointer = self.prev_page;
+
+        self.flusher.write_flush(Codec::encode(&leaf)?.get_data(), pointer)?;
+        self.level.push((first_key, pointer, len));
+
+        self.prev_page = Some(pointer);
+        self.next_page = next_pointer;
+
+        Ok(())
+    }
+}
+
+/// Splits `total` items into groups of roughly `target` items, none larger than `capacity`
+///
+/// Sizes differ by at most one, so the last group is never left underfull.
+fn group_sizes(total: usize, target: usize, capacity: usize) -> Vec<usize> {
+    if total == 0 {
+        return vec![0];
+    }
+
+    let num_groups = (total / target).max(total.div_ceil(capacity)).max(1);
+
+    let base = total / num_groups;
+    let remainder = total % num_groups;
+
+    (0..num_groups).map(|idx| if idx < remainder { base + 1 } else { base }).collect()
+}
@@ -0,0 +1,195 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::time::SystemTime;
+
+use crate::{
+    buffer::flusher::Lock,
+    index::tree::{
+        byte_box::ByteBox,
+        index_types::{ KeyValuePair, NodeKey },
+        tree_node::tree_node_inner::NodeInner,
+        tree_page::{ codec::Codec, page::TreePage },
+    },
+};
+
+use super::{ btree_obj::{ BPTree, WriteOperation }, btree_ttl::unix_millis };
+
+/// Outcome of a conditional write
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CasOutcome {
+    /// The key held the expected value and the write was made
+    Applied,
+    /// The key held another value, or none, and nothing was written
+    Mismatch {
+        current: Option<ByteBox>,
+    },
+}
+
+impl CasOutcome {
+    pub fn is_applied(&self) -> bool {
+        matches!(self, CasOutcome::Applied)
+    }
+}
+
+// A conditional write descends like any other write, latching the ancestors it might have to
+// split or rebalance. The value is then compared and the leaf rewritten before any latch is
+// released, so no write to the key can land between the two.
+impl BPTree {
+    /// Sets the key to `new` if it currently holds `expected`
+    ///
+    /// None stands for an absent key on either side, so `expected` None only matches a key that
+    /// is not stored, or has expired, and `new` None deletes it. A tree with duplicates keys
+    /// entries on their values, which leaves nothing to compare.
+    pub fn compare_and_set(
+        &self,
+        key: ByteBox,
+        expected: Option<ByteBox>,
+        new: Option<ByteBox>
+    ) -> anyhow::Result<CasOutcome> {
+        if self.allow_duplicates {
+            return Err(anyhow::Error::msg("Values of a tree with duplicates are part of the key"));
+        }
+
+        if let Some(value) = &new {
+            self.check_entry(&(KeyValuePair { key: key.clone(), value: value.clone() }))?;
+        }
+
+        if self.is_copy_on_write() {
+            return self.cow_compare_and_set(key, expected, new);
+        }
+
+        let search = NodeKey::GuidePost(key.clone());
+
+        let operation = match (&expected, &new) {
+            (Some(_), Some(_)) => WriteOperation::Replace,
+            (Some(_), None) => WriteOperation::Delete,
+            (None, _) => WriteOperation::Insert,
+        };
+
+        let context = self.tree_descent(search.clone(), Lock::EXLOCK, operation)?;
+        self.flusher.aqquire_context_ex(context)?;
+
+        let applied = self.set_if_current(&key, &expected, new);
+        self.flusher.release_ex_all()?;
+
+        match applied? {
+            Err(current) => Ok(CasOutcome::Mismatch { current }),
+            Ok(replaced) => {
+                self.recount_path(&search)?;
+
+                // The leaf no longer points at the old value, so its pages can be reused
+                if let Some(replaced) = replaced {
+                    self.free_value(&replaced)?;
+                }
+
+                Ok(CasOutcome::Applied)
+            }
+        }
+    }
+
+    /// Inserts the entry unless its key is already stored
+    pub fn put_if_absent(&self, entry: KeyValuePair) -> anyhow::Result<CasOutcome> {
+        self.compare_and_set(entry.key, None, Some(entry.value))
+    }
+
+    /// Compares and writes the latched leaf of `key`
+    ///
+    /// Returns the stored value the write replaced, or the current value when it did not match.
+    fn set_if_current(
+        &self,
+        key: &ByteBox,
+        expected: &Option<ByteBox>,
+        new: Option<ByteBox>
+    ) -> anyhow::Result<Result<Option<ByteBox>, Option<ByteBox>>> {
+        let mut leaf = self.codec.decode(&TreePage::new(self.flusher.read_top()?))?;
+
+        let stored = stored_value(&leaf, key);
+        let current = match &stored {
+            Some(value) if !value.is_expired(unix_millis(SystemTime::now())) => {
+                Some(self.resolve_value(value.clone())?)
+            }
+            _ => None,
+        };
+
+        if current != *expected {
+            return Ok(Err(current));
+        }
+
+        if stored.is_some() {
+            leaf.remove_entry(key)?;
+        }
+
+        match new {
+            Some(value) => {
+                let value = self.store_value(value)?;
+                leaf.insert_entry(KeyValuePair { key: key.clone(), value })?;
+            }
+            None if stored.is_none() => {
+                return Ok(Ok(None));
+            }
+            None => {}
+        }
+
+        self.write_latched_leaf(leaf)?;
+
+        Ok(Ok(stored))
+    }
+
+    /// Writes back the latched leaf, splitting or rebalancing it when it has to be
+    pub(crate) fn write_latched_leaf(&self, leaf: NodeInner) -> anyhow::Result<()> {
+        if self.is_overfull(&leaf) {
+            return self.propogate_upwards(leaf);
+        }
+
+        if !leaf.is_root && self.is_underfull(&leaf) {
+            let parent = self.codec.decode(&TreePage::new(self.flusher.read_parent()?))?;
+            let leaf_pointer = leaf.pointer;
+
+            return self.borrow_if_needed(parent, leaf, leaf_pointer);
+        }
+
+        self.flusher.pop_flush_test(Codec::encode(&leaf)?.get_data())
+    }
+
+    // Copy-on-write writes take no latches, a BPTree is never shared between threads
+    fn cow_compare_and_set(
+        &self,
+        key: ByteBox,
+        expected: Option<ByteBox>,
+        new: Option<ByteBox>
+    ) -> anyhow::Result<CasOutcome> {
+        let leaf = self.find_leaf(NodeKey::GuidePost(key.clone()))?;
+
+        let stored = stored_value(&leaf, &key);
+        let current = match &stored {
+            Some(value) if !value.is_expired(unix_millis(SystemTime::now())) => {
+                Some(self.resolve_value(value.clone())?)
+            }
+            _ => None,
+        };
+
+        if current != expected {
+            return Ok(CasOutcome::Mismatch { current });
+        }
+
+        match (stored, new) {
+            (_, Some(value)) => self.cow_insert(KeyValuePair { key, value }, true)?,
+            (Some(_), None) => self.cow_delete(NodeKey::GuidePost(key))?,
+            (None, None) => {}
+        }
+
+        Ok(CasOutcome::Applied)
+    }
+}
+
+/// The value stored under the key in a leaf, as the leaf holds it
+pub(crate) fn stored_value(leaf: &NodeInner, key: &ByteBox) -> Option<ByteBox> {
+    let search = NodeKey::GuidePost(key.clone());
+
+    leaf.get_key_value_index(&search)
+        .and_then(|idx| leaf.get_key_value_at(idx))
+        .ok()
+        .filter(|entry| entry.key == *key)
+        .map(|entry| entry.value)
+}
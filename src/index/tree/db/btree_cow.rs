@@ -0,0 +1,251 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use crate::index::tree::{
+    byte_box::ByteBox,
+    index_types::{ KeyValuePair, NodeKey },
+    iterators::tree_walk::{ descend, neighbour_leaf, Descent },
+    tree_node::{ node_type::{ NodeType, PagePointer }, tree_node_inner::NodeInner },
+    tree_page::codec::{ Codec, Projection },
+};
+
+use super::btree_obj::BPTree;
+
+// A copy-on-write tree never rewrites a page the current root reaches. A write copies the leaf
+// it changes and every node above it, along with any sibling a split, merge or borrow touches,
+// into fresh pages. Once all of them are written the copied root is published as the new root,
+// which readers pick up in one step. The replaced pages are retired, see `VersionSet`.
+//
+// Leaves are not chained, as relinking a neighbour would mean copying it and its own parent too.
+impl BPTree {
+    /// Copy-on-write counterpart of `insert`, replacing any entry stored under the key when
+    /// `replace` is set
+    pub(crate) fn cow_insert(&self, entry: KeyValuePair, replace: bool) -> anyhow::Result<()> {
+        let stored = KeyValuePair { key: entry.key, value: self.store_value(entry.value)? };
+        let search = NodeKey::GuidePost(stored.key.clone());
+
+        let (inserted, position) = (stored.clone(), search.clone());
+        let written = self.cow_write(&search, move |leaf| {
+            let found = leaf
+                .get_key_value_index(&position)
+                .and_then(|idx| leaf.get_key_value_at(idx));
+
+            let replaced = match found {
+                Ok(entry) if replace && entry.key == inserted.key => {
+                    leaf.remove_entry(&inserted.key)?
+                }
+                _ => None,
+            };
+            leaf.insert_entry(inserted)?;
+
+            Ok(replaced.map(|entry| entry.value))
+        });
+
+        if written.is_err() {
+            self.free_value(&stored.value)?;
+        }
+
+        written
+    }
+
+    /// Copy-on-write counterpart of `delete_entry`
+    pub(crate) fn cow_delete(&self, search: NodeKey) -> anyhow::Result<()> {
+        let key = search.to_guide_post()?;
+
+        self.cow_write(&NodeKey::GuidePost(key.clone()), |leaf| {
+            match leaf.remove_entry(&key) {
+                Ok(Some(entry)) => Ok(Some(entry.value)),
+                _ => Err(anyhow::Error::msg("Key not present")),
+            }
+        })
+    }
+
+    /// The leaf after `leaf` in key order, or the one before it when not `forward`
+    pub(crate) fn neighbour_leaf(
+        &self,
+        leaf: &NodeInner,
+        forward: bool
+    ) -> anyhow::Result<Option<NodeInner>> {
+        let root = self.versions.as_ref().map(|_| *self.root_page_id.borrow());
+
+        neighbour_leaf(&self.flusher, &self.codec, root, leaf, forward, &Projection::Whole)
+    }
+
+    /// Applies `change` to a copy of the leaf of `search` and publishes the copied path
+    ///
+    /// `change` returns the stored value it removed, if any, which is released once the new
+    /// root is published.
+    fn cow_write(
+        &self,
+        search: &NodeKey,
+        change: impl FnOnce(&mut NodeInner) -> anyhow::Result<Option<ByteBox>>
+    ) -> anyhow::Result<()> {
+        let root = *self.root_page_id.borrow();
+        let (mut path, mut node) = descend(&self.flusher, &self.codec, root, Descent::Key(search))?;
+
+        let removed = change(&mut node)?;
+
+        // Copies waiting to be written, and the pages they replace
+        let mut written: Vec<NodeInner> = Vec::new();
+        let mut retired: Vec<PagePointer> = Vec::new();
+
+        let new_root = loop {
+            let mut parent = match path.pop() {
+                Some((parent, _)) => parent,
+                None => {
+                    break self.copy_root(node, &mut written, &mut retired)?;
+                }
+            };
+
+            let pointer = node.pointer;
+
+            if self.is_overfull(&node) {
+                let (median, sibling) = self.split_copy(&mut node)?;
+
+                self.relocate(&mut node, &mut retired);
+                parent.replace_child_pointer(pointer, node.pointer)?;
+                parent.insert_sibling_node(median, sibling.pointer)?;
+
+                parent.set_child_count(node.pointer, node.entry_count())?;
+                parent.set_child_count(sibling.pointer, sibling.entry_count())?;
+
+                written.extend([node, sibling]);
+            } else if self.is_underfull(&node) {
+                let (mut candidate, is_left, separator, can_borrow) = self.get_candidate(
+                    &parent,
+                    &node
+                )?;
+
+                if can_borrow {
+                    self.borrow_enough(&mut node, &mut parent, &mut candidate, is_left, separator)?;
+
+                    for mut moved in [node, candidate] {
+                        let pointer = moved.pointer;
+
+                        self.relocate(&mut moved, &mut retired);
+                        parent.replace_child_pointer(pointer, moved.pointer)?;
+                        parent.set_child_count(moved.pointer, moved.entry_count())?;
+
+                        written.push(moved);
+                    }
+                } else {
+                    // The right node of the pair is folded into the left one
+                    let (mut left, right) = if is_left {
+                        (candidate, node)
+                    } else {
+                        (node, candidate)
+                    };
+                    let pointer = left.pointer;
+
+                    left.merge(&right)?;
+
+                    // Internal merges pull the separator down between the two halves
+                    if let NodeType::Internal(_, _, _) = left.node_type {
+                        left.insert_key(separator.clone())?;
+                    }
+
+                    parent.remove_sibling_node(separator, right.pointer)?;
+                    retired.push(right.pointer);
+
+                    self.relocate(&mut left, &mut retired);
+                    parent.replace_child_pointer(pointer, left.pointer)?;
+                    parent.set_child_count(left.pointer, left.entry_count())?;
+
+                    written.push(left);
+                }
+            } else {
+                self.relocate(&mut node, &mut retired);
+                parent.replace_child_pointer(pointer, node.pointer)?;
+                parent.set_child_count(node.pointer, node.entry_count())?;
+
+                written.push(node);
+            }
+
+            // A root left with a single child hands the root over to it, which is the merged node
+            if parent.is_root && parent.child_ptr_len() == 1 {
+                retired.push(parent.pointer);
+
+                let child = written.last_mut().unwrap();
+                child.is_root = true;
+
+                break child.pointer;
+            }
+
+            node = parent;
+        };
+
+        for node in &written {
+            self.flusher.write_flush(Codec::encode(node)?.get_data(), node.pointer)?;
+        }
+
+        self.publish_root(new_root, retired)?;
+
+        // Snapshots of older roots may still read the value, so it is retired rather than freed
+        if let Some(value) = removed {
+            self.free_value(&value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Copies the root, splitting it under a new root when it is overfull
+    fn copy_root(
+        &self,
+        mut node: NodeInner,
+        written: &mut Vec<NodeInner>,
+        retired: &mut Vec<PagePointer>
+    ) -> anyhow::Result<PagePointer> {
+        if !self.is_overfull(&node) {
+            self.relocate(&mut node, retired);
+            let pointer = node.pointer;
+            written.push(node);
+
+            return Ok(pointer);
+        }
+
+        let (median, sibling) = self.split_copy(&mut node)?;
+        self.relocate(&mut node, retired);
+
+        let root_pointer = self.flusher.new_page();
+        let mut root = NodeInner::new(
+            NodeType::Internal(vec![node.pointer, sibling.pointer], vec![median], root_pointer),
+            true,
+            root_pointer,
+            None
+        );
+
+        if self.order_statistics {
+            root.child_counts = Some(vec![node.entry_count(), sibling.entry_count()]);
+        }
+
+        written.extend([node, sibling, root]);
+
+        Ok(root_pointer)
+    }
+
+    /// Splits the node, placing the new sibling on a fresh page
+    fn split_copy(&self, node: &mut NodeInner) -> anyhow::Result<(NodeKey, NodeInner)> {
+        let (mut median, mut sibling) = node.split(BPTree::split_point(node))?;
+
+        // Only the leading bytes telling the two leaves apart need to go up
+        if let NodeType::Leaf(entries, _, _) = &node.node_type {
+            if let (Some(last), NodeKey::GuidePost(upper)) = (entries.last(), &median) {
+                let (lower, _) = NodeInner::deconstruct_value(last);
+                median = NodeKey::GuidePost(ByteBox::shortest_separator(&lower, upper));
+            }
+        }
+
+        sibling.pointer = self.flusher.new_page();
+
+        Ok((median, sibling))
+    }
+
+    /// Moves the copy of a node to a fresh page, retiring the one it was read from
+    fn relocate(&self, node: &mut NodeInner, retired: &mut Vec<PagePointer>) {
+        retired.push(node.pointer);
+        node.pointer = self.flusher.new_page();
+
+        node.next_pointer = None;
+        node.prev_pointer = None;
+    }
+}
@@ -0,0 +1,305 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::{ collections::{ HashSet, VecDeque }, fmt::Write, ops::{ Bound, RangeBounds } };
+
+use crate::index::tree::{
+    byte_box::{ ByteBox, DataType },
+    tree_node::{ node_type::{ NodeType, PagePointer }, tree_node_inner::NodeInner },
+    tree_page::page::TreePage,
+};
+
+use super::btree_obj::BPTree;
+
+/// Text formats `BPTree::dump` renders a tree in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// A Graphviz digraph, for `dot -Tsvg`
+    Dot,
+
+    /// A document listing the root and every node with its keys and links
+    Json,
+}
+
+/// A node as the dump shows it, keys rendered as text
+#[derive(Debug, Clone)]
+struct DumpNode {
+    page: PagePointer,
+    depth: usize,
+    is_leaf: bool,
+
+    // Separators of an internal node, every key of a leaf
+    keys: Vec<String>,
+
+    children: Vec<PagePointer>,
+    child_counts: Option<Vec<u64>>,
+    next: Option<PagePointer>,
+    prev: Option<PagePointer>,
+}
+
+/// Renders the structure of a tree, see `BPTree::dump`
+pub struct TreeDump<'a> {
+    tree: &'a BPTree,
+    format: DumpFormat,
+    max_depth: Option<usize>,
+    range: (Bound<ByteBox>, Bound<ByteBox>),
+}
+
+impl BPTree {
+    /// Starts a dump of the tree's nodes in the given format
+    ///
+    /// The dump shows every node with its page id and keys, the edges to the children of
+    /// internal nodes and the leaf chain. Values are left out. It can be cut down to the levels
+    /// above a depth or the nodes a key range reaches before `TreeDump::render` writes it.
+    pub fn dump(&self, format: DumpFormat) -> TreeDump<'_> {
+        TreeDump {
+            tree: self,
+            format,
+            max_depth: None,
+            range: (Bound::Unbounded, Bound::Unbounded),
+        }
+    }
+}
+
+impl<'a> TreeDump<'a> {
+    /// Leaves out nodes deeper than `depth`, the root being at depth 0
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Leaves out subtrees holding no key of the range
+    ///
+    /// Nodes that are kept are shown whole, keys outside the range included.
+    pub fn key_range(mut self, range: impl RangeBounds<ByteBox>) -> Self {
+        self.range = if self.tree.allow_duplicates {
+            BPTree::tiebreak_range(range)
+        } else {
+            (range.start_bound().cloned(), range.end_bound().cloned())
+        };
+        self
+    }
+
+    pub fn render(&self) -> anyhow::Result<String> {
+        let nodes = self.collect()?;
+        let root = *self.tree.root_page_id.borrow();
+
+        Ok(match self.format {
+            DumpFormat::Dot => TreeDump::to_dot(&nodes),
+            DumpFormat::Json => TreeDump::to_json(root, &nodes),
+        })
+    }
+
+    /// Nodes the dump shows, level by level from the root
+    fn collect(&self) -> anyhow::Result<Vec<DumpNode>> {
+        let mut nodes = Vec::new();
+        let mut queue = VecDeque::from([(*self.tree.root_page_id.borrow(), 0)]);
+
+        while let Some((page, depth)) = queue.pop_front() {
+            let node = self.tree.codec.decode(&TreePage::new(self.tree.flusher.read_drop(page)))?;
+
+            let dumped = match &node.node_type {
+                NodeType::Internal(children, keys, _) => {
+                    let separators: Vec<ByteBox> = keys
+                        .iter()
+                        .map(|key| NodeInner::deconstruct_value(key).0)
+                        .collect();
+
+                    if self.max_depth.is_none_or(|max_depth| depth < max_depth) {
+                        for (idx, child) in children.iter().enumerate() {
+                            let lower = idx.checked_sub(1).map(|idx| &separators[idx]);
+
+                            if self.overlaps(lower, separators.get(idx)) {
+                                queue.push_back((*child, depth + 1));
+                            }
+                        }
+                    }
+
+                    DumpNode {
+                        page,
+                        depth,
+                        is_leaf: false,
+                        keys: separators.iter().map(TreeDump::label).collect(),
+                        children: children.clone(),
+                        child_counts: node.child_counts.clone(),
+                        next: None,
+                        prev: None,
+                    }
+                }
+                NodeType::Leaf(entries, _, _) => {
+                    DumpNode {
+                        page,
+                        depth,
+                        is_leaf: true,
+                        keys: entries
+                            .iter()
+                            .map(|entry| TreeDump::label(&NodeInner::deconstruct_value(entry).0))
+                            .collect(),
+                        children: Vec::new(),
+                        child_counts: None,
+                        next: node.next_pointer,
+                        prev: node.prev_pointer,
+                    }
+                }
+                NodeType::Unexpected => {
+                    return Err(anyhow::Error::msg(format!("Page {} holds no tree node", page)));
+                }
+            };
+
+            nodes.push(dumped);
+        }
+
+        Ok(nodes)
+    }
+
+    /// Whether a child holding keys from `lower` up to, but not including, `upper` can hold a
+    /// key of the range
+    fn overlaps(&self, lower: Option<&ByteBox>, upper: Option<&ByteBox>) -> bool {
+        let above_start = match (&self.range.0, upper) {
+            (Bound::Included(start) | Bound::Excluded(start), Some(upper)) => start < upper,
+            _ => true,
+        };
+
+        let below_end = match (&self.range.1, lower) {
+            (Bound::Included(end), Some(lower)) => end >= lower,
+            (Bound::Excluded(end), Some(lower)) => end > lower,
+            _ => true,
+        };
+
+        above_start && below_end
+    }
+
+    /// A key as text, the components of composite keys listed in parentheses
+    fn label(key: &ByteBox) -> String {
+        match (&key.datatype, key.components()) {
+            (DataType::Composite(_), Ok(components)) => {
+                let components: Vec<String> = components
+                    .iter()
+                    .map(ByteBox::value_string)
+                    .collect();
+
+                format!("({})", components.join(", "))
+            }
+            _ => key.value_string(),
+        }
+    }
+
+    fn to_dot(nodes: &[DumpNode]) -> String {
+        let shown: HashSet<PagePointer> = nodes
+            .iter()
+            .map(|node| node.page)
+            .collect();
+        let mut dot = String::from("digraph BPTree {\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+        for node in nodes {
+            let kind = if node.is_leaf { "leaf" } else { "internal" };
+            let label = format!("{} {}\n[{}]", kind, node.page, node.keys.join(" | "));
+            let style = if node.is_leaf { ", style=rounded" } else { "" };
+
+            writeln!(dot, "    p{} [label=\"{}\"{}];", node.page, escape_dot(&label), style).ok();
+        }
+
+        for node in nodes {
+            for (idx, child) in node.children.iter().enumerate() {
+                if !shown.contains(child) {
+                    continue;
+                }
+
+                match node.child_counts.as_ref().and_then(|counts| counts.get(idx)) {
+                    Some(count) => {
+                        writeln!(dot, "    p{} -> p{} [label=\"{}\"];", node.page, child, count)
+                    }
+                    None => writeln!(dot, "    p{} -> p{};", node.page, child),
+                }.ok();
+            }
+
+            if let Some(next) = node.next.filter(|next| shown.contains(next)) {
+                writeln!(
+                    dot,
+                    "    p{} -> p{} [style=dashed, constraint=false];",
+                    node.page,
+                    next
+                ).ok();
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn to_json(root: PagePointer, nodes: &[DumpNode]) -> String {
+        let pointer = |pointer: Option<PagePointer>| {
+            pointer.map_or(String::from("null"), |pointer| pointer.to_string())
+        };
+
+        let mut json = format!("{{\n  \"root\": {},\n  \"nodes\": [", root);
+
+        for (idx, node) in nodes.iter().enumerate() {
+            let keys: Vec<String> = node.keys
+                .iter()
+                .map(|key| escape_json(key))
+                .collect();
+
+            json.push_str(if idx == 0 { "\n" } else { ",\n" });
+            write!(
+                json,
+                "    {{ \"page\": {}, \"kind\": \"{}\", \"depth\": {}, \"keys\": [{}]",
+                node.page,
+                if node.is_leaf { "leaf" } else { "internal" },
+                node.depth,
+                keys.join(", ")
+            ).ok();
+
+            if node.is_leaf {
+                write!(json, ", \"next\": {}, \"prev\": {}", pointer(node.next), pointer(node.prev))
+                    .ok();
+            } else {
+                let children: Vec<String> = node.children
+                    .iter()
+                    .map(|child| child.to_string())
+                    .collect();
+                write!(json, ", \"children\": [{}]", children.join(", ")).ok();
+            }
+
+            if let Some(counts) = &node.child_counts {
+                let counts: Vec<String> = counts
+                    .iter()
+                    .map(|count| count.to_string())
+                    .collect();
+                write!(json, ", \"child_counts\": [{}]", counts.join(", ")).ok();
+            }
+
+            json.push_str(" }");
+        }
+
+        json.push_str("\n  ]\n}\n");
+        json
+    }
+}
+
+/// Text of a DOT label, within double quotes
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// A JSON string holding the text, quotes included
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+
+    for character in text.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            control if (control as u32) < 0x20 => {
+                write!(escaped, "\\u{:04x}", control as u32).ok();
+            }
+            other => escaped.push(other),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
@@ -0,0 +1,100 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::ops::{ Bound, RangeBounds };
+
+use crate::index::tree::{ byte_box::{ ByteBox, DataType }, index_types::{ KeyValuePair, NodeKey } };
+
+use super::{ btree_obj::BPTree, btree_ops::composite_successor };
+
+// A tree built with `allow_duplicates` stores every entry under the composite key (key, value).
+// Stored keys stay unique, the value breaks ties between duplicates, and all entries of a key
+// are found by scanning the composite prefix (key).
+impl BPTree {
+    /// The type of the keys callers insert, rather than the type pages are keyed on
+    pub fn key_type(&self) -> DataType {
+        match &self.codec.key_type {
+            DataType::Composite(types) if self.allow_duplicates => types[0].clone(),
+            key_type => key_type.clone(),
+        }
+    }
+
+    /// Rewrites an entry of a tree with duplicates into the form it is stored in
+    pub(crate) fn tiebreak_entry(entry: KeyValuePair) -> anyhow::Result<KeyValuePair> {
+        if !entry.key.datatype.is_scalar() || !entry.value.datatype.is_scalar() {
+            return Err(anyhow::Error::msg("Schema Mismatch: duplicate keys need scalar types"));
+        }
+
+        Ok(KeyValuePair {
+            key: ByteBox::composite(&[entry.key, entry.value.clone()])?,
+            value: entry.value,
+        })
+    }
+
+    /// Rewrites a range over keys into the range of stored keys holding them
+    pub(crate) fn tiebreak_range(
+        range: impl RangeBounds<ByteBox>
+    ) -> (Bound<ByteBox>, Bound<ByteBox>) {
+        let key_prefix = |key: &ByteBox| ByteBox::composite_bound(std::slice::from_ref(key));
+
+        let successor = |key: &ByteBox| {
+            composite_successor(std::slice::from_ref(key))
+                .map(|next| ByteBox::composite_bound(&next))
+        };
+
+        let mut end = match range.end_bound() {
+            Bound::Included(key) => {
+                match successor(key) {
+                    Some(next) => Bound::Excluded(next),
+                    None => Bound::Unbounded,
+                }
+            }
+            Bound::Excluded(key) => Bound::Excluded(key_prefix(key)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+
+        let start = match range.start_bound() {
+            Bound::Included(key) => Bound::Included(key_prefix(key)),
+            Bound::Excluded(key) => {
+                match successor(key) {
+                    Some(next) => Bound::Included(next),
+                    None => {
+                        // Nothing sorts after the largest key, so the range is empty
+                        end = Bound::Excluded(key_prefix(key));
+                        Bound::Excluded(key_prefix(key))
+                    }
+                }
+            }
+            Bound::Unbounded => Bound::Unbounded,
+        };
+
+        (start, end)
+    }
+
+    /// Removes a single entry, or every entry of a key when given a guide post
+    pub(crate) fn delete_duplicates(&self, search: NodeKey) -> anyhow::Result<()> {
+        match search {
+            NodeKey::KeyValuePair(entry) => {
+                let stored = BPTree::tiebreak_entry(entry)?;
+
+                self.delete_entry(NodeKey::GuidePost(stored.key))
+            }
+            NodeKey::GuidePost(key) => {
+                let stored_keys: Vec<ByteBox> = self
+                    .scan_stored(BPTree::tiebreak_range(key.clone()..=key))
+                    .map(|entry| entry.key)
+                    .collect();
+
+                if stored_keys.is_empty() {
+                    return Err(anyhow::Error::msg("Key not present"));
+                }
+
+                for stored_key in stored_keys {
+                    self.delete_entry(NodeKey::GuidePost(stored_key))?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
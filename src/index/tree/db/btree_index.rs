@@ -0,0 +1,87 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::{ ops::Bound, path::PathBuf };
+
+use crate::index::{
+    index_api::{ Index, IndexKind, IndexStats },
+    tree::{
+        byte_box::ByteBox,
+        index_types::{ KeyValuePair, NodeKey },
+        tree_page::codec::Codec,
+    },
+};
+
+use super::{ btree_obj::BPTree, btree_verify::Violation };
+
+impl Index for BPTree {
+    fn kind(&self) -> IndexKind {
+        IndexKind::BPTree
+    }
+
+    fn codec(&self) -> &Codec {
+        &self.codec
+    }
+
+    fn get(&self, key: &ByteBox) -> anyhow::Result<KeyValuePair> {
+        BPTree::get(self, key.clone())
+    }
+
+    fn insert(&self, entry: KeyValuePair) -> anyhow::Result<()> {
+        BPTree::insert(self, entry)
+    }
+
+    fn put(&self, entry: KeyValuePair) -> anyhow::Result<()> {
+        BPTree::put(self, entry)
+    }
+
+    fn delete(&self, key: &ByteBox) -> anyhow::Result<()> {
+        BPTree::delete(self, NodeKey::GuidePost(key.clone()))
+    }
+
+    fn contains_key(&self, key: &ByteBox) -> anyhow::Result<bool> {
+        BPTree::contains_key(self, key)
+    }
+
+    fn supports_range_scan(&self) -> bool {
+        true
+    }
+
+    fn scan(
+        &self,
+        range: (Bound<ByteBox>, Bound<ByteBox>)
+    ) -> anyhow::Result<Box<dyn Iterator<Item = KeyValuePair>>> {
+        Ok(Box::new(BPTree::scan(self, range)))
+    }
+
+    fn stats(&self) -> anyhow::Result<IndexStats> {
+        let stats = BPTree::stats(self)?;
+
+        Ok(IndexStats {
+            kind: IndexKind::BPTree,
+            num_entries: stats.num_entries,
+            node_pages: stats.nodes_per_level.iter().sum(),
+            free_pages: stats.free_pages,
+        })
+    }
+
+    fn verify(&self) -> Vec<Violation> {
+        BPTree::verify(self)
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        BPTree::flush(self)
+    }
+
+    fn file_path(&self) -> Option<PathBuf> {
+        BPTree::file_path(self)
+    }
+
+    fn entry_page_id(&self) -> Option<u32> {
+        self.meta_page_id
+    }
+
+    fn as_tree(&self) -> Option<&BPTree> {
+        Some(self)
+    }
+}
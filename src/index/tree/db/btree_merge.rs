@@ -0,0 +1,162 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::time::SystemTime;
+
+use crate::{
+    buffer::flusher::Lock,
+    index::tree::{
+        byte_box::{ ByteBox, DataType },
+        index_types::{ KeyValuePair, NodeKey },
+        tree_page::page::TreePage,
+    },
+};
+
+use super::{
+    btree_cas::stored_value,
+    btree_obj::{ BPTree, WriteOperation },
+    btree_ttl::unix_millis,
+};
+
+/// Combines the value stored under a key with an operand into the value that replaces it
+///
+/// `existing` is None for a key without an entry, or whose entry has expired.
+pub type MergeOperator = fn(existing: Option<&ByteBox>, operand: &ByteBox) -> ByteBox;
+
+/// Adds the operand to the stored integer, a missing one counting as zero
+///
+/// The sum wraps around and takes the type of the operand. Operands that are not integers
+/// replace the stored value.
+pub fn integer_add(existing: Option<&ByteBox>, operand: &ByteBox) -> ByteBox {
+    let stored = existing.and_then(ByteBox::as_integer).unwrap_or(0);
+
+    match operand.as_integer() {
+        Some(value) => integer_of_type(stored.wrapping_add(value), &operand.datatype),
+        None => operand.clone(),
+    }
+}
+
+/// Keeps the larger of the stored integer and the operand, taking the type of the operand
+///
+/// Operands that are not integers replace the stored value.
+pub fn integer_max(existing: Option<&ByteBox>, operand: &ByteBox) -> ByteBox {
+    match (existing.and_then(ByteBox::as_integer), operand.as_integer()) {
+        (Some(stored), Some(value)) => integer_of_type(stored.max(value), &operand.datatype),
+        _ => operand.clone(),
+    }
+}
+
+fn integer_of_type(value: i64, datatype: &DataType) -> ByteBox {
+    match datatype {
+        DataType::Int => ByteBox::int(value as i32),
+        DataType::SmallInt => ByteBox::small_int(value as i16),
+        _ => ByteBox::big_int(value),
+    }
+}
+
+// A merge reads and rewrites the leaf under the latches a replace takes, so no other write to
+// the key lands between the stored value being read and the merged one being written.
+impl BPTree {
+    /// Replaces the value of the key with what the tree's merge operator makes of it and the
+    /// operand, returning the merged value
+    ///
+    /// A key that has none is given the operator's value for a missing one. An entry set with a
+    /// time to live keeps its expiry. A tree with duplicates keys entries on their values, which
+    /// leaves no single value to merge into.
+    pub fn merge(&self, key: ByteBox, operand: ByteBox) -> anyhow::Result<ByteBox> {
+        let operator = self.merge_operator.ok_or(
+            anyhow::Error::msg("Tree has no merge operator, see BTreeBuilder::merge_operator")
+        )?;
+
+        if self.allow_duplicates {
+            return Err(anyhow::Error::msg("Values of a tree with duplicates are part of the key"));
+        }
+
+        if self.is_copy_on_write() {
+            return self.cow_merge(key, operand, operator);
+        }
+
+        let search = NodeKey::GuidePost(key.clone());
+
+        let context = self.tree_descent(search.clone(), Lock::EXLOCK, WriteOperation::Replace)?;
+        self.flusher.aqquire_context_ex(context)?;
+
+        let merged = self.merge_latched(&key, &operand, operator);
+        self.flusher.release_ex_all()?;
+
+        let (merged, replaced) = merged?;
+        self.recount_path(&search)?;
+
+        if let Some(replaced) = replaced {
+            self.free_value(&replaced)?;
+        }
+
+        Ok(merged)
+    }
+
+    /// Merges the operand into the latched leaf of `key`
+    ///
+    /// Returns the merged value along with the stored value it replaced.
+    fn merge_latched(
+        &self,
+        key: &ByteBox,
+        operand: &ByteBox,
+        operator: MergeOperator
+    ) -> anyhow::Result<(ByteBox, Option<ByteBox>)> {
+        let mut leaf = self.codec.decode(&TreePage::new(self.flusher.read_top()?))?;
+
+        let stored = stored_value(&leaf, key);
+        let (merged, value) = self.merge_value(key, stored.clone(), operand, operator)?;
+
+        if stored.is_some() {
+            leaf.remove_entry(key)?;
+        }
+
+        leaf.insert_entry(KeyValuePair { key: key.clone(), value: self.store_value(value)? })?;
+        self.write_latched_leaf(leaf)?;
+
+        Ok((merged, stored))
+    }
+
+    /// Applies the operator to the stored value, returning the merged value and what to store
+    fn merge_value(
+        &self,
+        key: &ByteBox,
+        stored: Option<ByteBox>,
+        operand: &ByteBox,
+        operator: MergeOperator
+    ) -> anyhow::Result<(ByteBox, ByteBox)> {
+        let live = stored.filter(|value| !value.is_expired(unix_millis(SystemTime::now())));
+
+        let existing = match &live {
+            Some(value) => Some(self.resolve_value(value.clone())?),
+            None => None,
+        };
+
+        let merged = operator(existing.as_ref(), operand);
+        self.check_entry(&(KeyValuePair { key: key.clone(), value: merged.clone() }))?;
+
+        let value = match live.as_ref().and_then(ByteBox::expires_at) {
+            Some(expires_at) => ByteBox::expiring(merged.clone(), expires_at),
+            None => merged.clone(),
+        };
+
+        Ok((merged, value))
+    }
+
+    // Copy-on-write writes take no latches, a BPTree is never shared between threads
+    fn cow_merge(
+        &self,
+        key: ByteBox,
+        operand: ByteBox,
+        operator: MergeOperator
+    ) -> anyhow::Result<ByteBox> {
+        let leaf = self.find_leaf(NodeKey::GuidePost(key.clone()))?;
+        let stored = stored_value(&leaf, &key);
+
+        let (merged, value) = self.merge_value(&key, stored, &operand, operator)?;
+        self.cow_insert(KeyValuePair { key, value }, true)?;
+
+        Ok(merged)
+    }
+}
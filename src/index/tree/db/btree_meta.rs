@@ -0,0 +1,49 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use crate::index::tree::{ tree_node::node_type::PagePointer, tree_page::meta::TreeMeta };
+
+use super::btree_obj::BPTree;
+
+impl BPTree {
+    /// Moves the root to `page`, recording it on the meta page when the tree has one
+    pub(crate) fn set_root(&self, page: PagePointer) -> anyhow::Result<()> {
+        *self.root_page_id.borrow_mut() = page;
+        self.write_meta()
+    }
+
+    /// Rewrites the meta page from the tree as it is now
+    pub(crate) fn write_meta(&self) -> anyhow::Result<()> {
+        let meta_page_id = match self.meta_page_id {
+            Some(meta_page_id) => meta_page_id,
+            None => {
+                return Ok(());
+            }
+        };
+
+        let meta = TreeMeta {
+            root_page_id: *self.root_page_id.borrow(),
+            b: self.b,
+            fill_factor: self.fill_factor,
+            allow_duplicates: self.allow_duplicates,
+            order_statistics: self.order_statistics,
+            copy_on_write: self.versions.is_some(),
+            log_id: self.log_id,
+            codec: self.codec.clone(),
+        };
+
+        self.flusher.write_flush(meta.encode()?, meta_page_id)
+    }
+
+    /// Writes every page of the tree still held in memory to its file
+    pub fn flush(&self) -> anyhow::Result<()> {
+        self.flusher.flush_all()
+    }
+
+    /// File the tree lives in, for reopening it with `BTreeBuilder::build_from_file`
+    pub fn file_path(&self) -> Option<PathBuf> {
+        self.flusher.file_path()
+    }
+}
@@ -8,38 +8,43 @@ use crate::index::tree::{
 
 // Helper functions for btree node operations
 impl NodeInner {
+    /// Moves the entries from index `at` onwards into a new sibling
     ///
-    /// The current implementation uses the b param as an arbiturary predicate by which splits and merges occur
-    ///
-    /// Future optimizations will account for estimated page size as well as minimum and maximum capacity
-    pub fn split(&mut self, b: usize) -> anyhow::Result<(NodeKey, NodeInner)> {
+    /// Internal nodes hand the key at `at` up to the parent, leaves copy it up.
+    /// See `BPTree::split_point` for where nodes are split.
+    pub fn split(&mut self, at: usize) -> anyhow::Result<(NodeKey, NodeInner)> {
         match self.node_type {
             NodeType::Internal(ref mut children, ref mut keys, _) => {
                 // Populate siblings keys.
-                let mut sibling_keys = keys.split_off(b);
+                let mut sibling_keys = keys.split_off(at);
 
                 // Pop median key - to be added to the parent..
                 let median_key = sibling_keys.remove(0);
 
                 // Populate siblings children.
 
-                let sibling_children = children.split_off(b + 1);
+                let sibling_children = children.split_off(at + 1);
 
                 self.is_root = false;
 
-                let new_node = NodeInner::new(
+                let mut new_node = NodeInner::new(
                     NodeType::Internal(sibling_children, sibling_keys, u32::default()),
                     self.is_root,
                     self.pointer,
                     None
                 );
 
+                // Child counts go along with their children
+                new_node.child_counts = self.child_counts
+                    .as_mut()
+                    .map(|counts| counts.split_off(at + 1));
+
                 anyhow::Ok((median_key, new_node))
             }
 
             NodeType::Leaf(ref mut pairs, _, current_page) => {
                 // Populate siblings pairs
-                let sibling_pair = pairs.split_off(b);
+                let sibling_pair = pairs.split_off(at);
 
                 // copy median key.
                 let median_pair = sibling_pair
@@ -66,16 +71,6 @@ impl NodeInner {
         }
     }
 
-    /// Is this page sufficient large enough to borrow from
-    ///
-    pub fn can_borrow(&self, b_param: usize) -> bool {
-        return self.get_key_array_length() >= b_param;
-
-        // Future implementations will have the following predicate
-
-        // return self.get_key_array_length() >= Page::GetMinCapacity;
-    }
-
     pub fn borrow(
         &mut self,
         current_parent_node: &mut NodeInner,
@@ -125,8 +120,12 @@ impl NodeInner {
 
                     // Set promotion key
                     promotion_key = popped.pop_key;
-                    self.insert_child_pointer(popped.child_pointer.unwrap())?;
+                    self.insert_child_pointer_at(0, popped.child_pointer.unwrap())?;
                     self.insert_key(separator_key.clone())?;
+                    self.set_child_count(
+                        popped.child_pointer.unwrap(),
+                        popped.child_count.unwrap_or_default()
+                    )?;
                 } else {
                     let popped = current_candidate.pop_front()?;
 
@@ -134,8 +133,15 @@ impl NodeInner {
 
                     // println!("{:?}", popped);
                     promotion_key = popped.pop_key;
-                    self.insert_child_pointer(popped.child_pointer.unwrap())?;
+                    self.insert_child_pointer_at(
+                        self.child_ptr_len(),
+                        popped.child_pointer.unwrap()
+                    )?;
                     self.insert_key(separator_key.clone())?;
+                    self.set_child_count(
+                        popped.child_pointer.unwrap(),
+                        popped.child_count.unwrap_or_default()
+                    )?;
                 }
 
                 // Update the separator key
@@ -151,48 +157,53 @@ impl NodeInner {
         }
     }
 
+    /// Appends the contents of the right hand sibling to this node
+    ///
+    /// Child pointers are ordered by key rather than page id, so the sibling must be the right one
     pub fn merge(&mut self, sibling: &NodeInner) -> anyhow::Result<()> {
         match self.node_type {
             NodeType::Internal(ref mut children, ref mut keys, _) => {
                 if let NodeType::Internal(sibling_pointers, sibling_keys, _) = &sibling.node_type {
-                    let mut merged_pointers: Vec<PagePointer> = children
+                    let merged_pointers: Vec<PagePointer> = children
                         .iter()
                         .chain(sibling_pointers.iter())
                         .cloned()
                         .collect();
 
-                    let mut merged_keys: Vec<NodeKey> = keys
+                    let merged_keys: Vec<NodeKey> = keys
                         .iter()
                         .chain(sibling_keys.iter())
                         .cloned()
                         .collect();
 
-                    // Is not viable for large sets
-                    merged_pointers.sort();
-                    merged_keys.sort();
-
                     *children = merged_pointers;
                     *keys = merged_keys;
 
+                    if let (Some(counts), Some(sibling_counts)) = (
+                        &mut self.child_counts,
+                        &sibling.child_counts,
+                    ) {
+                        counts.extend(sibling_counts);
+                    }
+
                     anyhow::Ok(())
                 } else {
                     return Err(anyhow::Error::msg("Unexpected Error"));
                 }
             }
 
-            NodeType::Leaf(ref mut entries, ref mut current_next, _) => {
-                if let NodeType::Leaf(sibling_entries, sibling_next, _) = &sibling.node_type {
-                    let mut merged_entries: Vec<NodeKey> = entries
+            NodeType::Leaf(ref mut entries, _, _) => {
+                if let NodeType::Leaf(sibling_entries, _, _) = &sibling.node_type {
+                    let merged_entries: Vec<NodeKey> = entries
                         .iter()
                         .chain(sibling_entries.iter())
                         .cloned()
                         .collect();
 
-                    // Is not viable for large sets
-                    merged_entries.sort();
-
                     *entries = merged_entries;
-                    *current_next = sibling_next.clone();
+
+                    // The leaf chain is read from the node header, not the node type
+                    self.next_pointer = sibling.next_pointer;
 
                     anyhow::Ok(())
                 } else {
@@ -8,10 +8,16 @@ use anyhow::Ok;
 use crate::{
     buffer::{ buffer_pool_manager::{ BufferPoolManager, FileId }, flusher::{ Flusher, Lock } },
     index::tree::{
-        byte_box::DataType,
+        byte_box::{ ByteBox, DataType, MAX_COMPOSITE_SIZE },
+        db::{ btree_merge::MergeOperator, btree_snapshot::VersionSet },
         index_types::{ KeyValuePair, NodeKey },
         tree_node::{ node_type::{ NodeType, PagePointer }, tree_node_inner::NodeInner },
-        tree_page::{ codec::Codec, page::TreePage, tree_page_layout::PAGE_SIZE },
+        tree_page::{
+            codec::Codec,
+            meta::TreeMeta,
+            page::TreePage,
+            tree_page_layout::{ META_PAGE_ID, PAGE_SIZE },
+        },
     },
     storage::disk::manager::Manager,
 };
@@ -19,6 +25,8 @@ use crate::{
 pub enum WriteOperation {
     Delete,
     Insert,
+    // Swaps the value of an entry, which may grow or shrink the leaf
+    Replace,
 }
 
 pub struct BPTree {
@@ -27,27 +35,75 @@ pub struct BPTree {
 
     pub index_id: FileId,
 
+    // Identifies the tree's entries in a write-ahead log, see `BTreeBuilder::log_id`
+    pub(crate) log_id: u32,
+
     pub(crate) b: usize,
     pub codec: Codec,
+
+    // Target occupancy of nodes, those below half of it are rebalanced
+    pub(crate) fill_factor: f64,
+
+    // Entries are stored under their key and value, see `BTreeBuilder::allow_duplicates`
+    pub(crate) allow_duplicates: bool,
+
+    // Internal nodes keep the entry count of every child, see `BTreeBuilder::order_statistics`
+    pub(crate) order_statistics: bool,
+
+    // Page recording the root and parameters, for trees that own their file
+    pub(crate) meta_page_id: Option<PagePointer>,
+
+    // Root versions still read by snapshots, for trees built with `BTreeBuilder::copy_on_write`
+    pub(crate) versions: Option<Arc<Mutex<VersionSet>>>,
+
+    // Combines stored values with the operands of `BPTree::merge`
+    pub(crate) merge_operator: Option<MergeOperator>,
 }
 
 const NUM_FRAMES: usize = 10;
 const K_DIST: usize = 2;
 
+/// Fraction of a node's capacity filled by bulk loading
+///
+/// Nodes holding less than half of it are rebalanced on deletes.
+pub const DEFAULT_FILL_FACTOR: f64 = 0.9;
+
 pub struct BTreeBuilder {
     /// Path to the tree file
     // Parameter b is the orber of the tree
     // Each	non-leaf nobe contains b ≤ m ≤ 2b entries
     // minimum	50%	occupancy at all times
     // The root nobe can have 1 ≤ m ≤ 2b entries
-    b: usize,
+    //
     // The number of keys in a btree is representeb as
     // num_of_keys = 2b - 1
 
     // The number of pointers to chilb nodes in a btree is represented as
     // num_of_pointers = 2b
+    //
+    // Nodes are sized by their encoded bytes, b is an optional cap on top of that.
+    // Zero leaves nodes to fill their pages.
+    pub(crate) b: usize,
+
+    pub(crate) table_schema: Codec,
+
+    // Target occupancy of pages written by bulk loading, half of it is the minimum occupancy
+    pub(crate) fill_factor: f64,
+
+    // Whether several entries may share a key
+    pub(crate) allow_duplicates: bool,
+
+    // Whether internal nodes count the entries under each child
+    pub(crate) order_statistics: bool,
+
+    // Whether writes copy the pages they change rather than rewriting them
+    pub(crate) copy_on_write: bool,
 
-    table_schema: Codec,
+    // Operator `BPTree::merge` applies, which is not stored in the tree file
+    pub(crate) merge_operator: Option<MergeOperator>,
+
+    // Id of the tree's entries in a write-ahead log, the file id's low bits when unset
+    pub(crate) log_id: Option<u32>,
 }
 
 impl BTreeBuilder {
@@ -55,6 +111,12 @@ impl BTreeBuilder {
         BTreeBuilder {
             b: 0,
             table_schema: Codec { key_type: DataType::None, value_type: DataType::None },
+            fill_factor: DEFAULT_FILL_FACTOR,
+            allow_duplicates: false,
+            order_statistics: false,
+            copy_on_write: false,
+            merge_operator: None,
+            log_id: None,
         }
     }
     pub fn b_parameter(&mut self, b: usize) -> &mut Self {
@@ -67,36 +129,187 @@ impl BTreeBuilder {
         self
     }
 
-    pub fn build_from_file(
-        &self,
-        path: impl AsRef<Path> + std::marker::Copy + std::fmt::Debug
-    ) -> anyhow::Result<BPTree> {
+    pub fn fill_factor(&mut self, fill_factor: f64) -> &mut Self {
+        self.fill_factor = fill_factor;
+        self
+    }
+
+    /// Lets the tree hold several entries per key
+    ///
+    /// Duplicates are ordered by their value, so a key holds each value at most once.
+    /// Both the key and value types must be scalar.
+    pub fn allow_duplicates(&mut self, allow_duplicates: bool) -> &mut Self {
+        self.allow_duplicates = allow_duplicates;
+        self
+    }
+
+    /// Makes internal nodes keep the number of entries under each of their children
+    ///
+    /// The counts let `BPTree::count_range`, `rank` and `select` run in time logarithmic in the
+    /// size of the tree, at the cost of a few bytes per child and of rewriting the path to the
+    /// changed leaf on every write.
+    pub fn order_statistics(&mut self, order_statistics: bool) -> &mut Self {
+        self.order_statistics = order_statistics;
+        self
+    }
+
+    /// Makes writes copy the path from the changed leaf up to the root into new pages
+    ///
+    /// The copied root is published in one step once every page below it is written, so
+    /// `BPTree::snapshot` can hand out views that later writes never change. Pages are freed
+    /// once no snapshot older than the write replacing them is left. Leaves are not chained,
+    /// scans find the next leaf from the root instead.
+    pub fn copy_on_write(&mut self, copy_on_write: bool) -> &mut Self {
+        self.copy_on_write = copy_on_write;
+        self
+    }
+
+    /// Sets the operator `BPTree::merge` combines stored values and operands with
+    ///
+    /// Functions can not be written to the tree file, so a reopened tree takes the operator of
+    /// the builder opening it.
+    pub fn merge_operator(&mut self, operator: MergeOperator) -> &mut Self {
+        self.merge_operator = Some(operator);
+        self
+    }
+
+    /// Sets the id the tree's entries are logged under, which `BPTree::replay` picks them by
+    ///
+    /// Trees sharing a log need ids of their own. A tree built without one takes the low bits
+    /// of its file id. The id is kept on the meta page, so a reopened tree keeps its id even
+    /// though the file is given a new file id.
+    pub fn log_id(&mut self, log_id: u32) -> &mut Self {
+        self.log_id = Some(log_id);
+        self
+    }
+
+    /// Id a new tree in the file is logged under, see `log_id`
+    pub(crate) fn new_log_id(&self, file_id: FileId) -> u32 {
+        self.log_id.unwrap_or(file_id as u32)
+    }
+
+    /// Version set of a new tree, which only copy-on-write trees keep
+    pub(crate) fn new_versions(copy_on_write: bool) -> Option<Arc<Mutex<VersionSet>>> {
+        copy_on_write.then(|| Arc::new(Mutex::new(VersionSet::default())))
+    }
+
+    /// The codec the tree's pages are written with
+    ///
+    /// A tree with duplicates keys each entry on its key and value, making every stored key unique.
+    pub(crate) fn tree_codec(&self) -> anyhow::Result<Codec> {
+        if !self.allow_duplicates {
+            BTreeBuilder::check_composite(&self.table_schema.key_type)?;
+
+            return Ok(self.table_schema.clone());
+        }
+
+        let Codec { key_type, value_type } = &self.table_schema;
+
+        if !key_type.is_scalar() || !value_type.is_scalar() {
+            return Err(anyhow::Error::msg("Duplicate keys need scalar key and value types"));
+        }
+
+        // Entries are stored under (key, value), which has to fit a composite key
+        let stored_key_type = DataType::Composite(vec![key_type.clone(), value_type.clone()]);
+        BTreeBuilder::check_composite(&stored_key_type)?;
+
+        Ok(Codec {
+            key_type: stored_key_type,
+            value_type: value_type.clone(),
+        })
+    }
+
+    /// Rejects composite key types whose keys `ByteBox::composite` could not build
+    pub(crate) fn check_composite(key_type: &DataType) -> anyhow::Result<()> {
+        let types = match key_type {
+            DataType::Composite(types) => types,
+            _ => {
+                return Ok(());
+            }
+        };
+
+        if !types.iter().all(DataType::is_scalar) {
+            return Err(anyhow::Error::msg("Composite key components must be scalars"));
+        }
+
+        // Scalar components all have a largest size
+        let size = key_type.max_size().unwrap_or(usize::MAX);
+
+        if size > MAX_COMPOSITE_SIZE {
+            return Err(
+                anyhow::Error::msg(
+                    format!(
+                        "Composite keys of up to {} bytes are over the limit of {}",
+                        size,
+                        MAX_COMPOSITE_SIZE
+                    )
+                )
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Creates a buffer pool and flusher over a freshly allocated tree file
+    pub(crate) fn new_flusher() -> (Flusher, FileId) {
+        let (log_io, log_file_path) = Manager::open_log();
+        let manager = Manager::new(log_io, log_file_path);
+
+        let bpm = Arc::new(BufferPoolManager::new(NUM_FRAMES, manager, K_DIST));
+        let file_id = bpm.allocate_file();
+
+        (Flusher::new(bpm, file_id), file_id)
+    }
+
+    /// Creates a buffer pool and flusher over an existing file
+    pub(crate) fn open_flusher(path: impl AsRef<Path> + std::fmt::Debug) -> (Flusher, FileId) {
         let (log_io, log_file_path) = Manager::open_log();
         let manager = Manager::new(log_io, log_file_path);
 
         let bpm = Arc::new(BufferPoolManager::new(NUM_FRAMES, manager, K_DIST));
         let file_id = bpm.open_file(path);
 
-        let flusher = Flusher::new(bpm, file_id);
+        (Flusher::new(bpm, file_id), file_id)
+    }
+
+    /// Opens a tree file written by `build` or `bulk_load`
+    ///
+    /// The root and every parameter of the tree are read from its meta page, the builder's own
+    /// settings are not used. Only the merge operator, which the file can not hold, is taken
+    /// from the builder.
+    pub fn build_from_file(
+        &self,
+        path: impl AsRef<Path> + std::marker::Copy + std::fmt::Debug
+    ) -> anyhow::Result<BPTree> {
+        let (flusher, file_id) = BTreeBuilder::open_flusher(path);
+
+        if !flusher.is_allocated(META_PAGE_ID) {
+            return Err(anyhow::Error::msg(format!("{:?} holds no tree", path)));
+        }
+
+        let meta = TreeMeta::decode(&flusher.read_drop(META_PAGE_ID))?;
 
         Ok(BPTree {
             flusher: Arc::new(flusher),
-            root_page_id: RefCell::new(0),
+            root_page_id: RefCell::new(meta.root_page_id),
             index_id: file_id,
-            b: self.b.clone(),
-            codec: self.table_schema.clone(),
+            log_id: meta.log_id,
+            b: meta.b,
+            codec: meta.codec,
+            fill_factor: meta.fill_factor,
+            allow_duplicates: meta.allow_duplicates,
+            order_statistics: meta.order_statistics,
+            meta_page_id: Some(META_PAGE_ID),
+            versions: BTreeBuilder::new_versions(meta.copy_on_write),
+            merge_operator: self.merge_operator,
         })
     }
 
     pub fn build(&self) -> anyhow::Result<BPTree> {
-        let (log_io, log_file_path) = Manager::open_log();
-        let manager = Manager::new(log_io, log_file_path);
-
-        let bpm = Arc::new(BufferPoolManager::new(NUM_FRAMES, manager, K_DIST));
-        let file_id = bpm.allocate_file();
-
-        let flusher = Flusher::new(bpm, file_id);
+        let codec = self.tree_codec()?;
+        let (flusher, file_id) = BTreeBuilder::new_flusher();
 
+        let meta_page_id = flusher.new_page();
         let page_pointer = flusher.new_page();
         let root = NodeInner::new(
             NodeType::Leaf(vec![], page_pointer, None),
@@ -108,13 +321,23 @@ impl BTreeBuilder {
         let root_page_data = Codec::encode(&root)?;
         flusher.write_flush(root_page_data.get_data(), page_pointer)?;
 
-        Ok(BPTree {
+        let tree = BPTree {
             flusher: Arc::new(flusher),
             root_page_id: RefCell::new(page_pointer),
             index_id: file_id,
+            log_id: self.new_log_id(file_id),
             b: self.b.clone(),
-            codec: self.table_schema.clone(),
-        })
+            codec,
+            fill_factor: self.fill_factor,
+            allow_duplicates: self.allow_duplicates,
+            order_statistics: self.order_statistics,
+            meta_page_id: Some(meta_page_id),
+            versions: BTreeBuilder::new_versions(self.copy_on_write),
+            merge_operator: self.merge_operator,
+        };
+        tree.write_meta()?;
+
+        Ok(tree)
     }
 
     pub fn build_table(
@@ -145,45 +368,73 @@ impl BTreeBuilder {
             flusher: Arc::new(flusher),
             root_page_id: RefCell::new(page_pointer),
             index_id: file_id,
+            log_id: self.new_log_id(file_id),
             b: self.b.clone(),
-            codec: self.table_schema.clone(),
+            codec: self.tree_codec()?,
+            fill_factor: self.fill_factor,
+            allow_duplicates: self.allow_duplicates,
+            order_statistics: self.order_statistics,
+            // The file is shared with other tables, which the catalog keeps track of
+            meta_page_id: None,
+            versions: BTreeBuilder::new_versions(self.copy_on_write),
+            merge_operator: self.merge_operator,
         })
     }
 }
 
 impl BPTree {
-    pub(crate) fn new_root(&self, key: NodeKey, left_child: PagePointer, right_child: PagePointer) {
+    pub(crate) fn new_root(
+        &self,
+        key: NodeKey,
+        left_child: &NodeInner,
+        right_child: &NodeInner
+    ) -> anyhow::Result<()> {
         let new_root_pointer = self.flusher.new_page();
-        let new_root = NodeInner::new(
-            NodeType::Internal(vec![left_child, right_child], vec![key], new_root_pointer),
+        let mut new_root = NodeInner::new(
+            NodeType::Internal(
+                vec![left_child.pointer, right_child.pointer],
+                vec![key],
+                new_root_pointer
+            ),
             true,
             new_root_pointer,
             None
         );
 
-        let mut page_id = self.root_page_id.try_borrow_mut().unwrap();
-        *page_id = new_root_pointer;
+        if self.order_statistics {
+            new_root.child_counts = Some(vec![left_child.entry_count(), right_child.entry_count()]);
+        }
 
-        let page = Codec::encode(&new_root).unwrap();
+        let page = Codec::encode(&new_root)?;
+        self.flusher.write_flush(page.get_data(), new_root_pointer)?;
 
-        self.flusher.write_flush(page.get_data(), new_root_pointer).unwrap();
+        self.set_root(new_root_pointer)
     }
 
     pub fn search(&self, search: NodeKey) -> anyhow::Result<KeyValuePair> {
+        // Returns the first of the key's entries
+        if self.allow_duplicates {
+            let key = NodeInner::deconstruct_value(&search).0;
+
+            return self
+                .scan(key.clone()..=key)
+                .next()
+                .ok_or(anyhow::Error::msg("Key not present"));
+        }
+
         let page = self.flusher.read_drop(*self.root_page_id.borrow());
 
         let parent = self.codec.decode(&TreePage::new(page)).unwrap();
 
         if let NodeType::Leaf(entires, _, _) = parent.node_type {
-            let index = NodeInner::find_key(search, &entires)?;
+            let index = NodeInner::find_key(search.clone(), &entires)?;
 
-            let node_key = entires.get(index).unwrap();
-
-            let (key_bytes, value) = NodeInner::deconstruct_value(node_key);
+            let (key_bytes, value) = BPTree::matching_entry(&search, entires.get(index))?;
+            let value = BPTree::unexpired(value.unwrap())?;
 
             return Ok(KeyValuePair {
                 key: key_bytes,
-                value: value.unwrap(),
+                value: self.resolve_value(value)?,
             });
         }
 
@@ -213,15 +464,14 @@ impl BPTree {
                     }
                 }
                 NodeType::Leaf(entries, _, _) => {
-                    let index = NodeInner::find_key(search, &entries)?;
-
-                    let node_key = entries.get(index).unwrap();
+                    let index = NodeInner::find_key(search.clone(), entries)?;
 
-                    let (key_bytes, value) = NodeInner::deconstruct_value(node_key);
+                    let (key_bytes, value) = BPTree::matching_entry(&search, entries.get(index))?;
+                    let value = BPTree::unexpired(value.unwrap())?;
 
                     kv_pair = Some(KeyValuePair {
                         key: key_bytes,
-                        value: value.unwrap(),
+                        value: self.resolve_value(value)?,
                     });
 
                     break;
@@ -235,6 +485,21 @@ impl BPTree {
         return Ok(kv_pair.unwrap());
     }
 
+    /// Key and value of the leaf entry found for a search, if it holds the searched key
+    ///
+    /// The leaf's binary search lands on the entry after an absent key, or past the last one.
+    fn matching_entry(
+        search: &NodeKey,
+        found: Option<&NodeKey>
+    ) -> anyhow::Result<(ByteBox, Option<ByteBox>)> {
+        let (search_key, _) = NodeInner::deconstruct_value(search);
+
+        match found.map(NodeInner::deconstruct_value) {
+            Some((key, value)) if key == search_key => Ok((key, value)),
+            _ => Err(anyhow::Error::msg("Key not present")),
+        }
+    }
+
     pub fn find_min(&self) -> anyhow::Result<NodeInner> {
         let mut page = self.flusher.read_drop(*self.root_page_id.borrow());
 
@@ -264,6 +529,63 @@ impl BPTree {
         }
     }
 
+    pub fn find_max(&self) -> anyhow::Result<NodeInner> {
+        let mut current_node = self.codec.decode(
+            &TreePage::new(self.flusher.read_drop(*self.root_page_id.borrow()))
+        )?;
+
+        loop {
+            match &current_node.node_type {
+                NodeType::Internal(ref children, _, _) => {
+                    let child_pointer = *children
+                        .last()
+                        .ok_or(anyhow::Error::msg("Unexpected error"))?;
+
+                    let page = self.flusher.read_drop(child_pointer);
+
+                    current_node = self.codec.decode(&TreePage::new(page))?;
+                }
+                NodeType::Leaf(_, _, _) => {
+                    return Ok(current_node);
+                }
+                _ => {
+                    return Err(anyhow::Error::msg("Unexpected error"));
+                }
+            }
+        }
+    }
+
+    /// Returns the leaf the search key belongs in, whether or not the key is present
+    pub fn find_leaf(&self, search: NodeKey) -> anyhow::Result<NodeInner> {
+        let mut current_node = self.codec.decode(
+            &TreePage::new(self.flusher.read_drop(*self.root_page_id.borrow()))
+        )?;
+
+        loop {
+            match &current_node.node_type {
+                NodeType::Internal(ref children, ref keys, _) => {
+                    let idx = keys.binary_search(&search).unwrap_or_else(|x| x);
+
+                    let child_pointer = if idx >= keys.len() || keys[idx] != search {
+                        children[idx]
+                    } else {
+                        children[idx + 1]
+                    };
+
+                    let page = self.flusher.read_drop(child_pointer);
+
+                    current_node = self.codec.decode(&TreePage::new(page))?;
+                }
+                NodeType::Leaf(_, _, _) => {
+                    return Ok(current_node);
+                }
+                _ => {
+                    return Err(anyhow::Error::msg("Unexpected error"));
+                }
+            }
+        }
+    }
+
     pub fn find_node(&self, search: NodeKey) -> anyhow::Result<NodeInner> {
         let page = self.flusher.read_drop(*self.root_page_id.borrow());
 
@@ -368,21 +690,24 @@ impl BPTree {
         }
     }
 
-    pub(self) fn get_candidate(
+    pub(crate) fn get_candidate(
         &self,
         parent: &NodeInner,
-        child_id: PagePointer
+        child: &NodeInner
     ) -> anyhow::Result<(NodeInner, bool, NodeKey, bool)> {
+        let child_id = child.pointer;
+
         match &parent.node_type {
             NodeType::Internal(children, keys, _) => {
-                let node_idx = match children.binary_search(&child_id) {
-                    std::result::Result::Ok(idx) => idx,
-                    Err(_) => {
+                // Children are ordered by key, not by page id
+                let node_idx = match children.iter().position(|child| *child == child_id) {
+                    Some(idx) => idx,
+                    None => {
                         return Err(anyhow::Error::msg("Key not found"));
                     }
                 };
 
-                let separator_key = keys.get(node_idx).unwrap_or_else(|| &keys[node_idx - 1]);
+                let separator_key = BPTree::candidate_separator(keys, node_idx);
                 let mut is_left = false;
                 let candidate_index = node_idx + 1;
 
@@ -393,14 +718,14 @@ impl BPTree {
                     let page = self.flusher.read_drop(*id);
 
                     candidate = self.codec.decode(&TreePage::new(page))?;
-                    can_borrow = candidate.can_borrow(self.b);
+                    can_borrow = self.can_lend(child, &candidate, is_left);
                 } else {
                     let id = children.get(node_idx - 1).unwrap();
                     is_left = true;
 
                     let page = self.flusher.read_drop(*id);
                     candidate = self.codec.decode(&TreePage::new(page))?;
-                    can_borrow = candidate.can_borrow(self.b);
+                    can_borrow = self.can_lend(child, &candidate, is_left);
                 }
 
                 return Ok((candidate, is_left, separator_key.clone(), can_borrow));
@@ -412,6 +737,44 @@ impl BPTree {
         };
     }
 
+    /// The parent key between the child at `node_idx` and the sibling `get_candidate` picks
+    fn candidate_separator(keys: &[NodeKey], node_idx: usize) -> &NodeKey {
+        keys.get(node_idx).unwrap_or_else(|| &keys[node_idx - 1])
+    }
+
+    /// Moves entries from the candidate into the underfull node until it is underfull no more
+    ///
+    /// Entries vary in size, so one lent entry may not make up for a larger one deleted.
+    pub(crate) fn borrow_enough(
+        &self,
+        node: &mut NodeInner,
+        parent: &mut NodeInner,
+        candidate: &mut NodeInner,
+        is_left: bool,
+        mut separator: NodeKey
+    ) -> anyhow::Result<()> {
+        loop {
+            node.borrow(parent, candidate, is_left, separator)?;
+
+            if !self.is_underfull(node) || !self.can_lend(node, candidate, is_left) {
+                return Ok(());
+            }
+
+            let (children, keys) = match &parent.node_type {
+                NodeType::Internal(children, keys, _) => (children, keys),
+                _ => {
+                    return Err(anyhow::Error::msg("Unexpected error"));
+                }
+            };
+            let node_idx = children
+                .iter()
+                .position(|child| *child == node.pointer)
+                .ok_or(anyhow::Error::msg("Key not found"))?;
+
+            separator = BPTree::candidate_separator(keys, node_idx).clone();
+        }
+    }
+
     pub(crate) fn borrow_if_needed(
         &self,
         parent: NodeInner,
@@ -421,7 +784,7 @@ impl BPTree {
     ) -> anyhow::Result<()> {
         let (mut candidate, mut is_left, mut separator, mut can_borrow) = self.get_candidate(
             &parent,
-            child_node.pointer
+            &child_node
         )?;
 
         let mut current_node = child_node;
@@ -431,13 +794,23 @@ impl BPTree {
         loop {
             if can_borrow {
                 println!("Yes");
-                current_node.borrow(
+                self.borrow_enough(
+                    &mut current_node,
                     &mut current_parent_node,
                     &mut current_candidate,
                     is_left,
                     separator.clone()
                 )?;
 
+                current_parent_node.set_child_count(
+                    current_node.pointer,
+                    current_node.entry_count()
+                )?;
+                current_parent_node.set_child_count(
+                    current_candidate.pointer,
+                    current_candidate.entry_count()
+                )?;
+
                 self.flusher.pop_flush(
                     Codec::encode(&current_node).unwrap().get_data(),
                     current_node.pointer
@@ -458,53 +831,62 @@ impl BPTree {
                 return Ok(());
             }
 
-            current_node.merge(&current_candidate)?;
+            // The right node of the pair is always folded into the left one, which keeps the
+            // leaf chain intact as nothing but the left node points at the right one
+            let (mut left, right) = if is_left {
+                (current_candidate, current_node)
+            } else {
+                (current_node, current_candidate)
+            };
+
+            left.merge(&right)?;
 
-            match current_node.node_type {
-                NodeType::Internal(_, _, _) => {
-                    current_node.insert_key(separator.clone())?;
+            if left.is_leaf {
+                if let Some(next) = left.next_pointer {
+                    self.set_prev_pointer(next, Some(left.pointer))?;
                 }
-                _ => {}
             }
 
-            current_parent_node.remove_sibling_node(separator.clone(), current_candidate.pointer)?;
+            // Internal merges pull the separator down between the two halves
+            if let NodeType::Internal(_, _, _) = left.node_type {
+                left.insert_key(separator.clone())?;
+            }
+
+            current_parent_node.remove_sibling_node(separator.clone(), right.pointer)?;
+            current_parent_node.set_child_count(left.pointer, left.entry_count())?;
+
+            let collapse_root =
+                current_parent_node.is_root && current_parent_node.child_ptr_len() == 1;
 
-            if current_parent_node.is_root && current_parent_node.child_ptr_len() < self.b {
-                current_node.is_root = true;
+            if collapse_root {
+                left.is_root = true;
                 current_parent_node.is_root = false;
 
-                *self.root_page_id.borrow_mut() = current_node.pointer;
+                self.set_root(left.pointer)?;
+            }
 
-                self.flusher.pop_flush(
-                    Codec::encode(&current_node).unwrap().get_data(),
-                    current_node.pointer
-                )?;
+            // The locked node on top of the context stack is whichever node was underfull
+            if is_left {
+                self.flusher.release_ex()?;
+                self.flusher.write_flush(Codec::encode(&left).unwrap().get_data(), left.pointer)?;
+            } else {
+                self.flusher.pop_flush(Codec::encode(&left).unwrap().get_data(), left.pointer)?;
+            }
 
+            if collapse_root {
                 self.flusher.pop_flush(
                     Codec::encode(&current_parent_node).unwrap().get_data(),
                     current_parent_node.pointer
                 )?;
                 return Ok(());
-            } else {
-                self.flusher.pop_flush(
-                    Codec::encode(&current_node).unwrap().get_data(),
-                    current_node.pointer
-                )?;
             }
 
-            if current_parent_node.get_key_array_length() >= self.b - 1 {
+            if !self.is_underfull(&current_parent_node) || current_parent_node.is_root {
                 self.flusher.pop_flush(
                     Codec::encode(&current_parent_node).unwrap().get_data(),
                     current_parent_node.pointer
                 )?;
 
-                let candidate_id = current_candidate.pointer;
-
-                self.flusher.write_flush(
-                    Codec::encode(&current_candidate).unwrap().get_data(),
-                    candidate_id
-                )?;
-
                 return Ok(());
             } else {
                 current_node = current_parent_node.clone();
@@ -515,7 +897,7 @@ impl BPTree {
 
                 (candidate, is_left, separator, can_borrow) = self.get_candidate(
                     &parent,
-                    current_node.pointer
+                    &current_node
                 )?;
 
                 current_candidate = candidate;
@@ -561,19 +943,11 @@ impl BPTree {
 
                             let child_node = self.codec.decode(&TreePage::new(page))?;
 
-                            let key_array_length = child_node.get_key_array_length();
-                            let safe: bool;
-
-                            // Check if node is safe
-                            // Future implementation will rely on less arbituary params
-                            if let WriteOperation::Insert = operation {
-                                safe = key_array_length + 1 < self.b * 2;
-                            } else {
-                                safe = key_array_length - 1 > self.b - 1;
-                            }
+                            let safe = self.is_safe(&child_node, &operation);
 
+                            // A safe child can absorb the change, so no ancestor stays latched
                             if safe {
-                                let page = contex.pop_front();
+                                contex.clear();
                                 contex.push_front(child_pointer);
                             } else {
                                 contex.push_front(child_pointer);
@@ -603,6 +977,20 @@ impl BPTree {
         }
     }
 
+    /// Points a leaf that is not latched by the current operation at a new left neighbour
+    pub(crate) fn set_prev_pointer(
+        &self,
+        page_id: PagePointer,
+        prev_pointer: Option<PagePointer>
+    ) -> anyhow::Result<()> {
+        self.flusher.update_flush(page_id, |page| {
+            let mut leaf = self.codec.decode(&TreePage::new(page))?;
+            leaf.prev_pointer = prev_pointer;
+
+            Ok(Codec::encode(&leaf)?.get_data())
+        })
+    }
+
     pub(crate) fn propogate_upwards(
         &self,
         mut node: NodeInner
@@ -610,10 +998,18 @@ impl BPTree {
     ) -> anyhow::Result<()> {
         let mut was_root = node.is_root;
 
-        let (mut median, mut sibling) = node.split(self.b).unwrap();
+        let (mut median, mut sibling) = node.split(BPTree::split_point(&node)).unwrap();
 
-        // New func to set pointer
-        sibling.next_pointer = None;
+        // Only the leading bytes telling the two leaves apart need to go up
+        if let NodeType::Leaf(entries, _, _) = &node.node_type {
+            if let (Some(last), NodeKey::GuidePost(upper)) = (entries.last(), &median) {
+                let (lower, _) = NodeInner::deconstruct_value(last);
+                median = NodeKey::GuidePost(ByteBox::shortest_separator(&lower, upper));
+            }
+        }
+
+        // The sibling takes over the node's place in the leaf chain
+        sibling.next_pointer = node.next_pointer;
 
         let page_pointer = node.pointer;
 
@@ -623,6 +1019,14 @@ impl BPTree {
         // New func to set pointer
         node.next_pointer = Some(sibling.pointer);
 
+        if node.is_leaf {
+            sibling.prev_pointer = Some(node.pointer);
+
+            if let Some(next) = sibling.next_pointer {
+                self.set_prev_pointer(next, Some(sibling.pointer))?;
+            }
+        }
+
         let mut data: [u8; PAGE_SIZE];
 
         {
@@ -639,9 +1043,15 @@ impl BPTree {
         }
 
         if was_root {
-            self.new_root(median.clone(), node.pointer, sibling.pointer);
+            self.new_root(median.clone(), &node, &sibling)?;
         }
 
+        // The parent records how many entries each half of the split node kept
+        let mut split_counts = [
+            (node.pointer, node.entry_count()),
+            (sibling.pointer, sibling.entry_count()),
+        ];
+
         loop {
             let data = match self.flusher.read_top() {
                 anyhow::Result::Ok(data) => {
@@ -656,8 +1066,12 @@ impl BPTree {
             let mut current_node = self.codec.decode(&TreePage::new(data)).unwrap();
             current_node.insert_sibling_node(median.clone(), sibling.pointer)?;
 
+            for (child, count) in split_counts {
+                current_node.set_child_count(child, count)?;
+            }
+
             // Insert into current with no split
-            if current_node.get_key_array_length() < 2 * self.b {
+            if !self.is_overfull(&current_node) {
                 self.flusher
                     .pop_flush(
                         Codec::encode(&current_node).unwrap().get_data(),
@@ -672,7 +1086,7 @@ impl BPTree {
             // insert into current and split
             was_root = current_node.is_root;
 
-            (median, sibling) = current_node.split(self.b).unwrap();
+            (median, sibling) = current_node.split(BPTree::split_point(&current_node)).unwrap();
 
             // New func to set pointer
             sibling.pointer = self.flusher.new_page();
@@ -687,8 +1101,13 @@ impl BPTree {
             }
 
             if was_root {
-                self.new_root(median.clone(), current_node.pointer, sibling.pointer);
+                self.new_root(median.clone(), &current_node, &sibling)?;
             }
+
+            split_counts = [
+                (current_node.pointer, current_node.entry_count()),
+                (sibling.pointer, sibling.entry_count()),
+            ];
         }
     }
 }
@@ -1,24 +1,63 @@
 #![allow(unused_variables)] 
 #![allow(dead_code)] 
 
-use std::{ ops::RangeBounds, sync::Arc };
+use std::{ iter::Rev, ops::{ Bound, RangeBounds }, sync::Arc, time::SystemTime };
 
 use crate::index::tree::{
-    byte_box::ByteBox,
+    byte_box::{ ByteBox, DataType },
     index_types::{ KeyValuePair, NodeKey },
-    iterators::bptree_iterator::BPTreeIterator,
+    iterators::{ bptree_iterator::BPTreeIterator, cursor::Cursor },
+    tree_page::codec::Projection,
 };
 
-use super::btree_obj::BPTree;
+use super::{ btree_obj::BPTree, btree_ttl::unix_millis };
 
 impl BPTree {
+    /// The entry stored under the key, failing if there is none
     pub fn get(&self, key: ByteBox) -> anyhow::Result<KeyValuePair> {
-        let search_key = NodeKey::GuidePost(key);
-        unimplemented!()
+        self.search(NodeKey::GuidePost(key))
     }
 
+    /// Inserts the entry, replacing the value stored under its key if there is one
+    ///
+    /// A tree with duplicates keeps every value, so there it behaves like `insert`.
     pub fn put(&self, kv: KeyValuePair) -> anyhow::Result<()> {
-        unimplemented!()
+        // Copy-on-write trees swap the value within a single write
+        if self.is_copy_on_write() && !self.allow_duplicates {
+            self.check_entry(&kv)?;
+            return self.cow_insert(kv, true);
+        }
+
+        // An expired entry is replaced as well, as it is still stored
+        if !self.allow_duplicates && self.stored_value(&kv.key)?.is_some() {
+            self.delete_entry(NodeKey::GuidePost(kv.key.clone()))?;
+        }
+
+        self.insert(kv)
+    }
+
+    /// Whether an unexpired entry is stored under the key, without reading its value
+    pub fn contains_key(&self, key: &ByteBox) -> anyhow::Result<bool> {
+        // Values of a tree with duplicates are scalars kept inline, so a scan is cheap
+        if self.allow_duplicates {
+            return Ok(self.scan(key.clone()..=key.clone()).next().is_some());
+        }
+
+        let now = unix_millis(SystemTime::now());
+
+        Ok(self.stored_value(key)?.is_some_and(|value| !value.is_expired(now)))
+    }
+
+    /// The value stored under the key as its leaf holds it, expired or not
+    pub(crate) fn stored_value(&self, key: &ByteBox) -> anyhow::Result<Option<ByteBox>> {
+        let search = NodeKey::GuidePost(key.clone());
+        let leaf = self.find_leaf(search.clone())?;
+
+        let found = leaf
+            .get_key_value_index(&search)
+            .and_then(|idx| leaf.get_key_value_at(idx));
+
+        Ok(found.ok().filter(|entry| entry.key == *key).map(|entry| entry.value))
     }
 
     pub fn set(&self, kv: KeyValuePair) -> anyhow::Result<()> {
@@ -31,9 +70,43 @@ impl BPTree {
     }
 
     pub fn scan(&self, range: impl RangeBounds<ByteBox>) -> BPTreeIterator {
+        self.scan_with(range, Projection::Whole)
+    }
+
+    /// `scan`, handing out values as `projection` asks
+    pub(crate) fn scan_with(
+        &self,
+        range: impl RangeBounds<ByteBox>,
+        projection: Projection
+    ) -> BPTreeIterator {
+        if self.allow_duplicates {
+            let range = BPTree::tiebreak_range(range);
+            return self.scan_projected(range, projection).strip_tiebreaker();
+        }
+
+        self.scan_projected(range, projection)
+    }
+
+    /// Iterates over the keys as they are stored in the pages
+    pub(crate) fn scan_stored(&self, range: impl RangeBounds<ByteBox>) -> BPTreeIterator {
+        self.scan_projected(range, Projection::Whole)
+    }
+
+    /// `scan_stored`, handing out values as `projection` asks
+    pub(crate) fn scan_projected(
+        &self,
+        range: impl RangeBounds<ByteBox>,
+        projection: Projection
+    ) -> BPTreeIterator {
+        // Scans of a copy-on-write tree read a snapshot, so writes made while they run never
+        // free a page under them
+        if let Some(snapshot) = self.pin_root() {
+            return snapshot.scan_projected(range, projection);
+        }
+
         let lower_bound_page = match range.start_bound() {
             std::ops::Bound::Excluded(key) | std::ops::Bound::Included(key) => {
-                let page = self.find_node(NodeKey::GuidePost(key.clone())).unwrap();
+                let page = self.find_leaf(NodeKey::GuidePost(key.clone())).unwrap();
                 page
             }
             std::ops::Bound::Unbounded => {
@@ -42,14 +115,163 @@ impl BPTree {
             }
         };
 
+        let upper_bound_page = match range.end_bound() {
+            std::ops::Bound::Excluded(key) | std::ops::Bound::Included(key) => {
+                self.find_leaf(NodeKey::GuidePost(key.clone())).unwrap()
+            }
+            std::ops::Bound::Unbounded => self.find_max().unwrap(),
+        };
+
         let leaf_iter = BPTreeIterator::create_and_seek_to_key(
             self.flusher.clone(),
             self.codec.clone(),
             self.index_id,
+            None,
             Arc::new(lower_bound_page),
+            Arc::new(upper_bound_page),
             range
         );
 
-        leaf_iter
+        leaf_iter.projected(projection)
+    }
+
+    /// Iterates over every key starting with `prefix`, in key order
+    ///
+    /// Only `Char` and `Varchar` keys can be scanned by prefix.
+    pub fn scan_prefix(&self, prefix: &str) -> anyhow::Result<BPTreeIterator> {
+        let key_type = self.key_type();
+
+        match key_type {
+            DataType::Char(_) | DataType::Varchar(_) => {}
+            _ => {
+                return Err(
+                    anyhow::Error::msg(
+                        format!("Prefix scans need string keys, found {}", key_type.to_string())
+                    )
+                );
+            }
+        }
+
+        let start = Bound::Included(key_type.to_byte_box(prefix.as_bytes()));
+
+        // Keys compare as strings, so the first key past the prefix is at least its successor
+        let end = match prefix_successor(prefix) {
+            Some(successor) => {
+                Bound::Excluded(key_type.to_byte_box(successor.as_bytes()))
+            }
+            None => Bound::Unbounded,
+        };
+
+        Ok(self.scan((start, end)))
+    }
+
+    /// Iterates over every composite key whose leading components equal `prefix`
+    pub fn scan_composite_prefix(&self, prefix: &[ByteBox]) -> anyhow::Result<BPTreeIterator> {
+        let key_types = match self.key_type() {
+            DataType::Composite(types) => types,
+            _ => {
+                return Err(anyhow::Error::msg("Composite prefix scans need composite keys"));
+            }
+        };
+
+        let leading = prefix.iter().map(|component| &component.datatype);
+        if prefix.len() > key_types.len() || !key_types.iter().zip(leading).all(|(a, b)| a == b) {
+            return Err(anyhow::Error::msg("Schema Mismatch: prefix components"));
+        }
+
+        if prefix.is_empty() {
+            return Ok(self.scan(..));
+        }
+
+        let start = Bound::Included(ByteBox::composite_bound(prefix));
+
+        // A shorter composite sorts before every key extending it, so the successor bounds the scan
+        let end = match composite_successor(prefix) {
+            Some(successor) => Bound::Excluded(ByteBox::composite_bound(&successor)),
+            None => Bound::Unbounded,
+        };
+
+        Ok(self.scan((start, end)))
+    }
+
+    /// Returns an unpositioned cursor over the tree, see `Cursor::seek`
+    pub fn cursor(&self) -> Cursor<'_> {
+        Cursor::new(self)
+    }
+
+    /// Iterates over the range from its largest key down to its smallest
+    pub fn scan_rev(&self, range: impl RangeBounds<ByteBox>) -> Rev<BPTreeIterator> {
+        self.scan(range).rev()
+    }
+}
+
+/// Returns the smallest string greater than every string starting with `prefix`
+///
+/// The last character that has a successor is incremented and everything after it dropped.
+/// An empty prefix, or one made only of `char::MAX`, has no upper bound.
+fn prefix_successor(prefix: &str) -> Option<String> {
+    let mut chars: Vec<char> = prefix.chars().collect();
+
+    while let Some(last) = chars.pop() {
+        // Skips over the surrogate range, which holds no chars
+        let next = (last as u32 + 1..=char::MAX as u32).find_map(char::from_u32);
+
+        if let Some(next) = next {
+            chars.push(next);
+            return Some(chars.into_iter().collect());
+        }
+    }
+
+    None
+}
+
+/// Returns the smallest components greater than every composite key starting with `prefix`
+pub(crate) fn composite_successor(prefix: &[ByteBox]) -> Option<Vec<ByteBox>> {
+    let mut components = prefix.to_vec();
+
+    while let Some(last) = components.pop() {
+        if let Some(next) = component_successor(&last) {
+            components.push(next);
+            return Some(components);
+        }
+    }
+
+    None
+}
+
+/// Returns the smallest value of the component's type greater than it, if there is one
+fn component_successor(component: &ByteBox) -> Option<ByteBox> {
+    let data = &component.data[..];
+
+    match component.datatype {
+        DataType::BigInt => {
+            let val = i64::from_le_bytes(data.try_into().ok()?);
+            val.checked_add(1).map(ByteBox::big_int)
+        }
+        DataType::Int => {
+            let val = i32::from_le_bytes(data.try_into().ok()?);
+            val.checked_add(1).map(ByteBox::int)
+        }
+        DataType::SmallInt => {
+            let val = i16::from_le_bytes(data.try_into().ok()?);
+            val.checked_add(1).map(ByteBox::small_int)
+        }
+        DataType::Decimal => {
+            let val = f32::from_le_bytes(data.try_into().ok()?);
+            let next = val.next_up();
+
+            (next != val && !next.is_nan()).then(|| ByteBox::decimal(next))
+        }
+        DataType::Boolean => {
+            (data == [0]).then(|| ByteBox::boolean(true))
+        }
+        // Appending the smallest char gives the next string
+        DataType::Char(_) | DataType::Varchar(_) => {
+            let mut next = data.to_vec();
+            next.push(0);
+
+            Some(component.datatype.to_byte_box(&next))
+        }
+        _ => None,
     }
 }
@@ -0,0 +1,207 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::ops::{ Bound, RangeBounds };
+
+use crate::index::tree::{
+    byte_box::ByteBox,
+    index_types::NodeKey,
+    tree_node::{ node_type::NodeType, tree_node_inner::NodeInner },
+    tree_page::{ codec::Codec, page::TreePage },
+};
+
+use super::btree_obj::BPTree;
+
+// A tree built with `order_statistics` keeps, next to every child pointer of an internal node,
+// the number of entries in that child's subtree. Splits, merges and borrows hand the counts of
+// the children they move along with them and recount the pair of nodes they change. The path
+// from the root to the leaf a write went to is recounted once the write is done, so every count
+// above the change is brought up to date with a single pass.
+//
+// A query then descends a single path, adding up the counts of the children it passes over.
+impl BPTree {
+    /// Number of entries with a key in `range`
+    pub fn count_range(&self, range: impl RangeBounds<ByteBox>) -> anyhow::Result<u64> {
+        self.check_order_statistics()?;
+
+        let (start, end) = if self.allow_duplicates {
+            BPTree::tiebreak_range(range)
+        } else {
+            (range.start_bound().cloned(), range.end_bound().cloned())
+        };
+
+        let below_start = match &start {
+            Bound::Included(key) => self.count_below(key, false)?,
+            Bound::Excluded(key) => self.count_below(key, true)?,
+            Bound::Unbounded => 0,
+        };
+
+        let below_end = match &end {
+            Bound::Included(key) => self.count_below(key, true)?,
+            Bound::Excluded(key) => self.count_below(key, false)?,
+            Bound::Unbounded => self.root_node()?.entry_count(),
+        };
+
+        Ok(below_end.saturating_sub(below_start))
+    }
+
+    /// Number of entries with a key below `key`, which is the position the key has or would
+    /// have in the tree
+    pub fn rank(&self, key: &ByteBox) -> anyhow::Result<u64> {
+        // A key alone sorts before every (key, value) pair stored for it
+        if self.allow_duplicates {
+            return self.count_below(&ByteBox::composite_bound(std::slice::from_ref(key)), false);
+        }
+
+        self.count_below(key, false)
+    }
+
+    /// Key of the entry at position `n` in key order, counting from zero
+    ///
+    /// In a tree with duplicates every entry of a key has its own position.
+    pub fn select(&self, n: u64) -> anyhow::Result<Option<ByteBox>> {
+        self.check_order_statistics()?;
+
+        let mut node = self.root_node()?;
+        let mut n = n;
+
+        loop {
+            let child = match (&node.node_type, &node.child_counts) {
+                (NodeType::Internal(children, _, _), Some(counts)) => {
+                    let mut chosen = None;
+
+                    for (child, count) in children.iter().zip(counts) {
+                        if n < *count {
+                            chosen = Some(*child);
+                            break;
+                        }
+
+                        n -= count;
+                    }
+
+                    match chosen {
+                        Some(child) => child,
+                        None => {
+                            return Ok(None);
+                        }
+                    }
+                }
+                (NodeType::Leaf(entries, _, _), _) => {
+                    let key = entries
+                        .get(n as usize)
+                        .map(|entry| self.user_key(NodeInner::deconstruct_value(entry).0));
+
+                    return Ok(key);
+                }
+                _ => {
+                    return Err(anyhow::Error::msg("Node keeps no child counts"));
+                }
+            };
+
+            node = self.read_node(child)?;
+        }
+    }
+
+    /// Number of stored keys below `bound`, or at most `bound` when `inclusive`
+    fn count_below(&self, bound: &ByteBox, inclusive: bool) -> anyhow::Result<u64> {
+        self.check_order_statistics()?;
+
+        let search = NodeKey::GuidePost(bound.clone());
+
+        let mut node = self.root_node()?;
+        let mut count = 0;
+
+        loop {
+            let child = match (&node.node_type, &node.child_counts) {
+                (NodeType::Internal(children, keys, _), Some(counts)) => {
+                    // Keys at or above a separator sit right of it
+                    let idx = keys.partition_point(|key| *key <= search);
+                    count += counts[..idx].iter().sum::<u64>();
+
+                    children[idx]
+                }
+                (NodeType::Leaf(entries, _, _), _) => {
+                    let below = entries.partition_point(|entry| {
+                        let key = NodeInner::deconstruct_value(entry).0;
+
+                        if inclusive { key <= *bound } else { key < *bound }
+                    });
+
+                    return Ok(count + (below as u64));
+                }
+                _ => {
+                    return Err(anyhow::Error::msg("Node keeps no child counts"));
+                }
+            };
+
+            node = self.read_node(child)?;
+        }
+    }
+
+    /// Brings the child counts on the path to the leaf of `search` up to date
+    ///
+    /// Called once a write is done, after any split, merge or borrow it caused.
+    pub(crate) fn recount_path(&self, search: &NodeKey) -> anyhow::Result<()> {
+        if !self.order_statistics {
+            return Ok(());
+        }
+
+        let mut path: Vec<(NodeInner, usize)> = Vec::new();
+        let mut node = self.root_node()?;
+
+        while let NodeType::Internal(children, keys, _) = &node.node_type {
+            let idx = keys.partition_point(|key| key <= search);
+            let child = self.read_node(children[idx])?;
+
+            path.push((node, idx));
+            node = child;
+        }
+
+        let mut count = node.entry_count();
+
+        while let Some((mut parent, idx)) = path.pop() {
+            if let Some(counts) = &mut parent.child_counts {
+                if counts[idx] != count {
+                    counts[idx] = count;
+                    self.flusher.write_flush(Codec::encode(&parent)?.get_data(), parent.pointer)?;
+                }
+            }
+
+            count = parent.entry_count();
+        }
+
+        Ok(())
+    }
+
+    fn check_order_statistics(&self) -> anyhow::Result<()> {
+        if !self.order_statistics {
+            return Err(
+                anyhow::Error::msg(
+                    "Tree keeps no order statistics, see BTreeBuilder::order_statistics"
+                )
+            );
+        }
+
+        Ok(())
+    }
+
+    fn root_node(&self) -> anyhow::Result<NodeInner> {
+        self.read_node(*self.root_page_id.borrow())
+    }
+
+    fn read_node(&self, page: u32) -> anyhow::Result<NodeInner> {
+        self.codec.decode(&TreePage::new(self.flusher.read_drop(page)))
+    }
+
+    /// The key an entry was inserted under, without the value a tree with duplicates adds
+    fn user_key(&self, key: ByteBox) -> ByteBox {
+        if !self.allow_duplicates {
+            return key;
+        }
+
+        match key.components() {
+            Ok(mut components) => components.swap_remove(0),
+            Err(_) => key,
+        }
+    }
+}
@@ -0,0 +1,58 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use crate::index::tree::{
+    byte_box::{ ByteBox, DataType },
+    tree_page::{ overflow::OverflowChain, tree_page_layout::OVERFLOW_THRESHOLD },
+};
+
+use super::btree_obj::BPTree;
+
+// Values longer than `OVERFLOW_THRESHOLD` do not live in the leaf. Their bytes are written to a
+// chain of overflow pages and the leaf keeps an `Overflow` stub in their place, which reads
+// swap back for the value and deletes release.
+impl BPTree {
+    /// Moves a large value into overflow pages, returning what the leaf should store
+    pub(crate) fn store_value(&self, value: ByteBox) -> anyhow::Result<ByteBox> {
+        // The expiry stays in the leaf ahead of the stub
+        if let Some(expires_at) = value.expires_at() {
+            return Ok(ByteBox::expiring(self.store_value(value.without_expiry())?, expires_at));
+        }
+
+        if value.data.len() <= OVERFLOW_THRESHOLD {
+            return Ok(value);
+        }
+
+        Ok(OverflowChain::write(&self.flusher, &value.data)?.to_stub())
+    }
+
+    /// Reassembles a value the leaf only holds a stub for, leaving out any expiry
+    pub(crate) fn resolve_value(&self, value: ByteBox) -> anyhow::Result<ByteBox> {
+        let value = value.without_expiry();
+
+        if value.datatype != DataType::Overflow {
+            return Ok(value);
+        }
+
+        let data = OverflowChain::from_stub(&value)?.read(&self.flusher)?;
+        Ok(self.codec.value_type.to_byte_box(&data))
+    }
+
+    /// Releases the overflow pages behind a stored value, if it has any
+    pub(crate) fn free_value(&self, value: &ByteBox) -> anyhow::Result<()> {
+        let value = value.clone().without_expiry();
+
+        if value.datatype != DataType::Overflow {
+            return Ok(());
+        }
+
+        let chain = OverflowChain::from_stub(&value)?;
+
+        // Snapshots of a copy-on-write tree may still read the value
+        if self.is_copy_on_write() {
+            return self.retire(chain.pages(&self.flusher)?);
+        }
+
+        chain.free(&self.flusher)
+    }
+}
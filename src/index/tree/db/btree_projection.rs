@@ -0,0 +1,58 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::ops::RangeBounds;
+
+use crate::index::tree::{
+    byte_box::{ ByteBox, DataType },
+    iterators::bptree_iterator::{ BPTreeIterator, KeyIterator },
+    tree_page::codec::Projection,
+};
+
+use super::btree_obj::BPTree;
+
+// Scans that hand out less than the whole value decode leaves only as far as they need. A key
+// scan steps over every value, overflow stubs included, and a projected scan reads just the
+// columns asked for out of each tuple.
+impl BPTree {
+    /// Iterates over the keys of the range, in key order, without decoding any value
+    ///
+    /// Expired entries are skipped as in `scan`, and a tree with duplicates hands out a key once
+    /// for every value stored under it.
+    pub fn scan_keys(&self, range: impl RangeBounds<ByteBox>) -> KeyIterator {
+        KeyIterator::new(self.scan_with(range, Projection::Keys))
+    }
+
+    /// Iterates over the range as `scan` does, with every value cut down to the named columns
+    ///
+    /// Values come out as tuples of the columns in the order they are named. Only trees with
+    /// tuple values can be projected.
+    pub fn scan_project(
+        &self,
+        range: impl RangeBounds<ByteBox>,
+        columns: &[&str]
+    ) -> anyhow::Result<BPTreeIterator> {
+        let schema = match &self.codec.value_type {
+            DataType::Tuple(schema) => schema,
+            other => {
+                return Err(
+                    anyhow::Error::msg(
+                        format!("Projected scans need tuple values, found {}", other.to_string())
+                    )
+                );
+            }
+        };
+
+        let positions = columns
+            .iter()
+            .map(|name| {
+                schema.columns
+                    .iter()
+                    .position(|column| column.name == *name)
+                    .ok_or(anyhow::Error::msg(format!("No column named {}", name)))
+            })
+            .collect::<anyhow::Result<Vec<usize>>>()?;
+
+        Ok(self.scan_with(range, Projection::Columns(positions)))
+    }
+}
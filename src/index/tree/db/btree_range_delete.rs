@@ -0,0 +1,428 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::{ collections::{ HashMap, HashSet }, ops::{ Bound, RangeBounds } };
+
+use crate::index::tree::{
+    byte_box::ByteBox,
+    index_types::NodeKey,
+    tree_node::{ node_type::{ NodeType, PagePointer }, tree_node_inner::NodeInner },
+    tree_page::{ codec::Codec, page::TreePage },
+};
+
+use super::btree_obj::BPTree;
+
+type KeyRange = (Bound<ByteBox>, Bound<ByteBox>);
+
+/// Nodes touched by a range delete, written back once the whole pass is done
+#[derive(Default)]
+struct RangeDeleteState {
+    dirty: HashMap<PagePointer, NodeInner>,
+    freed: Vec<PagePointer>,
+
+    /// (prev, next) of every leaf the pass reached, as they were before it
+    leaf_links: HashMap<PagePointer, (Option<PagePointer>, Option<PagePointer>)>,
+
+    /// Values of the removed entries, whose overflow pages go once no leaf points at them
+    values: Vec<ByteBox>,
+    removed: usize,
+}
+
+impl RangeDeleteState {
+    fn collect_values(&mut self, entries: Vec<NodeKey>) {
+        self.removed += entries.len();
+
+        for entry in entries {
+            if let NodeKey::KeyValuePair(kv_pair) = entry {
+                self.values.push(kv_pair.value);
+            }
+        }
+    }
+}
+
+// A range delete walks down only the nodes whose key range overlaps the deleted one. Subtrees
+// lying wholly inside the range are freed without their entries being looked at one by one, so
+// at most two nodes per level, those holding the ends of the range, are rewritten. Those nodes
+// are then rebalanced against a neighbour on the way back up and the leaf chain is relinked
+// around the gap.
+impl BPTree {
+    /// Removes every entry with a key in `range` and returns how many were removed
+    pub fn delete_range(&self, range: impl RangeBounds<ByteBox>) -> anyhow::Result<usize> {
+        let range: KeyRange = if self.allow_duplicates {
+            BPTree::tiebreak_range(range)
+        } else {
+            (range.start_bound().cloned(), range.end_bound().cloned())
+        };
+
+        // Copy-on-write trees never rewrite a page in place, so keys go one at a time
+        if self.is_copy_on_write() {
+            let keys: Vec<ByteBox> = self.scan_stored(range).map(|entry| entry.key).collect();
+
+            for key in &keys {
+                self.cow_delete(NodeKey::GuidePost(key.clone()))?;
+            }
+
+            return Ok(keys.len());
+        }
+
+        let mut state = RangeDeleteState::default();
+
+        let old_root = *self.root_page_id.borrow();
+        self.prune(old_root, None, None, &range, &mut state)?;
+
+        let mut root = self.take_node(old_root, &mut state)?;
+
+        // Only a range covering the whole tree leaves the root without children
+        if !root.is_leaf && root.child_ptr_len() == 0 {
+            let pointer = root.pointer;
+            root = NodeInner::new(NodeType::Leaf(vec![], pointer, None), true, pointer, None);
+        }
+
+        while let NodeType::Internal(children, _, _) = &root.node_type {
+            if children.len() > 1 {
+                break;
+            }
+
+            let child = children[0];
+            state.freed.push(root.pointer);
+
+            root = self.take_node(child, &mut state)?;
+        }
+
+        root.is_root = true;
+        let root_pointer = root.pointer;
+        state.dirty.insert(root_pointer, root);
+
+        self.relink_leaves(root_pointer, &mut state)?;
+
+        if self.order_statistics {
+            self.recount_dirty(root_pointer, &mut state)?;
+        }
+
+        for (page, node) in &state.dirty {
+            self.flusher.write_flush(Codec::encode(node)?.get_data(), *page)?;
+        }
+
+        if root_pointer != old_root {
+            self.set_root(root_pointer)?;
+        }
+
+        for page in &state.freed {
+            self.flusher.free_page(*page)?;
+        }
+
+        for value in &state.values {
+            self.free_value(value)?;
+        }
+
+        Ok(state.removed)
+    }
+
+    /// Removes the range from the node on `page`, which holds keys within [lower, upper)
+    fn prune(
+        &self,
+        page: PagePointer,
+        lower: Option<&ByteBox>,
+        upper: Option<&ByteBox>,
+        range: &KeyRange,
+        state: &mut RangeDeleteState
+    ) -> anyhow::Result<()> {
+        let mut node = self.take_node(page, state)?;
+
+        match &mut node.node_type {
+            NodeType::Leaf(entries, _, _) => {
+                let (removed, kept): (Vec<NodeKey>, Vec<NodeKey>) = entries
+                    .drain(..)
+                    .partition(|entry| range.contains(&NodeInner::deconstruct_value(entry).0));
+
+                *entries = kept;
+                state.collect_values(removed);
+            }
+            NodeType::Internal(children, keys, _) => {
+                let bounds: Vec<ByteBox> = keys
+                    .iter()
+                    .map(|key| NodeInner::deconstruct_value(key).0)
+                    .collect();
+
+                let mut kept_children = Vec::with_capacity(children.len());
+                let mut kept_keys = Vec::with_capacity(keys.len());
+                let mut kept_counts = Vec::with_capacity(children.len());
+
+                for (idx, child) in children.iter().enumerate() {
+                    let child_lower = idx.checked_sub(1).map(|idx| &bounds[idx]).or(lower);
+                    let child_upper = bounds.get(idx).or(upper);
+
+                    if covers(range, child_lower, child_upper) {
+                        self.free_subtree(*child, state)?;
+                        continue;
+                    }
+
+                    if overlaps(range, child_lower, child_upper) {
+                        self.prune(*child, child_lower, child_upper, range, state)?;
+                    }
+
+                    // A child's lower bound separates it from whichever child is kept before it
+                    if !kept_children.is_empty() {
+                        kept_keys.push(NodeKey::GuidePost(child_lower.unwrap().clone()));
+                    }
+                    kept_children.push(*child);
+                    kept_counts.extend(node.child_counts.as_ref().map(|counts| counts[idx]));
+                }
+
+                *children = kept_children;
+                *keys = kept_keys;
+
+                // Pruned children are recounted once the pass is done
+                if node.child_counts.is_some() {
+                    node.child_counts = Some(kept_counts);
+                }
+            }
+            NodeType::Unexpected => {
+                return Err(anyhow::Error::msg("Unexpected node type"));
+            }
+        }
+
+        self.rebalance_children(&mut node, state)?;
+
+        state.dirty.insert(page, node);
+        Ok(())
+    }
+
+    /// Frees every page of the subtree on `page` along with the overflow pages of its values
+    fn free_subtree(&self, page: PagePointer, state: &mut RangeDeleteState) -> anyhow::Result<()> {
+        let node = self.take_node(page, state)?;
+
+        match node.node_type {
+            NodeType::Internal(children, _, _) => {
+                for child in children {
+                    self.free_subtree(child, state)?;
+                }
+            }
+            NodeType::Leaf(entries, _, _) => state.collect_values(entries),
+            NodeType::Unexpected => {
+                return Err(anyhow::Error::msg("Unexpected node type"));
+            }
+        }
+
+        state.freed.push(page);
+        Ok(())
+    }
+
+    /// Merges every underfull child the pass rewrote into a neighbour, splitting the pair again
+    /// when it no longer fits a page
+    fn rebalance_children(
+        &self,
+        node: &mut NodeInner,
+        state: &mut RangeDeleteState
+    ) -> anyhow::Result<()> {
+        let (children, keys) = match &mut node.node_type {
+            NodeType::Internal(children, keys, _) => (children, keys),
+            _ => {
+                return Ok(());
+            }
+        };
+
+        let mut idx = 0;
+
+        while children.len() > 1 && idx < children.len() {
+            let underfull = state.dirty
+                .get(&children[idx])
+                .is_some_and(|child| self.is_underfull(child));
+
+            if !underfull {
+                idx += 1;
+                continue;
+            }
+
+            let left_idx = if idx + 1 < children.len() { idx } else { idx - 1 };
+
+            let mut left = self.take_node(children[left_idx], state)?;
+            let right = self.take_node(children[left_idx + 1], state)?;
+            let separator = keys.remove(left_idx);
+
+            left.merge(&right)?;
+
+            // Internal merges pull the separator down between the two halves
+            if !left.is_leaf {
+                left.insert_key(separator)?;
+                self.rebalance_children(&mut left, state)?;
+            }
+
+            if self.is_overfull(&left) {
+                let (mut median, mut sibling) = left.split(BPTree::split_point(&left))?;
+
+                if let NodeType::Leaf(entries, _, _) = &left.node_type {
+                    if let (Some(last), NodeKey::GuidePost(upper)) = (entries.last(), &median) {
+                        let (lower, _) = NodeInner::deconstruct_value(last);
+                        median = NodeKey::GuidePost(ByteBox::shortest_separator(&lower, upper));
+                    }
+                }
+
+                // The right half takes over the page of the node it came from
+                sibling.pointer = right.pointer;
+                keys.insert(left_idx, median);
+                state.dirty.insert(sibling.pointer, sibling);
+            } else {
+                children.remove(left_idx + 1);
+                state.freed.push(right.pointer);
+
+                if let Some(counts) = &mut node.child_counts {
+                    counts.remove(left_idx + 1);
+                }
+            }
+
+            state.dirty.insert(left.pointer, left);
+            idx = left_idx;
+        }
+
+        Ok(())
+    }
+
+    /// Sets the child counts of every internal node the pass rewrote under `page`, returning the
+    /// number of entries under it
+    fn recount_dirty(
+        &self,
+        page: PagePointer,
+        state: &mut RangeDeleteState
+    ) -> anyhow::Result<u64> {
+        let children = match state.dirty.get(&page) {
+            Some(node) =>
+                match &node.node_type {
+                    NodeType::Internal(children, _, _) => children.clone(),
+                    _ => {
+                        return Ok(node.entry_count());
+                    }
+                }
+            None => {
+                // The pass left the node alone, so the counts it keeps still hold
+                let node = self.codec.decode(&TreePage::new(self.flusher.read_drop(page)))?;
+                return Ok(node.entry_count());
+            }
+        };
+
+        let counts = children
+            .iter()
+            .map(|child| self.recount_dirty(*child, state))
+            .collect::<anyhow::Result<Vec<u64>>>()?;
+        let count = counts.iter().sum();
+
+        state.dirty.get_mut(&page).unwrap().child_counts = Some(counts);
+
+        Ok(count)
+    }
+
+    /// Returns the node on `page`, taking it out of the dirty set if the pass already holds it
+    fn take_node(
+        &self,
+        page: PagePointer,
+        state: &mut RangeDeleteState
+    ) -> anyhow::Result<NodeInner> {
+        let node = match state.dirty.remove(&page) {
+            Some(node) => node,
+            None => self.codec.decode(&TreePage::new(self.flusher.read_drop(page)))?,
+        };
+
+        if node.is_leaf {
+            state.leaf_links.entry(page).or_insert((node.prev_pointer, node.next_pointer));
+        }
+
+        Ok(node)
+    }
+
+    /// Chains the leaves left after the pass between the untouched leaves around them
+    ///
+    /// The leaves the pass reached are adjacent in key order, so only the first of them points
+    /// back at a leaf outside the set and only the last points forward out of it.
+    fn relink_leaves(&self, root: PagePointer, state: &mut RangeDeleteState) -> anyhow::Result<()> {
+        let freed: HashSet<PagePointer> = state.freed.iter().copied().collect();
+
+        let mut before = None;
+        let mut after = None;
+
+        for (prev, next) in state.leaf_links.values() {
+            if let Some(prev) = prev.filter(|prev| !state.leaf_links.contains_key(prev)) {
+                before = Some(prev);
+            }
+            if let Some(next) = next.filter(|next| !state.leaf_links.contains_key(next)) {
+                after = Some(next);
+            }
+        }
+
+        let mut kept = Vec::new();
+        self.collect_dirty_leaves(root, state, &mut kept);
+        kept.retain(|page| !freed.contains(page));
+
+        for (idx, page) in kept.iter().enumerate() {
+            let leaf = state.dirty.get_mut(page).unwrap();
+
+            leaf.prev_pointer = idx.checked_sub(1).map(|idx| kept[idx]).or(before);
+            leaf.next_pointer = kept.get(idx + 1).copied().or(after);
+        }
+
+        if let Some(before) = before {
+            let mut leaf = self.codec.decode(&TreePage::new(self.flusher.read_drop(before)))?;
+            leaf.next_pointer = kept.first().copied().or(after);
+
+            self.flusher.write_flush(Codec::encode(&leaf)?.get_data(), before)?;
+        }
+
+        if let Some(after) = after {
+            self.set_prev_pointer(after, kept.last().copied().or(before))?;
+        }
+
+        Ok(())
+    }
+
+    /// Lists, in key order, the leaves below `page` that the pass rewrote
+    fn collect_dirty_leaves(
+        &self,
+        page: PagePointer,
+        state: &RangeDeleteState,
+        leaves: &mut Vec<PagePointer>
+    ) {
+        match state.dirty.get(&page).map(|node| &node.node_type) {
+            Some(NodeType::Internal(children, _, _)) => {
+                for child in children {
+                    self.collect_dirty_leaves(*child, state, leaves);
+                }
+            }
+            Some(NodeType::Leaf(_, _, _)) => leaves.push(page),
+            _ => {}
+        }
+    }
+}
+
+/// Whether every key in [lower, upper) lies inside the range
+fn covers(range: &KeyRange, lower: Option<&ByteBox>, upper: Option<&ByteBox>) -> bool {
+    let from_start = match &range.0 {
+        Bound::Included(start) => lower.is_some_and(|lower| lower >= start),
+        Bound::Excluded(start) => lower.is_some_and(|lower| lower > start),
+        Bound::Unbounded => true,
+    };
+
+    // Keys stay below upper, so an upper bound at or before the end of the range is enough
+    let to_end = match &range.1 {
+        Bound::Included(end) | Bound::Excluded(end) => upper.is_some_and(|upper| upper <= end),
+        Bound::Unbounded => true,
+    };
+
+    from_start && to_end
+}
+
+/// Whether some key in [lower, upper) may lie inside the range
+fn overlaps(range: &KeyRange, lower: Option<&ByteBox>, upper: Option<&ByteBox>) -> bool {
+    let below_start = match &range.0 {
+        Bound::Included(start) | Bound::Excluded(start) => {
+            upper.is_some_and(|upper| upper <= start)
+        }
+        Bound::Unbounded => false,
+    };
+
+    let past_end = match &range.1 {
+        Bound::Included(end) => lower.is_some_and(|lower| lower > end),
+        Bound::Excluded(end) => lower.is_some_and(|lower| lower >= end),
+        Bound::Unbounded => false,
+    };
+
+    !below_start && !past_end
+}
@@ -0,0 +1,300 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use crate::index::tree::{
+    byte_box::ByteBox,
+    index_types::{ KeyValuePair, NodeKey },
+    tree_node::{ node_type::{ NodeType, PagePointer }, tree_node_inner::NodeInner },
+    tree_page::{
+        codec::Codec,
+        page::TreePage,
+        tree_page_layout::{ LEAF_NODE_HEADER_SIZE, PAGE_SIZE },
+    },
+};
+
+use super::{ btree_bulk_load::LevelNode, btree_obj::{ BPTree, BTreeBuilder } };
+
+/// Leaves of the tree a single `TreeRebuild::step` copies
+pub const REBUILD_LEAVES_PER_STEP: usize = 16;
+
+impl BPTree {
+    /// Rewrites the tree as a compacted copy, see `TreeRebuild`
+    pub fn rebuild(&self) -> anyhow::Result<()> {
+        TreeRebuild::new().finish(self)
+    }
+
+    /// Builder with the settings the tree was built with
+    fn settings(&self) -> BTreeBuilder {
+        BTreeBuilder {
+            b: self.b,
+            table_schema: self.codec.clone(),
+            fill_factor: self.fill_factor,
+            allow_duplicates: self.allow_duplicates,
+            order_statistics: self.order_statistics,
+            copy_on_write: self.is_copy_on_write(),
+            merge_operator: self.merge_operator,
+            log_id: Some(self.log_id),
+        }
+    }
+
+    /// Pages of every node the root reaches, overflow pages left out
+    fn node_pages(&self) -> anyhow::Result<Vec<PagePointer>> {
+        let mut pages = vec![*self.root_page_id.borrow()];
+        let mut idx = 0;
+
+        while idx < pages.len() {
+            let node = self.codec.decode(&TreePage::new(self.flusher.read_drop(pages[idx])))?;
+
+            if let NodeType::Internal(children, _, _) = node.node_type {
+                pages.extend(children);
+            }
+
+            idx += 1;
+        }
+
+        Ok(pages)
+    }
+}
+
+/// Writes a compacted copy of a tree a few leaves at a time, then swaps it in for the tree
+///
+/// The copy packs entries to the tree's fill factor into leaves taken from past the end of the
+/// file, so reading them in key order reads the file front to back. Values are copied as they
+/// are stored, overflow stubs and expiry included, so overflow pages are shared with the copy.
+///
+/// Reads carry on against the tree until the copy is done, when its root is published in one
+/// step and the pages of the old tree are retired. Snapshots and scans taken earlier keep
+/// reading the old pages until they are dropped. Only a copy-on-write tree knows when that is,
+/// so other trees are refused rather than freeing pages their open scans still read. A write to
+/// the tree between two steps may change entries already copied, so the copy is abandoned and
+/// started over.
+#[derive(Debug, Default)]
+pub struct TreeRebuild {
+    // First key of the leaf the next step copies, None to start from the first leaf
+    resume: Option<ByteBox>,
+
+    // Whether every leaf has been copied
+    copied: bool,
+
+    // Entries copied but not yet written, kept back to balance the leaves written last
+    pending: Vec<KeyValuePair>,
+
+    // Leaves written so far in key order, with the last key of the last one
+    leaves: Vec<LevelNode>,
+    last_key: Option<ByteBox>,
+
+    // Page the next leaf is written to, taken early so the leaf before it can link to it
+    next_page: Option<PagePointer>,
+
+    // Every page the copy has taken, freed again if it is abandoned
+    pages: Vec<PagePointer>,
+
+    // Writes made to the tree's file once the last step was done
+    writes: Option<u64>,
+
+    done: bool,
+    restarts: u64,
+}
+
+impl TreeRebuild {
+    pub fn new() -> Self {
+        TreeRebuild::default()
+    }
+
+    /// Whether the copy has been swapped in for the tree
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Number of times writes to the tree made the copy start over
+    pub fn restarts(&self) -> u64 {
+        self.restarts
+    }
+
+    /// Copies the next leaves of the tree, swapping the copy in once the last one is copied
+    ///
+    /// Returns whether the rebuild is done, after which further steps do nothing.
+    pub fn step(&mut self, tree: &BPTree) -> anyhow::Result<bool> {
+        if self.done {
+            return Ok(true);
+        }
+
+        if !tree.is_copy_on_write() {
+            return Err(anyhow::Error::msg("Only copy-on-write trees can be rebuilt online"));
+        }
+
+        if self.writes.is_some_and(|writes| writes != tree.flusher.write_count()) {
+            self.abandon(tree)?;
+        }
+
+        self.copy_leaves(tree)?;
+
+        if self.copied {
+            self.swap(tree)?;
+        } else {
+            self.write_leaves(tree, false)?;
+        }
+
+        self.writes = Some(tree.flusher.write_count());
+
+        Ok(self.done)
+    }
+
+    /// Steps until the copy is swapped in
+    pub fn finish(&mut self, tree: &BPTree) -> anyhow::Result<()> {
+        while !self.step(tree)? {}
+
+        Ok(())
+    }
+
+    fn copy_leaves(&mut self, tree: &BPTree) -> anyhow::Result<()> {
+        for _ in 0..REBUILD_LEAVES_PER_STEP {
+            let leaf = match &self.resume {
+                Some(key) => tree.find_leaf(NodeKey::GuidePost(key.clone()))?,
+                None => tree.find_min()?,
+            };
+
+            if let NodeType::Leaf(entries, _, _) = &leaf.node_type {
+                self.pending.extend(
+                    entries.iter().filter_map(|entry| {
+                        match entry {
+                            NodeKey::KeyValuePair(kv) => Some(kv.clone()),
+                            NodeKey::GuidePost(_) => None,
+                        }
+                    })
+                );
+            }
+
+            self.resume = tree
+                .neighbour_leaf(&leaf, true)?
+                .and_then(|next| next.get_key_value_at(0).ok())
+                .map(|entry| entry.key);
+
+            if self.resume.is_none() {
+                self.copied = true;
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Packs pending entries into leaves and writes them
+    ///
+    /// Until every entry is copied, the last two leaves wait for the entries after them, so the
+    /// last leaf written can be balanced against the one before it.
+    fn write_leaves(&mut self, tree: &BPTree, last: bool) -> anyhow::Result<()> {
+        let settings = tree.settings();
+
+        let sizes: Vec<usize> = self.pending.iter().map(Codec::pair_size).collect();
+        let keys: Vec<&[u8]> = self.pending
+            .iter()
+            .map(|entry| &entry.key.data[..])
+            .collect();
+        let compressed_keys = Codec::compresses(&tree.codec.key_type).then_some(&keys[..]);
+
+        let groups = settings.pack(
+            &sizes,
+            compressed_keys,
+            LEAF_NODE_HEADER_SIZE,
+            PAGE_SIZE,
+            (tree.b > 0).then(|| 2 * tree.b - 1)
+        );
+
+        let ready = if last { groups.len() } else { groups.len().saturating_sub(2) };
+        let is_root = last && self.leaves.is_empty() && groups.len() == 1;
+
+        for (idx, group_len) in groups.into_iter().take(ready).enumerate() {
+            let entries: Vec<KeyValuePair> = self.pending.drain(..group_len).collect();
+            self.write_leaf(tree, entries, last && idx + 1 == ready, is_root)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_leaf(
+        &mut self,
+        tree: &BPTree,
+        entries: Vec<KeyValuePair>,
+        is_last: bool,
+        is_root: bool
+    ) -> anyhow::Result<()> {
+        let pointer = match self.next_page.take() {
+            Some(pointer) => pointer,
+            None => self.append_page(tree),
+        };
+
+        if !is_last {
+            self.next_page = Some(self.append_page(tree));
+        }
+
+        // Copy-on-write trees keep no leaf chain
+        let (next_pointer, prev_pointer) = if tree.is_copy_on_write() {
+            (None, None)
+        } else {
+            (self.next_page, self.leaves.last().map(|(_, pointer, _)| *pointer))
+        };
+
+        let first_key = entries.first().map(|entry| {
+            match &self.last_key {
+                Some(lower) => ByteBox::shortest_separator(lower, &entry.key),
+                None => entry.key.clone(),
+            }
+        });
+        self.last_key = entries.last().map(|entry| entry.key.clone());
+
+        let count = entries.len() as u64;
+        let entries = entries.into_iter().map(NodeKey::KeyValuePair).collect();
+
+        let mut leaf = NodeInner::new(
+            NodeType::Leaf(entries, pointer, next_pointer),
+            is_root,
+            pointer,
+            next_pointer
+        );
+        leaf.prev_pointer = prev_pointer;
+
+        tree.flusher.write_flush(Codec::encode(&leaf)?.get_data(), pointer)?;
+        self.leaves.push((first_key, pointer, count));
+
+        Ok(())
+    }
+
+    /// Writes the last leaves and the levels above them, then publishes the copy as the tree
+    fn swap(&mut self, tree: &BPTree) -> anyhow::Result<()> {
+        self.write_leaves(tree, true)?;
+
+        let settings = tree.settings();
+        let mut level = std::mem::take(&mut self.leaves);
+
+        while level.len() > 1 {
+            level = settings.build_internal_level(&tree.flusher, level)?;
+        }
+
+        let retired = tree.node_pages()?;
+        tree.publish_root(level[0].1, retired)?;
+
+        self.pages.clear();
+        self.done = true;
+
+        Ok(())
+    }
+
+    /// Frees what the copy has written so far and starts it over
+    fn abandon(&mut self, tree: &BPTree) -> anyhow::Result<()> {
+        for page in self.pages.drain(..) {
+            tree.flusher.free_page(page)?;
+        }
+
+        *self = TreeRebuild { restarts: self.restarts + 1, ..TreeRebuild::default() };
+
+        Ok(())
+    }
+
+    fn append_page(&mut self, tree: &BPTree) -> PagePointer {
+        let page = tree.flusher.append_page();
+        self.pages.push(page);
+
+        page
+    }
+}
@@ -0,0 +1,239 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use crate::index::tree::{
+    index_types::NodeKey,
+    tree_node::{ node_type::NodeType, tree_node_inner::NodeInner },
+    tree_page::{
+        codec::{ common_prefix_len, Codec },
+        tree_page_layout::{
+            CHILD_COUNT_SIZE,
+            MAX_INTERNAL_ENTRY_SIZE,
+            MAX_KEY_SIZE,
+            MAX_LEAF_ENTRY_SIZE,
+            PAGE_SIZE,
+            PTR_SIZE,
+        },
+    },
+};
+
+use super::btree_obj::{ BPTree, WriteOperation };
+
+// Nodes are sized by the bytes they encode to. A node splits once it outgrows its page and is
+// rebalanced when it holds less than half of the fill factor. A non zero b also caps nodes at
+// 2b - 1 keys and then decides underflow by count, which keeps small test trees deep.
+//
+// Leaves store a key prefix shared by their entries once. Whether a leaf fits is decided on its
+// compressed size, while the minimum occupancy and the room left for writes are measured on
+// full keys, since a single insert or delete can change the prefix of every entry.
+impl BPTree {
+    /// Bytes a node may take up
+    ///
+    /// Internal nodes keep room for one more key, so a separator replaced by a longer key while
+    /// borrowing still fits.
+    pub(crate) fn node_capacity(node: &NodeInner) -> usize {
+        if node.is_leaf { PAGE_SIZE } else { PAGE_SIZE - MAX_KEY_SIZE }
+    }
+
+    pub(crate) fn min_node_size(&self, node: &NodeInner) -> usize {
+        ((BPTree::node_capacity(node) as f64) * self.fill_factor / 2.0) as usize
+    }
+
+    pub(crate) fn is_overfull(&self, node: &NodeInner) -> bool {
+        if self.b > 0 && node.get_key_array_length() >= 2 * self.b {
+            return true;
+        }
+
+        Codec::encoded_size(node) > BPTree::node_capacity(node)
+    }
+
+    pub(crate) fn is_underfull(&self, node: &NodeInner) -> bool {
+        if self.b > 0 {
+            return node.get_key_array_length() < self.b - 1;
+        }
+
+        Codec::uncompressed_size(node) < self.min_node_size(node)
+    }
+
+    /// Whether the node absorbs any single insert or delete without splitting or underflowing
+    pub(crate) fn is_safe(&self, node: &NodeInner, operation: &WriteOperation) -> bool {
+        let size = Codec::uncompressed_size(node);
+        let length = node.get_key_array_length();
+
+        let max_entry = if node.is_leaf { MAX_LEAF_ENTRY_SIZE } else { MAX_INTERNAL_ENTRY_SIZE };
+        let max_entry = max_entry + BPTree::child_count_size(node);
+
+        match operation {
+            WriteOperation::Insert => {
+                let fits = size + max_entry <= BPTree::node_capacity(node);
+
+                fits && (self.b == 0 || length + 1 < 2 * self.b)
+            }
+            WriteOperation::Delete => {
+                if self.b > 0 {
+                    return length > self.b;
+                }
+
+                size >= self.min_node_size(node) + max_entry
+            }
+            WriteOperation::Replace => {
+                self.is_safe(node, &WriteOperation::Insert) &&
+                    self.is_safe(node, &WriteOperation::Delete)
+            }
+        }
+    }
+
+    /// Whether the candidate should give the underfull node an entry rather than be merged
+    ///
+    /// `is_left` tells the candidate is the node's left sibling, which lends its last entry.
+    pub(crate) fn can_lend(&self, node: &NodeInner, candidate: &NodeInner, is_left: bool) -> bool {
+        // Merging pulls the separator down into internal nodes, leaves take no extra key
+        let separator_size = if node.is_leaf { 0 } else { MAX_INTERNAL_ENTRY_SIZE };
+        let merged_size =
+            Codec::uncompressed_size(node) + Codec::uncompressed_size(candidate) + separator_size;
+
+        if merged_size > BPTree::node_capacity(node) {
+            return candidate.get_key_array_length() > 1;
+        }
+
+        if self.b > 0 {
+            return candidate.get_key_array_length() >= self.b;
+        }
+
+        let lent = match &candidate.node_type {
+            NodeType::Internal(_, keys, _) => if is_left { keys.last() } else { keys.first() }
+            NodeType::Leaf(entries, _, _) => if is_left { entries.last() } else { entries.first() }
+            NodeType::Unexpected => None,
+        };
+
+        match lent {
+            Some(entry) => {
+                Codec::uncompressed_size(candidate) - Codec::entry_size(entry) >=
+                    self.min_node_size(candidate)
+            }
+            None => false,
+        }
+    }
+
+    /// Bytes each child of the node takes up for its entry count
+    pub(crate) fn child_count_size(node: &NodeInner) -> usize {
+        if node.child_counts.is_some() { CHILD_COUNT_SIZE } else { 0 }
+    }
+
+    /// Index to split an overfull node at, leaving both halves with about the same bytes
+    pub(crate) fn split_point(node: &NodeInner) -> usize {
+        match &node.node_type {
+            NodeType::Internal(_, keys, _) => {
+                // Each key brings the child pointer on its right, the key at the split goes up
+                let sizes: Vec<usize> = keys
+                    .iter()
+                    .map(|key| {
+                        Codec::entry_size(key) + PTR_SIZE + BPTree::child_count_size(node)
+                    })
+                    .collect();
+
+                balanced_split(&sizes, true)
+            }
+            NodeType::Leaf(entries, _, _) => {
+                let sizes: Vec<usize> = entries.iter().map(Codec::entry_size).collect();
+
+                let keys: Vec<&[u8]> = entries
+                    .iter()
+                    .filter_map(|entry| {
+                        match entry {
+                            NodeKey::KeyValuePair(kv_pair) => Some(&kv_pair.key.data[..]),
+                            NodeKey::GuidePost(_) => None,
+                        }
+                    })
+                    .collect();
+
+                match entries.first() {
+                    Some(NodeKey::KeyValuePair(first)) if Codec::compresses(&first.key.datatype) => {
+                        balanced_leaf_split(&sizes, &keys)
+                    }
+                    _ => balanced_split(&sizes, false),
+                }
+            }
+            NodeType::Unexpected => 0,
+        }
+    }
+}
+
+/// Index dividing items of the given sizes so the larger side is as small as possible
+///
+/// Both sides keep at least one item. With `drop_split_item` the item at the index belongs to
+/// neither side, as with the median key of an internal node.
+pub(crate) fn balanced_split(sizes: &[usize], drop_split_item: bool) -> usize {
+    let total: usize = sizes.iter().sum();
+
+    let last = if drop_split_item {
+        sizes.len().saturating_sub(2)
+    } else {
+        sizes.len().saturating_sub(1)
+    };
+
+    let mut best = 1;
+    let mut best_cost = usize::MAX;
+    let mut left = sizes.first().copied().unwrap_or(0);
+
+    for at in 1..=last.max(1) {
+        let moved = if drop_split_item { sizes.get(at).copied().unwrap_or(0) } else { 0 };
+        let cost = left.max(total - left - moved);
+
+        if cost < best_cost {
+            best = at;
+            best_cost = cost;
+        }
+
+        left += sizes.get(at).copied().unwrap_or(0);
+    }
+
+    best
+}
+
+/// `balanced_split` for leaves that store a shared key prefix, measuring each side compressed
+///
+/// Keys sharing a prefix are kept together where possible, as splitting them up would write
+/// both halves out in full.
+pub(crate) fn balanced_leaf_split(sizes: &[usize], keys: &[&[u8]]) -> usize {
+    let len = sizes.len();
+    if len < 2 {
+        return 1;
+    }
+
+    // Prefix shared by keys[..at] and by keys[at..]
+    let mut left_prefix = vec![0; len + 1];
+    let mut right_prefix = vec![0; len + 1];
+
+    left_prefix[1] = keys[0].len();
+    for at in 2..=len {
+        left_prefix[at] = left_prefix[at - 1].min(common_prefix_len(&[keys[0], keys[at - 1]]));
+    }
+
+    right_prefix[len - 1] = keys[len - 1].len();
+    for at in (0..len - 1).rev() {
+        right_prefix[at] = right_prefix[at + 1].min(
+            common_prefix_len(&[keys[len - 1], keys[at]])
+        );
+    }
+
+    let total: usize = sizes.iter().sum();
+
+    let mut best = 1;
+    let mut best_cost = usize::MAX;
+    let mut left = sizes[0];
+
+    for at in 1..len {
+        let left_size = left + left_prefix[at] - at * left_prefix[at];
+        let right_size = total - left + right_prefix[at] - (len - at) * right_prefix[at];
+
+        if left_size.max(right_size) < best_cost {
+            best = at;
+            best_cost = left_size.max(right_size);
+        }
+
+        left += sizes[at];
+    }
+
+    best
+}
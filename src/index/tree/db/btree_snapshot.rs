@@ -0,0 +1,251 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::{
+    collections::{ BTreeMap, VecDeque },
+    iter::Rev,
+    ops::{ Bound, RangeBounds },
+    sync::{ Arc, Mutex },
+};
+
+use crate::{
+    buffer::flusher::Flusher,
+    index::tree::{
+        byte_box::ByteBox,
+        index_types::{ KeyValuePair, NodeKey },
+        iterators::{ bptree_iterator::BPTreeIterator, tree_walk::{ descend, Descent } },
+        tree_node::{ node_type::PagePointer, tree_node_inner::NodeInner },
+        tree_page::codec::{ Codec, Projection },
+    },
+};
+
+use super::btree_obj::BPTree;
+
+/// Root versions of a copy-on-write tree that readers still hold, and the pages only they use
+///
+/// Every write publishes a new version. The pages it replaced are retired at that version and
+/// freed once no snapshot of an older version is left.
+#[derive(Default)]
+pub(crate) struct VersionSet {
+    current: u64,
+
+    // Number of live pins of each version
+    pinned: BTreeMap<u64, usize>,
+
+    // Pages retired at each version, oldest first
+    retired: VecDeque<(u64, Vec<PagePointer>)>,
+}
+
+impl VersionSet {
+    fn pin(&mut self) -> u64 {
+        *self.pinned.entry(self.current).or_default() += 1;
+        self.current
+    }
+
+    /// Drops a pin, returning the pages no version still pinned can reach
+    fn unpin(&mut self, version: u64) -> Vec<PagePointer> {
+        if let Some(pins) = self.pinned.get_mut(&version) {
+            *pins -= 1;
+
+            if *pins == 0 {
+                self.pinned.remove(&version);
+            }
+        }
+
+        self.reclaim()
+    }
+
+    /// Starts the version a write is publishing
+    fn publish(&mut self) {
+        self.current += 1;
+    }
+
+    /// Records pages the current version no longer reaches, returning those free to reuse
+    fn retire(&mut self, pages: Vec<PagePointer>) -> Vec<PagePointer> {
+        if !pages.is_empty() {
+            self.retired.push_back((self.current, pages));
+        }
+
+        self.reclaim()
+    }
+
+    fn reclaim(&mut self) -> Vec<PagePointer> {
+        let oldest = self.pinned.keys().next().copied().unwrap_or(self.current);
+        let mut freed = Vec::new();
+
+        // Pages retired at a version are still reachable from every version before it
+        while let Some((version, _)) = self.retired.front() {
+            if *version > oldest {
+                break;
+            }
+
+            freed.extend(self.retired.pop_front().unwrap().1);
+        }
+
+        freed
+    }
+}
+
+/// Keeps the pages of one root version from being freed while it is alive
+pub(crate) struct VersionPin {
+    version: u64,
+    versions: Arc<Mutex<VersionSet>>,
+    flusher: Arc<Flusher>,
+}
+
+impl Drop for VersionPin {
+    fn drop(&mut self) {
+        let freed = self.versions.lock().unwrap().unpin(self.version);
+
+        for page in freed {
+            let _ = self.flusher.free_page(page);
+        }
+    }
+}
+
+/// A read-only view of a copy-on-write tree as it was when the snapshot was taken
+///
+/// Writes to the tree after that never change what the snapshot reads. The pages it reads are
+/// kept until the snapshot and every iterator over it are dropped.
+pub struct Snapshot {
+    flusher: Arc<Flusher>,
+    codec: Codec,
+    index_id: u64,
+    allow_duplicates: bool,
+
+    root_page_id: PagePointer,
+    pin: Arc<VersionPin>,
+}
+
+impl Snapshot {
+    /// The root version the snapshot reads, later snapshots have larger ones
+    pub fn version(&self) -> u64 {
+        self.pin.version
+    }
+
+    pub fn root_page_id(&self) -> PagePointer {
+        self.root_page_id
+    }
+
+    /// The entry stored under the key, the first of them in a tree with duplicates
+    pub fn get(&self, key: &ByteBox) -> Option<KeyValuePair> {
+        self.scan(key.clone()..=key.clone()).next()
+    }
+
+    pub fn scan(&self, range: impl RangeBounds<ByteBox>) -> BPTreeIterator {
+        if self.allow_duplicates {
+            return self.scan_stored(BPTree::tiebreak_range(range)).strip_tiebreaker();
+        }
+
+        self.scan_stored(range)
+    }
+
+    /// Iterates over the range from its largest key down to its smallest
+    pub fn scan_rev(&self, range: impl RangeBounds<ByteBox>) -> Rev<BPTreeIterator> {
+        self.scan(range).rev()
+    }
+
+    /// Iterates over the keys as they are stored in the pages
+    pub(crate) fn scan_stored(&self, range: impl RangeBounds<ByteBox>) -> BPTreeIterator {
+        self.scan_projected(range, Projection::Whole)
+    }
+
+    /// `scan_stored`, handing out values as `projection` asks
+    pub(crate) fn scan_projected(
+        &self,
+        range: impl RangeBounds<ByteBox>,
+        projection: Projection
+    ) -> BPTreeIterator {
+        let first = self.bounding_leaf(range.start_bound(), Descent::First);
+        let last = self.bounding_leaf(range.end_bound(), Descent::Last);
+
+        BPTreeIterator::create_and_seek_to_key(
+            self.flusher.clone(),
+            self.codec.clone(),
+            self.index_id,
+            Some(self.root_page_id),
+            Arc::new(first),
+            Arc::new(last),
+            range
+        ).projected(projection).pinned(self.pin.clone())
+    }
+
+    /// The leaf holding a bound of a range, the first or last leaf for an unbounded end
+    fn bounding_leaf(&self, bound: Bound<&ByteBox>, unbounded: Descent) -> NodeInner {
+        let search;
+
+        let to = match bound {
+            Bound::Included(key) | Bound::Excluded(key) => {
+                search = NodeKey::GuidePost(key.clone());
+                Descent::Key(&search)
+            }
+            Bound::Unbounded => unbounded,
+        };
+
+        descend(&self.flusher, &self.codec, self.root_page_id, to).unwrap().1
+    }
+}
+
+impl BPTree {
+    /// Whether writes copy the pages they change, see `BTreeBuilder::copy_on_write`
+    pub fn is_copy_on_write(&self) -> bool {
+        self.versions.is_some()
+    }
+
+    /// Takes a read-only view of the tree as it is now
+    ///
+    /// Only copy-on-write trees keep old root versions around to take snapshots of.
+    pub fn snapshot(&self) -> anyhow::Result<Snapshot> {
+        self.pin_root().ok_or(
+            anyhow::Error::msg("Tree keeps no snapshots, see BTreeBuilder::copy_on_write")
+        )
+    }
+
+    /// Snapshot of the current root, None unless the tree is copy-on-write
+    pub(crate) fn pin_root(&self) -> Option<Snapshot> {
+        let versions = self.versions.as_ref()?;
+        let version = versions.lock().unwrap().pin();
+
+        Some(Snapshot {
+            flusher: self.flusher.clone(),
+            codec: self.codec.clone(),
+            index_id: self.index_id,
+            allow_duplicates: self.allow_duplicates,
+            root_page_id: *self.root_page_id.borrow(),
+            pin: Arc::new(VersionPin {
+                version,
+                versions: versions.clone(),
+                flusher: self.flusher.clone(),
+            }),
+        })
+    }
+
+    /// Makes `root` the root of a new version, retiring the pages the old one no longer shares
+    pub(crate) fn publish_root(
+        &self,
+        root: PagePointer,
+        retired: Vec<PagePointer>
+    ) -> anyhow::Result<()> {
+        self.set_root(root)?;
+
+        if let Some(versions) = &self.versions {
+            versions.lock().unwrap().publish();
+        }
+
+        self.retire(retired)
+    }
+
+    /// Frees pages the current root no longer reaches once older snapshots are done with them
+    pub(crate) fn retire(&self, pages: Vec<PagePointer>) -> anyhow::Result<()> {
+        let freed = match &self.versions {
+            Some(versions) => versions.lock().unwrap().retire(pages),
+            None => pages,
+        };
+
+        for page in freed {
+            self.flusher.free_page(page)?;
+        }
+
+        Ok(())
+    }
+}
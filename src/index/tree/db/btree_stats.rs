@@ -0,0 +1,119 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use crate::index::tree::{
+    byte_box::DataType,
+    index_types::NodeKey,
+    tree_node::node_type::{ NodeType, PagePointer },
+    tree_page::{
+        codec::Codec,
+        overflow::OverflowChain,
+        page::TreePage,
+        tree_page_layout::PAGE_SIZE,
+    },
+};
+
+use super::btree_obj::BPTree;
+
+/// Number of buckets in the leaf fill histogram, each covering an equal share of a page
+pub const FILL_HISTOGRAM_BUCKETS: usize = 10;
+
+/// Shape of a tree at the time `BPTree::stats` walked it
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeStats {
+    /// Number of levels, a lone root leaf being a tree of height 1
+    pub height: usize,
+
+    /// Nodes on every level, the root level first
+    pub nodes_per_level: Vec<usize>,
+
+    /// Leaves by the share of their page they take up, bucket `i` holding leaves filled to
+    /// at least `i / FILL_HISTOGRAM_BUCKETS` of a page
+    pub leaf_fill_histogram: [usize; FILL_HISTOGRAM_BUCKETS],
+
+    /// Average keys per internal node, 0 when the root is a leaf
+    pub avg_keys_per_internal: f64,
+
+    pub num_entries: usize,
+
+    /// Bytes of every key and value, large values counted at their full length
+    pub key_bytes: usize,
+    pub value_bytes: usize,
+
+    /// Pages freed in the tree's file and not yet reused
+    pub free_pages: usize,
+}
+
+impl TreeStats {
+    pub fn num_leaves(&self) -> usize {
+        self.nodes_per_level.last().copied().unwrap_or(0)
+    }
+}
+
+impl BPTree {
+    /// Walks the tree level by level and collects its shape
+    pub fn stats(&self) -> anyhow::Result<TreeStats> {
+        let mut stats = TreeStats {
+            height: 0,
+            nodes_per_level: Vec::new(),
+            leaf_fill_histogram: [0; FILL_HISTOGRAM_BUCKETS],
+            avg_keys_per_internal: 0.0,
+            num_entries: 0,
+            key_bytes: 0,
+            value_bytes: 0,
+            free_pages: self.flusher.free_page_count(),
+        };
+
+        let (mut internal_nodes, mut internal_keys) = (0, 0);
+        let mut level: Vec<PagePointer> = vec![*self.root_page_id.borrow()];
+
+        while !level.is_empty() {
+            stats.height += 1;
+            stats.nodes_per_level.push(level.len());
+
+            let mut next_level = Vec::new();
+
+            for page in level {
+                let node = self.codec.decode(&TreePage::new(self.flusher.read_drop(page)))?;
+
+                match &node.node_type {
+                    NodeType::Internal(children, keys, _) => {
+                        internal_nodes += 1;
+                        internal_keys += keys.len();
+
+                        next_level.extend(children.iter().copied());
+                    }
+                    NodeType::Leaf(entries, _, _) => {
+                        let fill = Codec::encoded_size(&node) * FILL_HISTOGRAM_BUCKETS / PAGE_SIZE;
+                        stats.leaf_fill_histogram[fill.min(FILL_HISTOGRAM_BUCKETS - 1)] += 1;
+
+                        for entry in entries {
+                            if let NodeKey::KeyValuePair(kv_pair) = entry {
+                                stats.num_entries += 1;
+                                stats.key_bytes += kv_pair.key.data.len();
+
+                                let value = kv_pair.value.clone().without_expiry();
+                                stats.value_bytes += if value.datatype == DataType::Overflow {
+                                    OverflowChain::from_stub(&value)?.length as usize
+                                } else {
+                                    value.data.len()
+                                };
+                            }
+                        }
+                    }
+                    NodeType::Unexpected => {
+                        return Err(anyhow::Error::msg("Unexpected node type"));
+                    }
+                }
+            }
+
+            level = next_level;
+        }
+
+        if internal_nodes > 0 {
+            stats.avg_keys_per_internal = (internal_keys as f64) / (internal_nodes as f64);
+        }
+
+        Ok(stats)
+    }
+}
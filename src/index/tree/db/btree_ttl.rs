@@ -0,0 +1,165 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::time::{ Duration, SystemTime, UNIX_EPOCH };
+
+use crate::{
+    index::tree::{
+        byte_box::ByteBox,
+        index_types::{ KeyValuePair, NodeKey },
+        tree_node::node_type::NodeType,
+    },
+    wal::Wal,
+};
+
+use super::{ btree_batch::WriteBatch, btree_obj::BPTree };
+
+// An entry set with a time to live keeps its expiry in the leaf, ahead of the value. Reads
+// compare it against the time they run at and skip the entry once it has passed, but the entry
+// stays stored until an `ExpiryReaper` comes across it and deletes it.
+impl BPTree {
+    /// Stores the entry as `put` does, expiring it once `ttl` has passed
+    pub fn set_with_ttl(&self, entry: KeyValuePair, ttl: Duration) -> anyhow::Result<()> {
+        self.set_with_expiry(entry, SystemTime::now() + ttl)
+    }
+
+    /// Stores the entry as `put` does, expiring it at `expires_at`
+    ///
+    /// A tree with duplicates keeps every value of a key, so there is no single entry to expire.
+    pub fn set_with_expiry(
+        &self,
+        entry: KeyValuePair,
+        expires_at: SystemTime
+    ) -> anyhow::Result<()> {
+        if self.allow_duplicates {
+            return Err(anyhow::Error::msg("Entries of a tree with duplicates can not expire"));
+        }
+
+        self.check_entry(&entry)?;
+
+        self.put(KeyValuePair {
+            key: entry.key,
+            value: ByteBox::expiring(entry.value, unix_millis(expires_at)),
+        })
+    }
+
+    /// The stored value, unless it has expired
+    pub(crate) fn unexpired(value: ByteBox) -> anyhow::Result<ByteBox> {
+        if value.is_expired(unix_millis(SystemTime::now())) {
+            return Err(anyhow::Error::msg("Key not present"));
+        }
+
+        Ok(value)
+    }
+}
+
+/// Deletes expired entries of a tree a leaf at a time
+///
+/// Every `step` visits the leaf after the one the last step did, going back to the first leaf
+/// once it has been through the last. The expired entries of a leaf are deleted together as a
+/// single `WriteBatch`.
+#[derive(Debug, Default)]
+pub struct ExpiryReaper {
+    // First key of the leaf the next step visits, None to start from the first leaf
+    resume: Option<ByteBox>,
+
+    reaped: u64,
+}
+
+impl ExpiryReaper {
+    pub fn new() -> Self {
+        ExpiryReaper::default()
+    }
+
+    /// Number of entries deleted by every step so far
+    pub fn reaped(&self) -> u64 {
+        self.reaped
+    }
+
+    /// Whether the last step visited the last leaf, so the next one starts a new pass
+    pub fn is_pass_done(&self) -> bool {
+        self.resume.is_none()
+    }
+
+    /// Deletes the expired entries of the next leaf, returning how many there were
+    pub fn step(&mut self, tree: &BPTree, wal: Option<&Wal>) -> anyhow::Result<usize> {
+        self.step_rows(tree, wal).map(|rows| rows.len())
+    }
+
+    /// `step`, returning the deleted entries with their values read back
+    ///
+    /// Values are read before the entries are deleted, for callers keeping something derived
+    /// from them in step with the tree, such as the secondary indexes of a `Table`.
+    pub fn step_rows(
+        &mut self,
+        tree: &BPTree,
+        wal: Option<&Wal>
+    ) -> anyhow::Result<Vec<KeyValuePair>> {
+        let now = unix_millis(SystemTime::now());
+
+        let leaf = match &self.resume {
+            Some(key) => tree.find_leaf(NodeKey::GuidePost(key.clone()))?,
+            None => tree.find_min()?,
+        };
+
+        let expired: Vec<KeyValuePair> = match &leaf.node_type {
+            NodeType::Leaf(entries, _, _) => {
+                entries
+                    .iter()
+                    .filter_map(|entry| {
+                        match entry {
+                            NodeKey::KeyValuePair(kv) if kv.value.is_expired(now) => {
+                                Some(kv.clone())
+                            }
+                            _ => None,
+                        }
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+
+        // Deletes may rebalance the leaf with its neighbour, but every key from the neighbour's
+        // first one on still lies in its leaf or a later one
+        self.resume = tree
+            .neighbour_leaf(&leaf, true)?
+            .and_then(|next| next.get_key_value_at(0).ok())
+            .map(|entry| entry.key);
+
+        let rows = expired
+            .into_iter()
+            .map(|entry| {
+                Ok(KeyValuePair { value: tree.resolve_value(entry.value)?, key: entry.key })
+            })
+            .collect::<anyhow::Result<Vec<KeyValuePair>>>()?;
+
+        if !rows.is_empty() {
+            let mut batch = WriteBatch::new();
+            for row in &rows {
+                batch.delete(tree, row.key.clone());
+            }
+
+            batch.apply(wal)?;
+        }
+
+        self.reaped += rows.len() as u64;
+
+        Ok(rows)
+    }
+
+    /// Steps until the pass under way reaches the last leaf, returning the entries deleted
+    pub fn finish_pass(&mut self, tree: &BPTree, wal: Option<&Wal>) -> anyhow::Result<usize> {
+        let mut reaped = self.step(tree, wal)?;
+
+        while !self.is_pass_done() {
+            reaped += self.step(tree, wal)?;
+        }
+
+        Ok(reaped)
+    }
+}
+
+/// Milliseconds from the Unix epoch to `time`, the unit expiry times are stored in
+pub(crate) fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_millis() as u64)
+}
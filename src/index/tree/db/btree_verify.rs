@@ -0,0 +1,300 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::{ collections::HashSet, fmt };
+
+use crate::index::tree::{
+    byte_box::{ ByteBox, DataType },
+    index_types::NodeKey,
+    tree_node::{ node_type::{ NodeType, PagePointer }, tree_node_inner::NodeInner },
+    tree_page::{
+        codec::Codec,
+        overflow::OverflowChain,
+        page::TreePage,
+        tree_page_layout::NULL_OVERFLOW_POINTER,
+    },
+};
+
+use super::btree_obj::BPTree;
+
+/// A broken tree invariant and the page it was found on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub page: PagePointer,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "page {}: {}", self.page, self.message)
+    }
+}
+
+/// What the walk has seen so far
+#[derive(Default)]
+struct VerifyState {
+    violations: Vec<Violation>,
+    visited: HashSet<PagePointer>,
+
+    /// (page, next pointer, prev pointer) of every leaf in key order
+    leaves: Vec<(PagePointer, Option<PagePointer>, Option<PagePointer>)>,
+    leaf_depth: Option<usize>,
+}
+
+impl VerifyState {
+    fn report(&mut self, page: PagePointer, message: String) {
+        self.violations.push(Violation { page, message });
+    }
+}
+
+impl BPTree {
+    /// Walks the whole tree and reports every broken invariant it finds
+    ///
+    /// Checks that keys are sorted inside each node and lie within the separators of their
+    /// parent, that all leaves sit at the same depth, that nodes other than the root are within
+    /// their occupancy bounds, that the leaf chain links every leaf in key order and that every
+    /// page, overflow pages included, is allocated. Trees with order statistics also have the
+    /// child counts of every internal node checked, while copy-on-write trees keep no leaf chain
+    /// to check. An empty list means the tree is sound.
+    pub fn verify(&self) -> Vec<Violation> {
+        let mut state = VerifyState::default();
+
+        let root = *self.root_page_id.borrow();
+        self.verify_node(root, None, None, 0, &mut state);
+
+        if !self.is_copy_on_write() {
+            BPTree::verify_leaf_chain(&mut state);
+        }
+
+        state.violations
+    }
+
+    /// Checks the node on `page` holds keys within [lower, upper), then its subtree
+    ///
+    /// Returns the number of entries found under the node.
+    fn verify_node(
+        &self,
+        page: PagePointer,
+        lower: Option<&ByteBox>,
+        upper: Option<&ByteBox>,
+        depth: usize,
+        state: &mut VerifyState
+    ) -> u64 {
+        if !state.visited.insert(page) {
+            state.report(page, "Page is reachable more than once".to_string());
+            return 0;
+        }
+
+        if !self.flusher.is_allocated(page) {
+            state.report(page, "Page is not allocated".to_string());
+            return 0;
+        }
+
+        let node = match self.codec.decode(&TreePage::new(self.flusher.read_drop(page))) {
+            Ok(node) => node,
+            Err(err) => {
+                state.report(page, format!("Page does not decode: {}", err));
+                return 0;
+            }
+        };
+
+        if node.pointer != page {
+            state.report(page, format!("Node records page {} as its own", node.pointer));
+        }
+
+        if node.is_root != (depth == 0) {
+            state.report(page, format!("Root flag is {} at depth {}", node.is_root, depth));
+        }
+
+        let keys: Vec<ByteBox> = match &node.node_type {
+            NodeType::Internal(_, keys, _) => keys.iter(),
+            NodeType::Leaf(entries, _, _) => entries.iter(),
+            NodeType::Unexpected => [].iter(),
+        }
+            .map(|key| NodeInner::deconstruct_value(key).0)
+            .collect();
+
+        for (idx, pair) in keys.windows(2).enumerate() {
+            if pair[0] >= pair[1] {
+                state.report(
+                    page,
+                    format!("Keys out of order at {}: {:?} >= {:?}", idx + 1, pair[0], pair[1])
+                );
+            }
+        }
+
+        let out_of_bounds = keys.iter().find(|key| {
+            lower.is_some_and(|lower| *key < lower) || upper.is_some_and(|upper| *key >= upper)
+        });
+
+        if let Some(key) = out_of_bounds {
+            state.report(
+                page,
+                format!("Key {:?} lies outside its parent's range [{:?}, {:?})", key, lower, upper)
+            );
+        }
+
+        self.verify_occupancy(&node, depth, state);
+
+        match &node.node_type {
+            NodeType::Internal(children, _, _) => {
+                if children.len() != keys.len() + 1 {
+                    state.report(
+                        page,
+                        format!("{} children for {} keys", children.len(), keys.len())
+                    );
+                }
+
+                let mut counts = Vec::with_capacity(children.len());
+
+                for (idx, child) in children.iter().enumerate() {
+                    let child_lower = idx.checked_sub(1).and_then(|idx| keys.get(idx)).or(lower);
+                    let child_upper = keys.get(idx).or(upper);
+
+                    counts.push(
+                        self.verify_node(*child, child_lower, child_upper, depth + 1, state)
+                    );
+                }
+
+                self.verify_child_counts(&node, &counts, state);
+
+                counts.iter().sum()
+            }
+            NodeType::Leaf(entries, _, _) => {
+                match state.leaf_depth {
+                    Some(leaf_depth) if leaf_depth != depth => {
+                        state.report(
+                            page,
+                            format!("Leaf at depth {}, other leaves at depth {}", depth, leaf_depth)
+                        );
+                    }
+                    Some(_) => {}
+                    None => {
+                        state.leaf_depth = Some(depth);
+                    }
+                }
+
+                for entry in entries {
+                    if let NodeKey::KeyValuePair(kv_pair) = entry {
+                        let value = kv_pair.value.clone().without_expiry();
+
+                        if value.datatype == DataType::Overflow {
+                            self.verify_overflow(page, &value, state);
+                        }
+                    }
+                }
+
+                state.leaves.push((page, node.next_pointer, node.prev_pointer));
+
+                entries.len() as u64
+            }
+            NodeType::Unexpected => {
+                state.report(page, "Unexpected node type".to_string());
+
+                0
+            }
+        }
+    }
+
+    /// Checks the entry counts an internal node keeps against those found under its children
+    fn verify_child_counts(&self, node: &NodeInner, found: &[u64], state: &mut VerifyState) {
+        match &node.child_counts {
+            Some(counts) if !self.order_statistics => {
+                state.report(node.pointer, "Node keeps child counts the tree does not".to_string());
+            }
+            Some(counts) if counts[..] != *found => {
+                state.report(
+                    node.pointer,
+                    format!("Child counts {:?}, children hold {:?}", counts, found)
+                );
+            }
+            None if self.order_statistics => {
+                state.report(node.pointer, "Node keeps no child counts".to_string());
+            }
+            _ => {}
+        }
+    }
+
+    fn verify_occupancy(&self, node: &NodeInner, depth: usize, state: &mut VerifyState) {
+        let size = Codec::encoded_size(node);
+        let length = node.get_key_array_length();
+
+        if self.is_overfull(node) {
+            state.report(
+                node.pointer,
+                format!("Node is overfull: {} keys, {} bytes", length, size)
+            );
+        }
+
+        if depth == 0 {
+            if !node.is_leaf && length == 0 {
+                state.report(node.pointer, "Internal root without keys".to_string());
+            }
+        } else if self.is_underfull(node) {
+            state.report(
+                node.pointer,
+                format!("Node is underfull: {} keys, {} bytes", length, size)
+            );
+        }
+    }
+
+    /// Follows the overflow chain behind a value of the leaf on `page`
+    fn verify_overflow(&self, page: PagePointer, stub: &ByteBox, state: &mut VerifyState) {
+        let chain = match OverflowChain::from_stub(stub) {
+            Ok(chain) => chain,
+            Err(err) => {
+                state.report(page, format!("Invalid overflow stub: {}", err));
+                return;
+            }
+        };
+
+        let mut current = chain.first_page;
+
+        while current != NULL_OVERFLOW_POINTER {
+            if !state.visited.insert(current) {
+                state.report(
+                    current,
+                    format!("Overflow page of leaf {} is reachable more than once", page)
+                );
+                return;
+            }
+
+            if !self.flusher.is_allocated(current) {
+                state.report(current, format!("Overflow page of leaf {} is not allocated", page));
+                return;
+            }
+
+            current = match OverflowChain::next_page(&self.flusher, current) {
+                Ok(next) => next,
+                Err(err) => {
+                    state.report(current, format!("Overflow page of leaf {}: {}", page, err));
+                    return;
+                }
+            };
+        }
+    }
+
+    /// Checks the next and prev pointers link the leaves in the order the walk found them
+    fn verify_leaf_chain(state: &mut VerifyState) {
+        let leaves = std::mem::take(&mut state.leaves);
+
+        for (idx, (page, next, prev)) in leaves.iter().enumerate() {
+            let expected_next = leaves.get(idx + 1).map(|leaf| leaf.0);
+            let expected_prev = idx.checked_sub(1).map(|idx| leaves[idx].0);
+
+            if *next != expected_next {
+                state.report(
+                    *page,
+                    format!("Leaf chain points at {:?}, expected {:?}", next, expected_next)
+                );
+            }
+
+            if *prev != expected_prev {
+                state.report(
+                    *page,
+                    format!("Leaf chain points back at {:?}, expected {:?}", prev, expected_prev)
+                );
+            }
+        }
+    }
+}
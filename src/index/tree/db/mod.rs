@@ -2,3 +2,22 @@ pub mod btree_ops;
 pub mod btree_node_ops;
 pub mod btree_obj;
 pub mod btree_api;
+pub mod btree_bulk_load;
+pub mod btree_duplicates;
+pub mod btree_overflow;
+pub mod btree_sizing;
+pub mod btree_verify;
+pub mod btree_stats;
+pub mod btree_meta;
+pub mod btree_range_delete;
+pub mod btree_batch;
+pub mod btree_order_stats;
+pub mod btree_snapshot;
+pub mod btree_cow;
+pub mod btree_cas;
+pub mod btree_ttl;
+pub mod btree_merge;
+pub mod btree_projection;
+pub mod btree_rebuild;
+pub mod btree_index;
+pub mod btree_dump;
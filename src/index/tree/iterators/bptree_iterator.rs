@@ -1,14 +1,15 @@
 #![allow(unused_variables)] 
 #![allow(dead_code)] 
-use std::{ ops::{ Bound, RangeBounds }, sync::Arc };
+use std::{ ops::{ Bound, RangeBounds }, sync::Arc, time::SystemTime };
 
 use crate::{
     buffer::flusher::Flusher,
     index::tree::{
-        byte_box::ByteBox,
+        byte_box::{ ByteBox, DataType },
+        db::{ btree_snapshot::VersionPin, btree_ttl::unix_millis },
         index_types::{ KeyValuePair, NodeKey },
-        tree_node::tree_node_inner::NodeInner,
-        tree_page::codec::Codec,
+        tree_node::{ node_type::PagePointer, tree_node_inner::NodeInner },
+        tree_page::{ codec::{ Codec, Projection }, overflow::OverflowChain },
     },
 };
 
@@ -16,8 +17,33 @@ use super::{ iterator::StorageIterator, leaf_iterator::LeafIterator };
 
 pub struct BPTreeIterator {
     leaf_iter: LeafIterator,
+    back_iter: LeafIterator,
 
+    start: Bound<ByteBox>,
     end: Bound<ByteBox>,
+
+    // Keys last handed out by either end, the two cursors stop once they meet
+    last_front: Option<ByteBox>,
+    last_back: Option<ByteBox>,
+
+    // Whether stored keys carry the value as a tiebreaker that callers should not see
+    strip_tiebreaker: bool,
+
+    // Used to read back values kept in overflow pages
+    flusher: Arc<Flusher>,
+    value_type: DataType,
+
+    // How much of each value is handed out, leaves are decoded no further than it needs
+    projection: Projection,
+
+    // Keeps the pages of a copy-on-write tree's root version alive until the iterator is done
+    pin: Option<Arc<VersionPin>>,
+
+    // Entries expiring at or before this time are skipped, taken once so both ends agree
+    now: u64,
+
+    // First entry that could not be read, iteration stops at it
+    error: Option<anyhow::Error>,
 }
 
 pub(self) fn map_end(
@@ -25,6 +51,7 @@ pub(self) fn map_end(
     file_id: u64,
     page: Arc<NodeInner>,
     codec: Codec,
+    root: Option<PagePointer>,
     range: impl RangeBounds<ByteBox>
 ) -> (LeafIterator, Option<Bound<ByteBox>>) {
     let leaf_iter = match range.start_bound() {
@@ -33,11 +60,16 @@ pub(self) fn map_end(
                 flusher,
                 page.clone(),
                 codec,
+                root,
+                Projection::Whole,
                 NodeKey::GuidePost(k.clone()),
                 file_id
             );
 
-            leaf_iter.next();
+            // The seek lands on the first key at or after the bound
+            if leaf_iter.is_valid() && leaf_iter.value().key == *k {
+                leaf_iter.next();
+            }
 
             leaf_iter
         }
@@ -46,6 +78,8 @@ pub(self) fn map_end(
                 flusher,
                 page.clone(),
                 codec,
+                root,
+                Projection::Whole,
                 NodeKey::GuidePost(k.clone()),
                 file_id
             );
@@ -58,6 +92,8 @@ pub(self) fn map_end(
                 flusher,
                 page.clone(),
                 codec,
+                root,
+                Projection::Whole,
                 file_id
             );
             leaf_iter
@@ -74,37 +110,169 @@ pub(self) fn map_end(
     (leaf_iter, end)
 }
 
+/// Positions an iterator on the last entry of the range, mirroring `map_end`
+fn map_start(
+    flusher: Arc<Flusher>,
+    file_id: u64,
+    page: Arc<NodeInner>,
+    codec: Codec,
+    root: Option<PagePointer>,
+    range: &impl RangeBounds<ByteBox>
+) -> LeafIterator {
+    match range.end_bound() {
+        std::ops::Bound::Excluded(k) => {
+            let mut leaf_iter = LeafIterator::create_and_seek_for_prev(
+                flusher,
+                page,
+                codec,
+                root,
+                Projection::Whole,
+                NodeKey::GuidePost(k.clone()),
+                file_id
+            );
+
+            // The seek lands on the last key at or before the bound
+            if leaf_iter.is_valid() && leaf_iter.value().key == *k {
+                leaf_iter.prev();
+            }
+
+            leaf_iter
+        }
+        std::ops::Bound::Included(k) => {
+            LeafIterator::create_and_seek_for_prev(
+                flusher,
+                page,
+                codec,
+                root,
+                Projection::Whole,
+                NodeKey::GuidePost(k.clone()),
+                file_id
+            )
+        }
+
+        std::ops::Bound::Unbounded => {
+            LeafIterator::create_and_seek_to_last(
+                flusher,
+                page,
+                codec,
+                root,
+                Projection::Whole,
+                file_id
+            )
+        }
+    }
+}
+
 impl BPTreeIterator {
+    /// Creates an iterator over the range
+    ///
+    /// `page` is the leaf holding the start of the range and `last_page` the leaf holding its end.
+    /// Given a `root`, leaves are moved between by descending from it rather than along the leaf
+    /// chain, see `LeafIterator`.
     pub fn create_and_seek_to_key(
         flusher: Arc<Flusher>,
         codec: Codec,
         file_id: u64,
+        root: Option<PagePointer>,
         page: Arc<NodeInner>,
+        last_page: Arc<NodeInner>,
         range: impl RangeBounds<ByteBox>
     ) -> Self {
-        let (leaf_iter, end) = map_end(flusher, file_id, page, codec, range);
+        let back_iter = map_start(flusher.clone(), file_id, last_page, codec.clone(), root, &range);
+        let start = range.start_bound().cloned();
+        let value_type = codec.value_type.clone();
 
-        let mut iter = Self {
+        let (leaf_iter, end) = map_end(flusher.clone(), file_id, page, codec, root, range);
+
+        Self {
             leaf_iter,
-            end: Bound::Unbounded,
-        };
+            back_iter,
+            start,
+            end: end.unwrap_or(Bound::Unbounded),
+            last_front: None,
+            last_back: None,
+            strip_tiebreaker: false,
+            flusher,
+            value_type,
+            projection: Projection::Whole,
+            pin: None,
+            now: unix_millis(SystemTime::now()),
+            error: None,
+        }
+    }
+
+    /// Hands out values as `projection` asks, decoding the leaves read from here on no further
+    pub(crate) fn projected(mut self, projection: Projection) -> Self {
+        let projected = self.leaf_iter
+            .project(projection.clone())
+            .and_then(|_| self.back_iter.project(projection.clone()));
+
+        if let Err(err) = projected {
+            self.error = Some(err);
+        }
+
+        self.projection = projection;
+        self
+    }
 
-        if end.is_some() {
-            iter.end = end.unwrap();
+    /// Hands out entries of a tree with duplicates under their own key
+    pub(crate) fn strip_tiebreaker(mut self) -> Self {
+        self.strip_tiebreaker = true;
+        self
+    }
+
+    /// Holds on to the root version the iterator reads until it is dropped
+    pub(crate) fn pinned(mut self, pin: Arc<VersionPin>) -> Self {
+        self.pin = Some(pin);
+        self
+    }
+
+    /// Why iteration stopped early, if it did
+    ///
+    /// An entry whose value cannot be read back, such as one pointing at a missing overflow
+    /// chain, ends the iteration. Callers that need every entry check this once it returns `None`.
+    pub fn error(&self) -> Option<&anyhow::Error> {
+        self.error.as_ref()
+    }
+
+    fn to_entry(&self, mut output: KeyValuePair) -> anyhow::Result<KeyValuePair> {
+        if self.strip_tiebreaker {
+            if let Ok(mut components) = output.key.components() {
+                output.key = components.swap_remove(0);
+            }
+        }
+
+        output.value = output.value.without_expiry();
+
+        if output.value.datatype == DataType::Overflow {
+            let data = OverflowChain::from_stub(&output.value)?.read(&self.flusher)?;
+
+            output.value = self.projection.project(&data, &self.value_type)?;
         }
 
-        iter
+        Ok(output)
+    }
+
+    /// Hands out an entry read by either end, or records why it could not be read
+    fn yield_entry(&mut self, output: KeyValuePair) -> Option<KeyValuePair> {
+        match self.to_entry(output) {
+            Ok(entry) => Some(entry),
+            Err(err) => {
+                self.error = Some(err);
+                None
+            }
+        }
     }
 }
 
 impl StorageIterator for BPTreeIterator {
     type Item = KeyValuePair;
     fn value(&self) -> Self::Item {
-        self.leaf_iter.value()
+        self.to_entry(self.leaf_iter.value()).expect("Unreadable entry")
     }
 
     fn is_valid(&self) -> bool {
-        self.leaf_iter.is_valid()
+        self.error.is_none() && self.leaf_iter.is_valid()
     }
 
     fn advance(&mut self) -> anyhow::Result<()> {
@@ -116,33 +284,94 @@ impl Iterator for BPTreeIterator {
     type Item = KeyValuePair;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let output: Option<Self::Item>;
-        output = self.leaf_iter.next();
-
-        if let Bound::Unbounded = self.end {
-            return output;
-        } else {
-            if output.is_none() {
-                return output;
+        if self.error.is_some() {
+            return None;
+        }
+
+        loop {
+            let output = self.leaf_iter.next()?;
+
+            let past_end = match &self.end {
+                Bound::Excluded(key) => output.key >= *key,
+                Bound::Included(key) => output.key > *key,
+                Bound::Unbounded => false,
+            };
+
+            if past_end {
+                return None;
             }
 
-            let output_ref = output.clone().unwrap();
-            match &self.end {
-                Bound::Excluded(key) => {
-                    if output_ref.key >= *key {
-                        return None;
-                    }
+            if let Some(last_back) = &self.last_back {
+                if output.key >= *last_back {
+                    return None;
                 }
-                Bound::Included(key) => {
-                    if output_ref.key > *key {
-                        return None;
-                    }
+            }
+
+            self.last_front = Some(output.key.clone());
+
+            // Expired entries stay in the leaf until they are reaped
+            if !output.value.is_expired(self.now) {
+                return self.yield_entry(output);
+            }
+        }
+    }
+}
+
+impl DoubleEndedIterator for BPTreeIterator {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() {
+            return None;
+        }
+
+        loop {
+            let output = self.back_iter.prev()?;
+
+            let before_start = match &self.start {
+                Bound::Excluded(key) => output.key <= *key,
+                Bound::Included(key) => output.key < *key,
+                Bound::Unbounded => false,
+            };
+
+            if before_start {
+                return None;
+            }
+
+            if let Some(last_front) = &self.last_front {
+                if output.key <= *last_front {
+                    return None;
                 }
+            }
+
+            self.last_back = Some(output.key.clone());
 
-                _ => {}
+            if !output.value.is_expired(self.now) {
+                return self.yield_entry(output);
             }
         }
+    }
+}
+
+/// Iterator over the keys of a range, see `BPTree::scan_keys`
+pub struct KeyIterator {
+    entries: BPTreeIterator,
+}
+
+impl KeyIterator {
+    pub(crate) fn new(entries: BPTreeIterator) -> Self {
+        KeyIterator { entries }
+    }
+}
+
+impl Iterator for KeyIterator {
+    type Item = ByteBox;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(|entry| entry.key)
+    }
+}
 
-        output
+impl DoubleEndedIterator for KeyIterator {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.entries.next_back().map(|entry| entry.key)
     }
 }
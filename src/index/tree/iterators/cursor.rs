@@ -0,0 +1,243 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use crate::index::tree::{
+    byte_box::ByteBox,
+    db::btree_obj::BPTree,
+    index_types::{ KeyValuePair, NodeKey },
+    tree_node::{ node_type::NodeType, tree_node_inner::NodeInner },
+};
+
+/// A position in a tree that can move both ways and change the entry it is on
+///
+/// The cursor keeps the leaf it is on and walks the leaf chain from it. After any page write to
+/// the tree, made through the cursor or around it, the leaf may be stale: a merge can leave the
+/// page of a leaf holding its old entries while no longer part of the tree. The cursor then
+/// looks its key up from the root again. Once the entry under the cursor is gone, `next` and
+/// `prev` move on from the key it had.
+pub struct Cursor<'a> {
+    tree: &'a BPTree,
+
+    // The stored key the cursor is on, None while unpositioned
+    key: Option<ByteBox>,
+
+    // The leaf the key was read from and its index there, valid while no page was written since
+    leaf: Option<(NodeInner, usize)>,
+
+    // The flusher's write count when the leaf was read
+    writes: u64,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(tree: &'a BPTree) -> Self {
+        Cursor { tree, key: None, leaf: None, writes: 0 }
+    }
+
+    /// Whether the cursor is on an entry
+    pub fn is_valid(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Moves to the first entry with a key at or after `key`
+    pub fn seek(&mut self, key: &ByteBox) -> anyhow::Result<bool> {
+        // A key alone sorts before every (key, value) pair stored for it
+        let stored_key = if self.tree.allow_duplicates {
+            ByteBox::composite_bound(std::slice::from_ref(key))
+        } else {
+            key.clone()
+        };
+
+        let (leaf, position) = self.locate(&stored_key)?;
+        self.forward_from(leaf, position.unwrap_or_else(|idx| idx))
+    }
+
+    pub fn seek_first(&mut self) -> anyhow::Result<bool> {
+        let leaf = self.tree.find_min()?;
+        self.forward_from(leaf, 0)
+    }
+
+    pub fn seek_last(&mut self) -> anyhow::Result<bool> {
+        let leaf = self.tree.find_max()?;
+        let len = leaf.get_key_array_length();
+
+        self.back_from(leaf, len)
+    }
+
+    /// Moves to the entry after the current one, or the first one past its key if it was removed
+    pub fn next(&mut self) -> anyhow::Result<bool> {
+        match self.refresh()? {
+            Some((leaf, Ok(idx))) => self.forward_from(leaf, idx + 1),
+            Some((leaf, Err(idx))) => self.forward_from(leaf, idx),
+            None => Ok(false),
+        }
+    }
+
+    /// Moves to the entry before the current one, or the last one before its key if it was
+    /// removed
+    pub fn prev(&mut self) -> anyhow::Result<bool> {
+        match self.refresh()? {
+            Some((leaf, Ok(idx) | Err(idx))) => self.back_from(leaf, idx),
+            None => Ok(false),
+        }
+    }
+
+    /// The key of the current entry, as it was inserted
+    pub fn key(&self) -> Option<ByteBox> {
+        self.key.as_ref().map(|key| self.user_key(key.clone()))
+    }
+
+    /// Reads the current entry, None when the cursor is unpositioned or its entry was removed
+    pub fn entry(&mut self) -> anyhow::Result<Option<KeyValuePair>> {
+        let (leaf, idx) = match self.refresh()? {
+            Some((leaf, Ok(idx))) => (leaf, idx),
+            _ => {
+                return Ok(None);
+            }
+        };
+
+        let entry = leaf.get_key_value_at(idx)?;
+        self.leaf = Some((leaf, idx));
+
+        Ok(
+            Some(KeyValuePair {
+                key: self.user_key(entry.key),
+                value: self.tree.resolve_value(entry.value)?,
+            })
+        )
+    }
+
+    /// Replaces the value of the current entry, staying on it
+    ///
+    /// Values of a tree with duplicates are part of their key, so they cannot be updated.
+    pub fn update_value(&mut self, value: ByteBox) -> anyhow::Result<()> {
+        if self.tree.allow_duplicates {
+            return Err(anyhow::Error::msg("Values of a tree with duplicates are part of the key"));
+        }
+
+        let (leaf, idx) = self.current()?;
+        let key = leaf.get_key_value_at(idx)?.key;
+
+        // Checked up front, as `put` removes the old entry before inserting the new one
+        self.tree.check_entry(&(KeyValuePair { key: key.clone(), value: value.clone() }))?;
+        self.tree.put(KeyValuePair { key, value })
+    }
+
+    /// Removes the current entry and moves to the one after it
+    pub fn delete_current(&mut self) -> anyhow::Result<bool> {
+        self.current()?;
+        let key = self.key.clone().unwrap();
+
+        self.tree.delete_entry(NodeKey::GuidePost(key.clone()))?;
+
+        let (leaf, position) = self.locate(&key)?;
+        self.forward_from(leaf, position.unwrap_or_else(|idx| idx))
+    }
+
+    /// The up to date leaf and index of the current entry, failing if there is none
+    fn current(&mut self) -> anyhow::Result<(NodeInner, usize)> {
+        match self.refresh()? {
+            Some((leaf, Ok(idx))) => Ok((leaf, idx)),
+            _ => Err(anyhow::Error::msg("Cursor is not on an entry")),
+        }
+    }
+
+    /// The leaf of the current key and the key's index in it, or where it would be
+    ///
+    /// The kept leaf is used while no page was written since it was read. Otherwise the leaf is
+    /// found from the root rather than reread from the page it was last on: pages merged away
+    /// are not freed, so that page can still hold the key without being reachable.
+    fn refresh(&mut self) -> anyhow::Result<Option<(NodeInner, Result<usize, usize>)>> {
+        let key = match &self.key {
+            Some(key) => key.clone(),
+            None => {
+                return Ok(None);
+            }
+        };
+
+        if let Some((leaf, idx)) = self.leaf.take() {
+            if self.writes == self.tree.flusher.write_count() {
+                return Ok(Some((leaf, Ok(idx))));
+            }
+        }
+
+        self.locate(&key).map(Some)
+    }
+
+    /// Looks the stored key up from the root
+    fn locate(&self, key: &ByteBox) -> anyhow::Result<(NodeInner, Result<usize, usize>)> {
+        let leaf = self.tree.find_leaf(NodeKey::GuidePost(key.clone()))?;
+        let position = search(&leaf, key)?;
+
+        Ok((leaf, position))
+    }
+
+    /// Settles on the entry at `idx` or the first one after it, moving on to later leaves
+    fn forward_from(&mut self, mut leaf: NodeInner, mut idx: usize) -> anyhow::Result<bool> {
+        while idx >= leaf.get_key_array_length() {
+            match self.tree.neighbour_leaf(&leaf, true)? {
+                Some(next) => {
+                    leaf = next;
+                    idx = 0;
+                }
+                None => {
+                    return Ok(self.unposition());
+                }
+            }
+        }
+
+        self.position(leaf, idx)
+    }
+
+    /// Settles on the last entry before index `idx`, moving back to earlier leaves
+    fn back_from(&mut self, mut leaf: NodeInner, mut idx: usize) -> anyhow::Result<bool> {
+        while idx == 0 {
+            match self.tree.neighbour_leaf(&leaf, false)? {
+                Some(prev) => {
+                    leaf = prev;
+                    idx = leaf.get_key_array_length();
+                }
+                None => {
+                    return Ok(self.unposition());
+                }
+            }
+        }
+
+        self.position(leaf, idx - 1)
+    }
+
+    fn position(&mut self, leaf: NodeInner, idx: usize) -> anyhow::Result<bool> {
+        self.key = Some(leaf.get_key_value_at(idx)?.key);
+        self.writes = self.tree.flusher.write_count();
+        self.leaf = Some((leaf, idx));
+
+        Ok(true)
+    }
+
+    fn unposition(&mut self) -> bool {
+        self.key = None;
+        self.leaf = None;
+
+        false
+    }
+
+    fn user_key(&self, key: ByteBox) -> ByteBox {
+        if !self.tree.allow_duplicates {
+            return key;
+        }
+
+        match key.components() {
+            Ok(mut components) => components.swap_remove(0),
+            Err(_) => key,
+        }
+    }
+}
+
+/// Finds the stored key among the entries of a leaf, or the index it would be inserted at
+fn search(leaf: &NodeInner, key: &ByteBox) -> anyhow::Result<Result<usize, usize>> {
+    match &leaf.node_type {
+        NodeType::Leaf(entries, _, _) => {
+            Ok(entries.binary_search_by(|entry| NodeInner::deconstruct_value(entry).0.cmp(key)))
+        }
+        _ => Err(anyhow::Error::msg("Expected a leaf")),
+    }
+}
@@ -7,18 +7,24 @@ use crate::{
     buffer::flusher::Flusher,
     index::tree::{
         index_types::{ KeyValuePair, NodeKey },
-        tree_node::tree_node_inner::NodeInner,
-        tree_page::{ codec::Codec, page::TreePage },
+        tree_node::{ node_type::PagePointer, tree_node_inner::NodeInner },
+        tree_page::{ codec::{ Codec, Projection }, page::TreePage },
     },
 };
 
-use super::iterator::StorageIterator;
+use super::{ iterator::StorageIterator, tree_walk::neighbour_leaf };
 
 pub struct LeafIterator {
     flusher: Arc<Flusher>,
     page: Arc<NodeInner>,
     codec: Codec,
 
+    // Root neighbouring leaves are found from, None to follow the leaf chain
+    root: Option<PagePointer>,
+
+    // How much of each value leaves read from here on are decoded with
+    projection: Projection,
+
     current_key: Option<KeyValuePair>,
     file_id: u64,
     index: usize,
@@ -28,43 +34,119 @@ impl LeafIterator {
     pub fn get_page(&self, next_page: u32) -> NodeInner {
         let page = self.flusher.read_drop(next_page);
         let tree_page = TreePage::new(page);
-        let node = self.codec.decode(&tree_page).unwrap();
+        let node = self.codec.decode_projected(&tree_page, &self.projection).unwrap();
 
         node
     }
 
+    /// The leaf after this one, or the one before it when not `forward`
+    fn neighbour(&self, forward: bool) -> Option<NodeInner> {
+        neighbour_leaf(&self.flusher, &self.codec, self.root, &self.page, forward, &self.projection)
+            .ok()
+            .flatten()
+    }
+
     pub fn create_and_seek_to_key(
         flusher: Arc<Flusher>,
         page: Arc<NodeInner>,
         codec: Codec,
+        root: Option<PagePointer>,
+        projection: Projection,
 
         search_key: NodeKey,
         file_id: u64
     ) -> Self {
-        let current_key = match page.get_key_value(&search_key) {
-            Ok(key) => Some(key),
-            Err(e) => None,
-        };
+        let index = page.get_key_value_index(&search_key).unwrap_or_default();
 
-        let index = match page.get_key_value_index(&search_key) {
-            Ok(key) => key,
-            Err(e) => 0,
-        };
+        let current_key = page.get_key_value_at(index).ok();
 
-        Self {
+        let mut iter = Self {
             flusher,
             page,
             codec,
+            root,
+            projection,
             current_key,
             file_id,
             index,
+        };
+
+        // Every key in this leaf is smaller than the search key, so the next leaf holds the successor
+        if !iter.is_valid() {
+            if let Some(node) = iter.neighbour(true) {
+                iter = LeafIterator::create_and_seek_to_first(
+                    iter.flusher.clone(),
+                    Arc::new(node),
+                    iter.codec.clone(),
+                    iter.root,
+                    iter.projection.clone(),
+                    iter.file_id
+                );
+            }
         }
+
+        iter
+    }
+
+    /// Positions the iterator on the last entry less than or equal to the search key
+    pub fn create_and_seek_for_prev(
+        flusher: Arc<Flusher>,
+        page: Arc<NodeInner>,
+        codec: Codec,
+        root: Option<PagePointer>,
+        projection: Projection,
+
+        search_key: NodeKey,
+        file_id: u64
+    ) -> Self {
+        let index = page.get_key_value_index(&search_key).unwrap_or_default();
+
+        let (search, _) = NodeInner::deconstruct_value(&search_key);
+
+        match page.get_key_value_at(index) {
+            Ok(kv) if kv.key == search => {
+                return Self {
+                    flusher,
+                    page,
+                    codec,
+                    root,
+                    projection,
+                    current_key: Some(kv),
+                    file_id,
+                    index,
+                };
+            }
+            _ => {}
+        }
+
+        // The entry found is past the search key, start from the one before it
+        let mut iter = Self {
+            flusher,
+            page,
+            codec,
+            root,
+            projection,
+            current_key: None,
+            file_id,
+            index,
+        };
+
+        match iter.retreat() {
+            Ok(()) => {}
+            Err(e) => {
+                iter.current_key = None;
+            }
+        }
+
+        iter
     }
 
     pub fn create_and_seek_to_first(
         flusher: Arc<Flusher>,
         page: Arc<NodeInner>,
         codec: Codec,
+        root: Option<PagePointer>,
+        projection: Projection,
         file_id: u64
     ) -> Self {
         let current_key = match page.get_key_value_at(0) {
@@ -76,11 +158,101 @@ impl LeafIterator {
             flusher,
             page,
             codec,
+            root,
+            projection,
             file_id,
             current_key,
             index: 0,
         }
     }
+
+    pub fn create_and_seek_to_last(
+        flusher: Arc<Flusher>,
+        page: Arc<NodeInner>,
+        codec: Codec,
+        root: Option<PagePointer>,
+        projection: Projection,
+        file_id: u64
+    ) -> Self {
+        let index = page.get_key_array_length().saturating_sub(1);
+
+        let current_key = page.get_key_value_at(index).ok();
+
+        Self {
+            flusher,
+            page,
+            codec,
+            root,
+            projection,
+            file_id,
+            current_key,
+            index,
+        }
+    }
+
+    /// Decodes the leaves read from here on as `projection` asks, projecting the one it is on
+    ///
+    /// The leaf it is on is expected to be decoded whole, so this is done once, before iterating.
+    pub(crate) fn project(&mut self, projection: Projection) -> anyhow::Result<()> {
+        let value_type = &self.codec.value_type;
+
+        self.page = Arc::new(projection.apply_to_leaf((*self.page).clone(), value_type)?);
+
+        if let Some(current) = self.current_key.take() {
+            let value = projection.apply(current.value, value_type)?;
+            self.current_key = Some(KeyValuePair { key: current.key, value });
+        }
+
+        self.projection = projection;
+        Ok(())
+    }
+
+    /// Move to the previous position, moving to the leaf before this one when needed
+    pub fn retreat(&mut self) -> anyhow::Result<()> {
+        if self.index > 0 {
+            if let Ok(key) = self.page.get_key_value_at(self.index - 1) {
+                self.index -= 1;
+                self.current_key = Some(key);
+
+                return Ok(());
+            }
+        }
+
+        if let Some(node) = self.neighbour(false) {
+            *self = LeafIterator::create_and_seek_to_last(
+                self.flusher.clone(),
+                Arc::new(node),
+                self.codec.clone(),
+                self.root,
+                self.projection.clone(),
+                self.file_id
+            );
+
+            if self.is_valid() {
+                return Ok(());
+            }
+        }
+
+        Err(anyhow::Error::msg("Cannot Retreat"))
+    }
+
+    /// Returns the current entry and moves the iterator towards the start of the tree
+    pub fn prev(&mut self) -> Option<KeyValuePair> {
+        if !self.is_valid() {
+            return None;
+        }
+
+        let current = self.current_key.clone();
+
+        match self.retreat() {
+            Ok(()) => {}
+            Err(e) => {
+                self.current_key = None;
+            }
+        }
+
+        current
+    }
 }
 
 impl StorageIterator for LeafIterator {
@@ -111,20 +283,16 @@ impl StorageIterator for LeafIterator {
             return Ok(());
         }
 
-        let next_page = self.page.next_pointer;
-
-        if let Some(next_page) = next_page {
-            if next_page > 0 {
-                let node = self.get_page(next_page);
-
-                *self = LeafIterator::create_and_seek_to_first(
-                    self.flusher.clone(),
-                    Arc::new(node),
-                    self.codec.clone(),
-                    self.file_id
-                );
-                return Ok(());
-            }
+        if let Some(node) = self.neighbour(true) {
+            *self = LeafIterator::create_and_seek_to_first(
+                self.flusher.clone(),
+                Arc::new(node),
+                self.codec.clone(),
+                self.root,
+                self.projection.clone(),
+                self.file_id
+            );
+            return Ok(());
         }
         return Err(anyhow::Error::msg("Cannot Advance"));
     }
@@ -1,3 +1,5 @@
 pub mod leaf_iterator;
 pub mod bptree_iterator;
 pub mod iterator;
+pub mod cursor;
+pub mod tree_walk;
@@ -0,0 +1,142 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use crate::{
+    buffer::flusher::Flusher,
+    index::tree::{
+        index_types::NodeKey,
+        tree_node::{ node_type::{ NodeType, PagePointer }, tree_node_inner::NodeInner },
+        tree_page::{ codec::{ Codec, Projection }, page::TreePage },
+    },
+};
+
+/// Which child a descent from the root takes at every internal node
+pub(crate) enum Descent<'a> {
+    Key(&'a NodeKey),
+    First,
+    Last,
+}
+
+/// Walks from `root` down to a leaf, returning the internal nodes passed with the index of the
+/// child taken in each, root first, along with the leaf
+pub(crate) fn descend(
+    flusher: &Flusher,
+    codec: &Codec,
+    root: PagePointer,
+    to: Descent
+) -> anyhow::Result<(Vec<(NodeInner, usize)>, NodeInner)> {
+    descend_projected(flusher, codec, root, to, &Projection::Whole)
+}
+
+/// `descend`, decoding the values of the leaf reached as `projection` asks
+fn descend_projected(
+    flusher: &Flusher,
+    codec: &Codec,
+    root: PagePointer,
+    to: Descent,
+    projection: &Projection
+) -> anyhow::Result<(Vec<(NodeInner, usize)>, NodeInner)> {
+    let mut path = Vec::new();
+    let mut node = read_node(flusher, codec, root, projection)?;
+
+    loop {
+        let (child, idx) = match &node.node_type {
+            NodeType::Internal(children, keys, _) => {
+                let idx = match to {
+                    // Keys at or above a separator sit right of it
+                    Descent::Key(search) => keys.partition_point(|key| key <= search),
+                    Descent::First => 0,
+                    Descent::Last => children.len() - 1,
+                };
+
+                (children[idx], idx)
+            }
+            NodeType::Leaf(_, _, _) => {
+                return Ok((path, node));
+            }
+            NodeType::Unexpected => {
+                return Err(anyhow::Error::msg("Unexpected node type"));
+            }
+        };
+
+        let next = read_node(flusher, codec, child, projection)?;
+        path.push((node, idx));
+        node = next;
+    }
+}
+
+/// The leaf after `leaf` in key order, or the one before it when not `forward`
+///
+/// Without a root the leaf chain is followed. With one the neighbour is found from the root
+/// instead, for trees whose leaves do not keep the chain up to date. The path to the leaf is
+/// retraced up to the lowest node with a child past the leaf's, under which the nearest leaf is
+/// taken. Its values are decoded as `projection` asks.
+pub(crate) fn neighbour_leaf(
+    flusher: &Flusher,
+    codec: &Codec,
+    root: Option<PagePointer>,
+    leaf: &NodeInner,
+    forward: bool,
+    projection: &Projection
+) -> anyhow::Result<Option<NodeInner>> {
+    let root = match root {
+        Some(root) => root,
+        None => {
+            let neighbour = if forward { leaf.next_pointer } else { leaf.prev_pointer };
+
+            return match neighbour {
+                Some(page) if page > 0 => Ok(Some(read_node(flusher, codec, page, projection)?)),
+                _ => Ok(None),
+            };
+        }
+    };
+
+    // Only an empty root has no entries, and it has no neighbours either
+    let entries = match &leaf.node_type {
+        NodeType::Leaf(entries, _, _) => entries,
+        _ => {
+            return Err(anyhow::Error::msg("Expected a leaf"));
+        }
+    };
+    let edge = match if forward { entries.last() } else { entries.first() } {
+        Some(entry) => NodeKey::GuidePost(NodeInner::deconstruct_value(entry).0),
+        None => {
+            return Ok(None);
+        }
+    };
+
+    let (mut path, _) = descend(flusher, codec, root, Descent::Key(&edge))?;
+
+    while let Some((node, idx)) = path.pop() {
+        let children = match &node.node_type {
+            NodeType::Internal(children, _, _) => children,
+            _ => {
+                return Err(anyhow::Error::msg("Unexpected node type"));
+            }
+        };
+
+        let sibling = if forward {
+            children.get(idx + 1)
+        } else {
+            idx.checked_sub(1).and_then(|idx| children.get(idx))
+        };
+
+        if let Some(sibling) = sibling {
+            let to = if forward { Descent::First } else { Descent::Last };
+
+            return Ok(Some(descend_projected(flusher, codec, *sibling, to, projection)?.1));
+        }
+    }
+
+    Ok(None)
+}
+
+// Only leaves hold values, so internal nodes read the same under every projection
+fn read_node(
+    flusher: &Flusher,
+    codec: &Codec,
+    page: PagePointer,
+    projection: &Projection
+) -> anyhow::Result<NodeInner> {
+    codec.decode_projected(&TreePage::new(flusher.read_drop(page)), projection)
+}
@@ -57,11 +57,11 @@ impl GuidePostNode {
         match self.node_type {
             NodeType::Internal(ref mut children, ref mut keys, _) => {
                 let key = keys.remove(idx);
-                let child_pointer: PagePointer;
-                if take_right {
-                    child_pointer = children.remove(idx + 1);
-                } else {
-                    child_pointer = children.remove(idx);
+                let child_idx = if take_right { idx + 1 } else { idx };
+                let child_pointer = children.remove(child_idx);
+
+                if let Some(counts) = &mut self.child_counts {
+                    counts.remove(child_idx);
                 }
 
                 Ok((key, child_pointer))
@@ -71,6 +71,29 @@ impl GuidePostNode {
         }
     }
 
+    /// Inserts a child pointer at a position in an internal node
+    ///
+    /// Children are ordered by key, not by page id, so the caller supplies the position. Child
+    /// counts start at zero until `set_child_count` fills them in.
+    pub fn insert_child_pointer_at(&mut self, idx: usize, child: PagePointer) -> anyhow::Result<()> {
+        match self.node_type {
+            NodeType::Internal(ref mut children, _, _) => {
+                if idx > children.len() {
+                    return Err(anyhow::Error::msg("Child index out of bounds"));
+                }
+
+                children.insert(idx, child);
+
+                if let Some(counts) = &mut self.child_counts {
+                    counts.insert(idx, 0);
+                }
+                Ok(())
+            }
+
+            _ => Err(anyhow::Error::msg("Unexpected Error")),
+        }
+    }
+
     /// Inserts a child pointer into an internal node
     pub fn insert_child_pointer(&mut self, child: PagePointer) -> anyhow::Result<()> {
         let node_type = self.node_type.clone();
@@ -79,6 +102,10 @@ impl GuidePostNode {
                 let idx = children.binary_search(&child).unwrap_or_else(|x| x);
 
                 children.insert(idx, child);
+
+                if let Some(counts) = &mut self.child_counts {
+                    counts.insert(idx, 0);
+                }
                 Ok(())
             }
 
@@ -102,10 +129,16 @@ impl GuidePostNode {
         let node_type = self.node_type.clone();
         match self.node_type {
             NodeType::Internal(ref mut children, _, _) => {
-                let idx = children.binary_search(&child).unwrap_or_else(|x| x);
-                let dat = children.remove(idx);
+                let idx = children
+                    .iter()
+                    .position(|pointer| *pointer == child)
+                    .ok_or(anyhow::Error::msg("Child pointer not found"))?;
 
-             
+                children.remove(idx);
+
+                if let Some(counts) = &mut self.child_counts {
+                    counts.remove(idx);
+                }
 
                 Ok(())
             }
@@ -117,12 +150,66 @@ impl GuidePostNode {
     }
 
     /// Inserts a key and pointer into an internal node
+    ///
+    /// The pointer is placed directly right of its key, as it holds the keys at or above it
     pub fn insert_sibling_node(&mut self, key: NodeKey, child: PagePointer) -> anyhow::Result<()> {
         match self.node_type {
-            NodeType::Internal(_, _, _) => {
-                self.insert_key(key)?;
+            NodeType::Internal(ref mut children, ref mut keys, _) => {
+                let idx = keys.binary_search(&key).unwrap_or_else(|x| x);
+
+                keys.insert(idx, key);
+                children.insert(idx + 1, child);
+
+                if let Some(counts) = &mut self.child_counts {
+                    counts.insert(idx + 1, 0);
+                }
+
+                Ok(())
+            }
+            _ => Err(anyhow::Error::msg("Unexpected Error")),
+        }
+    }
+
+    /// Entries under the child at `idx`, for nodes keeping child counts
+    pub fn child_count_at(&self, idx: usize) -> Option<u64> {
+        self.child_counts.as_ref().and_then(|counts| counts.get(idx).copied())
+    }
+
+    /// Records the entries under a child, doing nothing for nodes without child counts
+    pub fn set_child_count(&mut self, child: PagePointer, count: u64) -> anyhow::Result<()> {
+        let idx = match &self.node_type {
+            NodeType::Internal(children, _, _) => {
+                children
+                    .iter()
+                    .position(|pointer| *pointer == child)
+                    .ok_or(anyhow::Error::msg("Child pointer not found"))?
+            }
+            _ => {
+                return Err(anyhow::Error::msg("Unexpected Error"));
+            }
+        };
+
+        if let Some(counts) = &mut self.child_counts {
+            counts[idx] = count;
+        }
+
+        Ok(())
+    }
+
+    /// Points the node at the page a child moved to, keeping the child's place and count
+    pub fn replace_child_pointer(
+        &mut self,
+        child: PagePointer,
+        moved_to: PagePointer
+    ) -> anyhow::Result<()> {
+        match &mut self.node_type {
+            NodeType::Internal(children, _, _) => {
+                let pointer = children
+                    .iter_mut()
+                    .find(|pointer| **pointer == child)
+                    .ok_or(anyhow::Error::msg("Child pointer not found"))?;
 
-                self.insert_child_pointer(child)?;
+                *pointer = moved_to;
 
                 Ok(())
             }
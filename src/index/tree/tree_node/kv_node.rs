@@ -38,8 +38,8 @@ impl KvNode {
         }
     }
 
-    /// Removes a key value pair entry from a leaf node
-    pub fn remove_entry(&mut self, key: &ByteBox) -> anyhow::Result<()> {
+    /// Removes a key value pair entry from a leaf node, returning it if the key was present
+    pub fn remove_entry(&mut self, key: &ByteBox) -> anyhow::Result<Option<KeyValuePair>> {
         let search_value = key;
 
         match self.node_type {
@@ -54,12 +54,13 @@ impl KvNode {
                 if let Some(entry) = entries.get(idx) {
                     if entry.to_kv_pair().unwrap().key == *key {
                         let entry = entries.remove(idx);
+                        return Ok(entry.to_kv_pair().ok());
                     } else {
                         return Err(anyhow::Error::msg("Mismatch nodes"));
                     }
                 }
 
-                Ok(())
+                Ok(None)
             }
 
             _ => {
@@ -11,7 +11,11 @@ pub struct NodeInner {
     pub is_root: bool,
     pub pointer: PagePointer,
     pub next_pointer: Option<PagePointer>,
+    pub prev_pointer: Option<PagePointer>,
     pub is_leaf: bool,
+
+    /// Entries under each child of an internal node, kept by trees with order statistics
+    pub child_counts: Option<Vec<u64>>,
 }
 
 pub type KvNode = NodeInner;
@@ -26,6 +30,7 @@ pub struct PopResult {
     pub(crate) pop_key: NodeKey,
     pub(crate) promotion_key: NodeKey,
     pub(crate) child_pointer: Option<PagePointer>,
+    pub(crate) child_count: Option<u64>,
 }
 
 impl NodeInner {
@@ -42,7 +47,9 @@ impl NodeInner {
                     is_root,
                     pointer,
                     next_pointer,
+                    prev_pointer: None,
                     is_leaf: false,
+                    child_counts: None,
                 },
             _ =>
                 NodeInner {
@@ -50,7 +57,9 @@ impl NodeInner {
                     is_root,
                     pointer,
                     next_pointer,
+                    prev_pointer: None,
                     is_leaf: true,
+                    child_counts: None,
                 },
         }
     }
@@ -63,6 +72,7 @@ impl NodeInner {
                     .get(len - 1)
                     .unwrap()
                     .clone();
+                let child_count = self.child_count_at(0);
                 let (key, page_id) = self.remove_inner_node_entry(0, false)?;
 
                 let (promotion_key, _) = NodeInner::deconstruct_value(&promotion_key);
@@ -73,15 +83,16 @@ impl NodeInner {
                     pop_key: NodeKey::GuidePost(key_box),
                     promotion_key: NodeKey::GuidePost(promotion_key),
                     child_pointer: Some(page_id),
+                    child_count,
                 };
 
                 Ok(res)
             }
             NodeType::Leaf(entries, _, _) => {
-                let len = entries.len();
+                // The entry after the popped one becomes the node's first key
                 let promotion_key = entries
-                    .get(len - 1)
-                    .unwrap()
+                    .get(1)
+                    .ok_or(anyhow::Error::msg("Cannot pop the last entry of a leaf"))?
                     .clone();
                 let entry = self.remove_key_value_at_index(0)?;
 
@@ -98,6 +109,7 @@ impl NodeInner {
                     pop_key: NodeKey::KeyValuePair(kv_pair),
                     promotion_key: NodeKey::GuidePost(promotion_key),
                     child_pointer: None,
+                    child_count: None,
                 })
             }
             NodeType::Unexpected => {
@@ -115,6 +127,7 @@ impl NodeInner {
                     .get(len - 1)
                     .unwrap()
                     .clone();
+                let child_count = self.child_count_at(len);
                 let (key, page_id) = self.remove_inner_node_entry(len - 1, true)?;
 
                 let (promotion_key, _) = NodeInner::deconstruct_value(&promotion_key);
@@ -124,6 +137,7 @@ impl NodeInner {
                     pop_key: NodeKey::GuidePost(key),
                     promotion_key: NodeKey::GuidePost(promotion_key),
                     child_pointer: Some(page_id),
+                    child_count,
                 })
             }
             NodeType::Leaf(ref mut entries, _, _) => {
@@ -148,6 +162,7 @@ impl NodeInner {
                     pop_key: NodeKey::KeyValuePair(kv_pair),
                     promotion_key: NodeKey::GuidePost(promotion_key),
                     child_pointer: None,
+                    child_count: None,
                 })
             }
             NodeType::Unexpected => {
@@ -164,6 +179,19 @@ impl NodeInner {
             NodeType::Unexpected => 0,
         }
     }
+
+    /// Entries stored in the subtree under the node, as far as its child counts tell
+    ///
+    /// Internal nodes of trees without order statistics report zero.
+    pub fn entry_count(&self) -> u64 {
+        match &self.node_type {
+            NodeType::Internal(_, _, _) => {
+                self.child_counts.as_ref().map_or(0, |counts| counts.iter().sum())
+            }
+            NodeType::Leaf(entries, _, _) => entries.len() as u64,
+            NodeType::Unexpected => 0,
+        }
+    }
 }
 
 // Node helper functions
@@ -4,10 +4,11 @@
 use std::io::{ Cursor, Read, Seek, SeekFrom, Write };
 
 use byteorder::{ LittleEndian, ReadBytesExt, WriteBytesExt };
+use bytes::Bytes;
 
 use crate::{
     index::tree::{
-        byte_box::DataType,
+        byte_box::{ ByteBox, DataType },
         index_types::{ KeyValuePair, NodeKey },
         tree_node::{ node_type::NodeType, tree_node_inner::NodeInner },
         tree_page::tree_page_layout::{
@@ -15,8 +16,15 @@ use crate::{
             InternalNodeHeader,
             LeafNodeHeader,
             NodeHeader,
+            CHILD_COUNT_SIZE,
+            COUNTED_NODE_FLAG,
+            EXPIRING_VALUE_FLAG,
+            EXPIRY_SIZE,
             INTERNAL_NODE_HEADER_SIZE,
+            NULL_PREV_LEAF_POINTER,
+            OVERFLOW_VALUE_FLAG,
             PAGE_SIZE,
+            PTR_SIZE,
         },
     },
 };
@@ -29,8 +37,88 @@ pub struct Codec {
     pub(crate) value_type: DataType,
 }
 
+/// How much of every leaf value `Codec::decode_projected` reads
+///
+/// Leaves decoded with anything but `Whole` hold partial values, and must never be written back.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Projection {
+    #[default]
+    Whole,
+
+    /// None of the value, only when it expires
+    Keys,
+
+    /// The columns of a tuple value at the listed positions, in the order listed
+    Columns(Vec<usize>),
+}
+
+impl Projection {
+    /// Projects the bytes of a whole, inline value
+    pub(crate) fn project(&self, data: &[u8], value_type: &DataType) -> anyhow::Result<ByteBox> {
+        match (self, value_type) {
+            (Projection::Whole, _) => Ok(value_type.to_byte_box(data)),
+            (Projection::Keys, _) => Ok(ByteBox::new(Bytes::new(), DataType::None)),
+            (Projection::Columns(columns), DataType::Tuple(schema)) => {
+                ByteBox::project_tuple(schema, data, columns)
+            }
+            (Projection::Columns(_), _) => {
+                Err(anyhow::Error::msg("Only tuple values can be projected"))
+            }
+        }
+    }
+
+    /// Projects a value as a leaf decoded whole holds it
+    ///
+    /// Overflow stubs are kept, as their values are only read once handed out.
+    pub(crate) fn apply(&self, value: ByteBox, value_type: &DataType) -> anyhow::Result<ByteBox> {
+        let expires_at = value.expires_at();
+        let value = value.without_expiry();
+
+        let projected = match self {
+            Projection::Whole => value,
+            Projection::Columns(_) if value.datatype == DataType::Overflow => value,
+            _ => self.project(&value.data, value_type)?,
+        };
+
+        Ok(match expires_at {
+            Some(expires_at) => ByteBox::expiring(projected, expires_at),
+            None => projected,
+        })
+    }
+
+    /// Projects every value of a leaf decoded whole
+    pub(crate) fn apply_to_leaf(
+        &self,
+        mut leaf: NodeInner,
+        value_type: &DataType
+    ) -> anyhow::Result<NodeInner> {
+        if *self == Projection::Whole {
+            return Ok(leaf);
+        }
+
+        if let NodeType::Leaf(entries, _, _) = &mut leaf.node_type {
+            for entry in entries.iter_mut() {
+                if let NodeKey::KeyValuePair(kv) = entry {
+                    kv.value = self.apply(kv.value.clone(), value_type)?;
+                }
+            }
+        }
+
+        Ok(leaf)
+    }
+}
+
 impl Codec {
     pub fn decode(&self, page: &TreePage) -> anyhow::Result<NodeInner> {
+        self.decode_projected(page, &Projection::Whole)
+    }
+
+    /// Decodes a page, reading only as much of each leaf value as `projection` asks for
+    pub fn decode_projected(
+        &self,
+        page: &TreePage,
+        projection: &Projection
+    ) -> anyhow::Result<NodeInner> {
         let mut raw = page.get_data();
         let mut cursor = Cursor::new(&mut raw[..]);
 
@@ -49,6 +137,9 @@ impl Codec {
                 cursor.seek(SeekFrom::Start(InternalNodeHeader::NumChildren.offset() as u64))?;
                 let num_children = cursor.read_u32::<LittleEndian>()?;
 
+                let counted = num_children & COUNTED_NODE_FLAG != 0;
+                let num_children = num_children & !COUNTED_NODE_FLAG;
+
                 let offset = INTERNAL_NODE_HEADER_SIZE;
                 cursor.seek(SeekFrom::Start(offset as u64))?;
 
@@ -70,13 +161,25 @@ impl Codec {
                     keys.push(NodeKey::GuidePost(data_box));
                 }
 
+                let child_counts = if counted {
+                    let counts = (0..num_children)
+                        .map(|_| cursor.read_u64::<LittleEndian>())
+                        .collect::<std::io::Result<Vec<u64>>>()?;
+
+                    Some(counts)
+                } else {
+                    None
+                };
+
                 return Ok(NodeInner {
                     node_type,
                     is_root,
                     pointer,
 
                     next_pointer: None,
+                    prev_pointer: None,
                     is_leaf: false,
+                    child_counts,
                 });
             }
             NodeType::Leaf(ref mut entries, _, _) => {
@@ -89,24 +192,63 @@ impl Codec {
                     }
                 }
 
+                cursor.seek(SeekFrom::Start(LeafNodeHeader::PrevLeafPointer.offset() as u64))?;
+
+                let prev_pointer = match cursor.read_u32::<LittleEndian>()? {
+                    NULL_PREV_LEAF_POINTER => None,
+                    prev => Some(prev),
+                };
+
                 cursor.seek(SeekFrom::Start(LeafNodeHeader::NumPairs.offset() as u64))?;
                 let num_entries = cursor.read_u32::<LittleEndian>()?;
 
+                cursor.seek(SeekFrom::Start(LeafNodeHeader::PrefixLength.offset() as u64))?;
+                let prefix_len = cursor.read_u32::<LittleEndian>()?;
+
                 let offset = LEAF_NODE_HEADER_SIZE;
                 cursor.seek(SeekFrom::Start(offset as u64))?;
 
+                let mut prefix = vec![0u8; prefix_len as usize];
+                cursor.read_exact(&mut prefix)?;
+
                 for _ in 0..num_entries {
                     let key_len = cursor.read_u32::<LittleEndian>()?;
                     let mut buf = vec![0u8; key_len as usize];
 
                     cursor.read_exact(&mut buf)?;
+                    buf.splice(0..0, prefix.iter().copied());
                     let key_box = self.key_type.to_byte_box(&buf);
 
                     let data_len = cursor.read_u32::<LittleEndian>()?;
-                    buf = vec![0u8; data_len as usize];
+                    let flags = OVERFLOW_VALUE_FLAG | EXPIRING_VALUE_FLAG;
+                    let mut value_len = (data_len & !flags) as usize;
+
+                    let expires_at = if data_len & EXPIRING_VALUE_FLAG != 0 {
+                        value_len -= EXPIRY_SIZE;
+                        Some(cursor.read_u64::<LittleEndian>()?)
+                    } else {
+                        None
+                    };
 
-                    cursor.read_exact(&mut buf)?;
-                    let value_box = self.value_type.to_byte_box(&buf);
+                    // Values left out are stepped over without being read
+                    let value_box = if *projection == Projection::Keys {
+                        cursor.seek(SeekFrom::Current(value_len as i64))?;
+                        projection.project(&[], &self.value_type)?
+                    } else {
+                        buf = vec![0u8; value_len];
+                        cursor.read_exact(&mut buf)?;
+
+                        if data_len & OVERFLOW_VALUE_FLAG != 0 {
+                            DataType::Overflow.to_byte_box(&buf)
+                        } else {
+                            projection.project(&buf, &self.value_type)?
+                        }
+                    };
+
+                    let value_box = match expires_at {
+                        Some(expires_at) => ByteBox::expiring(value_box, expires_at),
+                        None => value_box,
+                    };
 
                     entries.push(
                         NodeKey::KeyValuePair(KeyValuePair {
@@ -122,7 +264,9 @@ impl Codec {
                     pointer,
 
                     next_pointer,
+                    prev_pointer,
                     is_leaf: true,
+                    child_counts: None,
                 });
             }
             NodeType::Unexpected => {
@@ -131,7 +275,85 @@ impl Codec {
         }
     }
 
+    /// Number of bytes `encode` lays the node out in
+    pub fn encoded_size(node: &NodeInner) -> usize {
+        match &node.node_type {
+            NodeType::Internal(children, keys, _) => {
+                let counts_size = match node.child_counts {
+                    Some(_) => children.len() * CHILD_COUNT_SIZE,
+                    None => 0,
+                };
+
+                INTERNAL_NODE_HEADER_SIZE +
+                    children.len() * PTR_SIZE +
+                    keys.iter().map(Codec::entry_size).sum::<usize>() +
+                    counts_size
+            }
+            NodeType::Leaf(entries, _, _) => {
+                let prefix_len = Codec::shared_prefix_len(entries);
+
+                // The prefix is written once instead of with every key
+                Codec::uncompressed_size(node) + prefix_len - entries.len() * prefix_len
+            }
+            NodeType::Unexpected => 0,
+        }
+    }
+
+    /// Number of bytes the node would take up with every key stored in full
+    pub fn uncompressed_size(node: &NodeInner) -> usize {
+        match &node.node_type {
+            NodeType::Leaf(entries, _, _) => {
+                LEAF_NODE_HEADER_SIZE + entries.iter().map(Codec::entry_size).sum::<usize>()
+            }
+            _ => Codec::encoded_size(node),
+        }
+    }
+
+    /// Length of the key prefix a leaf stores once for all of its entries
+    ///
+    /// Only string keys are compressed. They order byte by byte, so the keys sharing a prefix
+    /// sit next to each other and a split can always keep them on one side.
+    pub fn shared_prefix_len(entries: &[NodeKey]) -> usize {
+        let keys: Vec<&[u8]> = entries
+            .iter()
+            .filter_map(|entry| {
+                match entry {
+                    NodeKey::KeyValuePair(kv_pair) => Some(&kv_pair.key.data[..]),
+                    NodeKey::GuidePost(_) => None,
+                }
+            })
+            .collect();
+
+        match entries.first() {
+            Some(NodeKey::KeyValuePair(first)) if Codec::compresses(&first.key.datatype) => {
+                common_prefix_len(&keys)
+            }
+            _ => 0,
+        }
+    }
+
+    /// Whether leaves keyed on the type share key prefixes
+    pub fn compresses(key_type: &DataType) -> bool {
+        matches!(key_type, DataType::Char(_) | DataType::Varchar(_))
+    }
+
+    /// Bytes a guide post or key value pair takes up in a page, length prefixes included
+    pub fn entry_size(entry: &NodeKey) -> usize {
+        match entry {
+            NodeKey::GuidePost(key) => PTR_SIZE + key.data.len(),
+            NodeKey::KeyValuePair(kv_pair) => Codec::pair_size(kv_pair),
+        }
+    }
+
+    pub fn pair_size(kv_pair: &KeyValuePair) -> usize {
+        2 * PTR_SIZE + kv_pair.key.data.len() + kv_pair.value.data.len()
+    }
+
     pub fn encode(node: &NodeInner) -> anyhow::Result<TreePage> {
+        if Codec::encoded_size(node) > PAGE_SIZE {
+            return Err(anyhow::Error::msg("Node does not fit in a page"));
+        }
+
         let is_root = node.is_root;
         let pointer = node.pointer;
 
@@ -154,9 +376,19 @@ impl Codec {
 
         match node.node_type {
             NodeType::Internal(ref children, ref keys, _) => {
+                let mut num_children = children.len() as u32;
+
+                if let Some(counts) = &node.child_counts {
+                    if counts.len() != children.len() {
+                        return Err(anyhow::Error::msg("Child counts out of step with children"));
+                    }
+
+                    num_children |= COUNTED_NODE_FLAG;
+                }
+
                 // Header
                 cursor.seek(SeekFrom::Start(InternalNodeHeader::NumChildren.offset() as u64))?;
-                cursor.write_u32::<LittleEndian>(children.len() as u32)?;
+                cursor.write_u32::<LittleEndian>(num_children)?;
 
                 // Data Section
                 cursor.seek(SeekFrom::Start(INTERNAL_NODE_HEADER_SIZE as u64))?;
@@ -175,12 +407,16 @@ impl Codec {
                         }
                     };
 
-                    let key_len = key.data_length as u32;
+                    let key_len = key.data.len() as u32;
                     cursor.write_u32::<LittleEndian>(key_len)?;
 
                     cursor.write_all(&key.data)?;
                 }
 
+                for count in node.child_counts.iter().flatten() {
+                    cursor.write_u64::<LittleEndian>(*count)?;
+                }
+
                 // Fits the page to a fixed size of 4KB
                 assert_eq!(raw.len(), PAGE_SIZE);
                 return Ok(TreePage::new(raw.try_into().unwrap()));
@@ -198,20 +434,46 @@ impl Codec {
                     cursor.write_u32::<LittleEndian>(0)?;
                 }
 
+                cursor.seek(SeekFrom::Start(LeafNodeHeader::PrevLeafPointer.offset() as u64))?;
+                cursor.write_u32::<LittleEndian>(
+                    node.prev_pointer.unwrap_or(NULL_PREV_LEAF_POINTER)
+                )?;
+
+                let prefix_len = Codec::shared_prefix_len(entries);
+
+                cursor.seek(SeekFrom::Start(LeafNodeHeader::PrefixLength.offset() as u64))?;
+                cursor.write_u32::<LittleEndian>(prefix_len as u32)?;
+
                 // Data Section
                 cursor.seek(SeekFrom::Start(LEAF_NODE_HEADER_SIZE as u64))?;
 
+                if let Some(NodeKey::KeyValuePair(first)) = entries.first() {
+                    cursor.write_all(&first.key.data[..prefix_len])?;
+                }
+
                 for pair in entries {
                     match pair {
                         NodeKey::KeyValuePair(kv_pair) => {
                             let key = &kv_pair.key;
                             let value = &kv_pair.value;
 
-                            let key_len = key.data_length as u32;
+                            let key_len = (key.data.len() - prefix_len) as u32;
                             cursor.write_u32::<LittleEndian>(key_len)?;
-                            cursor.write_all(&key.data)?;
-
-                            let value_len = value.data_length as u32;
+                            cursor.write_all(&key.data[prefix_len..])?;
+
+                            // Overflow stubs and expiring values are told apart from inline
+                            // values by the length
+                            let mut value_len = value.data.len() as u32;
+                            let stored_type = match &value.datatype {
+                                DataType::Expiring(inner) => {
+                                    value_len |= EXPIRING_VALUE_FLAG;
+                                    inner.as_ref()
+                                }
+                                datatype => datatype,
+                            };
+                            if *stored_type == DataType::Overflow {
+                                value_len |= OVERFLOW_VALUE_FLAG;
+                            }
                             cursor.write_u32::<LittleEndian>(value_len)?;
                             cursor.write_all(&value.data)?;
                         }
@@ -232,3 +494,21 @@ impl Codec {
         }
     }
 }
+
+/// Length of the prefix every one of the keys starts with
+pub fn common_prefix_len(keys: &[&[u8]]) -> usize {
+    let Some(first) = keys.first() else {
+        return 0;
+    };
+
+    keys.iter()
+        .map(|key| {
+            first
+                .iter()
+                .zip(key.iter())
+                .take_while(|(a, b)| a == b)
+                .count()
+        })
+        .min()
+        .unwrap_or(0)
+}
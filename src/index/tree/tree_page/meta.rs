@@ -0,0 +1,167 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::io::{ Cursor, Read, Seek, SeekFrom, Write };
+
+use bincode::config;
+use byteorder::{ LittleEndian, ReadBytesExt, WriteBytesExt };
+
+use crate::index::tree::{
+    byte_box::DataType,
+    tree_node::node_type::PagePointer,
+    tree_page::{
+        codec::Codec,
+        tree_page_layout::{
+            FromByte,
+            MetaPageHeader,
+            NodeHeader,
+            ToByte,
+            META_FORMAT_VERSION,
+            META_PAGE_HEADER_SIZE,
+            META_PAGE_ID,
+            META_PAGE_TYPE,
+            PAGE_SIZE,
+        },
+    },
+};
+
+/// Everything needed to open a tree file again, kept on its meta page
+#[derive(Clone)]
+pub struct TreeMeta {
+    pub root_page_id: PagePointer,
+    pub b: usize,
+    pub fill_factor: f64,
+    pub allow_duplicates: bool,
+    pub order_statistics: bool,
+    pub copy_on_write: bool,
+
+    /// Identifies the tree's entries in a write-ahead log, see `BTreeBuilder::log_id`
+    pub log_id: u32,
+
+    /// The codec pages are written with, duplicates already folded into the key type
+    pub codec: Codec,
+}
+
+impl TreeMeta {
+    pub fn encode(&self) -> anyhow::Result<[u8; PAGE_SIZE]> {
+        let schema = bincode
+            ::encode_to_vec((&self.codec.key_type, &self.codec.value_type), config::standard())
+            .map_err(|err| anyhow::Error::msg(format!("Unable to encode tree schema: {}", err)))?;
+
+        if META_PAGE_HEADER_SIZE + schema.len() > PAGE_SIZE {
+            return Err(anyhow::Error::msg("Tree schema does not fit in the meta page"));
+        }
+
+        let mut raw = [0u8; PAGE_SIZE];
+        let mut cursor = Cursor::new(&mut raw[..]);
+
+        cursor.seek(SeekFrom::Start(NodeHeader::NodeType.offset() as u64))?;
+        cursor.write_u8(META_PAGE_TYPE)?;
+
+        cursor.seek(SeekFrom::Start(NodeHeader::Pointer.offset() as u64))?;
+        cursor.write_u32::<LittleEndian>(META_PAGE_ID)?;
+
+        cursor.seek(SeekFrom::Start(MetaPageHeader::FormatVersion.offset() as u64))?;
+        cursor.write_u32::<LittleEndian>(META_FORMAT_VERSION)?;
+
+        cursor.seek(SeekFrom::Start(MetaPageHeader::RootPointer.offset() as u64))?;
+        cursor.write_u32::<LittleEndian>(self.root_page_id)?;
+
+        cursor.seek(SeekFrom::Start(MetaPageHeader::BParameter.offset() as u64))?;
+        cursor.write_u32::<LittleEndian>(self.b as u32)?;
+
+        cursor.seek(SeekFrom::Start(MetaPageHeader::FillFactor.offset() as u64))?;
+        cursor.write_f64::<LittleEndian>(self.fill_factor)?;
+
+        cursor.seek(SeekFrom::Start(MetaPageHeader::AllowDuplicates.offset() as u64))?;
+        cursor.write_u8(self.allow_duplicates.to_byte())?;
+
+        cursor.seek(SeekFrom::Start(MetaPageHeader::OrderStatistics.offset() as u64))?;
+        cursor.write_u8(self.order_statistics.to_byte())?;
+
+        cursor.seek(SeekFrom::Start(MetaPageHeader::CopyOnWrite.offset() as u64))?;
+        cursor.write_u8(self.copy_on_write.to_byte())?;
+
+        cursor.seek(SeekFrom::Start(MetaPageHeader::LogId.offset() as u64))?;
+        cursor.write_u32::<LittleEndian>(self.log_id)?;
+
+        cursor.seek(SeekFrom::Start(MetaPageHeader::SchemaLength.offset() as u64))?;
+        cursor.write_u32::<LittleEndian>(schema.len() as u32)?;
+
+        cursor.seek(SeekFrom::Start(META_PAGE_HEADER_SIZE as u64))?;
+        cursor.write_all(&schema)?;
+
+        Ok(raw)
+    }
+
+    pub fn decode(raw: &[u8; PAGE_SIZE]) -> anyhow::Result<Self> {
+        let mut cursor = Cursor::new(&raw[..]);
+
+        cursor.seek(SeekFrom::Start(NodeHeader::NodeType.offset() as u64))?;
+        if cursor.read_u8()? != META_PAGE_TYPE {
+            return Err(anyhow::Error::msg("Expected a tree meta page"));
+        }
+
+        cursor.seek(SeekFrom::Start(MetaPageHeader::FormatVersion.offset() as u64))?;
+        let version = cursor.read_u32::<LittleEndian>()?;
+
+        if version != META_FORMAT_VERSION {
+            return Err(
+                anyhow::Error::msg(
+                    format!(
+                        "Tree file has format version {}, expected {}",
+                        version,
+                        META_FORMAT_VERSION
+                    )
+                )
+            );
+        }
+
+        cursor.seek(SeekFrom::Start(MetaPageHeader::RootPointer.offset() as u64))?;
+        let root_page_id = cursor.read_u32::<LittleEndian>()?;
+
+        cursor.seek(SeekFrom::Start(MetaPageHeader::BParameter.offset() as u64))?;
+        let b = cursor.read_u32::<LittleEndian>()? as usize;
+
+        cursor.seek(SeekFrom::Start(MetaPageHeader::FillFactor.offset() as u64))?;
+        let fill_factor = cursor.read_f64::<LittleEndian>()?;
+
+        cursor.seek(SeekFrom::Start(MetaPageHeader::AllowDuplicates.offset() as u64))?;
+        let allow_duplicates = cursor.read_u8()?.from_byte();
+
+        cursor.seek(SeekFrom::Start(MetaPageHeader::OrderStatistics.offset() as u64))?;
+        let order_statistics = cursor.read_u8()?.from_byte();
+
+        cursor.seek(SeekFrom::Start(MetaPageHeader::CopyOnWrite.offset() as u64))?;
+        let copy_on_write = cursor.read_u8()?.from_byte();
+
+        cursor.seek(SeekFrom::Start(MetaPageHeader::LogId.offset() as u64))?;
+        let log_id = cursor.read_u32::<LittleEndian>()?;
+
+        cursor.seek(SeekFrom::Start(MetaPageHeader::SchemaLength.offset() as u64))?;
+        let schema_len = cursor.read_u32::<LittleEndian>()? as usize;
+
+        if META_PAGE_HEADER_SIZE + schema_len > PAGE_SIZE {
+            return Err(anyhow::Error::msg("Tree schema overruns the meta page"));
+        }
+
+        let mut schema = vec![0u8; schema_len];
+        cursor.seek(SeekFrom::Start(META_PAGE_HEADER_SIZE as u64))?;
+        cursor.read_exact(&mut schema)?;
+
+        let ((key_type, value_type), _): ((DataType, DataType), usize) = bincode
+            ::decode_from_slice(&schema, config::standard())
+            .map_err(|err| anyhow::Error::msg(format!("Unable to decode tree schema: {}", err)))?;
+
+        Ok(Self {
+            root_page_id,
+            b,
+            fill_factor,
+            allow_duplicates,
+            order_statistics,
+            copy_on_write,
+            log_id,
+            codec: Codec { key_type, value_type },
+        })
+    }
+}
@@ -1,3 +1,5 @@
 pub mod tree_page_layout;
 pub mod page;
-pub mod codec;
\ No newline at end of file
+pub mod codec;
+pub mod overflow;
+pub mod meta;
\ No newline at end of file
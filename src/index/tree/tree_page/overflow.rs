@@ -0,0 +1,178 @@
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use std::io::{ Cursor, Read, Seek, SeekFrom, Write };
+
+use byteorder::{ LittleEndian, ReadBytesExt, WriteBytesExt };
+
+use crate::{
+    buffer::flusher::Flusher,
+    index::tree::{
+        byte_box::{ ByteBox, DataType },
+        tree_node::node_type::PagePointer,
+        tree_page::tree_page_layout::{
+            NodeHeader,
+            OverflowPageHeader,
+            NULL_OVERFLOW_POINTER,
+            OVERFLOW_PAGE_CAPACITY,
+            OVERFLOW_PAGE_HEADER_SIZE,
+            OVERFLOW_PAGE_TYPE,
+            PAGE_SIZE,
+        },
+    },
+};
+
+/// A value spread over a linked list of overflow pages
+///
+/// The leaf only stores a stub made of the first page of the chain and the length of the value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverflowChain {
+    pub(crate) first_page: PagePointer,
+    pub(crate) length: u32,
+}
+
+impl OverflowChain {
+    /// Copies the value into freshly allocated overflow pages
+    pub fn write(flusher: &Flusher, data: &[u8]) -> anyhow::Result<Self> {
+        let chunks: Vec<&[u8]> = data.chunks(OVERFLOW_PAGE_CAPACITY).collect();
+        let pages: Vec<PagePointer> = chunks
+            .iter()
+            .map(|_| flusher.new_page())
+            .collect();
+
+        for (idx, chunk) in chunks.iter().enumerate() {
+            let next = pages
+                .get(idx + 1)
+                .copied()
+                .unwrap_or(NULL_OVERFLOW_POINTER);
+
+            flusher.write_flush(OverflowChain::encode_page(pages[idx], next, chunk)?, pages[idx])?;
+        }
+
+        Ok(Self {
+            first_page: pages.first().copied().unwrap_or(NULL_OVERFLOW_POINTER),
+            length: data.len() as u32,
+        })
+    }
+
+    /// Reassembles the value from its chain
+    pub fn read(&self, flusher: &Flusher) -> anyhow::Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(self.length as usize);
+
+        for page in self.pages(flusher)? {
+            let (_, chunk) = OverflowChain::decode_page(&flusher.read_drop(page))?;
+            data.extend_from_slice(&chunk);
+        }
+
+        if data.len() != (self.length as usize) {
+            return Err(anyhow::Error::msg("Overflow chain is shorter than its value"));
+        }
+
+        Ok(data)
+    }
+
+    /// Hands every page of the chain back to the buffer pool
+    pub fn free(&self, flusher: &Flusher) -> anyhow::Result<()> {
+        for page in self.pages(flusher)? {
+            flusher.free_page(page)?;
+        }
+
+        Ok(())
+    }
+
+    /// Page ids of the chain in order
+    pub fn pages(&self, flusher: &Flusher) -> anyhow::Result<Vec<PagePointer>> {
+        let mut pages = Vec::new();
+        let mut current = self.first_page;
+
+        while current != NULL_OVERFLOW_POINTER {
+            if pages.contains(&current) {
+                return Err(anyhow::Error::msg("Overflow chain loops back on itself"));
+            }
+
+            pages.push(current);
+            current = OverflowChain::decode_page(&flusher.read_drop(current))?.0;
+        }
+
+        Ok(pages)
+    }
+
+    /// Page following the given one in its chain
+    pub(crate) fn next_page(flusher: &Flusher, page_id: PagePointer) -> anyhow::Result<PagePointer> {
+        Ok(OverflowChain::decode_page(&flusher.read_drop(page_id))?.0)
+    }
+
+    pub fn from_stub(stub: &ByteBox) -> anyhow::Result<Self> {
+        if stub.datatype != DataType::Overflow {
+            return Err(anyhow::Error::msg("Value is not stored in overflow pages"));
+        }
+
+        let mut cursor = Cursor::new(&stub.data[..]);
+
+        Ok(Self {
+            first_page: cursor.read_u32::<LittleEndian>()?,
+            length: cursor.read_u32::<LittleEndian>()?,
+        })
+    }
+
+    /// The value the leaf keeps in place of the data
+    pub fn to_stub(self) -> ByteBox {
+        let mut stub = Vec::with_capacity(8);
+        stub.extend_from_slice(&self.first_page.to_le_bytes());
+        stub.extend_from_slice(&self.length.to_le_bytes());
+
+        DataType::Overflow.to_byte_box(&stub)
+    }
+
+    fn encode_page(
+        page_id: PagePointer,
+        next: PagePointer,
+        chunk: &[u8]
+    ) -> anyhow::Result<[u8; PAGE_SIZE]> {
+        let mut raw = [0u8; PAGE_SIZE];
+        let mut cursor = Cursor::new(&mut raw[..]);
+
+        cursor.seek(SeekFrom::Start(NodeHeader::NodeType.offset() as u64))?;
+        cursor.write_u8(OVERFLOW_PAGE_TYPE)?;
+
+        cursor.seek(SeekFrom::Start(NodeHeader::Pointer.offset() as u64))?;
+        cursor.write_u32::<LittleEndian>(page_id)?;
+
+        cursor.seek(SeekFrom::Start(OverflowPageHeader::NextOverflowPointer.offset() as u64))?;
+        cursor.write_u32::<LittleEndian>(next)?;
+
+        cursor.seek(SeekFrom::Start(OverflowPageHeader::ChunkLength.offset() as u64))?;
+        cursor.write_u32::<LittleEndian>(chunk.len() as u32)?;
+
+        cursor.seek(SeekFrom::Start(OVERFLOW_PAGE_HEADER_SIZE as u64))?;
+        cursor.write_all(chunk)?;
+
+        Ok(raw)
+    }
+
+    /// Returns the next page of the chain and the bytes held by this one
+    fn decode_page(raw: &[u8; PAGE_SIZE]) -> anyhow::Result<(PagePointer, Vec<u8>)> {
+        let mut cursor = Cursor::new(&raw[..]);
+
+        cursor.seek(SeekFrom::Start(NodeHeader::NodeType.offset() as u64))?;
+        if cursor.read_u8()? != OVERFLOW_PAGE_TYPE {
+            return Err(anyhow::Error::msg("Expected an overflow page"));
+        }
+
+        cursor.seek(SeekFrom::Start(OverflowPageHeader::NextOverflowPointer.offset() as u64))?;
+        let next = cursor.read_u32::<LittleEndian>()?;
+
+        cursor.seek(SeekFrom::Start(OverflowPageHeader::ChunkLength.offset() as u64))?;
+        let chunk_len = cursor.read_u32::<LittleEndian>()? as usize;
+
+        if chunk_len > OVERFLOW_PAGE_CAPACITY {
+            return Err(anyhow::Error::msg("Overflow page holds more than a page"));
+        }
+
+        let mut chunk = vec![0u8; chunk_len];
+        cursor.seek(SeekFrom::Start(OVERFLOW_PAGE_HEADER_SIZE as u64))?;
+        cursor.read_exact(&mut chunk)?;
+
+        Ok((next, chunk))
+    }
+}
@@ -51,6 +51,8 @@ pub const COMMON_NODE_HEADER_SIZE: usize = NODE_TYPE_SIZE + IS_ROOT_SIZE + POINT
 pub enum LeafNodeHeader {
     NumPairs,
     NextLeafPointer,
+    PrevLeafPointer,
+    PrefixLength,
 }
 
 impl LeafNodeHeader {
@@ -58,6 +60,8 @@ impl LeafNodeHeader {
         match *self {
             LeafNodeHeader::NumPairs => LEAF_NODE_NUM_PAIRS_SIZE,
             LeafNodeHeader::NextLeafPointer => NEXT_LEAF_POINTER_SIZE,
+            LeafNodeHeader::PrevLeafPointer => PREV_LEAF_POINTER_SIZE,
+            LeafNodeHeader::PrefixLength => KEY_PREFIX_LENGTH_SIZE,
         }
     }
 
@@ -65,6 +69,8 @@ impl LeafNodeHeader {
         match *self {
             LeafNodeHeader::NumPairs => LEAF_NODE_NUM_PAIRS_OFFSET,
             LeafNodeHeader::NextLeafPointer => NEXT_LEAF_POINTER_OFFSET,
+            LeafNodeHeader::PrevLeafPointer => PREV_LEAF_POINTER_OFFSET,
+            LeafNodeHeader::PrefixLength => KEY_PREFIX_LENGTH_OFFSET,
         }
     }
 }
@@ -76,8 +82,28 @@ pub const LEAF_NODE_NUM_PAIRS_SIZE: usize = PTR_SIZE;
 pub const NEXT_LEAF_POINTER_OFFSET: usize = COMMON_NODE_HEADER_SIZE + LEAF_NODE_NUM_PAIRS_SIZE;
 pub const NEXT_LEAF_POINTER_SIZE: usize = PTR_SIZE;
 
+pub const PREV_LEAF_POINTER_OFFSET: usize = NEXT_LEAF_POINTER_OFFSET + NEXT_LEAF_POINTER_SIZE;
+pub const PREV_LEAF_POINTER_SIZE: usize = PTR_SIZE;
+
+/// Marks a leaf without a left neighbour.
+///
+/// Page 0 of a tree in a file of its own is the meta page (format version 4), so no leaf links
+/// to it, but trees sharing a file keep no meta page and their first leaf can still be page 0.
+/// Unlike the next pointer, the previous pointer therefore uses a value no page can have.
+pub const NULL_PREV_LEAF_POINTER: u32 = u32::MAX;
+
+/// Length of the key prefix shared by every entry of the leaf.
+///
+/// The prefix bytes open the data section and each entry only stores the rest of its key.
+pub const KEY_PREFIX_LENGTH_OFFSET: usize = PREV_LEAF_POINTER_OFFSET + PREV_LEAF_POINTER_SIZE;
+pub const KEY_PREFIX_LENGTH_SIZE: usize = PTR_SIZE;
+
 pub const LEAF_NODE_HEADER_SIZE: usize =
-    COMMON_NODE_HEADER_SIZE + LEAF_NODE_NUM_PAIRS_SIZE + NEXT_LEAF_POINTER_SIZE;
+    COMMON_NODE_HEADER_SIZE +
+    LEAF_NODE_NUM_PAIRS_SIZE +
+    NEXT_LEAF_POINTER_SIZE +
+    PREV_LEAF_POINTER_SIZE +
+    KEY_PREFIX_LENGTH_SIZE;
 
 #[derive(Debug)]
 pub enum InternalNodeHeader {
@@ -105,6 +131,163 @@ pub const INTERNAL_NODE_NUM_CHILDREN_SIZE: usize = PTR_SIZE;
 pub const INTERNAL_NODE_HEADER_SIZE: usize =
     COMMON_NODE_HEADER_SIZE + INTERNAL_NODE_NUM_CHILDREN_SIZE;
 
+/// Set on an internal node's child count when the entry counts of its children follow the keys
+pub const COUNTED_NODE_FLAG: u32 = 1 << 31;
+
+/// Bytes of the entry count kept for every child by trees with order statistics
+pub const CHILD_COUNT_SIZE: usize = size_of::<u64>();
+
+#[derive(Debug)]
+pub enum OverflowPageHeader {
+    NextOverflowPointer,
+    ChunkLength,
+}
+
+impl OverflowPageHeader {
+    pub fn size(&self) -> usize {
+        match *self {
+            OverflowPageHeader::NextOverflowPointer => NEXT_OVERFLOW_POINTER_SIZE,
+            OverflowPageHeader::ChunkLength => OVERFLOW_CHUNK_LENGTH_SIZE,
+        }
+    }
+
+    pub fn offset(&self) -> usize {
+        match *self {
+            OverflowPageHeader::NextOverflowPointer => NEXT_OVERFLOW_POINTER_OFFSET,
+            OverflowPageHeader::ChunkLength => OVERFLOW_CHUNK_LENGTH_OFFSET,
+        }
+    }
+}
+
+// Defining the constants specific to the overflow page header
+pub const NEXT_OVERFLOW_POINTER_OFFSET: usize = COMMON_NODE_HEADER_SIZE;
+pub const NEXT_OVERFLOW_POINTER_SIZE: usize = PTR_SIZE;
+
+pub const OVERFLOW_CHUNK_LENGTH_OFFSET: usize =
+    NEXT_OVERFLOW_POINTER_OFFSET + NEXT_OVERFLOW_POINTER_SIZE;
+pub const OVERFLOW_CHUNK_LENGTH_SIZE: usize = PTR_SIZE;
+
+pub const OVERFLOW_PAGE_HEADER_SIZE: usize =
+    COMMON_NODE_HEADER_SIZE + NEXT_OVERFLOW_POINTER_SIZE + OVERFLOW_CHUNK_LENGTH_SIZE;
+
+/// Bytes of a value each overflow page holds
+pub const OVERFLOW_PAGE_CAPACITY: usize = PAGE_SIZE - OVERFLOW_PAGE_HEADER_SIZE;
+
+/// Node type byte of overflow pages, after the internal (0x01) and leaf (0x02) ones
+pub const OVERFLOW_PAGE_TYPE: u8 = 0x04;
+
+/// Marks the last page of an overflow chain
+pub const NULL_OVERFLOW_POINTER: u32 = u32::MAX;
+
+#[derive(Debug)]
+pub enum MetaPageHeader {
+    FormatVersion,
+    RootPointer,
+    BParameter,
+    FillFactor,
+    AllowDuplicates,
+    OrderStatistics,
+    CopyOnWrite,
+    LogId,
+    SchemaLength,
+}
+
+impl MetaPageHeader {
+    pub fn size(&self) -> usize {
+        match *self {
+            MetaPageHeader::FormatVersion => META_FORMAT_VERSION_SIZE,
+            MetaPageHeader::RootPointer => META_ROOT_POINTER_SIZE,
+            MetaPageHeader::BParameter => META_B_PARAMETER_SIZE,
+            MetaPageHeader::FillFactor => META_FILL_FACTOR_SIZE,
+            MetaPageHeader::AllowDuplicates => META_ALLOW_DUPLICATES_SIZE,
+            MetaPageHeader::OrderStatistics => META_ORDER_STATISTICS_SIZE,
+            MetaPageHeader::CopyOnWrite => META_COPY_ON_WRITE_SIZE,
+            MetaPageHeader::LogId => META_LOG_ID_SIZE,
+            MetaPageHeader::SchemaLength => META_SCHEMA_LENGTH_SIZE,
+        }
+    }
+
+    pub fn offset(&self) -> usize {
+        match *self {
+            MetaPageHeader::FormatVersion => META_FORMAT_VERSION_OFFSET,
+            MetaPageHeader::RootPointer => META_ROOT_POINTER_OFFSET,
+            MetaPageHeader::BParameter => META_B_PARAMETER_OFFSET,
+            MetaPageHeader::FillFactor => META_FILL_FACTOR_OFFSET,
+            MetaPageHeader::AllowDuplicates => META_ALLOW_DUPLICATES_OFFSET,
+            MetaPageHeader::OrderStatistics => META_ORDER_STATISTICS_OFFSET,
+            MetaPageHeader::CopyOnWrite => META_COPY_ON_WRITE_OFFSET,
+            MetaPageHeader::LogId => META_LOG_ID_OFFSET,
+            MetaPageHeader::SchemaLength => META_SCHEMA_LENGTH_OFFSET,
+        }
+    }
+}
+
+// Defining the constants specific to the meta page header
+pub const META_FORMAT_VERSION_OFFSET: usize = COMMON_NODE_HEADER_SIZE;
+pub const META_FORMAT_VERSION_SIZE: usize = PTR_SIZE;
+
+pub const META_ROOT_POINTER_OFFSET: usize = META_FORMAT_VERSION_OFFSET + META_FORMAT_VERSION_SIZE;
+pub const META_ROOT_POINTER_SIZE: usize = PTR_SIZE;
+
+pub const META_B_PARAMETER_OFFSET: usize = META_ROOT_POINTER_OFFSET + META_ROOT_POINTER_SIZE;
+pub const META_B_PARAMETER_SIZE: usize = PTR_SIZE;
+
+pub const META_FILL_FACTOR_OFFSET: usize = META_B_PARAMETER_OFFSET + META_B_PARAMETER_SIZE;
+pub const META_FILL_FACTOR_SIZE: usize = size_of::<f64>();
+
+pub const META_ALLOW_DUPLICATES_OFFSET: usize = META_FILL_FACTOR_OFFSET + META_FILL_FACTOR_SIZE;
+pub const META_ALLOW_DUPLICATES_SIZE: usize = 1;
+
+pub const META_ORDER_STATISTICS_OFFSET: usize =
+    META_ALLOW_DUPLICATES_OFFSET + META_ALLOW_DUPLICATES_SIZE;
+pub const META_ORDER_STATISTICS_SIZE: usize = 1;
+
+pub const META_COPY_ON_WRITE_OFFSET: usize =
+    META_ORDER_STATISTICS_OFFSET + META_ORDER_STATISTICS_SIZE;
+pub const META_COPY_ON_WRITE_SIZE: usize = 1;
+
+pub const META_LOG_ID_OFFSET: usize = META_COPY_ON_WRITE_OFFSET + META_COPY_ON_WRITE_SIZE;
+pub const META_LOG_ID_SIZE: usize = PTR_SIZE;
+
+pub const META_SCHEMA_LENGTH_OFFSET: usize = META_LOG_ID_OFFSET + META_LOG_ID_SIZE;
+pub const META_SCHEMA_LENGTH_SIZE: usize = PTR_SIZE;
+
+/// The encoded key and value types follow the header
+pub const META_PAGE_HEADER_SIZE: usize = META_SCHEMA_LENGTH_OFFSET + META_SCHEMA_LENGTH_SIZE;
+
+/// Node type byte of the meta page
+pub const META_PAGE_TYPE: u8 = 0x05;
+
+/// Page of a tree file holding its meta page, the first one allocated
+pub const META_PAGE_ID: u32 = 0;
+
+/// Bumped whenever the layout of tree pages changes
+pub const META_FORMAT_VERSION: u32 = 4;
+
+/// Values longer than this are moved out of the leaf into an overflow chain
+pub const OVERFLOW_THRESHOLD: usize = PAGE_SIZE / 4;
+
+/// Set on a leaf's value length when the value bytes are an overflow stub
+pub const OVERFLOW_VALUE_FLAG: u32 = 1 << 31;
+
+/// Set on a leaf's value length when the value bytes start with an expiry time
+pub const EXPIRING_VALUE_FLAG: u32 = 1 << 30;
+
+/// Bytes of the expiry time ahead of an expiring value, see `ByteBox::expiring`
+pub const EXPIRY_SIZE: usize = 8;
+
+/// Longest key a tree accepts
+///
+/// Bounding entries keeps every split able to produce two halves that fit a page.
+pub const MAX_KEY_SIZE: usize = PAGE_SIZE / 8;
+
+/// Most bytes one leaf entry can take up: a key, an inline value with its expiry and their lengths
+pub const MAX_LEAF_ENTRY_SIZE: usize = 2 * PTR_SIZE + MAX_KEY_SIZE + OVERFLOW_THRESHOLD +
+    EXPIRY_SIZE;
+
+/// Most bytes one internal node key can take up along with the child pointer it adds
+pub const MAX_INTERNAL_ENTRY_SIZE: usize = 2 * PTR_SIZE + MAX_KEY_SIZE;
+
 pub trait FromByte {
     fn from_byte(&self) -> bool;
 }
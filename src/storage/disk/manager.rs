@@ -46,7 +46,7 @@ struct FileMetadata {
 pub struct Manager {
     // Mapping of inode numbers to file paths
     // Might be replaced by a table within the system catalogue
-    // file_map: HashMap<u64, PathBuf>,
+    file_map: HashMap<u64, PathBuf>,
     // Pool of open file descriptors
     file_descriptors: FdPool, // File ID → File Descriptor Pool
 
@@ -71,7 +71,7 @@ pub struct Manager {
 impl Manager {
     pub fn new(log_io: File, log_file_path: PathBuf) -> Self {
         Manager {
-            // file_map: HashMap::new(),
+            file_map: HashMap::new(),
             file_descriptors: FdPool::new(NUMBER_OF_ENTRIES, K_TH_VALUE),
             files: HashMap::new(),
             flush_logs: Arc::new(AtomicBool::new(false)),
@@ -91,15 +91,26 @@ impl Manager {
             free_slots: VecDeque::new(),
         });
 
-        let pages_len = file_meta.pages.len();
-
         // Try to pop a free slot
         if let Some((page_id, offset)) = file_meta.free_slots.pop_front() {
             file_meta.pages.replace(page_id, Some(offset));
-            return (pages_len as u32, offset);
+            return (page_id, offset);
         }
 
         // No free slots, allocate a new page
+        self.append_page(file_id)
+    }
+
+    /// Allocates a page past the last one of the db file, leaving its free slots for later
+    ///
+    /// Pages appended one after another are contiguous on disk.
+    pub fn append_page(&mut self, file_id: u64) -> (u32, u64) {
+        let file_meta = self.files.entry(file_id).or_insert(FileMetadata {
+            pages: LinkedHashMap::new(),
+            free_slots: VecDeque::new(),
+        });
+
+        let pages_len = file_meta.pages.len();
         let offset = (pages_len * PAGE_SIZE) as u64;
         file_meta.pages.insert(pages_len as u32, Some(offset));
 
@@ -228,6 +239,34 @@ impl Manager {
         Ok(())
     }
 
+    /// Ids of the pages currently allocated in the db file
+    pub fn allocated_pages(&self, file_id: u64) -> Vec<u32> {
+        self.files.get(&file_id).map_or(Vec::new(), |file_meta| {
+            file_meta.pages
+                .iter()
+                .filter(|(_, offset)| offset.is_some())
+                .map(|(page_id, _)| *page_id)
+                .collect()
+        })
+    }
+
+    pub fn file_path(&self, file_id: u64) -> Option<PathBuf> {
+        self.file_map.get(&file_id).cloned()
+    }
+
+    /// Whether the page is currently allocated in the db file
+    pub fn is_allocated(&self, file_id: u64, page_id: u32) -> bool {
+        self.files
+            .get(&file_id)
+            .and_then(|file_meta| file_meta.pages.get(&page_id))
+            .is_some_and(|offset| offset.is_some())
+    }
+
+    /// Number of deallocated pages waiting to be reused in the db file
+    pub fn free_page_count(&self, file_id: u64) -> usize {
+        self.files.get(&file_id).map_or(0, |file_meta| file_meta.free_slots.len())
+    }
+
     pub fn delete_page(&mut self, file_id: u64, page_id: u32) -> Result<(), String> {
         let file_meta = self.files
             .get_mut(&file_id)
@@ -286,20 +325,35 @@ impl Manager {
     pub fn create_db_file(&mut self) -> Result<(u64, PathBuf), i8> {
         std::fs::create_dir_all("geodeData/base").unwrap();
 
-        let oid = self.mono_id.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-        let path_string = format!("geodeData/base/{}.bin", oid);
+        // Several managers may share the base directory, so an existing file is never
+        // reopened here. Reusing it would hand two trees the same inode.
+        let (path_string, new_file) = loop {
+            let oid = self.mono_id.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let path_string = format!("geodeData/base/{}.bin", oid);
+
+            match
+                OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create_new(true)
+                    .custom_flags(O_DIRECT)
+                    .open(&path_string)
+            {
+                Ok(file) => {
+                    break (path_string, file);
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                    continue;
+                }
+                Err(_) => {
+                    return Err(-1);
+                }
+            }
+        };
         let path = Path::new(&path_string);
 
-        let new_file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .custom_flags(O_DIRECT)
-            .open(&path)
-            .map_err(|_| -1)?;
-
         let file_ino = self.file_descriptors.set(new_file).0.ok_or(-1)?;
-        // self.file_map.insert(file_ino, path.to_path_buf());
+        self.file_map.insert(file_ino, path.to_path_buf());
         self.files.insert(file_ino, FileMetadata {
             pages: LinkedHashMap::new(),
             free_slots: VecDeque::new(),
@@ -328,10 +382,18 @@ impl Manager {
             .map_err(|_| -1)
             .unwrap();
 
+        // Pages are laid out back to back, so every page slot of the file is taken to be in use
+        let num_pages = (new_file.metadata()?.len() as usize) / PAGE_SIZE;
+
+        let mut pages = LinkedHashMap::new();
+        for page_id in 0..num_pages {
+            pages.insert(page_id as u32, Some((page_id * PAGE_SIZE) as u64));
+        }
+
         let file_ino = self.file_descriptors.set(new_file).0.ok_or(-1).unwrap();
-        // self.file_map.insert(file_ino, path.to_path_buf());
+        self.file_map.insert(file_ino, path.to_path_buf());
         self.files.insert(file_ino, FileMetadata {
-            pages: LinkedHashMap::new(),
+            pages,
             free_slots: VecDeque::new(),
         });
 
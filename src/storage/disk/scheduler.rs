@@ -108,7 +108,8 @@ impl DiskScheduler {
         let rx = self.shared_queue.1.take().unwrap();
         let manager = Arc::clone(&self.manager);
 
-        std::thread::spawn(move || loop {
+        // The worker exits once the scheduler, and with it the sender, is dropped
+        std::thread::spawn(move || {
             while let Ok(request) = rx.recv() {
                 let mut manager_guard = manager.lock().unwrap();
 
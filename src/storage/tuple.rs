@@ -30,6 +30,85 @@ impl ByteBox {
             data_length: tuple_len as u8,
         }
     }
+
+    /// Picks columns out of an encoded tuple without decoding the others
+    ///
+    /// The result is a tuple of the columns at `columns`, in the order they are listed.
+    pub fn project_tuple(
+        schema: &Schema,
+        raw: &[u8],
+        columns: &[usize]
+    ) -> anyhow::Result<ByteBox> {
+        let mut entry = raw;
+        let mut fields: Vec<&[u8]> = Vec::with_capacity(schema.columns.len());
+
+        if entry.remaining() < 4 {
+            return Err(anyhow::Error::msg("Truncated tuple"));
+        }
+        entry.get_u32_le();
+
+        // Every column up to the last one asked for is stepped over, names and all
+        let last = columns.iter().copied().max().map_or(0, |idx| idx + 1);
+
+        for column in schema.columns.iter().take(last) {
+            let start = entry;
+
+            let name_len = read_length(&mut entry)?;
+            skip(&mut entry, name_len)?;
+
+            let data_len = match column.datatype.fixed_size() {
+                Some(size) => size,
+                None => read_length(&mut entry)?,
+            };
+            skip(&mut entry, data_len)?;
+
+            fields.push(&start[..start.len() - entry.len()]);
+        }
+
+        let mut data: Vec<u8> = Vec::new();
+        let mut projected = Vec::with_capacity(columns.len());
+
+        for idx in columns {
+            let (field, column) = fields
+                .get(*idx)
+                .zip(schema.columns.get(*idx))
+                .ok_or(anyhow::Error::msg("No such column"))?;
+
+            data.extend_from_slice(field);
+            projected.push(column.clone());
+        }
+
+        let mut tuple_raw = Vec::with_capacity(data.len() + 4);
+        tuple_raw.put_u32_le(data.len() as u32);
+        tuple_raw.append(&mut data);
+
+        let tuple_len = tuple_raw.len();
+
+        Ok(ByteBox {
+            data: Bytes::from(tuple_raw),
+            datatype: DataType::Tuple(Schema { columns: projected }),
+            data_size: tuple_len,
+            data_length: tuple_len as u8,
+        })
+    }
+}
+
+/// Reads a length prefix of a tuple field
+fn read_length(entry: &mut &[u8]) -> anyhow::Result<usize> {
+    if entry.remaining() < 4 {
+        return Err(anyhow::Error::msg("Truncated tuple"));
+    }
+
+    Ok(entry.get_u32_le() as usize)
+}
+
+fn skip(entry: &mut &[u8], len: usize) -> anyhow::Result<()> {
+    if entry.remaining() < len {
+        return Err(anyhow::Error::msg("Truncated tuple"));
+    }
+
+    entry.advance(len);
+    Ok(())
 }
 
 impl Tuple {
@@ -178,6 +257,9 @@ pub fn to_flat_schema(tuple: &Tuple) -> Schema {
                     DataType::Decimal => {
                         schema.add_decimal(name);
                     }
+                    DataType::Int => {
+                        schema.add_int(name);
+                    }
                     DataType::SmallInt => {
                         schema.add_small_int(name);
                     }
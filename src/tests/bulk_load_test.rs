@@ -0,0 +1,137 @@
+#[cfg(test)]
+pub mod test {
+    use std::cell::Cell;
+
+    use crate::index::tree::{
+        byte_box::{ ByteBox, DataType },
+        db::btree_obj::BTreeBuilder,
+        index_types::{ KeyValuePair, NodeKey },
+        tree_page::codec::Codec,
+    };
+
+    fn builder() -> BTreeBuilder {
+        let mut builder = BTreeBuilder::new();
+        builder.b_parameter(3).tree_schema(Codec {
+            key_type: DataType::BigInt,
+            value_type: DataType::Varchar(15),
+        });
+
+        builder
+    }
+
+    fn entry(key: i64) -> KeyValuePair {
+        KeyValuePair {
+            key: ByteBox::big_int(key),
+            value: ByteBox::varchar(&format!("value {}", key), 15),
+        }
+    }
+
+    #[test]
+    fn bulk_load_scans_in_order() {
+        let entries: Vec<KeyValuePair> = (0..500).map(|key| entry(key * 2)).collect();
+
+        let tree = builder().bulk_load(entries.clone()).unwrap();
+
+        let scanned: Vec<KeyValuePair> = tree.scan(..).collect();
+        assert_eq!(scanned, entries);
+
+        for key in [0, 2, 498, 998] {
+            let found = tree.search(NodeKey::GuidePost(ByteBox::big_int(key))).unwrap();
+            assert_eq!(found, entry(key));
+        }
+
+        // The loaded tree accepts regular writes afterwards
+        for key in [1, 499, 999] {
+            tree.insert(entry(key)).unwrap();
+        }
+
+        tree.delete(NodeKey::GuidePost(ByteBox::big_int(0))).unwrap();
+
+        let keys: Vec<ByteBox> = tree
+            .scan(..)
+            .map(|kv| kv.key)
+            .collect();
+
+        assert_eq!(keys.len(), 502);
+        assert_eq!(keys[0], ByteBox::big_int(1));
+        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn bulk_load_small_inputs() {
+        let tree = builder().bulk_load(vec![]).unwrap();
+        assert_eq!(tree.scan(..).count(), 0);
+
+        let tree = builder().bulk_load(vec![entry(7)]).unwrap();
+        assert_eq!(tree.scan(..).collect::<Vec<_>>(), vec![entry(7)]);
+    }
+
+    #[test]
+    fn bulk_load_rejects_bad_input() {
+        let unsorted = vec![entry(1), entry(3), entry(2)];
+        let err = builder().bulk_load(unsorted).err().unwrap();
+        assert!(err.to_string().contains("not sorted"));
+
+        let duplicated = vec![entry(1), entry(2), entry(2)];
+        let err = builder().bulk_load(duplicated).err().unwrap();
+        assert!(err.to_string().contains("duplicate"));
+
+        let mistyped = vec![KeyValuePair {
+            key: ByteBox::small_int(1),
+            value: ByteBox::varchar("value", 15),
+        }];
+        assert!(builder().bulk_load(mistyped).is_err());
+    }
+
+    #[test]
+    fn bulk_load_stops_reading_at_the_first_bad_entry() {
+        // Input is checked as it is read, so an endless stream after the bad entry is never drained
+        let endless = [entry(5), entry(4)].into_iter().chain((6..).map(entry));
+
+        let err = builder().bulk_load(endless).err().unwrap();
+        assert!(err.to_string().contains("not sorted"));
+    }
+
+    #[test]
+    fn bulk_load_writes_leaves_while_reading() {
+        let builder = builder();
+        let codec = builder.tree_codec().unwrap();
+        let (flusher, _) = BTreeBuilder::new_flusher();
+
+        // Leaves are on disk long before the last entry is read
+        let writes_midway = Cell::new(0);
+        let entries = (0..3000).map(|key| {
+            if key == 1500 {
+                writes_midway.set(flusher.write_count());
+            }
+            entry(key)
+        });
+
+        let level = builder.load_leaves(&flusher, &codec, entries).unwrap();
+
+        assert!(writes_midway.get() > (level.len() as u64) / 3);
+        assert_eq!(level.iter().map(|(_, _, count)| count).sum::<u64>(), 3000);
+    }
+
+    #[test]
+    fn bulk_load_frees_overflow_pages_of_a_rejected_load() {
+        let mut builder = BTreeBuilder::new();
+        builder.tree_schema(Codec {
+            key_type: DataType::BigInt,
+            value_type: DataType::Varchar(6000),
+        });
+        let codec = builder.tree_codec().unwrap();
+        let (flusher, _) = BTreeBuilder::new_flusher();
+
+        let large = |key: i64| KeyValuePair {
+            key: ByteBox::big_int(key),
+            value: ByteBox::varchar(&"v".repeat(5000), 6000),
+        };
+        let entries = (0..20).map(large).chain([large(3)]);
+
+        assert!(builder.load_leaves(&flusher, &codec, entries).is_err());
+
+        // Each value took two overflow pages
+        assert_eq!(flusher.free_page_count(), 40);
+    }
+}
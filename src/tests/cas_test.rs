@@ -0,0 +1,128 @@
+#[cfg(test)]
+pub mod test {
+    use crate::index::tree::{
+        byte_box::{ ByteBox, DataType },
+        db::{ btree_cas::CasOutcome, btree_obj::{ BPTree, BTreeBuilder } },
+        index_types::{ KeyValuePair, NodeKey },
+        tree_page::codec::Codec,
+    };
+
+    fn tree(b: usize, copy_on_write: bool) -> BPTree {
+        let mut builder = BTreeBuilder::new();
+        builder.b_parameter(b).copy_on_write(copy_on_write).tree_schema(Codec {
+            key_type: DataType::BigInt,
+            value_type: DataType::Varchar(6000),
+        });
+
+        builder.build().unwrap()
+    }
+
+    fn value(text: &str) -> ByteBox {
+        ByteBox::varchar(text, 6000)
+    }
+
+    #[test]
+    fn counters_move_only_from_the_value_they_read() {
+        for (b, copy_on_write) in [(0, false), (2, false), (2, true)] {
+            let tree = tree(b, copy_on_write);
+
+            for key in 0..50 {
+                let entry = KeyValuePair { key: ByteBox::big_int(key), value: value("0") };
+                assert_eq!(tree.put_if_absent(entry).unwrap(), CasOutcome::Applied);
+            }
+
+            // Every counter is bumped a few times, each bump reading the value it replaces
+            for round in 0..3 {
+                for key in 0..50 {
+                    let key = ByteBox::big_int(key);
+                    let current = tree.search(NodeKey::GuidePost(key.clone())).unwrap().value;
+                    let next = value(&(round + 1).to_string());
+
+                    let outcome = tree.compare_and_set(key, Some(current), Some(next)).unwrap();
+                    assert!(outcome.is_applied());
+                }
+            }
+
+            // A stale read loses and learns the value it lost to
+            let key = ByteBox::big_int(7);
+            let stale = tree.compare_and_set(key, Some(value("2")), Some(value("9"))).unwrap();
+            assert_eq!(stale, CasOutcome::Mismatch { current: Some(value("3")) });
+
+            let entry = KeyValuePair { key: ByteBox::big_int(7), value: value("x") };
+            assert_eq!(
+                tree.put_if_absent(entry).unwrap(),
+                CasOutcome::Mismatch { current: Some(value("3")) }
+            );
+
+            assert_eq!(tree.verify(), vec![], "b = {}", b);
+            assert!(tree.scan(..).all(|entry| entry.value == value("3")));
+        }
+    }
+
+    #[test]
+    fn values_grow_shrink_and_go_away() {
+        for (b, copy_on_write) in [(0, false), (3, false), (0, true)] {
+            let tree = tree(b, copy_on_write);
+
+            for key in 0..200 {
+                let entry = KeyValuePair { key: ByteBox::big_int(key), value: value("short") };
+                tree.put_if_absent(entry).unwrap();
+            }
+
+            // Long values split leaves and move to overflow pages
+            let long = value(&"l".repeat(900));
+            for key in (0..200).step_by(2) {
+                let key = ByteBox::big_int(key);
+                let outcome = tree.compare_and_set(key, Some(value("short")), Some(long.clone()));
+                assert!(outcome.unwrap().is_applied());
+            }
+            assert_eq!(tree.verify(), vec![], "b = {}", b);
+
+            // Shrinking them again and deleting entries leaves leaves to rebalance
+            for key in (0..200).step_by(2) {
+                let key = ByteBox::big_int(key);
+                let outcome = tree.compare_and_set(key, Some(long.clone()), Some(value("s")));
+                assert!(outcome.unwrap().is_applied());
+            }
+            for key in (1..200).step_by(2) {
+                let key = ByteBox::big_int(key);
+                let outcome = tree.compare_and_set(key, Some(value("short")), None);
+                assert!(outcome.unwrap().is_applied());
+            }
+            assert_eq!(tree.verify(), vec![], "b = {}", b);
+
+            // Deleting an absent key only matches an absent expectation
+            let absent = ByteBox::big_int(1);
+            assert!(tree.compare_and_set(absent.clone(), None, None).unwrap().is_applied());
+            assert_eq!(
+                tree.compare_and_set(absent, Some(value("short")), None).unwrap(),
+                CasOutcome::Mismatch { current: None }
+            );
+
+            let keys: Vec<ByteBox> = tree
+                .scan(..)
+                .map(|entry| entry.key)
+                .collect();
+            let expected: Vec<ByteBox> = (0..200).step_by(2).map(ByteBox::big_int).collect();
+            assert_eq!(keys, expected);
+        }
+    }
+
+    #[test]
+    fn conditional_writes_check_the_schema() {
+        let tree = tree(2, false);
+
+        assert!(tree.compare_and_set(ByteBox::big_int(1), None, Some(ByteBox::int(1))).is_err());
+        assert_eq!(tree.scan(..).count(), 0);
+
+        let mut builder = BTreeBuilder::new();
+        builder.allow_duplicates(true).tree_schema(Codec {
+            key_type: DataType::BigInt,
+            value_type: DataType::Int,
+        });
+        let duplicates = builder.build().unwrap();
+
+        let entry = KeyValuePair { key: ByteBox::big_int(1), value: ByteBox::int(1) };
+        assert!(duplicates.put_if_absent(entry).is_err());
+    }
+}
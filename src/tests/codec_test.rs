@@ -52,6 +52,40 @@ pub mod test {
         assert_eq!(node, leaf_node);
     }
 
+    #[test]
+    fn char_values_have_their_declared_type() {
+        // Built values compare equal to the ones decoded from a page of the same schema
+        let built = ByteBox::char("abc", 8);
+        assert_eq!(built.datatype, DataType::Char(8));
+        assert_eq!(built, DataType::Char(8).to_byte_box(b"abc"));
+
+        let codec = Codec { key_type: DataType::Char(8), value_type: DataType::SmallInt };
+        let leaf_node = NodeInner::new(
+            NodeType::Leaf(
+                vec![
+                    NodeKey::KeyValuePair(KeyValuePair {
+                        key: built.clone(),
+                        value: ByteBox::small_int(1),
+                    })
+                ],
+                u32::default(),
+                None
+            ),
+            false,
+            u32::default(),
+            None
+        );
+
+        let node = codec.decode(&Codec::encode(&leaf_node).unwrap()).unwrap();
+        assert_eq!(node, leaf_node);
+    }
+
+    #[test]
+    #[should_panic(expected = "String Upperbound crossed")]
+    fn char_values_longer_than_declared_are_rejected() {
+        ByteBox::char("too long", 4);
+    }
+
     fn get_kv_vec() -> Vec<NodeKey> {
         vec![
             NodeKey::KeyValuePair(KeyValuePair {
@@ -0,0 +1,174 @@
+#[cfg(test)]
+pub mod test {
+    use crate::{
+        index::tree::{
+            byte_box::{ ByteBox, DataType },
+            db::btree_obj::{ BPTree, BTreeBuilder },
+            index_types::{ KeyValuePair, NodeKey },
+            tree_page::codec::Codec,
+        },
+        storage::tuple::Tuple,
+    };
+
+    fn tenant_key(tenant_id: i32, created_at: i64) -> ByteBox {
+        ByteBox::composite(&[ByteBox::int(tenant_id), ByteBox::big_int(created_at)]).unwrap()
+    }
+
+    fn tenant_tree() -> BPTree {
+        BTreeBuilder::new()
+            .b_parameter(2)
+            .tree_schema(Codec {
+                key_type: DataType::Composite(vec![DataType::Int, DataType::BigInt]),
+                value_type: DataType::BigInt,
+            })
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn composite_keys_order_by_component() {
+        let key = ByteBox::composite(&[ByteBox::varchar("tenant", 15), ByteBox::small_int(-4)])
+            .unwrap();
+        assert_eq!(key.components().unwrap(), vec![
+            ByteBox::varchar("tenant", 15),
+            ByteBox::small_int(-4),
+        ]);
+
+        // Components compare as values, not as raw little endian bytes
+        assert!(tenant_key(1, 256) > tenant_key(1, 2));
+        assert!(tenant_key(-1, 500) < tenant_key(0, -500));
+        assert!(tenant_key(2, i64::MIN) > tenant_key(1, i64::MAX));
+
+        // A key made of leading components sorts before every key extending it
+        let tenant = ByteBox::composite(&[ByteBox::int(1)]).unwrap();
+        assert!(tenant < tenant_key(1, i64::MIN));
+        assert!(tenant > tenant_key(0, i64::MAX));
+    }
+
+    #[test]
+    fn composite_keys_in_tree() {
+        let tree = tenant_tree();
+
+        let mut rows: Vec<(i32, i64)> = vec![];
+        for idx in 0..120i64 {
+            rows.push((((idx * 7) % 5) as i32, (idx * 37) % 120 - 60));
+        }
+
+        for (tenant_id, created_at) in rows.iter() {
+            tree.insert(KeyValuePair {
+                key: tenant_key(*tenant_id, *created_at),
+                value: ByteBox::big_int(*created_at),
+            }).unwrap();
+        }
+
+        rows.sort();
+
+        let scanned: Vec<ByteBox> = tree
+            .scan(..)
+            .map(|kv| kv.key)
+            .collect();
+        let expected: Vec<ByteBox> = rows
+            .iter()
+            .map(|(tenant_id, created_at)| tenant_key(*tenant_id, *created_at))
+            .collect();
+        assert_eq!(scanned, expected);
+
+        for (tenant_id, created_at) in rows.iter() {
+            let found = tree.search(NodeKey::GuidePost(tenant_key(*tenant_id, *created_at))).unwrap();
+            assert_eq!(found.value, ByteBox::big_int(*created_at));
+        }
+
+        // All rows of one tenant
+        let tenant_rows: Vec<ByteBox> = tree
+            .scan_composite_prefix(&[ByteBox::int(3)])
+            .unwrap()
+            .map(|kv| kv.key)
+            .collect();
+        let expected: Vec<ByteBox> = rows
+            .iter()
+            .filter(|(tenant_id, _)| *tenant_id == 3)
+            .map(|(tenant_id, created_at)| tenant_key(*tenant_id, *created_at))
+            .collect();
+        assert_eq!(tenant_rows.len(), 24);
+        assert_eq!(tenant_rows, expected);
+
+        let exact: Vec<ByteBox> = tree
+            .scan_composite_prefix(&[ByteBox::int(0), ByteBox::big_int(-60)])
+            .unwrap()
+            .map(|kv| kv.key)
+            .collect();
+        assert_eq!(exact, vec![tenant_key(0, -60)]);
+
+        assert_eq!(tree.scan_composite_prefix(&[ByteBox::int(9)]).unwrap().count(), 0);
+        assert_eq!(tree.scan_composite_prefix(&[]).unwrap().count(), rows.len());
+
+        assert!(tree.scan_composite_prefix(&[ByteBox::big_int(3)]).is_err());
+        assert!(
+            tree
+                .insert(KeyValuePair {
+                    key: ByteBox::int(3),
+                    value: ByteBox::big_int(0),
+                })
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn composite_prefix_edges() {
+        let tree = BTreeBuilder::new()
+            .b_parameter(2)
+            .tree_schema(Codec {
+                key_type: DataType::Composite(vec![DataType::Varchar(10), DataType::Int]),
+                value_type: DataType::Int,
+            })
+            .build()
+            .unwrap();
+
+        let rows = [("ab", 1), ("ab", i32::MAX), ("abc", 0), ("a", 5), ("b", i32::MIN), ("ab", -3)];
+
+        for (name, num) in rows.iter() {
+            tree.insert(KeyValuePair {
+                key: ByteBox::composite(&[ByteBox::varchar(name, 10), ByteBox::int(*num)]).unwrap(),
+                value: ByteBox::int(*num),
+            }).unwrap();
+        }
+
+        // String components are compared whole, so "abc" is not part of the "ab" group
+        let values: Vec<ByteBox> = tree
+            .scan_composite_prefix(&[ByteBox::varchar("ab", 10)])
+            .unwrap()
+            .map(|kv| kv.value)
+            .collect();
+        assert_eq!(values, vec![ByteBox::int(-3), ByteBox::int(1), ByteBox::int(i32::MAX)]);
+
+        let last: Vec<ByteBox> = tree
+            .scan_composite_prefix(&[ByteBox::varchar("ab", 10), ByteBox::int(i32::MAX)])
+            .unwrap()
+            .map(|kv| kv.value)
+            .collect();
+        assert_eq!(last, vec![ByteBox::int(i32::MAX)]);
+    }
+
+    #[test]
+    fn oversized_or_non_scalar_composites_are_errors() {
+        let long = "x".repeat(200);
+        let oversized = [ByteBox::varchar(&long, 200), ByteBox::varchar(&long[..100], 100)];
+        assert!(ByteBox::composite(&oversized).is_err());
+
+        let tuple = ByteBox::tuple(&Tuple::TupleData(vec![]));
+        assert!(ByteBox::composite(&[ByteBox::int(1), tuple]).is_err());
+
+        // Schemas whose keys could not all be built are refused up front
+        let schema = |key_type: DataType| {
+            BTreeBuilder::new()
+                .tree_schema(Codec { key_type, value_type: DataType::Int })
+                .build()
+        };
+
+        assert!(schema(DataType::Composite(vec![DataType::Varchar(200), DataType::Varchar(100)]))
+            .is_err());
+        assert!(schema(DataType::Composite(vec![DataType::Int, DataType::Composite(vec![])]))
+            .is_err());
+        assert!(schema(DataType::Composite(vec![DataType::Varchar(200), DataType::BigInt])).is_ok());
+    }
+}
@@ -0,0 +1,219 @@
+#[cfg(test)]
+pub mod test {
+    use crate::index::tree::{
+        byte_box::{ ByteBox, DataType },
+        db::btree_obj::{ BPTree, BTreeBuilder },
+        index_types::{ KeyValuePair, NodeKey },
+        iterators::cursor::Cursor,
+        tree_page::codec::Codec,
+    };
+
+    fn tree(b: usize) -> BPTree {
+        let mut builder = BTreeBuilder::new();
+        builder.b_parameter(b).tree_schema(Codec {
+            key_type: DataType::BigInt,
+            value_type: DataType::Varchar(6000),
+        });
+
+        builder.build().unwrap()
+    }
+
+    fn value(text: &str) -> ByteBox {
+        ByteBox::varchar(text, 6000)
+    }
+
+    // Keys are the even numbers below 2 * len
+    fn populated(b: usize, len: i64) -> BPTree {
+        let tree = tree(b);
+
+        for idx in 0..len {
+            let key = ((idx * 37) % len) * 2;
+            tree.insert(KeyValuePair { key: ByteBox::big_int(key), value: value(&key.to_string()) })
+                .unwrap();
+        }
+
+        tree
+    }
+
+    fn key_of(cursor: &Cursor) -> i64 {
+        i64::from_le_bytes(cursor.key().unwrap().data[..].try_into().unwrap())
+    }
+
+    #[test]
+    fn walks_both_ways_across_leaves() {
+        let tree = populated(2, 200);
+        let mut cursor = tree.cursor();
+
+        assert!(!cursor.is_valid());
+
+        let mut forward = Vec::new();
+        let mut positioned = cursor.seek_first().unwrap();
+        while positioned {
+            forward.push(key_of(&cursor));
+            positioned = cursor.next().unwrap();
+        }
+        assert_eq!(forward, (0..200).map(|key| key * 2).collect::<Vec<_>>());
+
+        let mut backward = Vec::new();
+        let mut positioned = cursor.seek_last().unwrap();
+        while positioned {
+            backward.push(key_of(&cursor));
+            positioned = cursor.prev().unwrap();
+        }
+        assert_eq!(backward, (0..200).rev().map(|key| key * 2).collect::<Vec<_>>());
+
+        // Seeking an absent key lands on the next one, and the cursor turns around freely
+        assert!(cursor.seek(&ByteBox::big_int(101)).unwrap());
+        assert_eq!(key_of(&cursor), 102);
+        assert!(cursor.prev().unwrap());
+        assert!(cursor.prev().unwrap());
+        assert_eq!(key_of(&cursor), 98);
+        assert!(cursor.next().unwrap());
+        assert_eq!(cursor.entry().unwrap().unwrap().value, value("100"));
+
+        assert!(!cursor.seek(&ByteBox::big_int(1000)).unwrap());
+        assert_eq!(cursor.entry().unwrap(), None);
+    }
+
+    #[test]
+    fn updates_values_in_place() {
+        let tree = populated(0, 500);
+        let mut cursor = tree.cursor();
+
+        let mut positioned = cursor.seek_first().unwrap();
+        while positioned {
+            assert!(cursor.entry().unwrap().is_some());
+            let key = key_of(&cursor);
+
+            // Some values grow past a page and have to move to overflow pages
+            let len = if key % 14 == 0 { 5000 } else { 30 };
+            cursor.update_value(value(&format!("{}{}", key, "u".repeat(len)))).unwrap();
+
+            positioned = cursor.next().unwrap();
+        }
+
+        assert_eq!(tree.verify(), vec![]);
+
+        for entry in tree.scan(..) {
+            let key = i64::from_le_bytes(entry.key.data[..].try_into().unwrap());
+            let len = if key % 14 == 0 { 5000 } else { 30 };
+
+            assert_eq!(entry.value, value(&format!("{}{}", key, "u".repeat(len))));
+        }
+    }
+
+    #[test]
+    fn deletes_while_walking() {
+        for b in [0, 2] {
+            let tree = populated(b, 300);
+            let mut cursor = tree.cursor();
+
+            let mut positioned = cursor.seek_first().unwrap();
+            while positioned {
+                positioned = if key_of(&cursor) % 3 == 0 {
+                    cursor.delete_current().unwrap()
+                } else {
+                    cursor.next().unwrap()
+                };
+            }
+
+            let keys: Vec<ByteBox> = tree
+                .scan(..)
+                .map(|entry| entry.key)
+                .collect();
+            let expected: Vec<ByteBox> = (0..300)
+                .map(|key| key * 2)
+                .filter(|key| key % 3 != 0)
+                .map(ByteBox::big_int)
+                .collect();
+
+            assert_eq!(keys, expected, "b = {}", b);
+            assert_eq!(tree.verify(), vec![], "b = {}", b);
+        }
+    }
+
+    #[test]
+    fn survives_writes_around_it() {
+        let tree = populated(2, 100);
+        let mut cursor = tree.cursor();
+
+        assert!(cursor.seek(&ByteBox::big_int(50)).unwrap());
+
+        // Splits move the cursor's entry to other pages
+        for key in 0..100 {
+            let key = key * 2 + 1;
+            tree.insert(KeyValuePair { key: ByteBox::big_int(key), value: value("odd") }).unwrap();
+        }
+
+        assert!(cursor.next().unwrap());
+        assert_eq!(key_of(&cursor), 51);
+
+        // Once its entry is gone the cursor moves on from the key it had
+        tree.delete(NodeKey::GuidePost(ByteBox::big_int(51))).unwrap();
+        assert_eq!(cursor.entry().unwrap(), None);
+
+        assert!(cursor.next().unwrap());
+        assert_eq!(key_of(&cursor), 52);
+
+        tree.delete(NodeKey::GuidePost(ByteBox::big_int(52))).unwrap();
+        assert!(cursor.prev().unwrap());
+        assert_eq!(key_of(&cursor), 50);
+    }
+
+    #[test]
+    fn seeks_to_the_first_duplicate() {
+        let mut builder = BTreeBuilder::new();
+        builder.b_parameter(2).allow_duplicates(true).tree_schema(Codec {
+            key_type: DataType::BigInt,
+            value_type: DataType::Int,
+        });
+        let tree = builder.build().unwrap();
+
+        for key in 0..30 {
+            for val in [3, 1, 2] {
+                tree.insert(KeyValuePair { key: ByteBox::big_int(key), value: ByteBox::int(val) })
+                    .unwrap();
+            }
+        }
+
+        let mut cursor = tree.cursor();
+        assert!(cursor.seek(&ByteBox::big_int(10)).unwrap());
+
+        let mut values = Vec::new();
+        while cursor.key() == Some(ByteBox::big_int(10)) {
+            values.push(cursor.entry().unwrap().unwrap().value);
+            cursor.next().unwrap();
+        }
+        assert_eq!(values, vec![ByteBox::int(1), ByteBox::int(2), ByteBox::int(3)]);
+
+        assert!(cursor.update_value(ByteBox::int(9)).is_err());
+
+        assert!(cursor.delete_current().unwrap());
+        assert_eq!(tree.scan(ByteBox::big_int(11)..=ByteBox::big_int(11)).count(), 2);
+        assert_eq!(tree.verify(), vec![]);
+    }
+
+    #[test]
+    fn writes_after_merges_reach_the_tree() {
+        for target in [10, 40, 60] {
+            let tree = populated(3, 40);
+            let mut cursor = tree.cursor();
+            assert!(cursor.seek(&ByteBox::big_int(target)).unwrap());
+
+            // Merges move the cursor's entry off the page it was read from
+            for key in (target - 8..=target + 8).step_by(2).filter(|key| *key != target) {
+                tree.delete(NodeKey::GuidePost(ByteBox::big_int(key))).unwrap();
+            }
+
+            cursor.update_value(value("updated")).unwrap();
+
+            let found = tree.search(NodeKey::GuidePost(ByteBox::big_int(target))).unwrap();
+            assert_eq!(found.value, value("updated"), "key = {}", target);
+
+            assert!(cursor.delete_current().unwrap());
+            assert!(tree.search(NodeKey::GuidePost(ByteBox::big_int(target))).is_err());
+            assert_eq!(tree.scan(..).count(), 40 - 9, "key = {}", target);
+            assert_eq!(tree.verify(), vec![], "key = {}", target);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+#[cfg(test)]
+pub mod test {
+    use crate::index::tree::{
+        byte_box::{ ByteBox, DataType },
+        db::{ btree_dump::DumpFormat, btree_obj::{ BPTree, BTreeBuilder } },
+        index_types::KeyValuePair,
+        tree_page::codec::Codec,
+    };
+
+    fn tree(order_statistics: bool) -> BPTree {
+        let mut builder = BTreeBuilder::new();
+        builder
+            .b_parameter(2)
+            .order_statistics(order_statistics)
+            .tree_schema(Codec {
+                key_type: DataType::Varchar(10),
+                value_type: DataType::BigInt,
+            });
+
+        let tree = builder.build().unwrap();
+
+        for idx in 0..60i64 {
+            let key = format!("k{:02}", (idx * 7) % 60);
+            tree.insert(KeyValuePair {
+                key: ByteBox::varchar(&key, 10),
+                value: ByteBox::big_int(idx),
+            }).unwrap();
+        }
+
+        tree
+    }
+
+    fn count(text: &str, pattern: &str) -> usize {
+        text.matches(pattern).count()
+    }
+
+    #[test]
+    fn dot_shows_every_node_child_edge_and_leaf_link() {
+        let tree = tree(false);
+        let stats = tree.stats().unwrap();
+        let nodes: usize = stats.nodes_per_level.iter().sum();
+
+        let dot = tree.dump(DumpFormat::Dot).render().unwrap();
+
+        assert!(dot.starts_with("digraph BPTree {"));
+        assert!(dot.trim_end().ends_with('}'));
+        assert_eq!(count(&dot, "[label=\""), nodes);
+        assert_eq!(count(&dot, "style=rounded"), stats.num_leaves());
+
+        // Every node but the root hangs off one parent, every leaf but the last links onwards
+        assert_eq!(count(&dot, "style=dashed"), stats.num_leaves() - 1);
+        assert_eq!(count(&dot, " -> "), nodes - 1 + stats.num_leaves() - 1);
+
+        // Keys are shown as the strings they are, quotes escaped
+        assert!(dot.contains("\\\"k00\\\""));
+        assert!(dot.contains(&format!("p{} [label=\"internal", tree.root_page_id.borrow())));
+    }
+
+    #[test]
+    fn json_lists_nodes_with_their_links() {
+        let tree = tree(true);
+        let stats = tree.stats().unwrap();
+        let internal = stats.nodes_per_level.iter().sum::<usize>() - stats.num_leaves();
+
+        let json = tree.dump(DumpFormat::Json).render().unwrap();
+
+        assert!(json.starts_with(&format!("{{\n  \"root\": {},", tree.root_page_id.borrow())));
+        assert_eq!(count(&json, "\"kind\": \"leaf\""), stats.num_leaves());
+        assert_eq!(count(&json, "\"kind\": \"internal\""), internal);
+        assert_eq!(count(&json, "\"child_counts\""), internal);
+        assert_eq!(count(&json, "\"prev\": null"), 1);
+        assert_eq!(count(&json, "\"next\": null"), 1);
+        assert!(json.contains("\"\\\"k59\\\"\""));
+    }
+
+    #[test]
+    fn dumps_can_be_cut_to_a_depth_or_key_range() {
+        let tree = tree(false);
+        let stats = tree.stats().unwrap();
+
+        let root_only = tree.dump(DumpFormat::Json).max_depth(0).render().unwrap();
+        assert_eq!(count(&root_only, "\"page\""), 1);
+
+        let two_levels = tree.dump(DumpFormat::Dot).max_depth(1).render().unwrap();
+        assert_eq!(count(&two_levels, "[label=\""), 1 + stats.nodes_per_level[1]);
+
+        let whole = tree.dump(DumpFormat::Json).render().unwrap();
+        let narrow = tree
+            .dump(DumpFormat::Json)
+            .key_range(ByteBox::varchar("k10", 10)..=ByteBox::varchar("k12", 10))
+            .render()
+            .unwrap();
+
+        // A path down from the root, or two where the range crosses a separator
+        let levels = stats.nodes_per_level.len();
+        let leaves = count(&narrow, "\"kind\": \"leaf\"");
+        let internal = count(&narrow, "\"kind\": \"internal\"");
+
+        assert!((1..=2).contains(&leaves), "{}", narrow);
+        assert!((levels - 1..=2 * (levels - 1)).contains(&internal), "{}", narrow);
+        assert!(narrow.contains("\"\\\"k11\\\"\""));
+        assert!(count(&narrow, "\"page\"") < count(&whole, "\"page\""));
+    }
+}
@@ -0,0 +1,208 @@
+#[cfg(test)]
+pub mod test {
+    use std::ops::Bound;
+
+    use crate::{
+        catalog::schema::SchemaBuilder,
+        index::tree::{
+            byte_box::{ ByteBox, DataType },
+            db::btree_obj::{ BPTree, BTreeBuilder },
+            index_types::{ KeyValuePair, NodeKey },
+            tree_page::codec::Codec,
+        },
+    };
+
+    fn builder() -> BTreeBuilder {
+        let mut builder = BTreeBuilder::new();
+        builder
+            .b_parameter(2)
+            .allow_duplicates(true)
+            .tree_schema(Codec {
+                key_type: DataType::SmallInt,
+                value_type: DataType::BigInt,
+            });
+
+        builder
+    }
+
+    fn entry(key: i16, value: i64) -> KeyValuePair {
+        KeyValuePair {
+            key: ByteBox::small_int(key),
+            value: ByteBox::big_int(value),
+        }
+    }
+
+    fn pairs(entries: impl Iterator<Item = KeyValuePair>) -> Vec<(i16, i64)> {
+        entries
+            .map(|kv| {
+                let key = i16::from_le_bytes(kv.key.data[..].try_into().unwrap());
+                let value = i64::from_le_bytes(kv.value.data[..].try_into().unwrap());
+                (key, value)
+            })
+            .collect()
+    }
+
+    fn populated() -> (BPTree, Vec<(i16, i64)>) {
+        let tree = builder().build().unwrap();
+
+        // Ten keys with twenty rows each, inserted in a scattered order
+        let mut rows: Vec<(i16, i64)> = (0..200i64)
+            .map(|idx| {
+                let row = (idx * 83) % 200;
+                ((row % 10) as i16, row)
+            })
+            .collect();
+
+        for (key, value) in rows.iter() {
+            tree.insert(entry(*key, *value)).unwrap();
+        }
+
+        rows.sort();
+        (tree, rows)
+    }
+
+    #[test]
+    fn duplicates_scan_in_key_then_value_order() {
+        let (tree, rows) = populated();
+
+        assert_eq!(pairs(tree.scan(..)), rows);
+
+        let mut reversed = pairs(tree.scan_rev(..));
+        reversed.reverse();
+        assert_eq!(reversed, rows);
+
+        let expected: Vec<(i16, i64)> = rows
+            .iter()
+            .filter(|(key, _)| *key == 4)
+            .cloned()
+            .collect();
+        let key = ByteBox::small_int(4);
+        assert_eq!(expected.len(), 20);
+        assert_eq!(pairs(tree.scan(key.clone()..=key.clone())), expected);
+
+        let found = tree.search(NodeKey::GuidePost(key)).unwrap();
+        assert_eq!(found, entry(4, 4));
+
+        let expected: Vec<(i16, i64)> = rows
+            .iter()
+            .filter(|(key, _)| *key > 2 && *key < 5)
+            .cloned()
+            .collect();
+        let range = (
+            Bound::Excluded(ByteBox::small_int(2)),
+            Bound::Excluded(ByteBox::small_int(5)),
+        );
+        assert_eq!(pairs(tree.scan(range)), expected);
+
+        let above_max = (Bound::Excluded(ByteBox::small_int(i16::MAX)), Bound::Unbounded);
+        assert_eq!(tree.scan(above_max).count(), 0);
+
+        // The exact pair is already stored
+        assert!(tree.insert(entry(4, 4)).is_err());
+    }
+
+    #[test]
+    fn duplicates_delete_one_or_all() {
+        let (tree, mut rows) = populated();
+
+        tree.delete(NodeKey::KeyValuePair(entry(3, 13))).unwrap();
+        rows.retain(|row| *row != (3, 13));
+        assert_eq!(pairs(tree.scan(..)), rows);
+
+        tree.delete(NodeKey::GuidePost(ByteBox::small_int(7))).unwrap();
+        rows.retain(|(key, _)| *key != 7);
+        assert_eq!(pairs(tree.scan(..)), rows);
+
+        assert!(tree.delete(NodeKey::GuidePost(ByteBox::small_int(7))).is_err());
+
+        for key in 0..10 {
+            let _ = tree.delete(NodeKey::GuidePost(ByteBox::small_int(key)));
+        }
+        assert_eq!(tree.scan(..).count(), 0);
+    }
+
+    #[test]
+    fn duplicates_bulk_load_and_schema() {
+        let entries: Vec<KeyValuePair> = (0..60).map(|idx| entry(idx / 6, idx as i64)).collect();
+
+        let tree = builder().bulk_load(entries.clone()).unwrap();
+        assert_eq!(tree.scan(..).collect::<Vec<_>>(), entries);
+
+        tree.insert(entry(0, -1)).unwrap();
+        let key = ByteBox::small_int(0);
+        assert_eq!(tree.scan(key.clone()..=key).next().unwrap(), entry(0, -1));
+
+        let unsorted = vec![entry(1, 5), entry(1, 2)];
+        assert!(builder().bulk_load(unsorted).is_err());
+
+        // Values break ties, so they have to be comparable scalars
+        let mut schema = SchemaBuilder::new();
+        schema.add_big_int(String::from("id"));
+
+        let mut tuple_values = BTreeBuilder::new();
+        tuple_values
+            .b_parameter(2)
+            .allow_duplicates(true)
+            .tree_schema(Codec {
+                key_type: DataType::SmallInt,
+                value_type: DataType::Tuple(schema.build()),
+            });
+        assert!(tuple_values.build().is_err());
+    }
+
+    #[test]
+    fn duplicates_prefix_scan() {
+        let tree = BTreeBuilder::new()
+            .b_parameter(2)
+            .allow_duplicates(true)
+            .tree_schema(Codec {
+                key_type: DataType::Varchar(10),
+                value_type: DataType::Int,
+            })
+            .build()
+            .unwrap();
+
+        for (idx, name) in ["ann", "bob", "anna", "ann", "al", "ann"].iter().enumerate() {
+            tree.insert(KeyValuePair {
+                key: ByteBox::varchar(name, 10),
+                value: ByteBox::int(idx as i32),
+            }).unwrap();
+        }
+
+        let found: Vec<ByteBox> = tree
+            .scan_prefix("ann")
+            .unwrap()
+            .map(|kv| kv.value)
+            .collect();
+        assert_eq!(found, vec![ByteBox::int(0), ByteBox::int(3), ByteBox::int(5), ByteBox::int(2)]);
+    }
+
+    #[test]
+    fn duplicates_refuse_schemas_too_large_to_store() {
+        let strings = |key_len: usize, value_len: usize| {
+            let mut builder = BTreeBuilder::new();
+            builder
+                .allow_duplicates(true)
+                .tree_schema(Codec {
+                    key_type: DataType::Varchar(key_len),
+                    value_type: DataType::Varchar(value_len),
+                });
+
+            builder
+        };
+
+        // A 190 byte key next to a 90 byte value is more than a stored key can hold
+        assert!(strings(200, 100).build().is_err());
+        assert!(strings(200, 100).bulk_load(vec![]).is_err());
+
+        // Keys and values as long as the schema allows fit
+        let tree = strings(150, 90).build().unwrap();
+        let long = KeyValuePair {
+            key: ByteBox::varchar(&"k".repeat(150), 150),
+            value: ByteBox::varchar(&"v".repeat(90), 90),
+        };
+
+        tree.insert(long.clone()).unwrap();
+        assert_eq!(tree.scan(..).collect::<Vec<_>>(), vec![long]);
+    }
+}
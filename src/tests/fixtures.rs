@@ -0,0 +1,39 @@
+#![allow(dead_code)]
+
+// Trees and entries shared by the tree tests
+
+use crate::index::tree::{
+    byte_box::{ ByteBox, DataType },
+    db::btree_obj::BTreeBuilder,
+    index_types::KeyValuePair,
+    tree_page::codec::Codec,
+};
+
+/// Longest value the shared schema holds
+pub const MAX_VALUE_LEN: usize = 6000;
+
+/// A builder for trees of BigInt keys and Varchar values of up to `MAX_VALUE_LEN` bytes
+pub fn builder(b: usize) -> BTreeBuilder {
+    let mut builder = BTreeBuilder::new();
+    builder.b_parameter(b).tree_schema(Codec {
+        key_type: DataType::BigInt,
+        value_type: DataType::Varchar(MAX_VALUE_LEN),
+    });
+
+    builder
+}
+
+/// An entry of the shared schema, every seventh value long enough to move to overflow pages
+pub fn entry(key: i64) -> KeyValuePair {
+    tagged_entry(key, "")
+}
+
+/// `entry` with the value starting with `tag`, for telling versions of an entry apart
+pub fn tagged_entry(key: i64, tag: &str) -> KeyValuePair {
+    let len = if key % 7 == 0 { 5000 } else { 16 };
+
+    KeyValuePair {
+        key: ByteBox::big_int(key),
+        value: ByteBox::varchar(&format!("{}{}", tag, "v".repeat(len)), MAX_VALUE_LEN),
+    }
+}
@@ -67,6 +67,34 @@ pub mod test {
         teardown();
     }
 
+    #[test]
+    fn update_flush_waits_for_no_other_ex_lock() {
+        let (log_io, log_file_path) = Manager::open_log();
+        let manager = Manager::new(log_io, log_file_path);
+
+        let bpm = BufferPoolManager::new(NUM_FRAMES, manager, K_DIST);
+        let file_id = bpm.allocate_file();
+
+        let flusher = Flusher::new(Arc::new(bpm), file_id);
+        let page = flusher.new_page();
+
+        flusher.write_flush([1u8; PAGE_SIZE], page).unwrap();
+        flusher
+            .update_flush(page, |data| {
+                assert_eq!(data, [1u8; PAGE_SIZE]);
+                Ok([2u8; PAGE_SIZE])
+            })
+            .unwrap();
+        assert_eq!(flusher.read_drop(page), [2u8; PAGE_SIZE]);
+
+        // A page latched by the current context is not rewritten behind its back
+        flusher.aqquire_context_ex(VecDeque::from([page])).unwrap();
+        assert!(flusher.update_flush(page, |_| Ok([3u8; PAGE_SIZE])).is_err());
+
+        flusher.release_ex_all().unwrap();
+        assert_eq!(flusher.read_drop(page), [2u8; PAGE_SIZE]);
+    }
+
     #[test]
     fn teardown() {
         let log_file_path = PathBuf::from("log_file_path.bin");
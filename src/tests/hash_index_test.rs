@@ -0,0 +1,195 @@
+#[cfg(test)]
+pub mod test {
+    use crate::index::{
+        hash::hash_index::{ HashIndex, HashIndexBuilder },
+        tree::{
+            byte_box::{ ByteBox, DataType },
+            index_types::{ KeyValuePair, NodeKey },
+            tree_page::codec::Codec,
+        },
+    };
+
+    fn index() -> HashIndex {
+        let mut builder = HashIndexBuilder::new();
+        builder.index_schema(Codec {
+            key_type: DataType::BigInt,
+            value_type: DataType::Varchar(6000),
+        });
+
+        builder.build().unwrap()
+    }
+
+    // Every eleventh value is long enough to move to overflow pages
+    fn entry(key: i64) -> KeyValuePair {
+        let len = if key % 11 == 0 { 5000 } else { 60 };
+
+        KeyValuePair {
+            key: ByteBox::big_int(key),
+            value: ByteBox::varchar(&format!("{}", key).repeat(len / 4), 6000),
+        }
+    }
+
+    fn search(index: &HashIndex, key: i64) -> anyhow::Result<KeyValuePair> {
+        index.search(NodeKey::GuidePost(ByteBox::big_int(key)))
+    }
+
+    #[test]
+    fn inserts_split_buckets_and_double_the_directory() {
+        let index = index();
+
+        for key in 0..1000i64 {
+            index.insert(entry(key)).unwrap();
+        }
+
+        assert!(index.global_depth().unwrap() > 2);
+        assert_eq!(index.verify(), vec![]);
+
+        for key in 0..1000i64 {
+            assert_eq!(search(&index, key).unwrap(), entry(key));
+        }
+
+        assert!(search(&index, 1000).is_err());
+        assert!(!index.contains_key(&ByteBox::big_int(-1)).unwrap());
+        assert!(index.contains_key(&ByteBox::big_int(999)).unwrap());
+    }
+
+    #[test]
+    fn insert_refuses_present_keys_and_put_replaces_them() {
+        let index = index();
+
+        for key in 0..300i64 {
+            index.insert(entry(key)).unwrap();
+        }
+
+        let pages = index.flusher.free_page_count();
+        assert!(index.insert(entry(22)).is_err());
+        assert_eq!(index.flusher.free_page_count(), pages);
+
+        // Swapping an overflow value for an inline one frees its chain
+        let replacement = KeyValuePair {
+            key: ByteBox::big_int(22),
+            value: ByteBox::varchar("short", 6000),
+        };
+        index.put(replacement.clone()).unwrap();
+        index.put(entry(300)).unwrap();
+
+        assert!(index.flusher.free_page_count() > pages);
+        assert_eq!(search(&index, 22).unwrap(), replacement);
+        assert_eq!(search(&index, 300).unwrap(), entry(300));
+        assert_eq!(index.verify(), vec![]);
+    }
+
+    #[test]
+    fn deletes_merge_buckets_and_halve_the_directory() {
+        let index = index();
+
+        for key in 0..1000i64 {
+            index.insert(entry(key)).unwrap();
+        }
+        let depth = index.global_depth().unwrap();
+
+        for key in (0..1000i64).filter(|key| key % 50 != 0) {
+            index.delete(NodeKey::GuidePost(ByteBox::big_int(key))).unwrap();
+        }
+
+        assert!(index.global_depth().unwrap() < depth);
+        assert_eq!(index.verify(), vec![]);
+        assert!(search(&index, 1).is_err());
+        assert!(index.delete(NodeKey::GuidePost(ByteBox::big_int(1))).is_err());
+
+        for key in (0..1000i64).step_by(50) {
+            assert_eq!(search(&index, key).unwrap(), entry(key));
+        }
+
+        // Emptied completely the directory is back to a single bucket
+        for key in (0..1000i64).step_by(50) {
+            index.delete(NodeKey::GuidePost(ByteBox::big_int(key))).unwrap();
+        }
+
+        assert_eq!(index.global_depth().unwrap(), 0);
+        assert_eq!(index.verify(), vec![]);
+    }
+
+    #[test]
+    fn reopened_index_keeps_its_schema_and_entries() {
+        let index = index();
+
+        for key in 0..500i64 {
+            index.insert(entry(key)).unwrap();
+        }
+        index.flush().unwrap();
+
+        let path = index.file_path().unwrap();
+        let reopened = HashIndexBuilder::new().build_from_file(&path).unwrap();
+
+        assert_eq!(reopened.codec.value_type, DataType::Varchar(6000));
+        assert_eq!(reopened.global_depth().unwrap(), index.global_depth().unwrap());
+        assert_eq!(reopened.verify(), vec![]);
+
+        for key in [0i64, 11, 250, 499] {
+            assert_eq!(search(&reopened, key).unwrap(), entry(key));
+        }
+    }
+
+    #[test]
+    fn entries_are_checked_against_the_schema() {
+        let index = index();
+
+        let wrong_key = KeyValuePair {
+            key: ByteBox::varchar("key", 10),
+            value: ByteBox::varchar("value", 6000),
+        };
+        let wrong_value = KeyValuePair {
+            key: ByteBox::big_int(1),
+            value: ByteBox::big_int(1),
+        };
+
+        assert!(index.insert(wrong_key).is_err());
+        assert!(index.insert(wrong_value).is_err());
+        assert!(search(&index, 1).is_err());
+    }
+
+    #[test]
+    fn directory_grows_past_its_first_page() {
+        let index = index();
+
+        // Buckets hold four of these, so thousands of them need more slots than a page holds
+        let wide = |key: i64| KeyValuePair {
+            key: ByteBox::big_int(key),
+            value: ByteBox::varchar(&format!("{:0>900}", key), 6000),
+        };
+
+        for key in 0..3000i64 {
+            index.insert(wide(key)).unwrap();
+        }
+
+        let depth = index.global_depth().unwrap();
+        assert!(depth > 9);
+        assert_eq!(index.verify(), vec![]);
+        assert_eq!(index.stats().unwrap().num_entries, 3000);
+
+        for key in (0..3000i64).step_by(7) {
+            assert_eq!(search(&index, key).unwrap(), wide(key));
+        }
+
+        index.flush().unwrap();
+        let path = index.file_path().unwrap();
+        let reopened = HashIndexBuilder::new().build_from_file(&path).unwrap();
+        assert_eq!(reopened.global_depth().unwrap(), depth);
+        assert_eq!(search(&reopened, 2345).unwrap(), wide(2345));
+
+        // Halving the directory hands its extension pages back
+        let pages = index.stats().unwrap().node_pages;
+        for key in (0..3000i64).filter(|key| key % 100 != 0) {
+            index.delete(NodeKey::GuidePost(ByteBox::big_int(key))).unwrap();
+        }
+
+        assert!(index.global_depth().unwrap() < depth);
+        assert!(index.stats().unwrap().node_pages < pages);
+        assert_eq!(index.verify(), vec![]);
+
+        for key in (0..3000i64).step_by(100) {
+            assert_eq!(search(&index, key).unwrap(), wide(key));
+        }
+    }
+}
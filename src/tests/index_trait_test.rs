@@ -0,0 +1,263 @@
+#[cfg(test)]
+pub mod test {
+    use std::{ cell::RefCell, ops::Bound, sync::Arc };
+
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManager,
+        catalog::{ schema::SchemaDataBuilder, table::Table, table_schema },
+        engine::{ StorageEngine, StorageOptions },
+        index::{
+            index_api::{ build_index, IndexKind },
+            tree::{
+                byte_box::{ ByteBox, DataType },
+                db::{ btree_obj::BTreeBuilder, btree_ttl::ExpiryReaper },
+                index_types::KeyValuePair,
+                tree_page::codec::Codec,
+            },
+        },
+        storage::{ disk::manager::Manager, tuple::{ to_flat_schema, Tuple } },
+        wal::{ Commands, Wal, WalEntry },
+    };
+
+    fn codec() -> Codec {
+        Codec {
+            key_type: DataType::BigInt,
+            value_type: DataType::Varchar(20),
+        }
+    }
+
+    fn entry(key: i64) -> KeyValuePair {
+        KeyValuePair {
+            key: ByteBox::big_int(key),
+            value: ByteBox::varchar(&format!("value {}", key), 20),
+        }
+    }
+
+    #[test]
+    fn every_kind_behaves_alike_through_the_trait() {
+        for kind in [IndexKind::BPTree, IndexKind::Hash] {
+            let index = build_index(kind, codec()).unwrap();
+            assert_eq!(index.kind(), kind);
+
+            for idx in 0..400i64 {
+                index.insert(entry((idx * 37) % 400)).unwrap();
+            }
+
+            assert!(index.insert(entry(5)).is_err(), "{}", kind);
+            assert_eq!(index.get(&ByteBox::big_int(5)).unwrap(), entry(5));
+
+            let replacement = KeyValuePair {
+                key: ByteBox::big_int(5),
+                value: ByteBox::varchar("replaced", 20),
+            };
+            index.put(replacement.clone()).unwrap();
+            assert_eq!(index.get(&ByteBox::big_int(5)).unwrap(), replacement);
+
+            for key in (0..400i64).step_by(2) {
+                index.delete(&ByteBox::big_int(key)).unwrap();
+            }
+
+            assert!(index.get(&ByteBox::big_int(2)).is_err(), "{}", kind);
+            assert!(index.delete(&ByteBox::big_int(2)).is_err(), "{}", kind);
+            assert!(index.contains_key(&ByteBox::big_int(3)).unwrap());
+            assert!(!index.contains_key(&ByteBox::big_int(4)).unwrap());
+
+            let stats = index.stats().unwrap();
+            assert_eq!(stats.kind, kind);
+            assert_eq!(stats.num_entries, 200, "{}", kind);
+            assert!(stats.node_pages > 1, "{}", kind);
+
+            assert_eq!(index.verify(), vec![], "{}", kind);
+            assert_eq!(index.as_tree().is_some(), kind == IndexKind::BPTree);
+        }
+    }
+
+    #[test]
+    fn range_scans_depend_on_the_kind() {
+        let tree = build_index(IndexKind::BPTree, codec()).unwrap();
+        let hash = build_index(IndexKind::Hash, codec()).unwrap();
+
+        for key in 0..50i64 {
+            tree.insert(entry(key)).unwrap();
+            hash.insert(entry(key)).unwrap();
+        }
+
+        let range = (Bound::Included(ByteBox::big_int(10)), Bound::Excluded(ByteBox::big_int(20)));
+
+        assert!(tree.supports_range_scan());
+        assert_eq!(
+            tree.scan(range.clone()).unwrap().collect::<Vec<_>>(),
+            (10..20).map(entry).collect::<Vec<_>>()
+        );
+
+        assert!(!hash.supports_range_scan());
+        assert!(hash.scan(range).is_err());
+    }
+
+    #[test]
+    fn kinds_round_trip_through_their_names() {
+        for kind in [IndexKind::BPTree, IndexKind::Hash] {
+            assert_eq!(IndexKind::from_name(kind.name()).unwrap(), kind);
+        }
+
+        assert!(IndexKind::from_name("LSM").is_err());
+    }
+
+    fn engine() -> StorageEngine {
+        let (log_io, log_file_path) = Manager::open_log();
+        let bpm = Arc::new(BufferPoolManager::new(10, Manager::new(log_io, log_file_path), 2));
+        let directory = |value_type: DataType| {
+            BTreeBuilder::new()
+                .tree_schema(Codec { key_type: DataType::Varchar(25), value_type })
+                .build()
+                .unwrap()
+                .into()
+        };
+
+        StorageEngine {
+            options: StorageOptions { page_size: 4096, enable_wal: false },
+            pages_directory: directory(DataType::BigInt),
+            table_directory: directory(DataType::Tuple(table_schema())),
+            state: Table::new(String::new(), build_index(IndexKind::BPTree, codec()).unwrap())
+                .unwrap(),
+            bpm,
+            wal: None,
+            reaper: RefCell::new(ExpiryReaper::new()),
+        }
+    }
+
+    #[test]
+    fn engine_opens_tables_as_the_kind_it_recorded() {
+        let mut engine = engine();
+
+        engine.create_table("points", IndexKind::Hash, &codec()).unwrap();
+        engine.create_table("ranges", IndexKind::BPTree, &codec()).unwrap();
+        assert!(engine.create_table("points", IndexKind::BPTree, &codec()).is_err());
+
+        engine.open_table("points", codec()).unwrap();
+        assert_eq!(engine.state.primary.kind(), IndexKind::Hash);
+        engine.put(entry(1)).unwrap();
+        assert_eq!(engine.get(&ByteBox::big_int(1)).unwrap(), entry(1));
+
+        engine.open_table("ranges", codec()).unwrap();
+        assert_eq!(engine.state.primary.kind(), IndexKind::BPTree);
+
+        // Tables are opened with the schema they were created with
+        let other = Codec { key_type: DataType::Int, value_type: DataType::Varchar(20) };
+        assert!(engine.open_table("ranges", other).is_err());
+        assert!(engine.open_table("missing", codec()).is_err());
+        assert_eq!(engine.state.primary.kind(), IndexKind::BPTree);
+    }
+
+    #[test]
+    fn engine_reopens_tables_with_their_entries() {
+        let mut engine = engine();
+
+        for kind in [IndexKind::BPTree, IndexKind::Hash] {
+            engine.create_table(kind.name(), kind, &codec()).unwrap();
+            engine.open_table(kind.name(), codec()).unwrap();
+
+            for key in 0..300 {
+                engine.put(entry(key)).unwrap();
+            }
+        }
+
+        for kind in [IndexKind::BPTree, IndexKind::Hash] {
+            engine.open_table(kind.name(), codec()).unwrap();
+            assert_eq!(engine.state.primary.kind(), kind);
+
+            for key in [0i64, 1, 150, 299] {
+                assert_eq!(engine.get(&ByteBox::big_int(key)).unwrap(), entry(key));
+            }
+            assert!(!engine.state.primary.contains_key(&ByteBox::big_int(300)).unwrap());
+            assert!(engine.state.primary.verify().is_empty());
+        }
+    }
+
+    #[test]
+    fn engine_replays_the_log_onto_its_tables() {
+        let path = std::env::temp_dir().join(
+            format!("geode_index_trait_{}_recover.wal", std::process::id())
+        );
+        let _ = std::fs::remove_file(&path);
+
+        let mut engine = engine();
+        engine.wal = Some(Wal::create(&path).unwrap());
+
+        engine.create_table("logged", IndexKind::BPTree, &codec()).unwrap();
+        engine.create_table("other", IndexKind::BPTree, &codec()).unwrap();
+        engine.open_table("logged", codec()).unwrap();
+        let log_id = engine.state.primary.as_tree().unwrap().log_id;
+
+        // Writes logged before a crash kept them from the table's file
+        let logged: Vec<WalEntry> = (0..50)
+            .map(|key| WalEntry {
+                command: Commands::PUT,
+                table_id: log_id,
+                key: entry(key).key,
+                data: Some(entry(key).value),
+            })
+            .collect();
+        engine.wal.as_ref().unwrap().put_batch(&logged).unwrap();
+
+        engine.state = Table::new(String::new(), build_index(IndexKind::BPTree, codec()).unwrap())
+            .unwrap();
+        engine.recover().unwrap();
+        assert!(engine.wal.as_ref().unwrap().entries().unwrap().is_empty());
+
+        engine.open_table("logged", codec()).unwrap();
+        for key in [0i64, 25, 49] {
+            assert_eq!(engine.get(&ByteBox::big_int(key)).unwrap(), entry(key));
+        }
+
+        // Entries are replayed onto the table they were logged for only
+        engine.open_table("other", codec()).unwrap();
+        assert_eq!(engine.state.primary.stats().unwrap().num_entries, 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn engine_writes_keep_the_open_table_indexed() {
+        let person = |id: i64, city: &str| {
+            let tuple = Tuple::TupleData(
+                SchemaDataBuilder::new()
+                    .add_varchar(String::from("city"), ByteBox::varchar(city, 20))
+                    .build()
+            );
+
+            KeyValuePair { key: ByteBox::big_int(id), value: ByteBox::tuple(&tuple) }
+        };
+        let people = Codec {
+            key_type: DataType::BigInt,
+            value_type: DataType::Tuple(to_flat_schema(&Tuple::TupleData(
+                SchemaDataBuilder::new()
+                    .add_varchar(String::from("city"), ByteBox::varchar("", 20))
+                    .build()
+            ))),
+        };
+        let city = |name: &str| ByteBox::varchar(name, 20);
+
+        let mut engine = engine();
+        engine.create_table("people", IndexKind::BPTree, &people).unwrap();
+        engine.open_table("people", people.clone()).unwrap();
+        engine.create_index("people", "city").unwrap();
+
+        for id in 0..30 {
+            engine.insert(person(id, if id % 2 == 0 { "Oslo" } else { "Lima" })).unwrap();
+        }
+        engine.put(person(4, "Pune")).unwrap();
+        engine.delete(&ByteBox::big_int(6)).unwrap();
+        assert!(engine.insert(person(8, "Pune")).is_err());
+
+        let found = engine.lookup_by("people", "city", &city("Pune")).unwrap();
+        assert_eq!(found, vec![person(4, "Pune")]);
+        assert_eq!(engine.lookup_by("people", "city", &city("Oslo")).unwrap().len(), 13);
+        assert_eq!(engine.lookup_by("people", "city", &city("Lima")).unwrap().len(), 15);
+
+        // Only the open table can be looked up
+        engine.create_table("other", IndexKind::BPTree, &codec()).unwrap();
+        engine.open_table("other", codec()).unwrap();
+        assert!(engine.lookup_by("people", "city", &city("Pune")).is_err());
+    }
+}
@@ -1,13 +1,13 @@
 #[cfg(test)]
 pub mod test {
-    use std::{ fs::{ remove_dir_all, remove_file }, path::PathBuf, sync::Arc };
+    use std::{ fs::{ remove_dir_all, remove_file }, ops::Bound, path::PathBuf, sync::Arc };
 
     use crate::index::tree::{
         byte_box::{ ByteBox, DataType },
         db::btree_obj::BTreeBuilder,
         index_types::{ KeyValuePair, NodeKey },
         iterators::leaf_iterator::LeafIterator,
-        tree_page::codec::Codec,
+        tree_page::codec::{ Codec, Projection },
     };
 
     #[test]
@@ -33,6 +33,8 @@ pub mod test {
             Arc::clone(&tree.flusher),
             Arc::new(page),
             tree.codec,
+            None,
+            Projection::Whole,
             tree.index_id
         );
 
@@ -68,6 +70,196 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn reverse_scan_test() {
+        let tree = BTreeBuilder::new()
+            .b_parameter(2)
+            .tree_schema(Codec {
+                key_type: DataType::SmallInt,
+                value_type: DataType::Varchar(15),
+            })
+            .build()
+            .unwrap();
+
+        // Out of order writes exercise the prev pointers through splits, merges and borrows
+        let key_vec = get_kv_vec();
+        for idx in 0..key_vec.len() {
+            tree.insert(key_vec[(idx * 7) % key_vec.len()].clone()).unwrap();
+        }
+
+        for key in [30, 40, 50, 160, 170, 300] {
+            tree.delete(NodeKey::GuidePost(ByteBox::small_int(key))).unwrap();
+        }
+
+        let forward: Vec<KeyValuePair> = tree.scan(..).collect();
+        let mut reversed: Vec<KeyValuePair> = tree.scan_rev(..).collect();
+        reversed.reverse();
+
+        assert_eq!(forward.len(), key_vec.len() - 6);
+        assert_eq!(forward, reversed);
+
+        // Latest entries below a bound, where the bounds themselves are absent from the tree
+        let latest: Vec<ByteBox> = tree
+            .scan_rev(ByteBox::small_int(15)..ByteBox::small_int(165))
+            .take(3)
+            .map(|kv| kv.key)
+            .collect();
+        assert_eq!(latest, vec![
+            ByteBox::small_int(150),
+            ByteBox::small_int(140),
+            ByteBox::small_int(130),
+        ]);
+
+        let excluded: Vec<ByteBox> = tree
+            .scan_rev((Bound::Excluded(ByteBox::small_int(60)), Bound::Excluded(ByteBox::small_int(100))))
+            .map(|kv| kv.key)
+            .collect();
+        assert_eq!(excluded, vec![
+            ByteBox::small_int(90),
+            ByteBox::small_int(80),
+            ByteBox::small_int(70),
+        ]);
+
+        // Both ends of one iterator meet without handing out an entry twice
+        let mut iter = tree.scan(ByteBox::small_int(10)..=ByteBox::small_int(100));
+        let mut seen = vec![];
+        loop {
+            match (iter.next(), iter.next_back()) {
+                (None, None) => break,
+                (front, back) => seen.extend(front.into_iter().chain(back).map(|kv| kv.key)),
+            }
+        }
+        seen.sort();
+        let expected: Vec<ByteBox> = [10, 20, 60, 70, 80, 90, 100]
+            .into_iter()
+            .map(ByteBox::small_int)
+            .collect();
+        assert_eq!(seen, expected);
+    }
+
+    #[test]
+    fn reverse_scan_bulk_loaded() {
+        let mut builder = BTreeBuilder::new();
+        builder.b_parameter(3).tree_schema(Codec {
+            key_type: DataType::SmallInt,
+            value_type: DataType::Varchar(15),
+        });
+
+        let tree = builder.bulk_load(get_kv_vec()).unwrap();
+
+        let mut reversed: Vec<KeyValuePair> = tree.scan_rev(..).collect();
+        reversed.reverse();
+        assert_eq!(reversed, get_kv_vec());
+    }
+
+    #[test]
+    fn prefix_scan_test() {
+        let tree = BTreeBuilder::new()
+            .b_parameter(2)
+            .tree_schema(Codec {
+                key_type: DataType::Varchar(15),
+                value_type: DataType::SmallInt,
+            })
+            .build()
+            .unwrap();
+
+        let keys = [
+            "user:42:name",
+            "user:4",
+            "user:42",
+            "user;",
+            "user:42:age",
+            "user:420",
+            "user:43:name",
+            "user",
+            "account:42",
+            "user:42:\u{10FFFF}",
+        ];
+
+        for (idx, key) in keys.iter().enumerate() {
+            tree.insert(KeyValuePair {
+                key: ByteBox::varchar(key, 15),
+                value: ByteBox::small_int(idx as i16),
+            }).unwrap();
+        }
+
+        let scan_keys = |prefix: &str| -> Vec<String> {
+            tree.scan_prefix(prefix)
+                .unwrap()
+                .map(|kv| String::from_utf8(kv.key.data.to_vec()).unwrap())
+                .collect()
+        };
+
+        assert_eq!(scan_keys("user:42:"), vec![
+            "user:42:age",
+            "user:42:name",
+            "user:42:\u{10FFFF}",
+        ]);
+        assert_eq!(scan_keys("user:42"), vec![
+            "user:42",
+            "user:420",
+            "user:42:age",
+            "user:42:name",
+            "user:42:\u{10FFFF}",
+        ]);
+        assert_eq!(scan_keys("user:"), vec![
+            "user:4",
+            "user:42",
+            "user:420",
+            "user:42:age",
+            "user:42:name",
+            "user:42:\u{10FFFF}",
+            "user:43:name",
+        ]);
+        assert!(scan_keys("user:5").is_empty());
+        assert!(scan_keys("zebra").is_empty());
+        assert_eq!(scan_keys("").len(), keys.len());
+
+        // Prefix scans share the range iterator, so they can also run backwards
+        let last = tree.scan_prefix("user:4").unwrap().next_back().unwrap();
+        assert_eq!(last.key, ByteBox::varchar("user:43:name", 15));
+    }
+
+    #[test]
+    fn prefix_scan_char_keys() {
+        let tree = BTreeBuilder::new()
+            .b_parameter(2)
+            .tree_schema(Codec {
+                key_type: DataType::Char(8),
+                value_type: DataType::SmallInt,
+            })
+            .build()
+            .unwrap();
+
+        for (idx, key) in ["ab", "abc", "abd", "ac", "b"].iter().enumerate() {
+            tree.insert(KeyValuePair {
+                key: ByteBox::char(key, 8),
+                value: ByteBox::small_int(idx as i16),
+            }).unwrap();
+        }
+
+        let found: Vec<ByteBox> = tree
+            .scan_prefix("ab")
+            .unwrap()
+            .map(|kv| kv.key)
+            .collect();
+        assert_eq!(found, vec![
+            ByteBox::char("ab", 8),
+            ByteBox::char("abc", 8),
+            ByteBox::char("abd", 8),
+        ]);
+
+        let numeric = BTreeBuilder::new()
+            .b_parameter(2)
+            .tree_schema(Codec {
+                key_type: DataType::SmallInt,
+                value_type: DataType::SmallInt,
+            })
+            .build()
+            .unwrap();
+        assert!(numeric.scan_prefix("1").is_err());
+    }
+
     fn teardown() {
         let log_file_path = PathBuf::from("log_file_path.bin");
         let db_path = PathBuf::from("geodeData");
@@ -0,0 +1,167 @@
+#[cfg(test)]
+pub mod test {
+    use crate::index::tree::{
+        byte_box::{ ByteBox, DataType },
+        db::btree_obj::{ BPTree, BTreeBuilder },
+        index_types::{ KeyValuePair, NodeKey },
+        tree_node::{ node_type::NodeType, tree_node_inner::NodeInner },
+        tree_page::{ codec::Codec, page::TreePage, tree_page_layout::PAGE_SIZE },
+    };
+
+    const KEY_LEN: usize = 200;
+
+    fn builder() -> BTreeBuilder {
+        let mut builder = BTreeBuilder::new();
+        builder.tree_schema(Codec {
+            key_type: DataType::Varchar(KEY_LEN),
+            value_type: DataType::BigInt,
+        });
+
+        builder
+    }
+
+    // Keys of one tenant share a long prefix
+    fn prefixed_entry(key: i64) -> KeyValuePair {
+        KeyValuePair {
+            key: ByteBox::varchar(
+                &format!("tenants/acme-corporation/accounts/{:06}", key),
+                KEY_LEN
+            ),
+            value: ByteBox::big_int(key),
+        }
+    }
+
+    // Keys told apart by their first bytes and followed by a long tail
+    fn tailed_entry(key: i64) -> KeyValuePair {
+        KeyValuePair {
+            key: ByteBox::varchar(&format!("{:04}/{}", key, "payload".repeat(12)), KEY_LEN),
+            value: ByteBox::big_int(key),
+        }
+    }
+
+    fn leaves(tree: &BPTree) -> Vec<NodeInner> {
+        let mut leaves = vec![tree.find_min().unwrap()];
+
+        while let Some(next) = leaves.last().unwrap().next_pointer {
+            let page = TreePage::new(tree.flusher.read_drop(next));
+            leaves.push(tree.codec.decode(&page).unwrap());
+        }
+
+        leaves
+    }
+
+    fn root_keys(tree: &BPTree) -> Vec<ByteBox> {
+        let page = TreePage::new(tree.flusher.read_drop(*tree.root_page_id.borrow()));
+
+        match tree.codec.decode(&page).unwrap().node_type {
+            NodeType::Internal(_, keys, _) =>
+                keys
+                    .iter()
+                    .map(|key| NodeInner::deconstruct_value(key).0)
+                    .collect(),
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    fn leaf_prefix_round_trips() {
+        let entries: Vec<NodeKey> = (0..10)
+            .map(prefixed_entry)
+            .map(NodeKey::KeyValuePair)
+            .collect();
+
+        let leaf = NodeInner::new(NodeType::Leaf(entries.clone(), 3, None), true, 3, None);
+
+        // "tenants/acme-corporation/accounts/00000" is stored once instead of ten times
+        let prefix_len = "tenants/acme-corporation/accounts/00000".len();
+        assert_eq!(Codec::shared_prefix_len(&entries), prefix_len);
+        assert_eq!(
+            Codec::encoded_size(&leaf),
+            Codec::uncompressed_size(&leaf) - 9 * prefix_len
+        );
+
+        let codec = builder().build().unwrap().codec;
+        let decoded = codec.decode(&Codec::encode(&leaf).unwrap()).unwrap();
+
+        match decoded.node_type {
+            NodeType::Leaf(decoded, _, _) => assert_eq!(decoded, entries),
+            _ => panic!("Expected a leaf"),
+        }
+    }
+
+    #[test]
+    fn shared_prefixes_raise_fanout() {
+        let tree = builder().build().unwrap();
+
+        for idx in 0..2000i64 {
+            tree.insert(prefixed_entry((idx * 777) % 2000)).unwrap();
+        }
+
+        let expected: Vec<KeyValuePair> = (0..2000).map(prefixed_entry).collect();
+        assert_eq!(tree.scan(..).collect::<Vec<_>>(), expected);
+
+        // Stored in full the leaves would not fit in their pages
+        let leaves = leaves(&tree);
+        let full_size: usize = leaves.iter().map(Codec::uncompressed_size).sum();
+
+        assert!(leaves.iter().all(|leaf| Codec::encoded_size(leaf) <= PAGE_SIZE));
+        assert!(leaves.len() * PAGE_SIZE < full_size);
+
+        let loaded = builder().bulk_load(expected.clone()).unwrap();
+        assert!(self::leaves(&loaded).len() * PAGE_SIZE < full_size);
+        assert_eq!(loaded.scan(..).collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn separators_keep_distinguishing_bytes() {
+        let tree = builder().build().unwrap();
+
+        for idx in 0..600i64 {
+            tree.insert(tailed_entry((idx * 7) % 600)).unwrap();
+        }
+
+        // "0123" separates "0122/payload..." from "0123/payload..."
+        let keys = root_keys(&tree);
+        assert!(!keys.is_empty());
+        assert!(keys.iter().all(|key| key.data.len() <= 4), "{:?}", keys);
+
+        let loaded = builder().bulk_load((0..600).map(tailed_entry)).unwrap();
+        let keys = root_keys(&loaded);
+        assert!(!keys.is_empty());
+        assert!(keys.iter().all(|key| key.data.len() <= 4), "{:?}", keys);
+
+        for key in [0, 1, 299, 300, 599] {
+            let search = NodeKey::GuidePost(tailed_entry(key).key);
+
+            assert_eq!(tree.search(search.clone()).unwrap(), tailed_entry(key));
+            assert_eq!(loaded.search(search).unwrap(), tailed_entry(key));
+        }
+    }
+
+    #[test]
+    fn deletes_keep_compressed_leaves_consistent() {
+        let tree = builder().build().unwrap();
+
+        for idx in 0..1500i64 {
+            tree.insert(prefixed_entry((idx * 389) % 1500)).unwrap();
+        }
+
+        for key in (0..1500).filter(|key| key % 4 != 1) {
+            tree.delete(NodeKey::GuidePost(prefixed_entry(key).key)).unwrap();
+        }
+
+        let expected: Vec<KeyValuePair> = (0..1500)
+            .filter(|key| key % 4 == 1)
+            .map(prefixed_entry)
+            .collect();
+
+        assert_eq!(tree.scan(..).collect::<Vec<_>>(), expected);
+        assert_eq!(
+            tree.scan_rev(..).collect::<Vec<_>>(),
+            expected.iter().rev().cloned().collect::<Vec<_>>()
+        );
+
+        let search = NodeKey::GuidePost(prefixed_entry(733).key);
+        assert_eq!(tree.search(search).unwrap(), prefixed_entry(733));
+    }
+}
@@ -0,0 +1,151 @@
+#[cfg(test)]
+pub mod test {
+    use std::time::{ Duration, SystemTime, UNIX_EPOCH };
+
+    use crate::index::tree::{
+        byte_box::{ ByteBox, DataType },
+        db::{
+            btree_merge::{ integer_add, integer_max, MergeOperator },
+            btree_obj::{ BPTree, BTreeBuilder },
+        },
+        index_types::{ KeyValuePair, NodeKey },
+        tree_page::codec::Codec,
+    };
+
+    fn tree(
+        b: usize,
+        copy_on_write: bool,
+        value_type: DataType,
+        operator: MergeOperator
+    ) -> BPTree {
+        let mut builder = BTreeBuilder::new();
+        builder
+            .b_parameter(b)
+            .copy_on_write(copy_on_write)
+            .merge_operator(operator)
+            .tree_schema(Codec { key_type: DataType::BigInt, value_type });
+
+        builder.build().unwrap()
+    }
+
+    fn value_of(tree: &BPTree, key: i64) -> ByteBox {
+        tree.search(NodeKey::GuidePost(ByteBox::big_int(key))).unwrap().value
+    }
+
+    // Joins list items with commas
+    fn append(existing: Option<&ByteBox>, operand: &ByteBox) -> ByteBox {
+        match existing {
+            Some(list) => {
+                let list = String::from_utf8(list.data.to_vec()).unwrap();
+                let item = String::from_utf8(operand.data.to_vec()).unwrap();
+
+                ByteBox::varchar(&format!("{},{}", list, item), 6000)
+            }
+            None => operand.clone(),
+        }
+    }
+
+    #[test]
+    fn counters_add_up_under_merges() {
+        for (b, copy_on_write) in [(0, false), (2, false), (3, true)] {
+            let tree = tree(b, copy_on_write, DataType::BigInt, integer_add);
+
+            for round in 1..=3 {
+                for key in 0..200 {
+                    let merged = tree.merge(ByteBox::big_int(key), ByteBox::big_int(key * round));
+                    assert_eq!(merged.unwrap(), ByteBox::big_int((key * round * (round + 1)) / 2));
+                }
+            }
+
+            for key in 0..200 {
+                assert_eq!(value_of(&tree, key), ByteBox::big_int(key * 6), "b = {}", b);
+            }
+            assert_eq!(tree.scan(..).count(), 200);
+            assert_eq!(tree.verify(), vec![], "b = {}", b);
+
+            // Only integers can be added to a BigInt value
+            let mismatch = tree.merge(ByteBox::big_int(1), ByteBox::varchar("one", 10));
+            assert!(mismatch.is_err());
+            assert_eq!(value_of(&tree, 1), ByteBox::big_int(6));
+        }
+
+        let tree = tree(2, false, DataType::Int, integer_max);
+        for operand in [3, 9, -4, 7] {
+            tree.merge(ByteBox::big_int(5), ByteBox::int(operand)).unwrap();
+        }
+        assert_eq!(value_of(&tree, 5), ByteBox::int(9));
+    }
+
+    #[test]
+    fn custom_operators_grow_values_in_place() {
+        for (b, copy_on_write) in [(0, false), (2, true)] {
+            let tree = tree(b, copy_on_write, DataType::Varchar(6000), append);
+
+            // Lists grow long enough to move to overflow pages
+            for item in 0..300 {
+                for key in 0..4 {
+                    let item = ByteBox::varchar(&format!("item{}", item), 6000);
+                    tree.merge(ByteBox::big_int(key), item).unwrap();
+                }
+            }
+
+            let expected: Vec<String> = (0..300).map(|item| format!("item{}", item)).collect();
+            let expected = ByteBox::varchar(&expected.join(","), 6000);
+
+            for key in 0..4 {
+                assert_eq!(value_of(&tree, key), expected, "b = {}", b);
+            }
+            assert_eq!(tree.verify(), vec![], "b = {}", b);
+        }
+    }
+
+    #[test]
+    fn merges_respect_expiry_and_tree_settings() {
+        let tree = tree(2, false, DataType::BigInt, integer_add);
+
+        // An expired entry counts as missing, a live one keeps its expiry
+        let past = UNIX_EPOCH + Duration::from_secs(1);
+        let entry = |key: i64| KeyValuePair {
+            key: ByteBox::big_int(key),
+            value: ByteBox::big_int(10),
+        };
+
+        tree.set_with_expiry(entry(1), past).unwrap();
+        tree.set_with_ttl(entry(2), Duration::from_secs(3600)).unwrap();
+
+        let merged = tree.merge(ByteBox::big_int(1), ByteBox::big_int(5)).unwrap();
+        assert_eq!(merged, ByteBox::big_int(5));
+        let merged = tree.merge(ByteBox::big_int(2), ByteBox::big_int(5)).unwrap();
+        assert_eq!(merged, ByteBox::big_int(15));
+
+        let expires_at = |key: i64| {
+            tree.stored_value(&ByteBox::big_int(key)).unwrap().unwrap().expires_at()
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+
+        assert_eq!(expires_at(1), None);
+        assert!(expires_at(2).unwrap() > now);
+
+        // The operator is not stored in the file, the builder opening it supplies one
+        tree.flush().unwrap();
+        let path = tree.file_path().unwrap();
+
+        let plain = BTreeBuilder::new().build_from_file(&path).unwrap();
+        assert!(plain.merge(ByteBox::big_int(1), ByteBox::big_int(1)).is_err());
+
+        let mut builder = BTreeBuilder::new();
+        let reopened = builder.merge_operator(integer_max).build_from_file(&path).unwrap();
+        reopened.merge(ByteBox::big_int(2), ByteBox::big_int(40)).unwrap();
+        assert_eq!(value_of(&reopened, 2), ByteBox::big_int(40));
+
+        let mut builder = BTreeBuilder::new();
+        builder
+            .b_parameter(2)
+            .allow_duplicates(true)
+            .merge_operator(integer_add)
+            .tree_schema(Codec { key_type: DataType::BigInt, value_type: DataType::BigInt });
+        let duplicates = builder.build().unwrap();
+
+        assert!(duplicates.merge(ByteBox::big_int(1), ByteBox::big_int(1)).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+#[cfg(test)]
+pub mod test {
+    use crate::index::tree::{
+        byte_box::{ ByteBox, DataType },
+        db::btree_obj::BTreeBuilder,
+        index_types::{ KeyValuePair, NodeKey },
+        tree_page::{
+            codec::Codec,
+            meta::TreeMeta,
+            tree_page_layout::{ MetaPageHeader, META_PAGE_ID, PAGE_SIZE },
+        },
+    };
+
+    fn builder() -> BTreeBuilder {
+        let mut builder = BTreeBuilder::new();
+        builder.b_parameter(4).tree_schema(Codec {
+            key_type: DataType::BigInt,
+            value_type: DataType::Varchar(20),
+        });
+
+        builder
+    }
+
+    fn entry(key: i64) -> KeyValuePair {
+        KeyValuePair {
+            key: ByteBox::big_int(key),
+            value: ByteBox::varchar(&format!("value {}", key), 20),
+        }
+    }
+
+    #[test]
+    fn reopened_tree_keeps_root_and_entries() {
+        let tree = builder().build().unwrap();
+
+        for idx in 0..500i64 {
+            tree.insert(entry((idx * 37) % 500)).unwrap();
+        }
+        tree.flush().unwrap();
+
+        let reopened = BTreeBuilder::new().build_from_file(&tree.file_path().unwrap()).unwrap();
+
+        assert_eq!(*reopened.root_page_id.borrow(), *tree.root_page_id.borrow());
+        assert_eq!(reopened.b, 4);
+        assert!(reopened.verify().is_empty());
+
+        for key in [0i64, 1, 250, 499] {
+            let found = reopened.search(NodeKey::GuidePost(ByteBox::big_int(key))).unwrap();
+            assert_eq!(found, entry(key));
+        }
+
+        assert_eq!(reopened.stats().unwrap().num_entries, 500);
+    }
+
+    #[test]
+    fn root_collapse_is_persisted() {
+        let tree = builder().build().unwrap();
+
+        for key in 0..200i64 {
+            tree.insert(entry(key)).unwrap();
+        }
+        for key in 0..198i64 {
+            tree.delete(NodeKey::GuidePost(ByteBox::big_int(key))).unwrap();
+        }
+        tree.flush().unwrap();
+
+        let reopened = BTreeBuilder::new().build_from_file(&tree.file_path().unwrap()).unwrap();
+
+        assert_eq!(*reopened.root_page_id.borrow(), *tree.root_page_id.borrow());
+        assert_eq!(reopened.stats().unwrap().num_entries, 2);
+    }
+
+    #[test]
+    fn bulk_loaded_duplicates_tree_reopens() {
+        let mut builder = builder();
+        builder.allow_duplicates(true).fill_factor(0.5);
+
+        // Every key three times, each with its own value
+        let entries: Vec<KeyValuePair> = (0..300i64)
+            .map(|idx| KeyValuePair {
+                key: ByteBox::big_int(idx / 3),
+                value: ByteBox::varchar(&format!("value {}", idx % 3), 20),
+            })
+            .collect();
+        let tree = builder.bulk_load(entries).unwrap();
+        tree.flush().unwrap();
+
+        let reopened = BTreeBuilder::new().build_from_file(&tree.file_path().unwrap()).unwrap();
+
+        assert!(reopened.allow_duplicates);
+        assert_eq!(reopened.fill_factor, 0.5);
+        assert_eq!(reopened.codec.key_type, tree.codec.key_type);
+        assert_eq!(reopened.stats().unwrap().num_entries, 300);
+    }
+
+    #[test]
+    fn reopened_tree_keeps_its_log_id() {
+        let logged = builder().log_id(42).build().unwrap();
+        let unnamed = builder().build().unwrap();
+
+        for tree in [&logged, &unnamed] {
+            tree.flush().unwrap();
+
+            let reopened = BTreeBuilder::new()
+                .log_id(7)
+                .build_from_file(&tree.file_path().unwrap())
+                .unwrap();
+            assert_eq!(reopened.log_id, tree.log_id);
+        }
+        assert_eq!(logged.log_id, 42);
+    }
+
+    #[test]
+    fn unknown_format_version_is_rejected() {
+        let tree = builder().build().unwrap();
+
+        let mut raw = tree.flusher.read_drop(META_PAGE_ID);
+        let offset = MetaPageHeader::FormatVersion.offset();
+        raw[offset..offset + 4].copy_from_slice(&99u32.to_le_bytes());
+
+        assert!(TreeMeta::decode(&raw).is_err());
+        assert!(TreeMeta::decode(&[0u8; PAGE_SIZE]).is_err());
+    }
+}
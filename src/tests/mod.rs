@@ -1,3 +1,5 @@
+#[cfg(test)]
+pub mod fixtures;
 pub mod db_io_test;
 pub mod page_test;
 pub mod scheduler_test;
@@ -6,4 +8,27 @@ pub mod tuple_test;
 pub mod flusher_test;
 pub mod codec_test;
 pub mod tree_api_test;
-pub mod iterator_test;
\ No newline at end of file
+pub mod iterator_test;
+pub mod bulk_load_test;
+pub mod composite_key_test;
+pub mod duplicate_keys_test;
+pub mod overflow_test;
+pub mod node_size_test;
+pub mod key_compression_test;
+pub mod verify_test;
+pub mod stats_test;
+pub mod meta_page_test;
+pub mod range_delete_test;
+pub mod write_batch_test;
+pub mod cursor_test;
+pub mod order_stats_test;
+pub mod snapshot_test;
+pub mod cas_test;
+pub mod ttl_test;
+pub mod merge_test;
+pub mod projection_test;
+pub mod rebuild_test;
+pub mod hash_index_test;
+pub mod index_trait_test;
+pub mod secondary_index_test;
+pub mod dump_test;
\ No newline at end of file
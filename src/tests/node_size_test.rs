@@ -0,0 +1,143 @@
+#[cfg(test)]
+pub mod test {
+    use crate::index::tree::{
+        byte_box::{ ByteBox, DataType },
+        db::btree_obj::{ BPTree, BTreeBuilder },
+        index_types::{ KeyValuePair, NodeKey },
+        tree_node::tree_node_inner::NodeInner,
+        tree_page::{ codec::Codec, page::TreePage, tree_page_layout::PAGE_SIZE },
+    };
+
+    fn leaves(tree: &BPTree) -> Vec<NodeInner> {
+        let mut leaves = vec![tree.find_min().unwrap()];
+
+        while let Some(next) = leaves.last().unwrap().next_pointer {
+            let page = TreePage::new(tree.flusher.read_drop(next));
+            leaves.push(tree.codec.decode(&page).unwrap());
+        }
+
+        leaves
+    }
+
+    fn small_tree() -> BTreeBuilder {
+        let mut builder = BTreeBuilder::new();
+        builder.tree_schema(Codec {
+            key_type: DataType::BigInt,
+            value_type: DataType::BigInt,
+        });
+
+        builder
+    }
+
+    fn small_entry(key: i64) -> KeyValuePair {
+        KeyValuePair {
+            key: ByteBox::big_int(key),
+            value: ByteBox::big_int(key * 10),
+        }
+    }
+
+    fn wide_tree() -> BTreeBuilder {
+        let mut builder = BTreeBuilder::new();
+        builder.tree_schema(Codec {
+            key_type: DataType::Varchar(400),
+            value_type: DataType::Varchar(1000),
+        });
+
+        builder
+    }
+
+    // Keys of 400 bytes with values of 1000, so only two entries share a leaf. Keys differ in
+    // their leading bytes, leaving no prefix for the leaf to share.
+    fn wide_entry(key: i64) -> KeyValuePair {
+        KeyValuePair {
+            key: ByteBox::varchar(&format!("{:03}{}", key, "k".repeat(397)), 400),
+            value: ByteBox::varchar(&format!("{:x>1000}", key), 1000),
+        }
+    }
+
+    #[test]
+    fn small_entries_pack_pages() {
+        let tree = small_tree().build().unwrap();
+
+        for idx in 0..1000i64 {
+            tree.insert(small_entry((idx * 389) % 1000)).unwrap();
+        }
+
+        let expected: Vec<KeyValuePair> = (0..1000).map(small_entry).collect();
+        assert_eq!(tree.scan(..).collect::<Vec<_>>(), expected);
+
+        // 24 bytes an entry puts about 170 in a page, splits leave leaves at least half full
+        let leaves = leaves(&tree);
+        assert!(leaves.len() <= 12, "{} leaves", leaves.len());
+        assert!(leaves.iter().all(|leaf| Codec::encoded_size(leaf) <= PAGE_SIZE));
+
+        for key in 0..950 {
+            tree.delete(NodeKey::GuidePost(ByteBox::big_int(key))).unwrap();
+        }
+
+        let expected: Vec<KeyValuePair> = (950..1000).map(small_entry).collect();
+        assert_eq!(tree.scan(..).collect::<Vec<_>>(), expected);
+        assert_eq!(self::leaves(&tree).len(), 1);
+    }
+
+    #[test]
+    fn wide_entries_split_by_size() {
+        let tree = wide_tree().build().unwrap();
+
+        for idx in 0..120i64 {
+            tree.insert(wide_entry((idx * 47) % 120)).unwrap();
+        }
+
+        let leaves = leaves(&tree);
+        assert!(leaves.iter().all(|leaf| leaf.get_key_array_length() <= 2));
+        assert!(leaves.len() >= 60);
+
+        for key in (0..120).step_by(3) {
+            tree.delete(NodeKey::GuidePost(wide_entry(key).key)).unwrap();
+        }
+
+        let expected: Vec<KeyValuePair> = (0..120)
+            .filter(|key| key % 3 != 0)
+            .map(wide_entry)
+            .collect();
+
+        assert_eq!(tree.scan(..).collect::<Vec<_>>(), expected);
+        assert_eq!(tree.search(NodeKey::GuidePost(wide_entry(61).key)).unwrap(), wide_entry(61));
+    }
+
+    #[test]
+    fn bulk_load_packs_to_fill_factor() {
+        let entries: Vec<KeyValuePair> = (0..1000).map(small_entry).collect();
+
+        let tree = small_tree().fill_factor(0.5).bulk_load(entries.clone()).unwrap();
+        let half_full = leaves(&tree);
+
+        let tree = small_tree().fill_factor(1.0).bulk_load(entries.clone()).unwrap();
+        let full = leaves(&tree);
+
+        assert_eq!(tree.scan(..).collect::<Vec<_>>(), entries);
+        assert!(half_full.iter().all(|leaf| Codec::encoded_size(leaf) <= PAGE_SIZE / 2 + 24));
+        assert!(full.len() * 2 <= half_full.len() + 1);
+
+        let tree = wide_tree().bulk_load((0..50).map(wide_entry)).unwrap();
+        assert_eq!(tree.scan(..).collect::<Vec<_>>(), (0..50).map(wide_entry).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn oversized_keys_are_rejected() {
+        let mut builder = BTreeBuilder::new();
+        builder.tree_schema(Codec {
+            key_type: DataType::Varchar(2000),
+            value_type: DataType::BigInt,
+        });
+        let tree = builder.build().unwrap();
+
+        let entry = KeyValuePair {
+            key: ByteBox::varchar(&"k".repeat(1500), 2000),
+            value: ByteBox::big_int(1),
+        };
+
+        let err = tree.insert(entry).err().unwrap();
+        assert!(err.to_string().contains("Key too large"));
+    }
+}
@@ -0,0 +1,174 @@
+#[cfg(test)]
+pub mod test {
+    use crate::{
+        index::tree::{
+            byte_box::{ ByteBox, DataType },
+            db::{ btree_batch::WriteBatch, btree_obj::{ BPTree, BTreeBuilder } },
+            index_types::{ KeyValuePair, NodeKey },
+            tree_page::codec::Codec,
+        },
+        tests::fixtures::{ self, entry },
+    };
+
+    fn builder(b: usize) -> BTreeBuilder {
+        let mut builder = fixtures::builder(b);
+        builder.order_statistics(true);
+
+        builder
+    }
+
+    /// Compares every query against the sorted keys the tree should hold
+    fn check(tree: &BPTree, model: &[i64]) {
+        assert_eq!(tree.verify(), vec![]);
+
+        assert_eq!(tree.count_range(..).unwrap(), model.len() as u64);
+        assert_eq!(tree.select(model.len() as u64).unwrap(), None);
+
+        for (idx, key) in model.iter().enumerate().step_by(7) {
+            assert_eq!(tree.select(idx as u64).unwrap(), Some(ByteBox::big_int(*key)));
+            assert_eq!(tree.rank(&ByteBox::big_int(*key)).unwrap(), idx as u64);
+
+            // Absent keys rank where they would be inserted
+            let below = model.partition_point(|other| *other < key + 1);
+            assert_eq!(tree.rank(&ByteBox::big_int(key + 1)).unwrap(), below as u64);
+        }
+
+        for (start, end) in [(-5, 40), (100, 101), (333, 777), (900, 2000)] {
+            let expected = model
+                .iter()
+                .filter(|key| (start..end).contains(*key))
+                .count();
+            let range = ByteBox::big_int(start)..ByteBox::big_int(end);
+
+            assert_eq!(tree.count_range(range).unwrap(), expected as u64);
+
+            let expected = model
+                .iter()
+                .filter(|key| (start..=end).contains(*key))
+                .count();
+            let range = ByteBox::big_int(start)..=ByteBox::big_int(end);
+
+            assert_eq!(tree.count_range(range).unwrap(), expected as u64);
+        }
+    }
+
+    #[test]
+    fn counts_follow_splits_merges_and_borrows() {
+        for b in [2, 3] {
+            let tree = builder(b).build().unwrap();
+            let mut model: Vec<i64> = Vec::new();
+
+            for idx in 0..600 {
+                let key = ((idx * 151) % 600) * 2;
+                tree.insert(entry(key)).unwrap();
+                model.push(key);
+            }
+            model.sort();
+            check(&tree, &model);
+
+            for idx in 0..400 {
+                let key = ((idx * 77) % 600) * 2;
+                tree.delete(NodeKey::GuidePost(ByteBox::big_int(key))).unwrap();
+                model.retain(|other| *other != key);
+            }
+            check(&tree, &model);
+
+            // Replacing values leaves the counts as they are
+            for key in model.clone().into_iter().step_by(5) {
+                tree.put(entry(key)).unwrap();
+            }
+            check(&tree, &model);
+        }
+    }
+
+    #[test]
+    fn counts_survive_bulk_loads_range_deletes_and_batches() {
+        let tree = builder(3)
+            .bulk_load((0..1000).map(entry))
+            .unwrap();
+        let mut model: Vec<i64> = (0..1000).collect();
+        check(&tree, &model);
+
+        tree.delete_range(ByteBox::big_int(200)..ByteBox::big_int(650)).unwrap();
+        model.retain(|key| !(200..650).contains(key));
+        check(&tree, &model);
+
+        let mut batch = WriteBatch::new();
+        for key in (300..500).step_by(2) {
+            batch.put(&tree, entry(key));
+            model.push(key);
+        }
+        for key in (0..200).step_by(3) {
+            batch.delete(&tree, ByteBox::big_int(key));
+        }
+        batch.apply(None).unwrap();
+
+        model.retain(|key| *key >= 200 || key % 3 != 0);
+        model.sort();
+        check(&tree, &model);
+
+        let mut cursor = tree.cursor();
+        let mut positioned = cursor.seek(&ByteBox::big_int(700)).unwrap();
+        while positioned {
+            positioned = cursor.delete_current().unwrap();
+            positioned = positioned && cursor.next().unwrap();
+        }
+
+        let mut kept = model.iter().filter(|key| **key >= 700);
+        let mut dropped = Vec::new();
+        while let (Some(_), Some(next)) = (kept.next(), kept.next()) {
+            dropped.push(*next);
+        }
+        model.retain(|key| *key < 700 || dropped.contains(key));
+        check(&tree, &model);
+    }
+
+    #[test]
+    fn duplicates_count_every_entry() {
+        let mut builder = BTreeBuilder::new();
+        builder.b_parameter(2).allow_duplicates(true).order_statistics(true).tree_schema(Codec {
+            key_type: DataType::BigInt,
+            value_type: DataType::Int,
+        });
+        let tree = builder.build().unwrap();
+
+        for key in 0..100 {
+            for value in 0..3 {
+                tree.insert(KeyValuePair { key: ByteBox::big_int(key), value: ByteBox::int(value) })
+                    .unwrap();
+            }
+        }
+        assert_eq!(tree.verify(), vec![]);
+
+        assert_eq!(tree.rank(&ByteBox::big_int(40)).unwrap(), 120);
+        assert_eq!(tree.select(121).unwrap(), Some(ByteBox::big_int(40)));
+        assert_eq!(tree.count_range(ByteBox::big_int(10)..=ByteBox::big_int(19)).unwrap(), 30);
+        assert_eq!(tree.count_range(ByteBox::big_int(10)..ByteBox::big_int(19)).unwrap(), 27);
+
+        tree.delete(NodeKey::GuidePost(ByteBox::big_int(15))).unwrap();
+        assert_eq!(tree.count_range(ByteBox::big_int(10)..=ByteBox::big_int(19)).unwrap(), 27);
+        assert_eq!(tree.verify(), vec![]);
+    }
+
+    #[test]
+    fn reopened_trees_keep_their_counts() {
+        let tree = builder(4).build().unwrap();
+        for idx in 0..300 {
+            tree.insert(entry((idx * 37) % 300)).unwrap();
+        }
+        tree.flush().unwrap();
+
+        let reopened = BTreeBuilder::new().build_from_file(&tree.file_path().unwrap()).unwrap();
+
+        assert_eq!(reopened.verify(), vec![]);
+        assert_eq!(reopened.rank(&ByteBox::big_int(150)).unwrap(), 150);
+
+        // Trees built without order statistics have nothing to answer from
+        let plain = builder(4).order_statistics(false).build().unwrap();
+        plain.insert(entry(1)).unwrap();
+
+        assert!(plain.count_range(..).is_err());
+        assert!(plain.rank(&ByteBox::big_int(1)).is_err());
+        assert!(plain.select(0).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+#[cfg(test)]
+pub mod test {
+    use crate::index::tree::{
+        byte_box::{ ByteBox, DataType },
+        db::btree_obj::{ BPTree, BTreeBuilder },
+        index_types::{ KeyValuePair, NodeKey },
+        tree_page::{ codec::Codec, overflow::OverflowChain, tree_page_layout::PAGE_SIZE },
+    };
+
+    const MAX_VALUE_LEN: usize = 20_000;
+
+    fn builder() -> BTreeBuilder {
+        let mut builder = BTreeBuilder::new();
+        builder.b_parameter(2).tree_schema(Codec {
+            key_type: DataType::BigInt,
+            value_type: DataType::Varchar(MAX_VALUE_LEN),
+        });
+
+        builder
+    }
+
+    // Sizes cycle through inline values, single page chains and chains of several pages
+    fn value(key: i64) -> String {
+        let len = [12, 900, 1500, 4090, 9000, 17000][(key % 6) as usize];
+
+        (0..len).map(|idx| (b'a' + (((idx as i64) + key) % 26) as u8) as char).collect()
+    }
+
+    fn entry(key: i64, value: &str) -> KeyValuePair {
+        KeyValuePair {
+            key: ByteBox::big_int(key),
+            value: ByteBox::varchar(value, MAX_VALUE_LEN),
+        }
+    }
+
+    fn search(tree: &BPTree, key: i64) -> KeyValuePair {
+        tree.search(NodeKey::GuidePost(ByteBox::big_int(key))).unwrap()
+    }
+
+    fn chain_pages(tree: &BPTree, key: i64) -> Vec<u32> {
+        let search = NodeKey::GuidePost(ByteBox::big_int(key));
+        let leaf = tree.find_leaf(search.clone()).unwrap();
+        let stored = leaf.get_key_value(&search).unwrap();
+
+        OverflowChain::from_stub(&stored.value).unwrap().pages(&tree.flusher).unwrap()
+    }
+
+    #[test]
+    fn large_values_round_trip() {
+        let tree = builder().build().unwrap();
+
+        let keys: Vec<i64> = (0..40).map(|idx| (idx * 17) % 40).collect();
+        for key in keys.iter() {
+            tree.insert(entry(*key, &value(*key))).unwrap();
+        }
+
+        for key in 0..40 {
+            assert_eq!(search(&tree, key), entry(key, &value(key)));
+        }
+
+        let scanned: Vec<KeyValuePair> = tree.scan(..).collect();
+        let expected: Vec<KeyValuePair> = (0..40).map(|key| entry(key, &value(key))).collect();
+        assert_eq!(scanned, expected);
+
+        let reversed: Vec<KeyValuePair> = tree.scan_rev(..).collect();
+        assert_eq!(reversed, expected.into_iter().rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn only_large_values_leave_the_leaf() {
+        let tree = builder().build().unwrap();
+
+        tree.insert(entry(1, &value(1))).unwrap();
+        tree.insert(entry(4, &value(4))).unwrap();
+
+        let search = NodeKey::GuidePost(ByteBox::big_int(1));
+        let leaf = tree.find_leaf(search.clone()).unwrap();
+
+        assert_eq!(leaf.get_key_value(&search).unwrap().value.datatype, DataType::Varchar(MAX_VALUE_LEN));
+        assert_eq!(chain_pages(&tree, 4).len(), 3);
+    }
+
+    #[test]
+    fn delete_frees_the_chain() {
+        let tree = builder().build().unwrap();
+
+        for key in 0..6 {
+            tree.insert(entry(key, &value(key))).unwrap();
+        }
+
+        let mut freed = chain_pages(&tree, 5);
+        tree.delete(NodeKey::GuidePost(ByteBox::big_int(5))).unwrap();
+
+        // The next pages handed out are the ones the chain gave back
+        let mut reused: Vec<u32> = freed
+            .iter()
+            .map(|_| tree.flusher.new_page())
+            .collect();
+
+        freed.sort();
+        reused.sort();
+        assert_eq!(reused, freed);
+
+        for key in 0..5 {
+            assert_eq!(search(&tree, key), entry(key, &value(key)));
+        }
+    }
+
+    #[test]
+    fn put_replaces_large_values() {
+        let tree = builder().build().unwrap();
+
+        for key in 0..12 {
+            tree.insert(entry(key, &value(key))).unwrap();
+        }
+
+        let old_pages = chain_pages(&tree, 4);
+
+        let replacement = "z".repeat(6000);
+        tree.put(entry(4, &replacement)).unwrap();
+        assert_eq!(search(&tree, 4), entry(4, &replacement));
+
+        // A small value replacing a large one is stored inline again
+        tree.put(entry(5, "short")).unwrap();
+        assert_eq!(search(&tree, 5), entry(5, "short"));
+
+        // The old chain of key 4 went back to the free list and now holds key 4's new value
+        let mut new_pages = chain_pages(&tree, 4);
+        new_pages.truncate(old_pages.len());
+        assert!(new_pages.iter().all(|page| old_pages.contains(page)));
+
+        let scanned: Vec<KeyValuePair> = tree.scan(..).collect();
+        assert_eq!(scanned.len(), 12);
+    }
+
+    #[test]
+    fn bulk_load_moves_large_values() {
+        let entries: Vec<KeyValuePair> = (0..60).map(|key| entry(key, &value(key))).collect();
+
+        let tree = builder().bulk_load(entries.clone()).unwrap();
+
+        assert_eq!(tree.scan(..).collect::<Vec<_>>(), entries);
+        assert_eq!(search(&tree, 29), entry(29, &value(29)));
+    }
+
+    #[test]
+    fn scans_stop_at_an_unreadable_chain() {
+        let tree = builder().build().unwrap();
+
+        for key in 0..12 {
+            tree.insert(entry(key, &value(key))).unwrap();
+        }
+
+        // Key 4 points at a chain whose first page no longer holds overflow data
+        let page = chain_pages(&tree, 4)[0];
+        tree.flusher.write_flush([0u8; PAGE_SIZE], page).unwrap();
+
+        let mut scan = tree.scan(..);
+        let read: Vec<KeyValuePair> = scan.by_ref().collect();
+
+        assert_eq!(read, (0..4).map(|key| entry(key, &value(key))).collect::<Vec<_>>());
+        assert!(scan.error().is_some());
+        assert_eq!(scan.next(), None);
+
+        // Reading backwards stops at the same entry
+        let mut scan = tree.scan(..).rev();
+        assert_eq!(scan.by_ref().count(), 7);
+
+        let mut past = tree.scan(ByteBox::big_int(5)..);
+        assert_eq!(past.by_ref().count(), 7);
+        assert!(past.error().is_none());
+    }
+}
@@ -0,0 +1,148 @@
+#[cfg(test)]
+pub mod test {
+    use std::time::{ Duration, SystemTime, UNIX_EPOCH };
+
+    use crate::{
+        catalog::schema::SchemaDataBuilder,
+        index::tree::{
+            byte_box::{ ByteBox, DataType },
+            db::btree_obj::{ BPTree, BTreeBuilder },
+            index_types::KeyValuePair,
+            tree_page::codec::Codec,
+        },
+        storage::tuple::{ to_flat_schema, Tuple },
+    };
+
+    // Every fifth row has notes long enough to move the whole tuple to overflow pages. Schemas
+    // put strings last, so the row does as well.
+    fn row(key: i64) -> Tuple {
+        let len = if key % 5 == 0 { 250 } else { 4 };
+
+        let mut builder = SchemaDataBuilder::new()
+            .add_big_int(String::from("id"), ByteBox::big_int(key))
+            .add_small_int(String::from("score"), ByteBox::small_int(key as i16))
+            .add_varchar(String::from("name"), ByteBox::varchar(&format!("user {}", key), 20));
+
+        for note in ["a", "b", "c", "d", "e"] {
+            let text = note.repeat(len);
+            builder = builder.add_varchar(format!("note_{}", note), ByteBox::varchar(&text, 250));
+        }
+
+        Tuple::TupleData(builder.build())
+    }
+
+    fn projected(key: i64) -> ByteBox {
+        let Tuple::TupleData(values) = row(key);
+        let score = values.iter().find(|value| value.name == "score").unwrap().clone();
+        let name = values.iter().find(|value| value.name == "name").unwrap().clone();
+
+        ByteBox::tuple(&Tuple::TupleData(vec![score, name]))
+    }
+
+    fn tuple_tree(b: usize, copy_on_write: bool) -> BPTree {
+        let mut builder = BTreeBuilder::new();
+        builder.b_parameter(b).copy_on_write(copy_on_write).tree_schema(Codec {
+            key_type: DataType::BigInt,
+            value_type: DataType::Tuple(to_flat_schema(&row(0))),
+        });
+
+        let tree = builder.build().unwrap();
+
+        // Keys divisible by seven have expired
+        for key in 0..300 {
+            let value = ByteBox::tuple(&row(key));
+            let entry = KeyValuePair { key: ByteBox::big_int(key), value };
+
+            if key % 7 == 0 {
+                tree.set_with_expiry(entry, UNIX_EPOCH + Duration::from_secs(1)).unwrap();
+            } else {
+                tree.put(entry).unwrap();
+            }
+        }
+
+        tree
+    }
+
+    fn live() -> impl DoubleEndedIterator<Item = i64> {
+        (0..300).filter(|key| key % 7 != 0)
+    }
+
+    #[test]
+    fn key_scans_hand_out_every_live_key() {
+        for (b, copy_on_write) in [(0, false), (2, false), (3, true)] {
+            let tree = tuple_tree(b, copy_on_write);
+
+            let keys: Vec<ByteBox> = tree.scan_keys(..).collect();
+            assert_eq!(keys, live().map(ByteBox::big_int).collect::<Vec<_>>(), "b = {}", b);
+
+            let reversed: Vec<ByteBox> = tree.scan_keys(..).rev().collect();
+            assert_eq!(reversed, live().rev().map(ByteBox::big_int).collect::<Vec<_>>());
+
+            let bounded = tree.scan_keys(ByteBox::big_int(10)..ByteBox::big_int(20));
+            assert_eq!(bounded.count(), 9, "b = {}", b);
+        }
+
+        // A tree with duplicates hands a key out once per value
+        let mut builder = BTreeBuilder::new();
+        let duplicates = builder
+            .b_parameter(2)
+            .allow_duplicates(true)
+            .tree_schema(Codec { key_type: DataType::BigInt, value_type: DataType::Int })
+            .build()
+            .unwrap();
+
+        for value in 0..40 {
+            let key = ByteBox::big_int((value % 4) as i64);
+            duplicates.insert(KeyValuePair { key, value: ByteBox::int(value) }).unwrap();
+        }
+
+        let range = ByteBox::big_int(1)..=ByteBox::big_int(2);
+        let keys: Vec<ByteBox> = duplicates.scan_keys(range).collect();
+        assert_eq!(keys.len(), 20);
+        assert!(keys[..10].iter().all(|key| *key == ByteBox::big_int(1)));
+        assert!(keys[10..].iter().all(|key| *key == ByteBox::big_int(2)));
+    }
+
+    #[test]
+    fn projected_scans_hand_out_the_named_columns() {
+        for (b, copy_on_write) in [(0, false), (2, false), (3, true)] {
+            let tree = tuple_tree(b, copy_on_write);
+
+            let rows: Vec<KeyValuePair> = tree
+                .scan_project(.., &["score", "name"])
+                .unwrap()
+                .collect();
+            let expected: Vec<KeyValuePair> = live()
+                .map(|key| KeyValuePair { key: ByteBox::big_int(key), value: projected(key) })
+                .collect();
+            assert_eq!(rows, expected, "b = {}", b);
+
+            let last = tree.scan_project(.., &["score", "name"]).unwrap().next_back().unwrap();
+            assert_eq!(last.value, projected(299));
+
+            // Whole scans are left as they were
+            let whole = tree.scan(ByteBox::big_int(5)..=ByteBox::big_int(5)).next().unwrap();
+            assert_eq!(whole.value, ByteBox::tuple(&row(5)));
+        }
+    }
+
+    #[test]
+    fn projected_scans_need_known_tuple_columns() {
+        let tree = tuple_tree(2, false);
+        assert!(tree.scan_project(.., &["score", "age"]).is_err());
+
+        let mut builder = BTreeBuilder::new();
+        let scalars = builder
+            .b_parameter(2)
+            .tree_schema(Codec { key_type: DataType::BigInt, value_type: DataType::Int })
+            .build()
+            .unwrap();
+
+        assert!(scalars.scan_project(.., &["score"]).is_err());
+
+        let soon = SystemTime::now() + Duration::from_secs(3600);
+        let entry = KeyValuePair { key: ByteBox::big_int(1), value: ByteBox::int(1) };
+        scalars.set_with_expiry(entry, soon).unwrap();
+        assert_eq!(scalars.scan_keys(..).collect::<Vec<_>>(), vec![ByteBox::big_int(1)]);
+    }
+}
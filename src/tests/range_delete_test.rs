@@ -0,0 +1,121 @@
+#[cfg(test)]
+pub mod test {
+    use std::ops::Bound;
+
+    use crate::{
+        index::tree::{
+            byte_box::{ ByteBox, DataType },
+            db::btree_obj::{ BPTree, BTreeBuilder },
+            index_types::KeyValuePair,
+            tree_page::codec::Codec,
+        },
+        tests::fixtures::{ builder, entry },
+    };
+
+    fn populated(b: usize, len: i64) -> BPTree {
+        let tree = builder(b).build().unwrap();
+
+        for idx in 0..len {
+            tree.insert(entry((idx * 151) % len)).unwrap();
+        }
+
+        tree
+    }
+
+    fn keys(tree: &BPTree) -> Vec<i64> {
+        tree.scan(..)
+            .map(|entry| i64::from_le_bytes(entry.key.data[..].try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn removes_a_range_from_the_middle() {
+        for b in [0, 2, 3] {
+            let tree = populated(b, 400);
+            let before = tree.stats().unwrap();
+
+            let removed = tree.delete_range(ByteBox::big_int(50)..ByteBox::big_int(350)).unwrap();
+
+            assert_eq!(removed, 300, "b = {}", b);
+            assert_eq!(tree.verify(), vec![], "b = {}", b);
+            assert_eq!(keys(&tree), (0..50).chain(350..400).collect::<Vec<_>>(), "b = {}", b);
+
+            let after = tree.stats().unwrap();
+            assert!(after.num_leaves() < before.num_leaves());
+            assert!(after.free_pages > before.free_pages);
+
+            // Reverse scans follow the relinked chain too
+            let rev: Vec<i64> = tree
+                .scan_rev(..)
+                .map(|entry| i64::from_le_bytes(entry.key.data[..].try_into().unwrap()))
+                .collect();
+            assert_eq!(rev, (0..50).chain(350..400).rev().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn matches_per_key_deletes() {
+        let ranges = [
+            (Bound::Unbounded, Bound::Excluded(40)),
+            (Bound::Included(100), Bound::Included(100)),
+            (Bound::Excluded(150), Bound::Included(260)),
+            (Bound::Included(390), Bound::Unbounded),
+            (Bound::Included(500), Bound::Unbounded),
+        ];
+
+        let tree = populated(2, 400);
+        let mut model: Vec<i64> = (0..400).collect();
+
+        for (start, end) in ranges {
+            let range = (start.map(ByteBox::big_int), end.map(ByteBox::big_int));
+            let expected = model.len();
+            model.retain(|key| !std::ops::RangeBounds::contains(&(start, end), key));
+
+            assert_eq!(tree.delete_range(range).unwrap(), expected - model.len());
+            assert_eq!(tree.verify(), vec![]);
+            assert_eq!(keys(&tree), model);
+        }
+    }
+
+    #[test]
+    fn clearing_the_tree_leaves_an_empty_root() {
+        let tree = populated(3, 400);
+
+        assert_eq!(tree.delete_range(..).unwrap(), 400);
+        assert_eq!(tree.verify(), vec![]);
+
+        let stats = tree.stats().unwrap();
+        assert_eq!(stats.height, 1);
+        assert_eq!(stats.num_entries, 0);
+
+        for key in 0..100 {
+            tree.insert(entry(key)).unwrap();
+        }
+        assert_eq!(tree.verify(), vec![]);
+        assert_eq!(keys(&tree), (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn removes_every_duplicate_of_the_range() {
+        let mut builder = BTreeBuilder::new();
+        builder.b_parameter(3).allow_duplicates(true).tree_schema(Codec {
+            key_type: DataType::BigInt,
+            value_type: DataType::Int,
+        });
+        let tree = builder.build().unwrap();
+
+        for key in 0..100i64 {
+            for value in 0..3 {
+                tree.insert(KeyValuePair { key: ByteBox::big_int(key), value: ByteBox::int(value) })
+                    .unwrap();
+            }
+        }
+
+        let removed = tree.delete_range(ByteBox::big_int(10)..=ByteBox::big_int(89)).unwrap();
+
+        assert_eq!(removed, 240);
+        assert_eq!(tree.verify(), vec![]);
+        assert_eq!(tree.scan(..).count(), 60);
+        assert_eq!(tree.scan(ByteBox::big_int(89)..=ByteBox::big_int(90)).count(), 3);
+    }
+}
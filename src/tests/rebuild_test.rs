@@ -0,0 +1,159 @@
+#[cfg(test)]
+pub mod test {
+    use crate::{
+        index::tree::{
+            byte_box::ByteBox,
+            db::{ btree_obj::{ BPTree, BTreeBuilder }, btree_rebuild::TreeRebuild },
+            index_types::{ KeyValuePair, NodeKey },
+            tree_node::node_type::PagePointer,
+        },
+        tests::fixtures::{ self, entry },
+    };
+
+    fn builder(b: usize, copy_on_write: bool, order_statistics: bool) -> BTreeBuilder {
+        let mut builder = fixtures::builder(b);
+        builder.copy_on_write(copy_on_write).order_statistics(order_statistics);
+
+        builder
+    }
+
+    // Inserted out of order and then thinned out, which leaves half empty leaves behind
+    fn fragmented(tree: &BPTree, len: i64) -> Vec<KeyValuePair> {
+        for idx in 0..len {
+            tree.insert(entry((idx * 37) % len)).unwrap();
+        }
+
+        for key in (0..len).filter(|key| key % 4 != 0) {
+            tree.delete(NodeKey::GuidePost(ByteBox::big_int(key))).unwrap();
+        }
+
+        (0..len)
+            .filter(|key| key % 4 == 0)
+            .map(entry)
+            .collect()
+    }
+
+    fn leaf_pages(tree: &BPTree) -> Vec<PagePointer> {
+        let mut pages = Vec::new();
+        let mut leaf = Some(tree.find_min().unwrap());
+
+        while let Some(current) = leaf {
+            pages.push(current.pointer);
+            leaf = tree.neighbour_leaf(&current, true).unwrap();
+        }
+
+        pages
+    }
+
+    #[test]
+    fn rebuild_packs_leaves_onto_contiguous_pages() {
+        for (b, order_statistics) in [(0, false), (2, true), (3, false), (0, true)] {
+            let tree = builder(b, true, order_statistics).build().unwrap();
+            let live = fragmented(&tree, 800);
+
+            let leaves_before = tree.stats().unwrap().num_leaves();
+            let free_before = tree.flusher.free_page_count();
+
+            tree.rebuild().unwrap();
+
+            assert_eq!(tree.verify(), vec![], "b = {}", b);
+            assert_eq!(tree.scan(..).collect::<Vec<_>>(), live, "b = {}", b);
+            assert_eq!(tree.scan_rev(..).count(), live.len());
+            assert!(tree.stats().unwrap().num_leaves() < leaves_before, "b = {}", b);
+
+            // Leaves follow one another through the file in key order
+            let pages = leaf_pages(&tree);
+            assert!(pages.windows(2).all(|pair| pair[1] == pair[0] + 1), "b = {}", b);
+
+            // Pages of the old tree are freed, overflow pages are shared with the new one
+            assert!(tree.flusher.free_page_count() > free_before, "b = {}", b);
+            assert_eq!(tree.search(NodeKey::GuidePost(ByteBox::big_int(28))).unwrap(), entry(28));
+
+            if order_statistics {
+                assert_eq!(tree.rank(&ByteBox::big_int(400)).unwrap(), 100);
+            }
+
+            // The rebuilt tree takes writes as any other
+            for key in (1..800).step_by(4) {
+                tree.insert(entry(key)).unwrap();
+            }
+            assert_eq!(tree.verify(), vec![], "b = {}", b);
+            assert_eq!(tree.scan(..).count(), live.len() + 200);
+        }
+
+        // An empty tree is rebuilt into an empty root leaf
+        let empty = builder(2, true, false).build().unwrap();
+        empty.rebuild().unwrap();
+
+        assert_eq!(empty.verify(), vec![]);
+        assert_eq!(empty.scan(..).count(), 0);
+    }
+
+    #[test]
+    fn reads_keep_working_while_the_copy_is_built() {
+        let tree = builder(2, true, false).build().unwrap();
+        let live = fragmented(&tree, 800);
+
+        let snapshot = tree.snapshot().unwrap();
+        let mut rebuild = TreeRebuild::new();
+
+        while !rebuild.step(&tree).unwrap() {
+            assert_eq!(tree.scan(..).count(), live.len());
+            assert_eq!(tree.search(NodeKey::GuidePost(ByteBox::big_int(8))).unwrap(), entry(8));
+        }
+
+        assert!(rebuild.is_done());
+        assert_eq!(rebuild.restarts(), 0);
+        assert_eq!(tree.verify(), vec![]);
+
+        // The snapshot holds on to the old pages until it is dropped
+        let free_before = tree.flusher.free_page_count();
+        assert_eq!(snapshot.scan(..).collect::<Vec<_>>(), live);
+        assert_ne!(snapshot.root_page_id(), *tree.root_page_id.borrow());
+
+        drop(snapshot);
+        assert!(tree.flusher.free_page_count() > free_before);
+        assert_eq!(tree.scan(..).collect::<Vec<_>>(), live);
+    }
+
+    #[test]
+    fn writes_between_steps_start_the_copy_over() {
+        let tree = builder(2, true, false).build().unwrap();
+        let mut live = fragmented(&tree, 800);
+
+        let mut rebuild = TreeRebuild::new();
+        assert!(!rebuild.step(&tree).unwrap());
+
+        // The entry lands in a leaf the first step has already copied
+        tree.insert(entry(1)).unwrap();
+        live.insert(1, entry(1));
+
+        rebuild.finish(&tree).unwrap();
+
+        assert_eq!(rebuild.restarts(), 1);
+        assert_eq!(tree.verify(), vec![]);
+        assert_eq!(tree.scan(..).collect::<Vec<_>>(), live);
+        assert!(rebuild.step(&tree).unwrap());
+    }
+
+    #[test]
+    fn trees_without_copy_on_write_are_refused() {
+        let tree = builder(2, false, false).build().unwrap();
+        let live = fragmented(&tree, 800);
+
+        // Nothing would tell the rebuild when this scan is done with the old pages
+        let mut scan = tree.scan(..);
+        let first = scan.next();
+
+        let free_before = tree.flusher.free_page_count();
+        let root_before = *tree.root_page_id.borrow();
+
+        assert!(tree.rebuild().is_err());
+        assert!(TreeRebuild::new().step(&tree).is_err());
+
+        assert_eq!(*tree.root_page_id.borrow(), root_before);
+        assert_eq!(tree.flusher.free_page_count(), free_before);
+        assert_eq!(first.into_iter().chain(scan).collect::<Vec<_>>(), live);
+        assert_eq!(tree.verify(), vec![]);
+    }
+}
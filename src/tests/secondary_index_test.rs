@@ -0,0 +1,263 @@
+#[cfg(test)]
+pub mod test {
+    use std::time::{ Duration, SystemTime };
+
+    use crate::{
+        catalog::{ schema::SchemaDataBuilder, table::Table },
+        index::tree::{
+            byte_box::{ ByteBox, DataType },
+            db::{ btree_obj::BTreeBuilder, btree_ttl::ExpiryReaper },
+            index_types::{ KeyValuePair, NodeKey },
+            tree_page::codec::Codec,
+        },
+        storage::tuple::{ to_flat_schema, Tuple },
+    };
+
+    const CITIES: [&str; 4] = ["Oslo", "Lima", "Pune", "Kyiv"];
+
+    fn row(id: i64, city: &str) -> KeyValuePair {
+        let tuple = Tuple::TupleData(
+            SchemaDataBuilder::new()
+                .add_small_int(String::from("age"), ByteBox::small_int((id % 60) as i16))
+                .add_varchar(String::from("city"), ByteBox::varchar(city, 20))
+                .build()
+        );
+
+        KeyValuePair { key: ByteBox::big_int(id), value: ByteBox::tuple(&tuple) }
+    }
+
+    fn city_of(id: i64) -> &'static str {
+        CITIES[(id as usize) % CITIES.len()]
+    }
+
+    fn table() -> Table {
+        let schema = to_flat_schema(&Tuple::TupleData(
+            SchemaDataBuilder::new()
+                .add_small_int(String::from("age"), ByteBox::small_int(0))
+                .add_varchar(String::from("city"), ByteBox::varchar("", 20))
+                .build()
+        ));
+
+        let primary = BTreeBuilder::new()
+            .b_parameter(3)
+            .tree_schema(Codec { key_type: DataType::BigInt, value_type: DataType::Tuple(schema) })
+            .build()
+            .unwrap();
+
+        Table::new(String::from("people"), Box::new(primary)).unwrap()
+    }
+
+    fn ids_in(table: &Table, city: &str) -> Vec<i64> {
+        ids(table.lookup_by("city", &ByteBox::varchar(city, 20)).unwrap())
+    }
+
+    /// Number of entries of every index, which matches the number of stored rows
+    fn index_sizes(table: &Table) -> Vec<usize> {
+        table.indexes
+            .iter()
+            .map(|index| index.tree.stats().unwrap().num_entries)
+            .collect()
+    }
+
+    /// Lets the stored row expire, leaving its column values and so its index entries as they are
+    fn expire(table: &Table, id: i64) {
+        let row = table.get(&ByteBox::big_int(id)).unwrap();
+        let past = SystemTime::now() - Duration::from_secs(1);
+
+        table.primary.as_tree().unwrap().set_with_expiry(row, past).unwrap();
+    }
+
+    fn ids(rows: Vec<KeyValuePair>) -> Vec<i64> {
+        rows.into_iter()
+            .map(|row| i64::from_le_bytes(row.key.data[..].try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn index_is_built_from_existing_rows_and_kept_up_to_date() {
+        let mut table = table();
+
+        for id in 0..200i64 {
+            table.insert(row(id, city_of(id))).unwrap();
+        }
+
+        table.create_index("city").unwrap();
+        table.create_index("age").unwrap();
+        assert_eq!(table.indexed_columns(), vec!["city", "age"]);
+
+        let oslo = table.lookup_by("city", &ByteBox::varchar("Oslo", 20)).unwrap();
+        assert_eq!(ids(oslo), (0..200).step_by(4).collect::<Vec<_>>());
+
+        // Inserts, updates and deletes all reach the indexes
+        table.insert(row(200, "Oslo")).unwrap();
+        table.put(row(4, "Lima")).unwrap();
+        table.put(row(1, "Lima")).unwrap();
+        table.delete(&ByteBox::big_int(8)).unwrap();
+
+        let oslo = ids(table.lookup_by("city", &ByteBox::varchar("Oslo", 20)).unwrap());
+        assert!(oslo.contains(&200));
+        assert!(!oslo.contains(&4) && !oslo.contains(&8));
+        assert_eq!(oslo.len(), 50 - 2 + 1);
+
+        let lima = ids(table.lookup_by("city", &ByteBox::varchar("Lima", 20)).unwrap());
+        assert!(lima.contains(&4) && lima.contains(&1));
+        assert_eq!(lima.len(), 51);
+
+        let aged = ids(table.lookup_by("age", &ByteBox::small_int(8)).unwrap());
+        assert_eq!(aged, vec![68, 128, 188]);
+
+        assert_eq!(table.lookup_by("city", &ByteBox::varchar("Rome", 20)).unwrap(), vec![]);
+
+        for index in &table.indexes {
+            assert_eq!(index.tree.verify(), vec![], "{}", index.column);
+        }
+    }
+
+    #[test]
+    fn lookups_return_whole_rows() {
+        let mut table = table();
+        table.create_index("city").unwrap();
+
+        table.insert(row(7, "Pune")).unwrap();
+        table.insert(row(3, "Pune")).unwrap();
+
+        let rows = table.lookup_by("city", &ByteBox::varchar("Pune", 20)).unwrap();
+        assert_eq!(rows, vec![row(3, "Pune"), row(7, "Pune")]);
+    }
+
+    #[test]
+    fn indexes_need_known_columns_and_tuple_rows() {
+        let mut table = table();
+
+        assert!(table.create_index("country").is_err());
+        assert!(table.lookup_by("city", &ByteBox::varchar("Oslo", 20)).is_err());
+
+        table.create_index("city").unwrap();
+        assert!(table.create_index("city").is_err());
+
+        let plain = BTreeBuilder::new()
+            .tree_schema(Codec { key_type: DataType::BigInt, value_type: DataType::BigInt })
+            .build()
+            .unwrap();
+        let mut plain = Table::new(String::from("plain"), Box::new(plain)).unwrap();
+        assert!(plain.create_index("city").is_err());
+    }
+
+    #[test]
+    fn inserting_a_present_key_changes_nothing() {
+        let mut table = table();
+        for id in 0..40i64 {
+            table.insert(row(id, city_of(id))).unwrap();
+        }
+        table.create_index("city").unwrap();
+
+        assert!(table.insert(row(7, "Rome")).is_err());
+        assert_eq!(table.get(&ByteBox::big_int(7)).unwrap(), row(7, city_of(7)));
+        assert_eq!(ids_in(&table, "Rome"), vec![]);
+        assert!(ids_in(&table, city_of(7)).contains(&7));
+        assert_eq!(index_sizes(&table), vec![40]);
+        assert_eq!(table.primary.stats().unwrap().num_entries, 40);
+    }
+
+    #[test]
+    fn expired_rows_take_their_index_entries_with_them() {
+        let mut table = table();
+        for id in 0..60i64 {
+            table.insert(row(id, city_of(id))).unwrap();
+        }
+        table.create_index("city").unwrap();
+        table.create_index("age").unwrap();
+
+        for id in [3, 10, 20, 30] {
+            expire(&table, id);
+        }
+
+        // Expired rows keep their entries until reaped, but lookups leave them out
+        assert_eq!(index_sizes(&table), vec![60, 60]);
+        assert!(!ids(table.lookup_by("age", &ByteBox::small_int(3)).unwrap()).contains(&3));
+
+        // Writing over an expired row removes its entries, as it no longer holds its old values
+        table.put(row(3, "Rome")).unwrap();
+        table.insert(row(10, "Rome")).unwrap();
+        assert_eq!(ids_in(&table, "Rome"), vec![3, 10]);
+        assert!(!ids_in(&table, city_of(3)).contains(&3));
+        assert_eq!(index_sizes(&table), vec![60, 60]);
+
+        // Reaping through the table deletes the entries of the rows it deletes
+        expire(&table, 40);
+        let mut reaper = ExpiryReaper::new();
+        let mut reaped = table.reap(&mut reaper, None).unwrap();
+        while !reaper.is_pass_done() {
+            reaped += table.reap(&mut reaper, None).unwrap();
+        }
+
+        assert_eq!(reaped, 3);
+        assert_eq!(index_sizes(&table), vec![57, 57]);
+        assert_eq!(table.lookup_by("age", &ByteBox::small_int(20)).unwrap(), vec![]);
+        for index in &table.indexes {
+            assert_eq!(index.tree.verify(), vec![], "{}", index.column);
+        }
+    }
+
+    #[test]
+    fn failed_index_writes_leave_the_table_as_it_was() {
+        let mut table = table();
+        for id in 0..40i64 {
+            table.insert(row(id, city_of(id))).unwrap();
+        }
+        table.create_index("age").unwrap();
+        table.create_index("city").unwrap();
+
+        let city = |name: &str, id: i64| KeyValuePair {
+            key: ByteBox::varchar(name, 20),
+            value: ByteBox::big_int(id),
+        };
+        let city_index = &table.indexes[1].tree;
+
+        // The city entry is already there, so the insert fails after the age index took its own
+        city_index.insert(city("Rome", 50)).unwrap();
+        assert!(table.insert(row(50, "Rome")).is_err());
+        assert!(!table.primary.contains_key(&ByteBox::big_int(50)).unwrap());
+        assert_eq!(table.lookup_by("age", &ByteBox::small_int(50)).unwrap(), vec![]);
+        city_index.delete(NodeKey::KeyValuePair(city("Rome", 50))).unwrap();
+
+        // Without its city entry the row can neither be moved nor deleted
+        city_index.delete(NodeKey::KeyValuePair(city(city_of(5), 5))).unwrap();
+        assert!(table.put(row(5, "Rome")).is_err());
+        assert_eq!(table.get(&ByteBox::big_int(5)).unwrap(), row(5, city_of(5)));
+        assert_eq!(table.lookup_by("city", &ByteBox::varchar("Rome", 20)).unwrap(), vec![]);
+
+        assert!(table.delete(&ByteBox::big_int(5)).is_err());
+        assert_eq!(table.get(&ByteBox::big_int(5)).unwrap(), row(5, city_of(5)));
+        assert_eq!(ids(table.lookup_by("age", &ByteBox::small_int(5)).unwrap()), vec![5]);
+
+        for index in &table.indexes {
+            assert_eq!(index.tree.verify(), vec![], "{}", index.column);
+        }
+    }
+
+    #[test]
+    fn indexes_whose_entries_cannot_fit_a_key_are_refused() {
+        let schema = to_flat_schema(&Tuple::TupleData(
+            SchemaDataBuilder::new()
+                .add_varchar(String::from("notes"), ByteBox::varchar("", 250))
+                .build()
+        ));
+        let primary = BTreeBuilder::new()
+            .tree_schema(Codec { key_type: DataType::BigInt, value_type: DataType::Tuple(schema) })
+            .build()
+            .unwrap();
+        let mut table = Table::new(String::from("notes"), Box::new(primary)).unwrap();
+
+        let notes = Tuple::TupleData(
+            SchemaDataBuilder::new()
+                .add_varchar(String::from("notes"), ByteBox::varchar(&"n".repeat(250), 250))
+                .build()
+        );
+        table.insert(KeyValuePair { key: ByteBox::big_int(1), value: ByteBox::tuple(&notes) })
+            .unwrap();
+
+        assert!(table.create_index("notes").is_err());
+        assert!(table.indexed_columns().is_empty());
+    }
+}
@@ -0,0 +1,201 @@
+#[cfg(test)]
+pub mod test {
+    use crate::{
+        index::tree::{
+            byte_box::{ ByteBox, DataType },
+            db::{ btree_batch::WriteBatch, btree_obj::{ BPTree, BTreeBuilder } },
+            index_types::{ KeyValuePair, NodeKey },
+            tree_page::codec::Codec,
+        },
+        tests::fixtures::{ self, tagged_entry },
+    };
+
+    fn builder(b: usize) -> BTreeBuilder {
+        let mut builder = fixtures::builder(b);
+        builder.copy_on_write(true);
+
+        builder
+    }
+
+    fn populated(b: usize, len: i64) -> BPTree {
+        let tree = builder(b).build().unwrap();
+
+        for idx in 0..len {
+            tree.insert(tagged_entry((idx * 37) % len, "a")).unwrap();
+        }
+
+        tree
+    }
+
+    #[test]
+    fn snapshots_keep_the_tree_as_it_was() {
+        for b in [0, 2, 3] {
+            let tree = populated(b, 400);
+            let before: Vec<KeyValuePair> = (0..400).map(|key| tagged_entry(key, "a")).collect();
+
+            let snapshot = tree.snapshot().unwrap();
+
+            for key in (0..400).step_by(3) {
+                tree.delete(NodeKey::GuidePost(ByteBox::big_int(key))).unwrap();
+            }
+            for key in (1..400).step_by(3) {
+                tree.put(tagged_entry(key, "b")).unwrap();
+            }
+            for key in 400..600 {
+                tree.insert(tagged_entry(key, "c")).unwrap();
+            }
+
+            let after: Vec<KeyValuePair> = (0..600)
+                .filter(|key| key % 3 != 0 || *key >= 400)
+                .map(|key| {
+                    let tag = if key >= 400 { "c" } else if key % 3 == 1 { "b" } else { "a" };
+                    tagged_entry(key, tag)
+                })
+                .collect();
+
+            assert_eq!(tree.verify(), vec![], "b = {}", b);
+            assert_eq!(tree.scan(..).collect::<Vec<_>>(), after, "b = {}", b);
+            assert_eq!(tree.scan_rev(..).count(), after.len(), "b = {}", b);
+
+            assert_eq!(snapshot.scan(..).collect::<Vec<_>>(), before, "b = {}", b);
+            assert_eq!(
+                snapshot.scan_rev(..).collect::<Vec<_>>(),
+                before.iter().rev().cloned().collect::<Vec<_>>()
+            );
+            assert_eq!(snapshot.get(&ByteBox::big_int(21)), Some(tagged_entry(21, "a")));
+            assert_eq!(snapshot.get(&ByteBox::big_int(500)), None);
+
+            let later = tree.snapshot().unwrap();
+            assert!(later.version() > snapshot.version());
+            assert_eq!(later.scan(..).count(), after.len());
+        }
+    }
+
+    #[test]
+    fn scans_read_the_root_they_started_on() {
+        let tree = populated(2, 200);
+
+        let mut scan = tree.scan(..);
+        assert_eq!(scan.next(), Some(tagged_entry(0, "a")));
+
+        // Every page the scan has yet to read is replaced or freed behind it
+        tree.delete_range(ByteBox::big_int(50)..ByteBox::big_int(150)).unwrap();
+        for key in 0..200 {
+            tree.put(tagged_entry(key, "b")).unwrap();
+        }
+
+        let before: Vec<KeyValuePair> = (1..200).map(|key| tagged_entry(key, "a")).collect();
+        let after: Vec<KeyValuePair> = (0..200).map(|key| tagged_entry(key, "b")).collect();
+
+        assert_eq!(scan.collect::<Vec<_>>(), before);
+        assert_eq!(tree.scan(..).collect::<Vec<_>>(), after);
+    }
+
+    #[test]
+    fn pages_are_freed_once_no_snapshot_reads_them() {
+        let tree = populated(3, 300);
+
+        // Copies of underfull leaves are rebalanced the first time they are written
+        for key in 0..100 {
+            tree.put(tagged_entry(key * 3 + 1, "b")).unwrap();
+        }
+        let free = tree.flusher.free_page_count();
+
+        // Without snapshots a write frees as many pages as it takes
+        for key in 0..100 {
+            tree.put(tagged_entry(key * 3 + 1, "c")).unwrap();
+        }
+        assert_eq!(tree.flusher.free_page_count(), free);
+
+        let snapshot = tree.snapshot().unwrap();
+        for key in 0..100 {
+            tree.put(tagged_entry(key * 3 + 1, "d")).unwrap();
+        }
+        let held = tree.flusher.free_page_count();
+
+        // Overflow pages of the replaced values are held back as well
+        assert_eq!(snapshot.get(&ByteBox::big_int(7)), Some(tagged_entry(7, "c")));
+        drop(snapshot);
+
+        assert!(tree.flusher.free_page_count() >= held + 100);
+        assert_eq!(tree.verify(), vec![]);
+    }
+
+    #[test]
+    fn batches_cursors_and_reopened_trees_copy_on_write() {
+        let tree = builder(2)
+            .bulk_load((0..300).map(|key| tagged_entry(key, "a")))
+            .unwrap();
+        let snapshot = tree.snapshot().unwrap();
+
+        let mut batch = WriteBatch::new();
+        for key in (0..300).step_by(2) {
+            batch.delete(&tree, ByteBox::big_int(key));
+        }
+        batch.put(&tree, tagged_entry(301, "b"));
+        batch.apply(None).unwrap();
+
+        let mut cursor = tree.cursor();
+        assert!(cursor.seek(&ByteBox::big_int(101)).unwrap());
+        cursor.update_value(tagged_entry(101, "c").value).unwrap();
+        assert!(cursor.delete_current().unwrap());
+        assert_eq!(cursor.key(), Some(ByteBox::big_int(103)));
+        assert!(cursor.prev().unwrap());
+        assert_eq!(cursor.key(), Some(ByteBox::big_int(99)));
+
+        let keys: Vec<ByteBox> = tree
+            .scan(..)
+            .map(|entry| entry.key)
+            .collect();
+        let expected: Vec<ByteBox> = (0..302)
+            .filter(|key| key % 2 == 1 && *key != 101)
+            .map(ByteBox::big_int)
+            .collect();
+
+        assert_eq!(keys, expected);
+        assert_eq!(tree.verify(), vec![]);
+        assert_eq!(snapshot.scan(..).count(), 300);
+
+        tree.flush().unwrap();
+        let reopened = BTreeBuilder::new().build_from_file(&tree.file_path().unwrap()).unwrap();
+
+        assert!(reopened.is_copy_on_write());
+        assert_eq!(reopened.verify(), vec![]);
+        assert_eq!(reopened.snapshot().unwrap().scan(..).count(), expected.len());
+
+        // Trees that rewrite pages in place keep no older roots to read
+        let plain = builder(2).copy_on_write(false).build().unwrap();
+        assert!(plain.snapshot().is_err());
+    }
+
+    #[test]
+    fn duplicates_and_counts_copy_on_write() {
+        let mut builder = BTreeBuilder::new();
+        builder
+            .b_parameter(2)
+            .allow_duplicates(true)
+            .order_statistics(true)
+            .copy_on_write(true)
+            .tree_schema(Codec { key_type: DataType::BigInt, value_type: DataType::Int });
+        let tree = builder.build().unwrap();
+
+        for key in 0..100 {
+            for value in 0..3 {
+                tree.insert(KeyValuePair { key: ByteBox::big_int(key), value: ByteBox::int(value) })
+                    .unwrap();
+            }
+        }
+        let snapshot = tree.snapshot().unwrap();
+
+        tree.delete(NodeKey::GuidePost(ByteBox::big_int(40))).unwrap();
+        tree.delete_range(ByteBox::big_int(60)..ByteBox::big_int(70)).unwrap();
+
+        assert_eq!(tree.verify(), vec![]);
+        assert_eq!(tree.count_range(..).unwrap(), 267);
+        assert_eq!(tree.rank(&ByteBox::big_int(50)).unwrap(), 147);
+        assert_eq!(tree.scan(ByteBox::big_int(40)..=ByteBox::big_int(40)).count(), 0);
+
+        assert_eq!(snapshot.scan(ByteBox::big_int(40)..=ByteBox::big_int(40)).count(), 3);
+        assert_eq!(snapshot.get(&ByteBox::big_int(65)).unwrap().value, ByteBox::int(0));
+    }
+}
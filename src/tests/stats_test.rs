@@ -0,0 +1,96 @@
+#[cfg(test)]
+pub mod test {
+    use crate::index::tree::{
+        byte_box::{ ByteBox, DataType },
+        db::{ btree_obj::BTreeBuilder, btree_stats::FILL_HISTOGRAM_BUCKETS },
+        index_types::{ KeyValuePair, NodeKey },
+        tree_page::codec::Codec,
+    };
+
+    fn builder() -> BTreeBuilder {
+        let mut builder = BTreeBuilder::new();
+        builder.tree_schema(Codec {
+            key_type: DataType::BigInt,
+            value_type: DataType::Varchar(3000),
+        });
+
+        builder
+    }
+
+    // The value of key 0 is large enough to live in overflow pages
+    fn entry(key: i64) -> KeyValuePair {
+        let len = if key == 0 { 2500 } else { 20 };
+
+        KeyValuePair {
+            key: ByteBox::big_int(key),
+            value: ByteBox::varchar(&"s".repeat(len), 3000),
+        }
+    }
+
+    #[test]
+    fn empty_tree_is_a_single_leaf() {
+        let stats = builder().build().unwrap().stats().unwrap();
+
+        assert_eq!(stats.height, 1);
+        assert_eq!(stats.nodes_per_level, vec![1]);
+        assert_eq!(stats.num_entries, 0);
+        assert_eq!(stats.avg_keys_per_internal, 0.0);
+        assert_eq!(stats.leaf_fill_histogram.iter().sum::<usize>(), 1);
+    }
+
+    #[test]
+    fn stats_describe_the_tree() {
+        let tree = builder().build().unwrap();
+
+        for idx in 0..3000i64 {
+            tree.insert(entry((idx * 1237) % 3000)).unwrap();
+        }
+
+        let stats = tree.stats().unwrap();
+
+        assert!(stats.height >= 2);
+        assert_eq!(stats.nodes_per_level.len(), stats.height);
+        assert_eq!(stats.nodes_per_level[0], 1);
+        assert_eq!(stats.leaf_fill_histogram.iter().sum::<usize>(), stats.num_leaves());
+
+        assert_eq!(stats.num_entries, 3000);
+        assert_eq!(stats.key_bytes, 3000 * 8);
+        assert_eq!(stats.value_bytes, 2999 * 20 + 2500);
+
+        let internal_nodes: usize = stats.nodes_per_level[..stats.height - 1].iter().sum();
+        let children: usize = stats.nodes_per_level[1..].iter().sum();
+        assert_eq!(
+            stats.avg_keys_per_internal,
+            ((children - internal_nodes) as f64) / (internal_nodes as f64)
+        );
+
+        // Splits leave leaves close to half full at the least
+        let sparse = &stats.leaf_fill_histogram[..FILL_HISTOGRAM_BUCKETS / 2 - 1];
+        assert_eq!(sparse.iter().sum::<usize>(), 0);
+    }
+
+    #[test]
+    fn deletes_show_up_in_stats() {
+        let tree = builder().build().unwrap();
+
+        for key in 0..2000i64 {
+            tree.insert(entry(key)).unwrap();
+        }
+
+        let before = tree.stats().unwrap();
+        assert_eq!(before.free_pages, 0);
+
+        for key in 0..1900 {
+            tree.delete(NodeKey::GuidePost(ByteBox::big_int(key))).unwrap();
+        }
+
+        let after = tree.stats().unwrap();
+
+        assert_eq!(after.num_entries, 100);
+        assert!(after.num_leaves() < before.num_leaves());
+        assert!(after.height <= before.height);
+
+        // The overflow page of key 0 went back to the file
+        assert!(after.free_pages > 0);
+    }
+}
@@ -94,6 +94,69 @@ pub mod test {
         teardown();
     }
 
+    #[test]
+    fn out_of_order_writes_work() {
+        for b in [2, 3] {
+            let tree = BTreeBuilder::new()
+                .b_parameter(b)
+                .tree_schema(Codec {
+                    key_type: DataType::BigInt,
+                    value_type: DataType::BigInt,
+                })
+                .build()
+                .unwrap();
+
+            // 7919 is coprime with 300, so this visits every key once in a scattered order
+            let keys: Vec<i64> = (0..300).map(|idx| (idx * 7919) % 300).collect();
+
+            for key in keys.iter() {
+                tree.insert(KeyValuePair {
+                    key: ByteBox::big_int(*key),
+                    value: ByteBox::big_int(key * 10),
+                }).unwrap();
+            }
+
+            for key in keys.iter() {
+                let found = tree.search(NodeKey::GuidePost(ByteBox::big_int(*key))).unwrap();
+                assert_eq!(found.value, ByteBox::big_int(key * 10));
+            }
+
+            let scanned: Vec<ByteBox> = tree
+                .scan(..)
+                .map(|kv| kv.key)
+                .collect();
+            let expected: Vec<ByteBox> = (0..300).map(ByteBox::big_int).collect();
+            assert_eq!(scanned, expected);
+
+            for key in keys.iter().filter(|key| *key % 3 != 0) {
+                tree.delete(NodeKey::GuidePost(ByteBox::big_int(*key))).unwrap();
+            }
+
+            let scanned: Vec<ByteBox> = tree
+                .scan(..)
+                .map(|kv| kv.key)
+                .collect();
+            let expected: Vec<ByteBox> = (0..300)
+                .filter(|key| key % 3 == 0)
+                .map(ByteBox::big_int)
+                .collect();
+            assert_eq!(scanned, expected);
+
+            // The leaf chain stays linked both ways through merges and borrows
+            let mut reversed: Vec<ByteBox> = tree
+                .scan_rev(..)
+                .map(|kv| kv.key)
+                .collect();
+            reversed.reverse();
+            assert_eq!(reversed, expected);
+
+            for key in expected.iter() {
+                let found = tree.search(NodeKey::GuidePost(key.clone())).unwrap();
+                assert_eq!(&found.key, key);
+            }
+        }
+    }
+
     #[test]
     fn teardown() {
         let log_file_path = PathBuf::from("log_file_path.bin");
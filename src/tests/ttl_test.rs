@@ -0,0 +1,173 @@
+#[cfg(test)]
+pub mod test {
+    use std::{
+        cell::RefCell,
+        sync::Arc,
+        time::{ Duration, SystemTime, UNIX_EPOCH },
+    };
+
+    use crate::{
+        buffer::buffer_pool_manager::BufferPoolManager,
+        catalog::table::Table,
+        engine::{ StorageEngine, StorageOptions },
+        index::tree::{
+            byte_box::{ ByteBox, DataType },
+            db::{
+                btree_cas::CasOutcome,
+                btree_obj::{ BPTree, BTreeBuilder },
+                btree_ttl::ExpiryReaper,
+            },
+            index_types::{ KeyValuePair, NodeKey },
+            tree_page::codec::Codec,
+        },
+        storage::disk::manager::Manager,
+        tests::fixtures::{ self, entry },
+    };
+
+    fn builder(b: usize, copy_on_write: bool) -> BTreeBuilder {
+        let mut builder = fixtures::builder(b);
+        builder.copy_on_write(copy_on_write);
+
+        builder
+    }
+
+    fn expired_at() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1)
+    }
+
+    // Keys divisible by three have expired, the others by four expire in an hour
+    fn populated(b: usize, copy_on_write: bool, len: i64) -> BPTree {
+        let tree = builder(b, copy_on_write).build().unwrap();
+
+        for key in 0..len {
+            if key % 3 == 0 {
+                tree.set_with_expiry(entry(key), expired_at()).unwrap();
+            } else if key % 4 == 0 {
+                tree.set_with_ttl(entry(key), Duration::from_secs(3600)).unwrap();
+            } else {
+                tree.put(entry(key)).unwrap();
+            }
+        }
+
+        tree
+    }
+
+    fn live(len: i64) -> Vec<KeyValuePair> {
+        (0..len)
+            .filter(|key| key % 3 != 0)
+            .map(entry)
+            .collect()
+    }
+
+    #[test]
+    fn reads_hide_expired_entries() {
+        for (b, copy_on_write) in [(0, false), (2, false), (3, true)] {
+            let tree = populated(b, copy_on_write, 300);
+
+            assert_eq!(tree.verify(), vec![], "b = {}", b);
+            assert_eq!(tree.scan(..).collect::<Vec<_>>(), live(300), "b = {}", b);
+            assert_eq!(
+                tree.scan_rev(..).collect::<Vec<_>>(),
+                live(300).into_iter().rev().collect::<Vec<_>>()
+            );
+            assert_eq!(tree.scan(ByteBox::big_int(30)..=ByteBox::big_int(33)).count(), 2);
+
+            assert!(tree.search(NodeKey::GuidePost(ByteBox::big_int(21))).is_err());
+            assert!(!tree.contains_key(&ByteBox::big_int(21)).unwrap());
+            assert_eq!(tree.search(NodeKey::GuidePost(ByteBox::big_int(28))).unwrap(), entry(28));
+            assert!(tree.contains_key(&ByteBox::big_int(28)).unwrap());
+
+            // An expired key counts as absent, and writing it replaces the stored entry
+            let absent = tree.put_if_absent(entry(42)).unwrap();
+            assert_eq!(absent, CasOutcome::Applied);
+            tree.put(entry(45)).unwrap();
+
+            assert_eq!(tree.search(NodeKey::GuidePost(ByteBox::big_int(42))).unwrap(), entry(42));
+            assert_eq!(tree.scan(ByteBox::big_int(45)..=ByteBox::big_int(45)).count(), 1);
+            assert_eq!(tree.verify(), vec![], "b = {}", b);
+        }
+    }
+
+    #[test]
+    fn expiry_is_kept_in_the_leaf() {
+        let tree = populated(2, false, 200);
+
+        tree.flush().unwrap();
+        let reopened = BTreeBuilder::new().build_from_file(&tree.file_path().unwrap()).unwrap();
+
+        assert_eq!(reopened.scan(..).collect::<Vec<_>>(), live(200));
+
+        let soon = builder(2, false).build().unwrap();
+        soon.set_with_ttl(entry(7), Duration::from_millis(50)).unwrap();
+        assert_eq!(soon.scan(..).count(), 1);
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(soon.scan(..).count(), 0);
+
+        // Values of a tree with duplicates are part of the key
+        let duplicates = BTreeBuilder::new()
+            .allow_duplicates(true)
+            .tree_schema(Codec { key_type: DataType::BigInt, value_type: DataType::Varchar(16) })
+            .build()
+            .unwrap();
+        assert!(duplicates.set_with_ttl(entry(1), Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn reaper_deletes_expired_entries_a_leaf_at_a_time() {
+        for (b, copy_on_write) in [(0, false), (2, false), (3, true)] {
+            let tree = populated(b, copy_on_write, 400);
+            let expired = (0..400).filter(|key| key % 3 == 0).count() as u64;
+
+            let mut reaper = ExpiryReaper::new();
+
+            // A step only reaps a single leaf
+            let first = reaper.step(&tree, None).unwrap();
+            assert!(first > 0 && (first as u64) < expired, "b = {}", b);
+            assert!(!reaper.is_pass_done());
+
+            reaper.finish_pass(&tree, None).unwrap();
+
+            assert_eq!(reaper.reaped(), expired, "b = {}", b);
+            assert!(reaper.is_pass_done());
+            assert_eq!(tree.verify(), vec![], "b = {}", b);
+            assert_eq!(tree.stats().unwrap().num_entries, live(400).len());
+            assert_eq!(tree.scan(..).collect::<Vec<_>>(), live(400));
+
+            // Nothing is left to reap, and entries yet to expire are kept
+            assert_eq!(reaper.finish_pass(&tree, None).unwrap(), 0);
+            assert_eq!(reaper.reaped(), expired);
+        }
+    }
+
+    #[test]
+    fn engine_reaps_after_every_write() {
+        let (log_io, log_file_path) = Manager::open_log();
+        let bpm = Arc::new(BufferPoolManager::new(10, Manager::new(log_io, log_file_path), 2));
+
+        let engine = StorageEngine {
+            options: StorageOptions { page_size: 4096, enable_wal: false },
+            pages_directory: builder(0, false).build().unwrap().into(),
+            table_directory: builder(0, false).build().unwrap().into(),
+            state: Table::new(String::new(), Box::new(populated(2, false, 400))).unwrap(),
+            bpm,
+            wal: None,
+            reaper: RefCell::new(ExpiryReaper::new()),
+        };
+        let expired = (0..400).filter(|key| key % 3 == 0).count() as u64;
+
+        // Every write reaps a leaf, so enough of them get through every expired entry
+        let mut writes = 0;
+        while engine.reaped_entries() < expired && writes < 400 {
+            engine.put(entry(1000 + writes)).unwrap();
+            writes += 1;
+
+            assert!(engine.reaped_entries() > 0);
+        }
+
+        let tree = engine.state.primary.as_tree().unwrap();
+        assert_eq!(engine.reaped_entries(), expired);
+        assert_eq!(tree.stats().unwrap().num_entries, live(400).len() + (writes as usize));
+        assert_eq!(tree.verify(), vec![]);
+    }
+}
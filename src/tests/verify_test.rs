@@ -0,0 +1,113 @@
+#[cfg(test)]
+pub mod test {
+    use crate::{
+        index::tree::{
+            byte_box::ByteBox,
+            db::btree_obj::BPTree,
+            index_types::NodeKey,
+            tree_node::{ node_type::NodeType, tree_node_inner::NodeInner },
+            tree_page::{ codec::Codec, page::TreePage },
+        },
+        tests::fixtures::{ builder, entry },
+    };
+
+    fn populated(b: usize) -> BPTree {
+        let tree = builder(b).build().unwrap();
+
+        for idx in 0..400i64 {
+            tree.insert(entry((idx * 151) % 400)).unwrap();
+        }
+
+        tree
+    }
+
+    fn read(tree: &BPTree, page: u32) -> NodeInner {
+        tree.codec.decode(&TreePage::new(tree.flusher.read_drop(page))).unwrap()
+    }
+
+    fn write(tree: &BPTree, node: &NodeInner) {
+        tree.flusher.write_flush(Codec::encode(node).unwrap().get_data(), node.pointer).unwrap();
+    }
+
+    #[test]
+    fn sound_trees_verify() {
+        for b in [0, 2, 3] {
+            let tree = populated(b);
+            assert_eq!(tree.verify(), vec![], "b = {}", b);
+
+            for key in (0..400).filter(|key| key % 5 != 0) {
+                tree.delete(NodeKey::GuidePost(ByteBox::big_int(key))).unwrap();
+            }
+            assert_eq!(tree.verify(), vec![], "b = {}", b);
+        }
+
+        let tree = builder(0).bulk_load((0..400).map(entry)).unwrap();
+        assert_eq!(tree.verify(), vec![]);
+    }
+
+    #[test]
+    fn unsorted_leaf_is_reported() {
+        let tree = populated(2);
+        let mut leaf = tree.find_min().unwrap();
+
+        if let NodeType::Leaf(entries, _, _) = &mut leaf.node_type {
+            entries.swap(0, 1);
+        }
+        write(&tree, &leaf);
+
+        let violations = tree.verify();
+        let reported = violations
+            .iter()
+            .any(|violation| violation.page == leaf.pointer && violation.message.contains("order"));
+        assert!(reported, "{:?}", violations);
+    }
+
+    #[test]
+    fn key_outside_separators_is_reported() {
+        let tree = populated(2);
+        let mut leaf = tree.find_min().unwrap();
+
+        if let NodeType::Leaf(entries, _, _) = &mut leaf.node_type {
+            let last = entries.len() - 1;
+            entries[last] = NodeKey::KeyValuePair(entry(1000));
+        }
+        write(&tree, &leaf);
+
+        let violations = tree.verify();
+        let reported = violations
+            .iter()
+            .any(|violation| violation.page == leaf.pointer && violation.message.contains("range"));
+        assert!(reported, "{:?}", violations);
+    }
+
+    #[test]
+    fn broken_leaf_chain_is_reported() {
+        let tree = populated(2);
+        let mut first = tree.find_min().unwrap();
+        let second = read(&tree, first.next_pointer.unwrap());
+
+        // The first leaf skips the second one
+        first.next_pointer = second.next_pointer;
+        write(&tree, &first);
+
+        let violations = tree.verify();
+        let reported = violations.iter().any(|violation| violation.page == first.pointer);
+        assert!(reported, "{:?}", violations);
+        assert!(violations.iter().all(|violation| violation.message.contains("chain")));
+    }
+
+    #[test]
+    fn freed_pages_are_reported() {
+        let tree = populated(2);
+        let first = tree.find_min().unwrap();
+        let second = first.next_pointer.unwrap();
+
+        tree.flusher.free_page(second).unwrap();
+
+        let violations = tree.verify();
+        let reported = violations.iter().any(|violation| {
+            violation.page == second && violation.message.contains("not allocated")
+        });
+        assert!(reported, "{:?}", violations);
+    }
+}
@@ -0,0 +1,255 @@
+#[cfg(test)]
+pub mod test {
+    use std::{ fs::OpenOptions, io::Write, path::PathBuf };
+
+    use crate::{
+        index::tree::{
+            byte_box::ByteBox,
+            db::{ btree_batch::WriteBatch, btree_obj::BPTree },
+            index_types::{ KeyValuePair, NodeKey },
+        },
+        tests::fixtures::{ builder, tagged_entry },
+        wal::Wal,
+    };
+
+    fn tree(b: usize) -> BPTree {
+        builder(b).build().unwrap()
+    }
+
+    fn entries(tree: &BPTree) -> Vec<KeyValuePair> {
+        tree.scan(..).collect()
+    }
+
+    fn wal_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(
+            format!("geode_write_batch_{}_{}.wal", std::process::id(), name)
+        );
+        let _ = std::fs::remove_file(&path);
+
+        path
+    }
+
+    #[test]
+    fn applies_puts_and_deletes_across_trees() {
+        let (first, second) = (tree(3), tree(0));
+
+        for key in 0..300 {
+            first.insert(tagged_entry(key, "a")).unwrap();
+        }
+
+        let mut batch = WriteBatch::new();
+        for key in (0..300).rev().step_by(3) {
+            batch.delete(&first, ByteBox::big_int(key));
+        }
+        for key in 0..300 {
+            batch.put(&first, tagged_entry(key * 2, "b"));
+            batch.put(&second, tagged_entry(key, "c"));
+        }
+
+        // The last write of a key wins, and deleting an absent key does nothing
+        batch.put(&second, tagged_entry(5, "d"));
+        batch.delete(&second, ByteBox::big_int(10_000));
+        assert_eq!(batch.len(), 702);
+
+        batch.apply(None).unwrap();
+
+        let mut expected: Vec<KeyValuePair> = (0..300)
+            .filter(|key| (299 - key) % 3 != 0)
+            .map(|key| tagged_entry(key, "a"))
+            .filter(|entry| i64::from_le_bytes(entry.key.data[..].try_into().unwrap()) % 2 != 0)
+            .chain((0..300).map(|key| tagged_entry(key * 2, "b")))
+            .collect();
+        expected.sort_by(|a, b| a.key.cmp(&b.key));
+
+        assert_eq!(entries(&first), expected);
+        assert_eq!(first.verify(), vec![]);
+
+        let mut expected: Vec<KeyValuePair> = (0..300).map(|key| tagged_entry(key, "c")).collect();
+        expected[5] = tagged_entry(5, "d");
+
+        assert_eq!(entries(&second), expected);
+        assert_eq!(second.verify(), vec![]);
+    }
+
+    #[test]
+    fn invalid_batches_change_nothing() {
+        let tree = tree(3);
+        for key in 0..50 {
+            tree.insert(tagged_entry(key, "a")).unwrap();
+        }
+
+        let path = wal_path("invalid");
+        let wal = Wal::create(&path).unwrap();
+
+        let mut batch = WriteBatch::new();
+        batch.delete(&tree, ByteBox::big_int(1));
+        batch.put(&tree, KeyValuePair { key: ByteBox::int(2), value: ByteBox::int(2) });
+
+        assert!(batch.apply(Some(&wal)).is_err());
+        assert_eq!(entries(&tree), (0..50).map(|key| tagged_entry(key, "a")).collect::<Vec<_>>());
+        assert!(Wal::build_buf(&path).unwrap().is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn batches_are_logged_as_one_record() {
+        let tree = tree(0);
+        let path = wal_path("logged");
+        let wal = Wal::create(&path).unwrap();
+
+        let mut batch = WriteBatch::new();
+        for key in 0..20 {
+            batch.put(&tree, tagged_entry(key, "a"));
+        }
+        batch.apply(Some(&wal)).unwrap();
+
+        let mut batch = WriteBatch::new();
+        batch.delete(&tree, ByteBox::big_int(3)).put(&tree, tagged_entry(40, "b"));
+        batch.apply(Some(&wal)).unwrap();
+
+        let logged = Wal::build_buf(&path).unwrap();
+        assert_eq!(logged.len(), 22);
+        assert_eq!(logged[21].key, ByteBox::big_int(40));
+
+        // A crash in the middle of writing the second batch loses all of it
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        let len = file.metadata().unwrap().len();
+        file.set_len(len - 6).unwrap();
+
+        let recovered = Wal::build_buf(&path).unwrap();
+        assert_eq!(recovered.len(), 20);
+        assert_eq!(recovered[19].data.as_ref().unwrap().data, tagged_entry(19, "a").value.data);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn truncating_the_log_keeps_later_records() {
+        let tree = tree(3);
+        let path = wal_path("truncate");
+        let wal = Wal::create(&path).unwrap();
+
+        let mut batch = WriteBatch::new();
+        for key in 0..20 {
+            batch.put(&tree, tagged_entry(key, "a"));
+        }
+        batch.apply(Some(&wal)).unwrap();
+        assert_eq!(wal.entries().unwrap().len(), 20);
+
+        wal.truncate().unwrap();
+        assert!(wal.entries().unwrap().is_empty());
+
+        let mut batch = WriteBatch::new();
+        batch.delete(&tree, ByteBox::big_int(3));
+        batch.apply(Some(&wal)).unwrap();
+
+        // Records written after truncating are read back, also once the log is reopened
+        assert_eq!(wal.entries().unwrap().len(), 1);
+        drop(wal);
+
+        let wal = Wal::re_init_log(&path).unwrap();
+        wal.truncate().unwrap();
+        let mut batch = WriteBatch::new();
+        batch.delete(&tree, ByteBox::big_int(4));
+        batch.apply(Some(&wal)).unwrap();
+        assert_eq!(Wal::build_buf(&path).unwrap().len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn replaying_the_log_completes_a_batch() {
+        let (first, second) = (tree(3), tree(0));
+        for key in 0..100 {
+            first.insert(tagged_entry(key, "a")).unwrap();
+        }
+
+        let path = wal_path("replay");
+        let wal = Wal::create(&path).unwrap();
+
+        let mut batch = WriteBatch::new();
+        for key in 0..100 {
+            batch.put(&first, tagged_entry(key * 2, "b"));
+            batch.put(&second, tagged_entry(key, "c"));
+        }
+        for key in (0..100).step_by(5) {
+            batch.delete(&first, ByteBox::big_int(key));
+        }
+        batch.apply(Some(&wal)).unwrap();
+
+        let (expected_first, expected_second) = (entries(&first), entries(&second));
+
+        // Put the trees back to how a failure part way through the batch would leave them
+        for key in 0..60 {
+            first.put(tagged_entry(key, "a")).unwrap();
+        }
+        for key in 50..100 {
+            second.delete(NodeKey::GuidePost(ByteBox::big_int(key))).unwrap();
+        }
+
+        let logged = Wal::build_buf(&path).unwrap();
+        for _ in 0..2 {
+            first.replay(&logged).unwrap();
+            second.replay(&logged).unwrap();
+
+            assert_eq!(entries(&first), expected_first);
+            assert_eq!(entries(&second), expected_second);
+        }
+        assert_eq!(first.verify(), vec![]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn logs_of_another_format_are_refused() {
+        let path = wal_path("format");
+        Wal::create(&path).unwrap();
+        assert!(Wal::re_init_log(&path).is_ok());
+
+        // A log from before the format was versioned starts straight with a record
+        let mut file = OpenOptions::new().write(true).truncate(true).open(&path).unwrap();
+        file.write_all(&[22, 0, 0, 0]).unwrap();
+        file.write_all(b"DEL").unwrap();
+        file.write_all(&[0; 15]).unwrap();
+
+        assert!(Wal::build_buf(&path).is_err());
+        assert!(Wal::re_init_log(&path).is_err());
+
+        let mut file = OpenOptions::new().write(true).truncate(true).open(&path).unwrap();
+        file.write_all(b"GWAL").unwrap();
+        file.write_all(&1u32.to_le_bytes()).unwrap();
+
+        assert!(Wal::build_buf(&path).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn corrupted_batches_are_refused() {
+        let tree = tree(0);
+        let path = wal_path("corrupt");
+        let wal = Wal::create(&path).unwrap();
+
+        let mut batch = WriteBatch::new();
+        for key in 0..5 {
+            batch.put(&tree, tagged_entry(key, "a"));
+        }
+        batch.apply(Some(&wal)).unwrap();
+
+        let logged = std::fs::read(&path).unwrap();
+
+        // Whichever byte of the record is damaged, the batch is never read back as logged
+        for offset in 8..logged.len() {
+            let mut corrupted = logged.clone();
+            corrupted[offset] ^= 0x5a;
+            std::fs::write(&path, &corrupted).unwrap();
+
+            if let Ok(entries) = Wal::build_buf(&path) {
+                assert!(entries.len() < 5, "offset {}", offset);
+            }
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
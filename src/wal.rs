@@ -18,8 +18,8 @@
 use std::fs::{ File, OpenOptions };
 
 use std::hash::Hasher;
-use std::io::{ BufWriter, Read, Write };
-use std::path::Path;
+use std::io::{ BufWriter, Read, Seek, SeekFrom, Write };
+use std::path::{ Path, PathBuf };
 use std::sync::{ Arc, Mutex };
 
 use anyhow::{ Context, Ok, Result };
@@ -29,11 +29,18 @@ use crate::index::tree::byte_box::{ ByteBox, DataType };
 
 pub struct Wal {
     file: Arc<Mutex<BufWriter<File>>>,
+    path: PathBuf,
 }
 
 pub const SET_COMMAND: &str = "SET";
 pub const PUT_COMMAND: &str = "PUT";
 pub const DEL_COMMAND: &str = "DEL";
+pub const BATCH_COMMAND: &str = "BAT";
+
+// Every log starts with the magic and the version of the record format it is written in
+pub const WAL_MAGIC: &[u8; 4] = b"GWAL";
+pub const WAL_VERSION: u32 = 2;
+const HEADER_SIZE: usize = 8;
 
 #[derive(Debug, Clone)]
 pub enum Commands {
@@ -54,134 +61,118 @@ impl Commands {
 
 #[derive(Debug, Clone)]
 pub struct WalEntry {
-    command: Commands,
-    table_id: u32,
-    key: ByteBox,
-    data: Option<ByteBox>,
+    pub(crate) command: Commands,
+    pub(crate) table_id: u32,
+    pub(crate) key: ByteBox,
+    pub(crate) data: Option<ByteBox>,
 }
 
 impl Wal {
     pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let mut file = BufWriter::new(
+            OpenOptions::new()
+                .read(true)
+                .create_new(true)
+                .write(true)
+                .open(path.as_ref())
+                .context("failed to create WAL")?
+        );
+
+        file.write_all(WAL_MAGIC)?;
+        file.write_all(&WAL_VERSION.to_le_bytes())?;
+        file.flush()?;
+
         Ok(Self {
-            file: Arc::new(
-                Mutex::new(
-                    BufWriter::new(
-                        OpenOptions::new()
-                            .read(true)
-                            .create_new(true)
-                            .write(true)
-                            .open(path)
-                            .context("failed to create WAL")?
-                    )
-                )
-            ),
+            file: Arc::new(Mutex::new(file)),
+            path: path.as_ref().to_path_buf(),
         })
     }
 
+    /// Opens an existing log to append to, refusing one written in another record format
     pub fn re_init_log(path: impl AsRef<Path>) -> Result<Self> {
-        let file = OpenOptions::new()
+        let mut file = OpenOptions::new()
             .read(true)
             .append(true)
-            .open(path)
+            .open(path.as_ref())
             .context("failed to recover from WAL")?;
 
+        let mut header = [0u8; HEADER_SIZE];
+        file.read_exact(&mut header).context("failed to read the WAL header")?;
+        check_header(&header)?;
+
         Ok(Self {
             file: Arc::new(Mutex::new(BufWriter::new(file))),
+            path: path.as_ref().to_path_buf(),
         })
     }
 
-    pub fn build_buf(path: impl AsRef<Path>) -> Vec<WalEntry> {
+    /// Reads back every entry logged so far, see `build_buf`
+    pub fn entries(&self) -> Result<Vec<WalEntry>> {
+        self.file.lock().unwrap().flush()?;
+        Wal::build_buf(&self.path)
+    }
+
+    /// Drops every record of the log, keeping its header
+    ///
+    /// Only safe once every tree the records were written for is flushed, so that no write is
+    /// left in the log alone.
+    pub fn truncate(&self) -> Result<()> {
+        let mut file = self.file.lock().unwrap();
+        file.flush()?;
+
+        let file = file.get_mut();
+        file.set_len(HEADER_SIZE as u64)?;
+        file.seek(SeekFrom::Start(HEADER_SIZE as u64))?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    /// Reads back every entry of the log, those of a batch in the order they were written
+    ///
+    /// A record cut short by a crash is the last one written and never completed, so reading
+    /// stops there. A batch lives in a single record, so it is read back whole or not at all.
+    /// Logs written in another record format are refused rather than misread.
+    pub fn build_buf(path: impl AsRef<Path>) -> Result<Vec<WalEntry>> {
         let mut wal_entries: Vec<WalEntry> = Vec::new();
         let path = path.as_ref();
         let mut file = OpenOptions::new()
             .read(true)
-            .append(true)
             .open(path)
-            .context("failed to recover from WAL")
-            .unwrap();
+            .context("failed to recover from WAL")?;
 
         let mut buf = Vec::new();
-        file.read_to_end(&mut buf).unwrap();
+        file.read_to_end(&mut buf)?;
 
-        let mut rbuf: &[u8] = buf.as_slice();
+        if buf.len() < HEADER_SIZE {
+            return Err(anyhow::Error::msg("WAL is missing its header"));
+        }
+        check_header(&buf[..HEADER_SIZE])?;
 
-        while rbuf.has_remaining() {
-            let entry_len = rbuf.get_u32() as usize;
+        let mut rbuf: &[u8] = &buf[HEADER_SIZE..];
 
-            if rbuf.remaining() < entry_len {
-                panic!("Incomplete WAL");
+        // The length of a record counts its own 4 bytes
+        while rbuf.remaining() >= 4 {
+            let entry_len = (&rbuf[..4]).get_u32_le() as usize;
+
+            if entry_len < 4 + 3 || rbuf.remaining() < entry_len {
+                break;
             }
 
-            let mut entry = &rbuf[..entry_len];
+            let mut entry = &rbuf[4..entry_len];
 
             let mut command_buf = [0u8; 3];
-            entry.read_exact(&mut command_buf).unwrap();
-            let command = String::from_utf8(command_buf.to_vec()).unwrap();
-            entry.advance(3);
-
-            let table_id = entry.get_u32_le();
-
-            let type_len = entry.get_u32_le();
-            let mut data_type_raw = vec![0; type_len as usize];
-            entry.read_exact(&mut data_type_raw).unwrap();
-            entry.advance(type_len as usize);
-
-            let key_len = entry.get_u32_le();
-            let mut key_buf = vec![0; key_len as usize];
-            entry.read_exact(&mut key_buf).unwrap();
-            entry.advance(key_len as usize);
-
-            let data_type_string = String::from_utf8(data_type_raw.clone()).unwrap();
-            let data_type = map_type(&data_type_string, key_len as usize);
-            let key = data_type.to_byte_box(&key_buf);
-
-            let mut value: Option<ByteBox> = None;
-            if command != "DEL" {
-                let val_type_len = entry.get_u32_le();
-                let mut val_type_raw = vec![0; val_type_len as usize];
-                entry.read_exact(&mut val_type_raw).unwrap();
-                entry.advance(val_type_len as usize);
-
-                let val_len = entry.get_u32_le();
-                let mut val_buf = vec![0; val_len as usize];
-                entry.read_exact(&mut val_buf).unwrap();
-                entry.advance(val_len as usize);
-
-                let val_type_string = String::from_utf8(val_type_raw).unwrap();
-                let val_type = map_type(&val_type_string, val_len as usize);
-                value = Some(val_type.to_byte_box(&val_buf));
-            }
+            entry.copy_to_slice(&mut command_buf);
 
-            let checksum = entry.get_u32_le();
-
-            let mut hasher = crc32fast::Hasher::new();
-            hasher.write_u32(table_id);
-            hasher.update(&data_type_raw);
-            hasher.write_u32(key_len);
-            hasher.update(&key.data);
-            if let Some(ref val) = value {
-                let val_type_str = val.datatype.to_string();
-                hasher.update(val_type_str.as_bytes());
-                hasher.write_u32(val.data_length as u32);
-                hasher.update(&val.data);
+            if command_buf[..] == *BATCH_COMMAND.as_bytes() {
+                wal_entries.extend(decode_batch(entry)?);
+            } else {
+                wal_entries.push(decode_entry(&command_buf, entry)?);
             }
 
-            if hasher.finalize() != checksum {
-                panic!("Checksum mismatch");
-            }
-
-            let command = match command.as_str() {
-                "DEL" => Commands::DEL,
-                "SET" => Commands::SET,
-                "PUT" => Commands::PUT,
-                _ => panic!("Unknown Command"),
-            };
-
-            wal_entries.push(WalEntry { command, table_id, key, data: value });
             rbuf.advance(entry_len);
         }
 
-        wal_entries
+        Ok(wal_entries)
     }
 
     pub fn put(
@@ -191,75 +182,45 @@ impl Wal {
         command: Commands,
         table_id: u32
     ) -> Result<()> {
-        let mut file = self.file.lock().unwrap();
-        let mut buf = Vec::<u8>::new();
+        let value = match command {
+            Commands::DEL => None,
+            Commands::PUT | Commands::SET => Some(&value),
+        };
+
+        let buf = encode_entry(&command, table_id, &key, value);
 
-        match command {
-            Commands::DEL => {
-                buf.put_u32_le(0);
-                buf.put_slice(DEL_COMMAND.as_bytes());
-                buf.put_u32_le(table_id);
+        let mut file = self.file.lock().unwrap();
+        file.write_all(&buf)?;
+        Ok(())
+    }
 
-                let data_type = key.datatype.to_string();
-                buf.put_u32_le(data_type.len() as u32);
-                buf.put_slice(data_type.as_bytes());
+    /// Logs the entries as a single record, so recovery sees all of them or none
+    pub fn put_batch(&self, entries: &[WalEntry]) -> Result<()> {
+        let mut body = Vec::<u8>::new();
 
-                buf.put_u32_le(key.data_length as u32);
-                buf.put_slice(&key.data);
+        for entry in entries {
+            body.put_slice(
+                &encode_entry(&entry.command, entry.table_id, &entry.key, entry.data.as_ref())
+            );
+        }
 
-                let mut hasher = crc32fast::Hasher::new();
-                hasher.write_u32(table_id);
-                hasher.update(data_type.as_bytes());
-                hasher.write_u32(key.data_length as u32);
-                hasher.update(&key.data);
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.write_u32(entries.len() as u32);
+        hasher.update(&body);
 
-                let checksum = hasher.finalize();
-                buf.put_u32_le(checksum);
+        let mut buf = Vec::<u8>::new();
+        buf.put_u32_le(0);
+        buf.put_slice(BATCH_COMMAND.as_bytes());
+        buf.put_u32_le(entries.len() as u32);
+        buf.put_slice(&body);
+        buf.put_u32_le(hasher.finalize());
 
-                let buf_len = buf.len() as u32;
-                (&mut buf[..4]).copy_from_slice(&buf_len.to_le_bytes());
+        let buf_len = buf.len() as u32;
+        buf[..4].copy_from_slice(&buf_len.to_le_bytes());
 
-                file.write_all(&buf)?;
-                return Ok(());
-            }
-            Commands::PUT | Commands::SET => {
-                buf.put_u32_le(0);
-                buf.put_slice(command.as_str().as_bytes());
-                buf.put_u32_le(table_id);
-
-                let key_type = key.datatype.to_string();
-                buf.put_u32_le(key_type.len() as u32);
-                buf.put_slice(key_type.as_bytes());
-
-                buf.put_u32_le(key.data_length as u32);
-                buf.put_slice(&key.data);
-
-                let val_type = value.datatype.to_string();
-                buf.put_u32_le(val_type.len() as u32);
-                buf.put_slice(val_type.as_bytes());
-
-                buf.put_u32_le(value.data_length as u32);
-                buf.put_slice(&value.data);
-
-                let mut hasher = crc32fast::Hasher::new();
-                hasher.write_u32(table_id);
-                hasher.update(key_type.as_bytes());
-                hasher.write_u32(key.data_length as u32);
-                hasher.update(&key.data);
-                hasher.update(val_type.as_bytes());
-                hasher.write_u32(value.data_length as u32);
-                hasher.update(&value.data);
-
-                let checksum = hasher.finalize();
-                buf.put_u32_le(checksum);
-
-                let buf_len = buf.len() as u32;
-                (&mut buf[..4]).copy_from_slice(&buf_len.to_le_bytes());
-
-                file.write_all(&buf)?;
-                Ok(())
-            }
-        }
+        let mut file = self.file.lock().unwrap();
+        file.write_all(&buf)?;
+        Ok(())
     }
 
     pub fn sync(&self) -> Result<()> {
@@ -270,6 +231,181 @@ impl Wal {
     }
 }
 
+fn check_header(header: &[u8]) -> Result<()> {
+    if header[..4] != WAL_MAGIC[..] {
+        return Err(anyhow::Error::msg("Not a WAL, or one written before the format was versioned"));
+    }
+
+    let version = (&header[4..HEADER_SIZE]).get_u32_le();
+    if version != WAL_VERSION {
+        return Err(anyhow::Error::msg(format!("Unsupported WAL version {}", version)));
+    }
+
+    Ok(())
+}
+
+/// Lays out a single entry as a record of its own: length, command, table, key, value, checksum
+fn encode_entry(
+    command: &Commands,
+    table_id: u32,
+    key: &ByteBox,
+    value: Option<&ByteBox>
+) -> Vec<u8> {
+    let mut buf = Vec::<u8>::new();
+    buf.put_u32_le(0);
+    buf.put_slice(command.as_str().as_bytes());
+    buf.put_u32_le(table_id);
+
+    let key_type = key.datatype.to_string();
+    buf.put_u32_le(key_type.len() as u32);
+    buf.put_slice(key_type.as_bytes());
+
+    buf.put_u32_le(key.data.len() as u32);
+    buf.put_slice(&key.data);
+
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.write_u32(table_id);
+    hasher.update(key_type.as_bytes());
+    hasher.write_u32(key.data.len() as u32);
+    hasher.update(&key.data);
+
+    if let Some(value) = value {
+        let val_type = value.datatype.to_string();
+        buf.put_u32_le(val_type.len() as u32);
+        buf.put_slice(val_type.as_bytes());
+
+        buf.put_u32_le(value.data.len() as u32);
+        buf.put_slice(&value.data);
+
+        hasher.update(val_type.as_bytes());
+        hasher.write_u32(value.data.len() as u32);
+        hasher.update(&value.data);
+    }
+
+    buf.put_u32_le(hasher.finalize());
+
+    let buf_len = buf.len() as u32;
+    buf[..4].copy_from_slice(&buf_len.to_le_bytes());
+
+    buf
+}
+
+/// Reads the body of a single entry record, the command already taken off
+fn decode_entry(command_buf: &[u8; 3], mut entry: &[u8]) -> Result<WalEntry> {
+    let command = match command_buf {
+        b"DEL" => Commands::DEL,
+        b"SET" => Commands::SET,
+        b"PUT" => Commands::PUT,
+        _ => {
+            return Err(anyhow::Error::msg("Unknown WAL command"));
+        }
+    };
+
+    let table_id = read_u32(&mut entry)?;
+
+    let type_len = read_u32(&mut entry)?;
+    let data_type_raw = read_bytes(&mut entry, type_len as usize)?;
+
+    let key_len = read_u32(&mut entry)?;
+    let key_buf = read_bytes(&mut entry, key_len as usize)?;
+
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.write_u32(table_id);
+    hasher.update(&data_type_raw);
+    hasher.write_u32(key_len);
+    hasher.update(&key_buf);
+
+    let mut logged_value: Option<(Vec<u8>, Vec<u8>)> = None;
+    if !matches!(command, Commands::DEL) {
+        let val_type_len = read_u32(&mut entry)?;
+        let val_type_raw = read_bytes(&mut entry, val_type_len as usize)?;
+
+        let val_len = read_u32(&mut entry)?;
+        let val_buf = read_bytes(&mut entry, val_len as usize)?;
+
+        hasher.update(&val_type_raw);
+        hasher.write_u32(val_len);
+        hasher.update(&val_buf);
+
+        logged_value = Some((val_type_raw, val_buf));
+    }
+
+    if hasher.finalize() != read_u32(&mut entry)? {
+        return Err(anyhow::Error::msg("WAL entry checksum mismatch"));
+    }
+
+    // Types are only read once the checksum vouches for their bytes
+    let key = map_type(&String::from_utf8(data_type_raw)?, key_buf.len()).to_byte_box(&key_buf);
+
+    let data = match logged_value {
+        Some((val_type_raw, val_buf)) => {
+            let val_type = map_type(&String::from_utf8(val_type_raw)?, val_buf.len());
+            Some(val_type.to_byte_box(&val_buf))
+        }
+        None => None,
+    };
+
+    Ok(WalEntry { command, table_id, key, data })
+}
+
+/// Reads the entries of a batch record, each laid out as a record of its own
+fn decode_batch(mut batch: &[u8]) -> Result<Vec<WalEntry>> {
+    let count = read_u32(&mut batch)?;
+
+    if batch.remaining() < 4 {
+        return Err(anyhow::Error::msg("Incomplete WAL batch"));
+    }
+    let (mut body, mut checksum) = batch.split_at(batch.len() - 4);
+
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.write_u32(count);
+    hasher.update(body);
+
+    if hasher.finalize() != checksum.get_u32_le() {
+        return Err(anyhow::Error::msg("WAL batch checksum mismatch"));
+    }
+
+    let mut entries = Vec::new();
+
+    for _ in 0..count {
+        // The length of an entry counts its own 4 bytes and the command
+        let entry_len = read_u32(&mut &body[..])? as usize;
+        if entry_len < 4 + 3 || body.remaining() < entry_len {
+            return Err(anyhow::Error::msg("WAL batch entry overruns its batch"));
+        }
+
+        let mut command_buf = [0u8; 3];
+        command_buf.copy_from_slice(&body[4..7]);
+
+        entries.push(decode_entry(&command_buf, &body[7..entry_len])?);
+        body.advance(entry_len);
+    }
+
+    if body.has_remaining() {
+        return Err(anyhow::Error::msg("WAL batch holds more than its entries"));
+    }
+
+    Ok(entries)
+}
+
+fn read_u32(buf: &mut &[u8]) -> Result<u32> {
+    if buf.remaining() < 4 {
+        return Err(anyhow::Error::msg("Truncated WAL entry"));
+    }
+
+    Ok(buf.get_u32_le())
+}
+
+fn read_bytes(buf: &mut &[u8], len: usize) -> Result<Vec<u8>> {
+    if buf.remaining() < len {
+        return Err(anyhow::Error::msg("Truncated WAL entry"));
+    }
+
+    let mut bytes = vec![0; len];
+    buf.copy_to_slice(&mut bytes);
+    Ok(bytes)
+}
+
 pub(crate) fn map_type(data_type_string: &str, len: usize) -> DataType {
     match data_type_string {
         "BIGINT" => DataType::BigInt,
@@ -279,6 +415,8 @@ pub(crate) fn map_type(data_type_string: &str, len: usize) -> DataType {
         "CHAR" => DataType::Char(len),
         "VARCHAR" => DataType::Varchar(len),
         "BOOLEAN" => DataType::Boolean,
+        // The log does not record the inner type, the tree replaying the entry knows it
+        "EXPIRING" => DataType::Expiring(Box::new(DataType::None)),
         _ => DataType::None,
     }
 }